@@ -1,9 +1,11 @@
-use crate::models::{ConnectionConfig, DatabaseType};
+pub mod workspace;
+
+use crate::models::{ConnectionConfig, DatabaseType, QueryLimits};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 use uuid::Uuid;
 
@@ -15,6 +17,8 @@ pub enum ConfigError {
     ParseError(#[from] toml::de::Error),
     #[error("Failed to serialize config: {0}")]
     SerializeError(#[from] toml::ser::Error),
+    #[error("Failed to serialize history: {0}")]
+    HistoryError(#[from] serde_json::Error),
     #[error("Config directory not found")]
     ConfigDirNotFound,
 }
@@ -41,15 +45,66 @@ pub struct StoredConnection {
     // For SQLite
     #[serde(default)]
     pub file: Option<String>,
+    #[serde(default)]
+    pub read_only: bool,
+    // Query limits, unlimited when absent
+    #[serde(default)]
+    pub statement_timeout: Option<u64>,
+    #[serde(default)]
+    pub max_rows: Option<usize>,
+    #[serde(default)]
+    pub max_result_bytes: Option<usize>,
 }
 
 /// Application settings
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NebulaSettings {
+    /// The connection last opened, as a `section.name` key
     #[serde(default)]
     pub last_connection: Option<String>,
+    /// Open `last_connection` on start
+    #[serde(default)]
+    pub auto_connect: bool,
     #[serde(default)]
     pub theme: Option<String>,
+    /// Rows fetched before a streamed query pauses for "Fetch more"
+    #[serde(default = "default_fetch_size")]
+    pub fetch_size: usize,
+    /// Stop a multi-statement script at the first failing statement
+    #[serde(default = "default_stop_on_error")]
+    pub stop_on_error: bool,
+    /// Folder of the saved-queries library, `queries` in the config
+    /// directory by default
+    #[serde(default)]
+    pub queries_dir: Option<PathBuf>,
+    /// `.sql` files opened or saved lately, most recent first
+    #[serde(default)]
+    pub recent_files: Vec<PathBuf>,
+}
+
+impl Default for NebulaSettings {
+    fn default() -> Self {
+        Self {
+            last_connection: None,
+            auto_connect: false,
+            theme: None,
+            fetch_size: default_fetch_size(),
+            stop_on_error: default_stop_on_error(),
+            queries_dir: None,
+            recent_files: Vec::new(),
+        }
+    }
+}
+
+/// Files kept in `recent_files`
+const MAX_RECENT_FILES: usize = 10;
+
+fn default_fetch_size() -> usize {
+    1000
+}
+
+fn default_stop_on_error() -> bool {
+    true
 }
 
 /// Root configuration structure matching config.toml format
@@ -75,6 +130,17 @@ impl AppConfig {
             .ok_or(ConfigError::ConfigDirNotFound)
     }
 
+    /// The saved-queries library folder, with a leading `~` expanded
+    pub fn queries_dir(&self) -> Result<PathBuf, ConfigError> {
+        match &self.nebula.queries_dir {
+            Some(dir) => match (dir.strip_prefix("~"), dirs::home_dir()) {
+                (Ok(rest), Some(home)) => Ok(home.join(rest)),
+                _ => Ok(dir.clone()),
+            },
+            None => Ok(Self::config_dir()?.join("queries")),
+        }
+    }
+
     /// Get the config file path
     pub fn config_path() -> Result<PathBuf, ConfigError> {
         Ok(Self::config_dir()?.join("config.toml"))
@@ -195,6 +261,55 @@ impl AppConfig {
     pub fn set_last_connection(&mut self, name: &str) {
         self.nebula.last_connection = Some(name.to_string());
     }
+
+    /// Put a file at the top of the recent files
+    pub fn add_recent_file(&mut self, path: &Path) {
+        let recent = &mut self.nebula.recent_files;
+        recent.retain(|p| p != path);
+        recent.insert(0, path.to_path_buf());
+        recent.truncate(MAX_RECENT_FILES);
+    }
+
+    /// The key a connection is remembered by across restarts, which unlike
+    /// its id is the same every time the config is loaded
+    pub fn connection_key(config: &ConnectionConfig) -> String {
+        format!("{}.{}", section(config.db_type), config.name)
+    }
+
+    /// The connection a key refers to. Besides the form `connection_key`
+    /// writes, the name part may be the connection's table key.
+    pub fn find_connection<'a>(
+        &self,
+        key: &str,
+        connections: &'a [ConnectionConfig],
+    ) -> Option<&'a ConnectionConfig> {
+        let (section_name, name) = key.split_once('.')?;
+        let (db_type, table) = [
+            (DatabaseType::MySQL, &self.mysql),
+            (DatabaseType::PostgreSQL, &self.postgres),
+            (DatabaseType::SQLite, &self.sqlite),
+            (DatabaseType::MongoDB, &self.mongodb),
+        ]
+        .into_iter()
+        .find(|(db_type, _)| section(*db_type) == section_name)?;
+        let name = match table.get(name) {
+            Some(stored) if !stored.name.is_empty() => stored.name.as_str(),
+            _ => name,
+        };
+        connections
+            .iter()
+            .find(|c| c.db_type == db_type && c.name == name)
+    }
+}
+
+/// The config table a database type's connections are stored under
+fn section(db_type: DatabaseType) -> &'static str {
+    match db_type {
+        DatabaseType::MySQL => "mysql",
+        DatabaseType::PostgreSQL => "postgres",
+        DatabaseType::SQLite => "sqlite",
+        DatabaseType::MongoDB => "mongodb",
+    }
 }
 
 fn stored_to_connection_config(
@@ -218,6 +333,12 @@ fn stored_to_connection_config(
         password: stored.password.clone(),
         database: stored.database.clone(),
         ssl_enabled: stored.ssl_enabled,
+        read_only: stored.read_only,
+        limits: QueryLimits {
+            statement_timeout_secs: stored.statement_timeout,
+            max_rows: stored.max_rows,
+            max_result_bytes: stored.max_result_bytes,
+        },
         color: stored.color.clone(),
     }
 }
@@ -233,6 +354,10 @@ fn connection_config_to_stored(config: &ConnectionConfig) -> StoredConnection {
         ssl_enabled: config.ssl_enabled,
         color: config.color.clone(),
         file: None,
+        read_only: config.read_only,
+        statement_timeout: config.limits.statement_timeout_secs,
+        max_rows: config.limits.max_rows,
+        max_result_bytes: config.limits.max_result_bytes,
     }
 }
 
@@ -240,6 +365,21 @@ fn connection_config_to_stored(config: &ConnectionConfig) -> StoredConnection {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_recent_files() {
+        let mut config = AppConfig::default();
+        for i in 0..12 {
+            config.add_recent_file(Path::new(&format!("/queries/{}.sql", i)));
+        }
+        config.add_recent_file(Path::new("/queries/5.sql"));
+
+        let recent = &config.nebula.recent_files;
+        assert_eq!(recent.len(), MAX_RECENT_FILES);
+        assert_eq!(recent[0], Path::new("/queries/5.sql"));
+        assert_eq!(recent[1], Path::new("/queries/11.sql"));
+        assert_eq!(recent.iter().filter(|p| p.ends_with("5.sql")).count(), 1);
+    }
+
     #[test]
     fn test_default_config() {
         let config = AppConfig::default();
@@ -267,6 +407,8 @@ database = "app"
 
 [sqlite.local]
 file = "/path/to/database.db"
+statement_timeout = 30
+max_rows = 5000
 
 [nebula]
 last_connection = "mysql.default"
@@ -280,5 +422,48 @@ last_connection = "mysql.default"
             config.nebula.last_connection,
             Some("mysql.default".to_string())
         );
+        assert_eq!(config.nebula.fetch_size, 1000);
+        assert!(config.nebula.stop_on_error);
+
+        let connections = config.get_connections();
+        let sqlite = connections
+            .iter()
+            .find(|c| c.db_type == DatabaseType::SQLite)
+            .unwrap();
+        assert_eq!(sqlite.limits.statement_timeout_secs, Some(30));
+        assert_eq!(sqlite.limits.max_rows, Some(5000));
+        assert_eq!(sqlite.limits.max_result_bytes, None);
+    }
+
+    #[test]
+    fn test_connection_keys() {
+        let toml_content = r#"
+[mysql.local]
+name = "Local MySQL"
+
+[postgres.local]
+name = "Local Postgres"
+"#;
+        let config: AppConfig = toml::from_str(toml_content).unwrap();
+        let connections = config.get_connections();
+
+        // Table keys from a hand-written config and the keys we write
+        let found = config.find_connection("mysql.local", &connections).unwrap();
+        assert_eq!(found.name, "Local MySQL");
+        let key = AppConfig::connection_key(found);
+        assert_eq!(key, "mysql.Local MySQL");
+        assert_eq!(
+            config.find_connection(&key, &connections).unwrap().id,
+            found.id
+        );
+
+        let found = config
+            .find_connection("postgres.local", &connections)
+            .unwrap();
+        assert_eq!(found.db_type, DatabaseType::PostgreSQL);
+        assert!(config
+            .find_connection("sqlite.local", &connections)
+            .is_none());
+        assert!(config.find_connection("local", &connections).is_none());
     }
 }
@@ -0,0 +1,125 @@
+use super::{AppConfig, ConfigError};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+/// What was on screen when the app last closed, restored on the next start
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Workspace {
+    #[serde(default)]
+    pub sidebar_width: Option<f32>,
+    #[serde(default)]
+    pub window_size: Option<[f32; 2]>,
+    #[serde(default)]
+    pub active_tab: usize,
+    /// Expanded databases in each connection's tree, by connection key
+    #[serde(default)]
+    pub expanded_databases: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    pub tabs: Vec<StoredTab>,
+}
+
+/// A query tab as saved in the workspace
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StoredTab {
+    #[serde(default)]
+    pub name: String,
+    /// Key of the tab's connection, see `AppConfig::connection_key`
+    #[serde(default)]
+    pub connection: Option<String>,
+    #[serde(default)]
+    pub database: Option<String>,
+    /// Id of the saved query the tab was opened from
+    #[serde(default)]
+    pub saved_query: Option<Uuid>,
+    /// The `.sql` file open in the tab
+    #[serde(default)]
+    pub file: Option<PathBuf>,
+    /// The table the tab was opened on, whose structure it can show
+    #[serde(default)]
+    pub table: Option<String>,
+    /// The schema `table` is in, for engines that have them (PostgreSQL)
+    #[serde(default)]
+    pub schema: Option<String>,
+    #[serde(default)]
+    pub sql: String,
+}
+
+impl Workspace {
+    pub fn path() -> Result<PathBuf, ConfigError> {
+        Ok(AppConfig::config_dir()?.join("workspace.toml"))
+    }
+
+    /// Load the saved workspace, or an empty one if nothing was saved yet
+    pub fn load() -> Result<Self, ConfigError> {
+        let path = Self::path()?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(&path)?;
+        Ok(toml::from_str(&content)?)
+    }
+
+    pub fn save(&self) -> Result<(), ConfigError> {
+        fs::create_dir_all(AppConfig::config_dir()?)?;
+        let content = toml::to_string_pretty(self)?;
+        fs::write(Self::path()?, content)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let workspace = Workspace {
+            sidebar_width: Some(310.0),
+            window_size: Some([1200.0, 800.0]),
+            active_tab: 1,
+            expanded_databases: HashMap::from([(
+                "mysql.Local MySQL".to_string(),
+                vec!["shop".to_string()],
+            )]),
+            tabs: vec![
+                StoredTab {
+                    name: "orders".to_string(),
+                    connection: Some("mysql.Local MySQL".to_string()),
+                    database: Some("shop".to_string()),
+                    saved_query: None,
+                    file: Some(PathBuf::from("/queries/orders.sql")),
+                    table: Some("orders".to_string()),
+                    schema: None,
+                    sql: "SELECT *\nFROM `orders`\nLIMIT 100".to_string(),
+                },
+                StoredTab {
+                    name: "Query 2".to_string(),
+                    ..StoredTab::default()
+                },
+            ],
+        };
+
+        let text = toml::to_string_pretty(&workspace).unwrap();
+        let restored: Workspace = toml::from_str(&text).unwrap();
+        assert_eq!(restored.sidebar_width, Some(310.0));
+        assert_eq!(restored.window_size, Some([1200.0, 800.0]));
+        assert_eq!(restored.active_tab, 1);
+        assert_eq!(
+            restored.expanded_databases["mysql.Local MySQL"],
+            vec!["shop".to_string()]
+        );
+        assert_eq!(restored.tabs.len(), 2);
+        assert_eq!(restored.tabs[0].sql, workspace.tabs[0].sql);
+        assert_eq!(restored.tabs[0].database.as_deref(), Some("shop"));
+        assert_eq!(restored.tabs[0].file, workspace.tabs[0].file);
+        assert_eq!(restored.tabs[0].table.as_deref(), Some("orders"));
+        assert!(restored.tabs[1].connection.is_none());
+
+        // A workspace from an older version with fields missing still loads
+        let restored: Workspace = toml::from_str("active_tab = 0").unwrap();
+        assert!(restored.tabs.is_empty());
+    }
+}
@@ -1,20 +1,39 @@
+pub mod mongodb;
 pub mod mysql;
-// pub mod postgres; // TODO: Implement PostgreSQL support
-// pub mod sqlite;   // TODO: Implement SQLite support
-// pub mod mongodb;  // TODO: Implement MongoDB support
+pub mod postgres;
+pub mod quote;
+pub mod script;
+pub mod sqlite;
+pub mod stream;
+pub mod wkb;
 
-use crate::models::{ConnectionConfig, DatabaseType, QueryResult};
+use crate::models::{
+    CellValue, ColumnInfo, ConnectionConfig, DatabaseType, ParamValue, QueryLimits, QueryResult,
+    ResultLimit,
+};
 use async_trait::async_trait;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use sqlx::{Column, ColumnIndex, Database, Decode, Describe, Row, Type, TypeInfo};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
 use thiserror::Error;
 
+/// Largest number of rows handed over in a single streamed batch
+const STREAM_BATCH_SIZE: usize = 500;
+
 #[derive(Error, Debug)]
 pub enum DatabaseError {
     #[error("Connection failed: {0}")]
     ConnectionFailed(String),
     #[error("Query failed: {0}")]
     QueryFailed(String),
-    #[error("Unsupported database type: {0}")]
-    UnsupportedType(String),
+    #[error("Statement timeout of {0} s exceeded")]
+    Timeout(u64),
+    #[error("{0} is not supported by this database")]
+    NotSupported(String),
 }
 
 #[derive(Debug, Clone)]
@@ -26,8 +45,13 @@ pub struct DatabaseInfo {
 #[derive(Debug, Clone)]
 pub struct TableInfo {
     pub name: String,
+    /// Schema within the database, for engines that have them (PostgreSQL)
+    pub schema: Option<String>,
     pub database: String,
     pub engine: Option<String>,
+    pub character_set: Option<String>,
+    pub collation: Option<String>,
+    /// Estimated by the engine rather than counted
     pub row_count: Option<u64>,
     pub data_size: Option<u64>,
     pub columns: Vec<ColumnDetails>,
@@ -36,9 +60,113 @@ pub struct TableInfo {
 #[derive(Debug, Clone)]
 pub struct ViewInfo {
     pub name: String,
+    pub schema: Option<String>,
     pub definition: Option<String>,
 }
 
+impl TableInfo {
+    /// Name including the schema prefix, if any
+    pub fn qualified_name(&self) -> String {
+        qualify(self.schema.as_deref(), &self.name)
+    }
+}
+
+impl ViewInfo {
+    /// Name including the schema prefix, if any
+    pub fn qualified_name(&self) -> String {
+        qualify(self.schema.as_deref(), &self.name)
+    }
+}
+
+/// Kinds of objects with a CREATE statement of their own
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectKind {
+    Table,
+    View,
+    Procedure,
+    Function,
+    Trigger,
+    Event,
+}
+
+impl ObjectKind {
+    /// The keyword that follows CREATE, as in `SHOW CREATE <keyword>`
+    pub fn keyword(self) -> &'static str {
+        match self {
+            ObjectKind::Table => "TABLE",
+            ObjectKind::View => "VIEW",
+            ObjectKind::Procedure => "PROCEDURE",
+            ObjectKind::Function => "FUNCTION",
+            ObjectKind::Trigger => "TRIGGER",
+            ObjectKind::Event => "EVENT",
+        }
+    }
+
+    pub fn icon(self) -> &'static str {
+        match self {
+            ObjectKind::Table => "📋",
+            ObjectKind::View => "👁",
+            ObjectKind::Procedure => "⚙",
+            ObjectKind::Function => "ƒ",
+            ObjectKind::Trigger => "⚡",
+            ObjectKind::Event => "⏰",
+        }
+    }
+}
+
+/// A named object in a database, whose DDL can be read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaObject {
+    pub kind: ObjectKind,
+    pub name: String,
+    /// Schema within the database, for engines that have them (PostgreSQL)
+    pub schema: Option<String>,
+    /// The table a trigger is on
+    pub table: Option<String>,
+    /// Argument types, which tell overloaded PostgreSQL routines apart
+    pub arguments: Option<String>,
+}
+
+impl SchemaObject {
+    pub fn table(table: &TableInfo) -> Self {
+        Self::new(ObjectKind::Table, table.schema.clone(), &table.name)
+    }
+
+    pub fn view(view: &ViewInfo) -> Self {
+        Self::new(ObjectKind::View, view.schema.clone(), &view.name)
+    }
+
+    pub fn new(kind: ObjectKind, schema: Option<String>, name: &str) -> Self {
+        Self {
+            kind,
+            name: name.to_string(),
+            schema,
+            table: None,
+            arguments: None,
+        }
+    }
+
+    /// Name including the schema prefix, if any
+    pub fn qualified_name(&self) -> String {
+        qualify(self.schema.as_deref(), &self.name)
+    }
+
+    /// Name as the schema tree shows it, with a routine's arguments
+    pub fn display_name(&self) -> String {
+        match &self.arguments {
+            Some(arguments) => format!("{}({})", self.qualified_name(), arguments),
+            None => self.qualified_name(),
+        }
+    }
+}
+
+fn qualify(schema: Option<&str>, name: &str) -> String {
+    match schema {
+        Some(schema) => format!("{}.{}", schema, name),
+        None => name.to_string(),
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ColumnDetails {
     pub name: String,
@@ -46,10 +174,143 @@ pub struct ColumnDetails {
     pub nullable: bool,
     pub default_value: Option<String>,
     pub is_primary_key: bool,
+    /// Has a unique index of its own, so its value, unless NULL, picks
+    /// out a single row
+    pub is_unique: bool,
     pub is_auto_increment: bool,
     pub comment: Option<String>,
 }
 
+#[derive(Debug, Clone)]
+pub struct IndexInfo {
+    pub name: String,
+    /// Indexed columns in key order; expressions are shown as written
+    pub columns: Vec<String>,
+    pub is_unique: bool,
+    pub is_primary: bool,
+    /// Index method (BTREE, HASH, gin, ...), where the engine has several
+    pub method: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ForeignKeyInfo {
+    /// Constraint name; SQLite doesn't keep one
+    pub name: Option<String>,
+    pub columns: Vec<String>,
+    /// Referenced table, qualified when it lives in another schema or
+    /// database
+    pub referenced_table: String,
+    /// Empty when the key refers to the other table's primary key without
+    /// naming its columns
+    pub referenced_columns: Vec<String>,
+    pub on_update: Option<String>,
+    pub on_delete: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TriggerInfo {
+    pub name: String,
+    /// BEFORE, AFTER or INSTEAD OF
+    pub timing: String,
+    /// The statements it fires on, such as `INSERT OR UPDATE`
+    pub event: String,
+    /// The trigger's body, or its whole definition where that is what the
+    /// engine keeps
+    pub statement: Option<String>,
+}
+
+/// Everything the structure view shows about a table
+#[derive(Debug, Clone)]
+pub struct TableStructure {
+    pub table: TableInfo,
+    pub indexes: Vec<IndexInfo>,
+    pub foreign_keys: Vec<ForeignKeyInfo>,
+    pub triggers: Vec<TriggerInfo>,
+}
+
+/// Statements as one script, each ending in a semicolon
+pub fn ddl_script<S: AsRef<str>>(statements: impl IntoIterator<Item = S>) -> String {
+    statements
+        .into_iter()
+        .map(|statement| format!("{};", statement.as_ref().trim_end().trim_end_matches(';')))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// A chunk of rows from a streamed query
+#[derive(Debug, Clone, Default)]
+pub struct RowBatch {
+    /// Column metadata, sent with the first batch only
+    pub columns: Option<Vec<ColumnInfo>>,
+    pub rows: Vec<Vec<CellValue>>,
+    /// Source documents for the rows (document stores only)
+    pub documents: Vec<String>,
+    /// Set on the last batch when a connection limit ended the query early
+    pub limit: Option<ResultLimit>,
+}
+
+impl RowBatch {
+    /// Split off the rows from `at` onwards into a new batch, which also
+    /// takes over the limit marker since it now ends the result
+    pub fn split_off(&mut self, at: usize) -> RowBatch {
+        RowBatch {
+            columns: None,
+            limit: self.limit.take(),
+            rows: self.rows.split_off(at),
+            documents: if self.documents.len() > at {
+                self.documents.split_off(at)
+            } else {
+                Vec::new()
+            },
+        }
+    }
+}
+
+/// Identifies a query started from the editor so it can be cancelled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct QueryId(u64);
+
+impl QueryId {
+    pub fn new() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(1);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl Default for QueryId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Server session ids of the queries running on a connection, so that
+/// another session can cancel them
+#[derive(Debug, Default)]
+pub struct RunningQueries(std::sync::Mutex<HashMap<QueryId, i64>>);
+
+impl RunningQueries {
+    /// Record that `id` runs in `session` until the returned guard is dropped
+    pub fn track(&self, id: QueryId, session: i64) -> RunningQuery<'_> {
+        self.0.lock().unwrap().insert(id, session);
+        RunningQuery { queries: self, id }
+    }
+
+    pub fn session(&self, id: QueryId) -> Option<i64> {
+        self.0.lock().unwrap().get(&id).copied()
+    }
+}
+
+pub struct RunningQuery<'a> {
+    queries: &'a RunningQueries,
+    id: QueryId,
+}
+
+impl Drop for RunningQuery<'_> {
+    fn drop(&mut self) {
+        self.queries.0.lock().unwrap().remove(&self.id);
+    }
+}
+
 /// Database connection trait
 #[async_trait]
 pub trait DatabaseConnection: Send + Sync {
@@ -65,19 +326,109 @@ pub trait DatabaseConnection: Send + Sync {
     /// Get views in a database
     async fn list_views(&self, database: &str) -> Result<Vec<ViewInfo>, DatabaseError>;
 
-    /// Get table structure
-    async fn describe_table(&self, database: &str, table: &str) -> Result<TableInfo, DatabaseError>;
+    /// Get table structure. `schema` is the table's schema within the
+    /// database, for engines that have them (PostgreSQL, where it defaults
+    /// to `public`); the others ignore it, as do the methods below.
+    async fn describe_table(
+        &self,
+        database: &str,
+        schema: Option<&str>,
+        table: &str,
+    ) -> Result<TableInfo, DatabaseError>;
+
+    /// Get the indexes of a table, the primary key first
+    async fn list_indexes(
+        &self,
+        database: &str,
+        schema: Option<&str>,
+        table: &str,
+    ) -> Result<Vec<IndexInfo>, DatabaseError>;
+
+    /// Get the foreign keys of a table. Engines without them have none.
+    async fn list_foreign_keys(
+        &self,
+        _database: &str,
+        _schema: Option<&str>,
+        _table: &str,
+    ) -> Result<Vec<ForeignKeyInfo>, DatabaseError> {
+        Ok(Vec::new())
+    }
+
+    /// Get the triggers on a table. Engines without them have none.
+    async fn list_triggers(
+        &self,
+        _database: &str,
+        _schema: Option<&str>,
+        _table: &str,
+    ) -> Result<Vec<TriggerInfo>, DatabaseError> {
+        Ok(Vec::new())
+    }
+
+    /// Get a table's columns, keys and triggers together
+    async fn table_structure(
+        &self,
+        database: &str,
+        schema: Option<&str>,
+        table: &str,
+    ) -> Result<TableStructure, DatabaseError> {
+        let (table_info, indexes, foreign_keys, triggers) = futures::try_join!(
+            self.describe_table(database, schema, table),
+            self.list_indexes(database, schema, table),
+            self.list_foreign_keys(database, schema, table),
+            self.list_triggers(database, schema, table),
+        )?;
+        Ok(TableStructure {
+            table: table_info,
+            indexes,
+            foreign_keys,
+            triggers,
+        })
+    }
+
+    /// Get the procedures, functions, triggers and events in a database.
+    /// Engines without them have none.
+    async fn list_routines(&self, _database: &str) -> Result<Vec<SchemaObject>, DatabaseError> {
+        Ok(Vec::new())
+    }
+
+    /// Get the statement that creates `object`, as the engine reports it or
+    /// as rebuilt from its catalog
+    async fn get_ddl(&self, database: &str, object: &SchemaObject)
+        -> Result<String, DatabaseError>;
 
-    /// Execute a query and return results
+    /// Execute a query and return results. For document stores this is a
+    /// shell-style command such as `db.users.find({})`.
     async fn execute_query(&self, sql: &str) -> Result<QueryResult, DatabaseError>;
 
-    /// Execute a query without returning results (INSERT, UPDATE, DELETE)
-    async fn execute_statement(&self, sql: &str) -> Result<u64, DatabaseError>;
+    /// Execute a query and deliver its rows in batches as they arrive.
+    /// Engines without a native cursor fall back to `execute_query`.
+    /// `params` are bound to the placeholders of `sql`, which are in the
+    /// engine's own positional form (`?` or `$1`). The query runs in
+    /// `database` rather than the connection's own, where one is given.
+    fn stream_query<'a>(
+        &'a self,
+        _database: Option<&'a str>,
+        sql: &'a str,
+        _params: &'a [ParamValue],
+        _id: QueryId,
+    ) -> BoxStream<'a, Result<RowBatch, DatabaseError>> {
+        futures::stream::once(async move {
+            let result = self.execute_query(sql).await?;
+            Ok(RowBatch {
+                columns: Some(result.columns),
+                rows: result.rows,
+                documents: result.documents,
+                limit: result.limit,
+            })
+        })
+        .boxed()
+    }
 
     /// Get table data with pagination
     async fn get_table_data(
         &self,
         database: &str,
+        schema: Option<&str>,
         table: &str,
         limit: u32,
         offset: u32,
@@ -85,6 +436,297 @@ pub trait DatabaseConnection: Send + Sync {
 
     /// Close the connection
     async fn close(&self) -> Result<(), DatabaseError>;
+
+    /// Stop a query started with `stream_query` or `execute_statement` on the
+    /// server. The caller is expected to drop the query's future as well, so
+    /// engines that run in-process have nothing to do here.
+    async fn cancel(&self, _id: QueryId) -> Result<(), DatabaseError> {
+        Ok(())
+    }
+
+    /// SQL-only operations, or `None` for engines that don't speak SQL
+    fn as_sql(&self) -> Option<&dyn SqlConnection> {
+        None
+    }
+}
+
+/// Capabilities shared by the SQL engines
+#[async_trait]
+pub trait SqlConnection: Send + Sync {
+    /// Execute a query without returning results (INSERT, UPDATE, DELETE),
+    /// in `database` rather than the connection's own where one is given
+    async fn execute_statement(
+        &self,
+        database: Option<&str>,
+        sql: &str,
+        params: &[ParamValue],
+        id: QueryId,
+    ) -> Result<u64, DatabaseError>;
+
+    /// Reserve one server session for running several statements in order,
+    /// starting out in `database` rather than the connection's own where
+    /// one is given. Cancelling `id` stops whichever statement the session
+    /// is running.
+    async fn session(
+        &self,
+        database: Option<&str>,
+        id: QueryId,
+    ) -> Result<Box<dyn SqlSession + '_>, DatabaseError>;
+}
+
+/// A single server session, so statements that depend on each other
+/// (transactions, `USE`, session variables) see each other's effects
+#[async_trait]
+pub trait SqlSession: Send {
+    async fn execute(&mut self, sql: &str, params: &[ParamValue]) -> Result<u64, DatabaseError>;
+
+    /// Run a statement as plain text rather than preparing it, for those an
+    /// engine may refuse to prepare, such as transaction control
+    async fn execute_unprepared(&mut self, sql: &str) -> Result<u64, DatabaseError>;
+
+    /// Run a query and read all of its rows, within the connection's limits
+    async fn fetch(
+        &mut self,
+        sql: &str,
+        params: &[ParamValue],
+    ) -> Result<QueryResult, DatabaseError>;
+}
+
+/// Column metadata from a statement's description, which is known before
+/// any row arrives. Primary keys are left for the driver to fill in, as
+/// each engine reports them differently.
+pub fn described_columns<DB: Database>(describe: &Describe<DB>) -> Vec<ColumnInfo> {
+    describe
+        .columns()
+        .iter()
+        .enumerate()
+        .map(|(i, col)| ColumnInfo {
+            name: col.name().to_string(),
+            data_type: col.type_info().name().to_string(),
+            // Unknown for computed columns, which may well be NULL
+            nullable: describe.nullable(i).unwrap_or(true),
+            is_primary_key: false,
+        })
+        .collect()
+}
+
+/// Column metadata as reported by a result row, for statements that can't
+/// be described
+pub fn columns_of<R: sqlx::Row>(row: &R) -> Vec<ColumnInfo> {
+    row.columns()
+        .iter()
+        .map(|col| ColumnInfo {
+            name: col.name().to_string(),
+            data_type: col.type_info().name().to_string(),
+            nullable: true,
+            is_primary_key: false,
+        })
+        .collect()
+}
+
+/// Decode column `i` as `T` and convert it, telling a NULL apart from a
+/// value that could not be decoded
+pub fn decode_cell<'r, R, T>(
+    row: &'r R,
+    i: usize,
+    to_cell: impl FnOnce(T) -> CellValue,
+) -> CellValue
+where
+    R: Row,
+    usize: ColumnIndex<R>,
+    T: Decode<'r, R::Database> + Type<R::Database>,
+{
+    cell_or_undecodable(row, i, row.try_get::<Option<T>, _>(i), to_cell)
+}
+
+/// `decode_cell` for types whose wire format fits `T` although sqlx doesn't
+/// consider them compatible, e.g. MySQL BIT or PostgreSQL enums as bytes or
+/// text
+pub fn decode_cell_unchecked<'r, R, T>(
+    row: &'r R,
+    i: usize,
+    to_cell: impl FnOnce(T) -> CellValue,
+) -> CellValue
+where
+    R: Row,
+    usize: ColumnIndex<R>,
+    T: Decode<'r, R::Database>,
+{
+    cell_or_undecodable(row, i, row.try_get_unchecked::<Option<T>, _>(i), to_cell)
+}
+
+fn cell_or_undecodable<R: Row, T>(
+    row: &R,
+    i: usize,
+    value: Result<Option<T>, sqlx::Error>,
+    to_cell: impl FnOnce(T) -> CellValue,
+) -> CellValue
+where
+    usize: ColumnIndex<R>,
+{
+    match value {
+        Ok(Some(value)) => to_cell(value),
+        Ok(None) => CellValue::Null,
+        Err(_) => CellValue::Undecodable(row.column(i).type_info().name().to_string()),
+    }
+}
+
+/// The first `len` bits of `bytes` as `0` and `1` digits, most significant
+/// bit first
+pub fn bit_string(bytes: &[u8], len: usize) -> String {
+    bytes
+        .iter()
+        .flat_map(|byte| {
+            (0..8)
+                .rev()
+                .map(move |bit| if byte >> bit & 1 == 1 { '1' } else { '0' })
+        })
+        .take(len)
+        .collect()
+}
+
+/// Group a driver's row stream into batches, converting rows as they
+/// arrive. The first batch carries the columns from the statement's
+/// description when there is one, or else from the first row. A described
+/// result without rows still yields its columns.
+pub fn batch_rows<'a, R: sqlx::Row>(
+    rows: BoxStream<'a, Result<R, sqlx::Error>>,
+    described: Option<Vec<ColumnInfo>>,
+    to_values: fn(&R) -> Vec<CellValue>,
+) -> BoxStream<'a, Result<RowBatch, DatabaseError>> {
+    let mut chunks = rows.ready_chunks(STREAM_BATCH_SIZE);
+    Box::pin(async_stream::try_stream! {
+        let mut described = described;
+        let mut columns_sent = false;
+        while let Some(chunk) = chunks.next().await {
+            let rows = chunk
+                .into_iter()
+                .collect::<Result<Vec<R>, _>>()
+                .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+
+            let columns = match rows.first() {
+                Some(first) if !columns_sent => {
+                    columns_sent = true;
+                    Some(described.take().unwrap_or_else(|| columns_of(first)))
+                }
+                _ => None,
+            };
+
+            yield RowBatch {
+                columns,
+                rows: rows.iter().map(to_values).collect(),
+                documents: Vec::new(),
+                limit: None,
+            };
+        }
+
+        if let Some(columns) = described.filter(|columns| !columns.is_empty() && !columns_sent) {
+            yield RowBatch {
+                columns: Some(columns),
+                ..Default::default()
+            };
+        }
+    })
+}
+
+/// Enforce a connection's row and byte limits on a stream of batches, and
+/// its statement timeout on the time spent waiting for batches in all. Time
+/// spent paused between pages doesn't count. The batch that hits a limit
+/// carries it and ends the stream.
+pub fn limit_batches<'a>(
+    mut batches: BoxStream<'a, Result<RowBatch, DatabaseError>>,
+    limits: QueryLimits,
+) -> BoxStream<'a, Result<RowBatch, DatabaseError>> {
+    Box::pin(async_stream::try_stream! {
+        let mut rows = 0;
+        let mut bytes = 0;
+        let mut deadline = limits
+            .statement_timeout()
+            .map(|timeout| tokio::time::Instant::now() + timeout);
+        loop {
+            let next = match deadline {
+                Some(deadline) => match tokio::time::timeout_at(deadline, batches.next()).await {
+                    Ok(next) => next,
+                    Err(_) => {
+                        yield RowBatch {
+                            limit: limits.statement_timeout_secs.map(ResultLimit::Timeout),
+                            ..Default::default()
+                        };
+                        break;
+                    }
+                },
+                None => batches.next().await,
+            };
+            let Some(batch) = next else {
+                break;
+            };
+            let mut batch = batch?;
+
+            let mut cut = None;
+            for (i, row) in batch.rows.iter().enumerate() {
+                if let Some(max) = limits.max_rows.filter(|&max| rows >= max) {
+                    cut = Some((i, ResultLimit::MaxRows(max)));
+                    break;
+                }
+                let size: usize = row.iter().map(CellValue::approx_size).sum();
+                if let Some(max) = limits.max_result_bytes.filter(|&max| bytes + size > max) {
+                    cut = Some((i, ResultLimit::MaxBytes(max)));
+                    break;
+                }
+                rows += 1;
+                bytes += size;
+            }
+
+            match cut {
+                Some((at, limit)) => {
+                    batch.split_off(at);
+                    batch.limit = Some(limit);
+                    yield batch;
+                    break;
+                }
+                None => {
+                    let paused = tokio::time::Instant::now();
+                    yield batch;
+                    // The consumer took its time asking for the next batch
+                    if let Some(deadline) = &mut deadline {
+                        *deadline += paused.elapsed();
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Read a whole stream of batches into one result
+pub async fn collect_batches(
+    mut batches: BoxStream<'_, Result<RowBatch, DatabaseError>>,
+) -> Result<QueryResult, DatabaseError> {
+    let start = Instant::now();
+    let mut result = QueryResult::default();
+    while let Some(batch) = batches.next().await {
+        let batch = batch?;
+        if let Some(columns) = batch.columns {
+            result.columns = columns;
+        }
+        result.rows.extend(batch.rows);
+        result.documents.extend(batch.documents);
+        result.limit = batch.limit.or(result.limit);
+    }
+    result.execution_time_ms = start.elapsed().as_millis() as u64;
+    Ok(result)
+}
+
+/// Run a query under the connection's statement timeout, if it has one
+pub async fn with_timeout<T>(
+    limits: &QueryLimits,
+    query: impl Future<Output = Result<T, DatabaseError>>,
+) -> Result<T, DatabaseError> {
+    match (limits.statement_timeout(), limits.statement_timeout_secs) {
+        (Some(timeout), Some(secs)) => tokio::time::timeout(timeout, query)
+            .await
+            .map_err(|_| DatabaseError::Timeout(secs))?,
+        _ => query.await,
+    }
 }
 
 /// Create a database connection based on config
@@ -97,22 +739,59 @@ pub async fn create_connection(
             Ok(Box::new(conn))
         }
         DatabaseType::PostgreSQL => {
-            // TODO: Implement PostgreSQL
-            Err(DatabaseError::UnsupportedType(
-                "PostgreSQL support coming soon".to_string(),
-            ))
+            let conn = postgres::PostgresConnection::connect(config).await?;
+            Ok(Box::new(conn))
         }
         DatabaseType::SQLite => {
-            // TODO: Implement SQLite
-            Err(DatabaseError::UnsupportedType(
-                "SQLite support coming soon".to_string(),
-            ))
+            let conn = sqlite::SqliteConnection::connect(config).await?;
+            Ok(Box::new(conn))
         }
         DatabaseType::MongoDB => {
-            // TODO: Implement MongoDB
-            Err(DatabaseError::UnsupportedType(
-                "MongoDB support coming soon".to_string(),
-            ))
+            let conn = mongodb::MongoConnection::connect(config).await?;
+            Ok(Box::new(conn))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// One single-row batch every `interval`
+    fn trickle(
+        count: usize,
+        interval: Duration,
+    ) -> BoxStream<'static, Result<RowBatch, DatabaseError>> {
+        Box::pin(async_stream::stream! {
+            for i in 0..count {
+                tokio::time::sleep(interval).await;
+                yield Ok(RowBatch {
+                    rows: vec![vec![CellValue::Int(i as i64)]],
+                    ..Default::default()
+                });
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_statement_timeout_spans_batches() {
+        let limits = QueryLimits {
+            statement_timeout_secs: Some(1),
+            ..Default::default()
+        };
+
+        // Each batch arrives well within the timeout, but not all of them do
+        let batches = limit_batches(trickle(5, Duration::from_millis(300)), limits);
+        let result = collect_batches(batches).await.unwrap();
+        assert_eq!(result.rows.len(), 3);
+        assert_eq!(result.limit, Some(ResultLimit::Timeout(1)));
+
+        // Waiting on the consumer doesn't count
+        let mut batches = limit_batches(trickle(2, Duration::ZERO), limits);
+        while let Some(batch) = batches.next().await {
+            assert!(batch.unwrap().limit.is_none());
+            tokio::time::sleep(Duration::from_millis(1200)).await;
         }
     }
 }
@@ -0,0 +1,1163 @@
+use crate::db::{
+    collect_batches, ddl_script, limit_batches, with_timeout, ColumnDetails, DatabaseConnection,
+    DatabaseError, DatabaseInfo, IndexInfo, ObjectKind, QueryId, RowBatch, SchemaObject, TableInfo,
+    ViewInfo, STREAM_BATCH_SIZE,
+};
+use crate::models::{
+    CellValue, ColumnInfo, ConnectionConfig, ParamValue, QueryLimits, QueryResult,
+};
+use ::mongodb::bson::spec::BinarySubtype;
+use ::mongodb::bson::{doc, to_document, Bson, Document};
+use ::mongodb::results::CollectionSpecification;
+use ::mongodb::{Client, IndexModel};
+use async_trait::async_trait;
+use futures::stream::{BoxStream, StreamExt};
+use futures::TryStreamExt;
+use sqlx::types::BigDecimal;
+use uuid::Uuid;
+
+/// Database used when neither the connection nor the command names one
+const DEFAULT_DATABASE: &str = "test";
+
+/// Number of documents sampled to infer the fields of a collection
+const SAMPLE_SIZE: i64 = 100;
+
+pub struct MongoConnection {
+    client: Client,
+    default_database: String,
+    limits: QueryLimits,
+}
+
+/// A command typed into the editor. Parsed once per execution, so the
+/// size of the `Find` variant doesn't matter.
+#[derive(Debug, Clone, PartialEq)]
+#[allow(clippy::large_enum_variant)]
+enum MongoCommand {
+    Find {
+        collection: String,
+        filter: Document,
+        projection: Option<Document>,
+        sort: Option<Document>,
+        skip: Option<u64>,
+        limit: Option<i64>,
+    },
+    Aggregate {
+        collection: String,
+        pipeline: Vec<Document>,
+    },
+    Count {
+        collection: String,
+        filter: Document,
+    },
+    /// A raw command document, e.g. `{"listIndexes": "users"}`
+    RunCommand(Document),
+}
+
+/// A command plus the database picked by a leading `use <db>`
+#[derive(Debug, PartialEq)]
+struct ParsedCommand {
+    database: Option<String>,
+    command: MongoCommand,
+}
+
+impl ParsedCommand {
+    /// Lower a find's limit to one past `max_rows`, so that a cut-off result
+    /// can still be told apart from one that fits exactly
+    fn cap_rows(&mut self, max_rows: usize) {
+        if let MongoCommand::Find { limit, .. } = &mut self.command {
+            let cap = i64::try_from(max_rows)
+                .unwrap_or(i64::MAX)
+                .saturating_add(1);
+            *limit = Some(limit.map_or(cap, |limit| limit.min(cap)));
+        }
+    }
+}
+
+impl MongoConnection {
+    pub async fn connect(config: &ConnectionConfig) -> Result<Self, DatabaseError> {
+        let client = Client::with_uri_str(config.connection_string())
+            .await
+            .map_err(|e| DatabaseError::ConnectionFailed(e.to_string()))?;
+
+        let default_database = if config.database.is_empty() {
+            DEFAULT_DATABASE.to_string()
+        } else {
+            config.database.clone()
+        };
+
+        Ok(Self {
+            client,
+            default_database,
+            limits: config.limits,
+        })
+    }
+
+    async fn list_specifications(
+        &self,
+        database: &str,
+        collection_type: &str,
+    ) -> Result<Vec<CollectionSpecification>, DatabaseError> {
+        let specs: Vec<CollectionSpecification> = self
+            .client
+            .database(database)
+            .list_collections()
+            .filter(doc! { "type": collection_type })
+            .await
+            .map_err(query_failed)?
+            .try_collect()
+            .await
+            .map_err(query_failed)?;
+
+        let mut specs: Vec<_> = specs
+            .into_iter()
+            .filter(|spec| !spec.name.starts_with("system."))
+            .collect();
+        specs.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(specs)
+    }
+
+    /// Run a command and read its documents in batches, within the
+    /// connection's limits. Documents are read from the cursor as the
+    /// batches are, so reading stops once a limit is reached; a find also
+    /// asks the server for no more than `max_rows`.
+    fn read(&self, mut parsed: ParsedCommand) -> BoxStream<'_, Result<RowBatch, DatabaseError>> {
+        if let Some(max_rows) = self.limits.max_rows {
+            parsed.cap_rows(max_rows);
+        }
+        let batches = Box::pin(async_stream::try_stream! {
+            let documents = self.run(parsed).await?;
+            let mut batches = document_batches(documents);
+            while let Some(batch) = batches.next().await {
+                yield batch?;
+            }
+        });
+        limit_batches(batches, self.limits)
+    }
+
+    /// Start a command. Find and aggregate hand back their cursor, so
+    /// documents are read from the server as the stream is polled.
+    async fn run(
+        &self,
+        parsed: ParsedCommand,
+    ) -> Result<BoxStream<'static, Result<Document, DatabaseError>>, DatabaseError> {
+        let database = self
+            .client
+            .database(parsed.database.as_deref().unwrap_or(&self.default_database));
+        // Also stops the work on the server once the statement timeout passes
+        let max_time = self.limits.statement_timeout();
+
+        match parsed.command {
+            MongoCommand::Find {
+                collection,
+                filter,
+                projection,
+                sort,
+                skip,
+                limit,
+            } => Ok(database
+                .collection::<Document>(&collection)
+                .find(filter)
+                .optional(projection, |find, p| find.projection(p))
+                .optional(sort, |find, s| find.sort(s))
+                .optional(skip, |find, s| find.skip(s))
+                .optional(limit, |find, l| find.limit(l))
+                .optional(max_time, |find, t| find.max_time(t))
+                .await
+                .map_err(query_failed)?
+                .map_err(query_failed)
+                .boxed()),
+            MongoCommand::Aggregate {
+                collection,
+                pipeline,
+            } => Ok(database
+                .collection::<Document>(&collection)
+                .aggregate(pipeline)
+                .optional(max_time, |aggregate, t| aggregate.max_time(t))
+                .await
+                .map_err(query_failed)?
+                .map_err(query_failed)
+                .boxed()),
+            MongoCommand::Count { collection, filter } => {
+                let count = database
+                    .collection::<Document>(&collection)
+                    .count_documents(filter)
+                    .optional(max_time, |count, t| count.max_time(t))
+                    .await
+                    .map_err(query_failed)?;
+                Ok(futures::stream::iter([Ok(doc! { "count": count as i64 })]).boxed())
+            }
+            MongoCommand::RunCommand(command) => {
+                let reply = database.run_command(command).await.map_err(query_failed)?;
+                // Cursor-returning commands (find, aggregate, listIndexes...)
+                // are unwrapped to their first batch
+                let batch = reply
+                    .get_document("cursor")
+                    .ok()
+                    .and_then(|cursor| cursor.get_array("firstBatch").ok())
+                    .map(|batch| {
+                        batch
+                            .iter()
+                            .filter_map(|doc| doc.as_document().cloned())
+                            .collect()
+                    });
+                let documents = batch.unwrap_or_else(|| vec![reply]);
+                Ok(futures::stream::iter(documents.into_iter().map(Ok)).boxed())
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl DatabaseConnection for MongoConnection {
+    async fn test_connection(&self) -> Result<(), DatabaseError> {
+        self.client
+            .database("admin")
+            .run_command(doc! { "ping": 1 })
+            .await
+            .map_err(|e| DatabaseError::ConnectionFailed(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn list_databases(&self) -> Result<Vec<DatabaseInfo>, DatabaseError> {
+        let specs = self
+            .client
+            .list_databases()
+            .authorized_databases(true)
+            .await
+            .map_err(query_failed)?;
+
+        Ok(specs
+            .into_iter()
+            .map(|spec| DatabaseInfo {
+                name: spec.name,
+                character_set: None,
+            })
+            .collect())
+    }
+
+    async fn list_tables(&self, database: &str) -> Result<Vec<TableInfo>, DatabaseError> {
+        let specs = self.list_specifications(database, "collection").await?;
+
+        Ok(specs
+            .into_iter()
+            .map(|spec| TableInfo {
+                name: spec.name,
+                schema: None,
+                database: database.to_string(),
+                engine: None,
+                character_set: None,
+                collation: None,
+                row_count: None,
+                data_size: None,
+                columns: Vec::new(),
+            })
+            .collect())
+    }
+
+    async fn list_views(&self, database: &str) -> Result<Vec<ViewInfo>, DatabaseError> {
+        let specs = self.list_specifications(database, "view").await?;
+
+        Ok(specs
+            .into_iter()
+            .map(|spec| {
+                let definition = spec.options.view_on.map(|source| {
+                    let pipeline = Bson::from(spec.options.pipeline.unwrap_or_default());
+                    format!(
+                        "db.getCollection({}).aggregate({})",
+                        Bson::String(source).into_relaxed_extjson(),
+                        pipeline.into_relaxed_extjson()
+                    )
+                });
+                ViewInfo {
+                    name: spec.name,
+                    schema: None,
+                    definition,
+                }
+            })
+            .collect())
+    }
+
+    /// Collections have no fixed schema, so fields are inferred from a sample
+    async fn describe_table(
+        &self,
+        database: &str,
+        _schema: Option<&str>,
+        table: &str,
+    ) -> Result<TableInfo, DatabaseError> {
+        let collection = self.client.database(database).collection::<Document>(table);
+
+        let sample: Vec<Document> = collection
+            .aggregate([doc! { "$sample": { "size": SAMPLE_SIZE } }])
+            .await
+            .map_err(query_failed)?
+            .try_collect()
+            .await
+            .map_err(query_failed)?;
+
+        let row_count = collection.estimated_document_count().await.ok();
+
+        let columns = field_names(&sample)
+            .into_iter()
+            .map(|name| {
+                let mut types: Vec<&str> = Vec::new();
+                let mut nullable = false;
+                for doc in &sample {
+                    match doc.get(&name) {
+                        None | Some(Bson::Null) => nullable = true,
+                        Some(value) => {
+                            let type_name = bson_type_name(value);
+                            if !types.contains(&type_name) {
+                                types.push(type_name);
+                            }
+                        }
+                    }
+                }
+
+                ColumnDetails {
+                    is_primary_key: name == "_id",
+                    is_unique: name == "_id",
+                    name,
+                    data_type: types.join(" | "),
+                    nullable,
+                    default_value: None,
+                    is_auto_increment: false,
+                    comment: None,
+                }
+            })
+            .collect();
+
+        Ok(TableInfo {
+            name: table.to_string(),
+            schema: None,
+            database: database.to_string(),
+            engine: None,
+            character_set: None,
+            collation: None,
+            row_count,
+            data_size: None,
+            columns,
+        })
+    }
+
+    async fn list_indexes(
+        &self,
+        database: &str,
+        _schema: Option<&str>,
+        table: &str,
+    ) -> Result<Vec<IndexInfo>, DatabaseError> {
+        let models: Vec<IndexModel> = self
+            .client
+            .database(database)
+            .collection::<Document>(table)
+            .list_indexes()
+            .await
+            .map_err(query_failed)?
+            .try_collect()
+            .await
+            .map_err(query_failed)?;
+
+        let mut indexes: Vec<IndexInfo> = models
+            .into_iter()
+            .map(|model| {
+                let options = model.options.unwrap_or_default();
+                // Special indexes name their kind in place of a direction
+                let method = model.keys.values().find_map(|value| match value {
+                    Bson::String(kind) => Some(kind.clone()),
+                    _ => None,
+                });
+                let columns = model
+                    .keys
+                    .iter()
+                    .map(|(field, direction)| match direction.as_i64() {
+                        Some(-1) => format!("{} DESC", field),
+                        _ => field.clone(),
+                    })
+                    .collect();
+                let name = options.name.unwrap_or_default();
+                IndexInfo {
+                    is_primary: name == "_id_",
+                    is_unique: name == "_id_" || options.unique.unwrap_or(false),
+                    columns,
+                    method,
+                    name,
+                }
+            })
+            .collect();
+        indexes.sort_by_key(|index| !index.is_primary);
+        Ok(indexes)
+    }
+
+    /// The `create` command document that makes the collection or view
+    /// again, plus `createIndexes` for a collection's own indexes
+    async fn get_ddl(
+        &self,
+        database: &str,
+        object: &SchemaObject,
+    ) -> Result<String, DatabaseError> {
+        let collection_type = match object.kind {
+            ObjectKind::Table => "collection",
+            ObjectKind::View => "view",
+            _ => {
+                let kind = object.kind.keyword().to_lowercase();
+                return Err(DatabaseError::NotSupported(format!("DDL of a {}", kind)));
+            }
+        };
+        let spec = self
+            .list_specifications(database, collection_type)
+            .await?
+            .into_iter()
+            .find(|spec| spec.name == object.name)
+            .ok_or_else(|| query_failed(format!("No {} named {}", collection_type, object.name)))?;
+
+        let mut create = doc! { "create": &spec.name };
+        create.extend(to_document(&spec.options).map_err(query_failed)?);
+        let mut commands = vec![create];
+
+        if object.kind == ObjectKind::Table {
+            let models: Vec<IndexModel> = self
+                .client
+                .database(database)
+                .collection::<Document>(&spec.name)
+                .list_indexes()
+                .await
+                .map_err(query_failed)?
+                .try_collect()
+                .await
+                .map_err(query_failed)?;
+            let indexes = models
+                .iter()
+                .filter(|model| {
+                    let name = model.options.as_ref().and_then(|o| o.name.as_deref());
+                    name != Some("_id_")
+                })
+                .map(to_document)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(query_failed)?;
+            if !indexes.is_empty() {
+                commands.push(doc! { "createIndexes": &spec.name, "indexes": indexes });
+            }
+        }
+
+        Ok(ddl_script(commands.into_iter().map(|command| {
+            Bson::Document(command).into_relaxed_extjson().to_string()
+        })))
+    }
+
+    async fn execute_query(&self, sql: &str) -> Result<QueryResult, DatabaseError> {
+        with_timeout(
+            &self.limits,
+            collect_batches(self.read(parse_command(sql)?)),
+        )
+        .await
+    }
+
+    /// A command naming no database runs in `database`
+    fn stream_query<'a>(
+        &'a self,
+        database: Option<&'a str>,
+        sql: &'a str,
+        _params: &'a [ParamValue],
+        _id: QueryId,
+    ) -> BoxStream<'a, Result<RowBatch, DatabaseError>> {
+        match parse_command(sql) {
+            Ok(mut parsed) => {
+                parsed.database = parsed.database.or(database.map(str::to_string));
+                self.read(parsed)
+            }
+            Err(e) => futures::stream::once(async { Err(e) }).boxed(),
+        }
+    }
+
+    async fn get_table_data(
+        &self,
+        database: &str,
+        _schema: Option<&str>,
+        table: &str,
+        limit: u32,
+        offset: u32,
+    ) -> Result<QueryResult, DatabaseError> {
+        collect_batches(self.read(ParsedCommand {
+            database: Some(database.to_string()),
+            command: MongoCommand::Find {
+                collection: table.to_string(),
+                filter: Document::new(),
+                projection: None,
+                sort: None,
+                skip: Some(offset.into()),
+                limit: Some(limit.into()),
+            },
+        }))
+        .await
+    }
+
+    async fn close(&self) -> Result<(), DatabaseError> {
+        self.client.clone().shutdown().await;
+        Ok(())
+    }
+}
+
+/// Apply a builder step only when the option is set
+trait Optional: Sized {
+    fn optional<T>(self, value: Option<T>, apply: impl FnOnce(Self, T) -> Self) -> Self {
+        match value {
+            Some(value) => apply(self, value),
+            None => self,
+        }
+    }
+}
+
+impl<T> Optional for T {}
+
+fn query_failed(e: impl ToString) -> DatabaseError {
+    DatabaseError::QueryFailed(e.to_string())
+}
+
+fn invalid_command(message: &str) -> DatabaseError {
+    DatabaseError::QueryFailed(format!("Invalid MongoDB command: {}", message))
+}
+
+/// Flatten documents into a grid, in batches as the cursor yields them.
+/// Every top-level field becomes a column, in order of first appearance;
+/// nested documents and arrays are kept as JSON. Documents have no fixed
+/// fields, so the columns are those of the first batch, and fields that
+/// only appear later are left to the document view.
+fn document_batches(
+    documents: BoxStream<'_, Result<Document, DatabaseError>>,
+) -> BoxStream<'_, Result<RowBatch, DatabaseError>> {
+    let mut chunks = documents.ready_chunks(STREAM_BATCH_SIZE);
+    Box::pin(async_stream::try_stream! {
+        let mut columns: Option<Vec<ColumnInfo>> = None;
+        while let Some(chunk) = chunks.next().await {
+            let documents = chunk.into_iter().collect::<Result<Vec<_>, _>>()?;
+            let first = columns.is_none();
+            let columns = columns.get_or_insert_with(|| document_columns(&documents));
+            yield RowBatch {
+                columns: first.then(|| columns.clone()),
+                rows: document_rows(&documents, columns),
+                documents: document_texts(documents),
+                limit: None,
+            };
+        }
+    })
+}
+
+fn document_columns(documents: &[Document]) -> Vec<ColumnInfo> {
+    field_names(documents)
+        .into_iter()
+        .map(|name| ColumnInfo {
+            data_type: documents
+                .iter()
+                .find_map(|doc| doc.get(&name).filter(|v| !matches!(v, Bson::Null)))
+                .map(bson_type_name)
+                .unwrap_or("null")
+                .to_string(),
+            nullable: true,
+            is_primary_key: name == "_id",
+            name,
+        })
+        .collect()
+}
+
+fn document_rows(documents: &[Document], columns: &[ColumnInfo]) -> Vec<Vec<CellValue>> {
+    documents
+        .iter()
+        .map(|doc| {
+            columns
+                .iter()
+                .map(|column| {
+                    doc.get(&column.name)
+                        .map(bson_to_cell)
+                        .unwrap_or(CellValue::Null)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Each document as pretty-printed JSON, for the document view
+fn document_texts(documents: Vec<Document>) -> Vec<String> {
+    documents
+        .into_iter()
+        .map(|doc| {
+            serde_json::to_string_pretty(&Bson::Document(doc).into_relaxed_extjson())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+fn field_names(documents: &[Document]) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for doc in documents {
+        for key in doc.keys() {
+            if !names.contains(key) {
+                names.push(key.clone());
+            }
+        }
+    }
+    names
+}
+
+fn bson_to_cell(value: &Bson) -> CellValue {
+    match value {
+        Bson::Null | Bson::Undefined => CellValue::Null,
+        Bson::Boolean(b) => CellValue::Bool(*b),
+        Bson::Int32(i) => CellValue::Int((*i).into()),
+        Bson::Int64(i) => CellValue::Int(*i),
+        Bson::Double(f) => CellValue::Float(*f),
+        Bson::String(s) => CellValue::String(s.clone()),
+        Bson::ObjectId(oid) => CellValue::String(oid.to_hex()),
+        Bson::DateTime(dt) => {
+            match chrono::DateTime::from_timestamp_millis(dt.timestamp_millis()) {
+                Some(dt) => CellValue::DateTimeTz(dt.fixed_offset()),
+                // Outside the range chrono can represent
+                None => CellValue::String(dt.to_string()),
+            }
+        }
+        // Decimal128 prints large exponents in scientific notation
+        Bson::Decimal128(d) => {
+            let text = d.to_string();
+            match text.parse::<BigDecimal>() {
+                Ok(v) => CellValue::Decimal(v.to_plain_string()),
+                // NaN and Infinity
+                Err(_) => CellValue::Decimal(text),
+            }
+        }
+        Bson::Binary(b) if b.subtype == BinarySubtype::Uuid => match Uuid::from_slice(&b.bytes) {
+            Ok(uuid) => CellValue::Uuid(uuid),
+            Err(_) => CellValue::Bytes(b.bytes.clone()),
+        },
+        Bson::Binary(b) => CellValue::Bytes(b.bytes.clone()),
+        Bson::Document(_) | Bson::Array(_) => {
+            CellValue::Json(value.clone().into_relaxed_extjson().to_string())
+        }
+        other => CellValue::String(other.to_string()),
+    }
+}
+
+/// Type names as used by MongoDB's `$type` operator
+fn bson_type_name(value: &Bson) -> &'static str {
+    match value {
+        Bson::Double(_) => "double",
+        Bson::String(_) => "string",
+        Bson::Document(_) => "object",
+        Bson::Array(_) => "array",
+        Bson::Binary(_) => "binData",
+        Bson::Undefined => "undefined",
+        Bson::ObjectId(_) => "objectId",
+        Bson::Boolean(_) => "bool",
+        Bson::DateTime(_) => "date",
+        Bson::Null => "null",
+        Bson::RegularExpression(_) => "regex",
+        Bson::DbPointer(_) => "dbPointer",
+        Bson::JavaScriptCode(_) => "javascript",
+        Bson::Symbol(_) => "symbol",
+        Bson::JavaScriptCodeWithScope(_) => "javascriptWithScope",
+        Bson::Int32(_) => "int",
+        Bson::Timestamp(_) => "timestamp",
+        Bson::Int64(_) => "long",
+        Bson::Decimal128(_) => "decimal",
+        Bson::MinKey => "minKey",
+        Bson::MaxKey => "maxKey",
+    }
+}
+
+/// Parse shell-style editor text:
+///
+/// ```text
+/// use shop;
+/// db.orders.find({ status: "open" }, { total: 1 }).sort({ total: -1 }).limit(20)
+/// db.orders.aggregate([{ $group: { _id: "$status", n: { $sum: 1 } } }])
+/// db.getCollection("order.items").countDocuments({})
+/// { "listIndexes": "orders" }
+/// ```
+fn parse_command(text: &str) -> Result<ParsedCommand, DatabaseError> {
+    let mut rest = text.trim();
+    let mut database = None;
+
+    if let Some(after) = rest
+        .strip_prefix("use")
+        .filter(|after| after.starts_with(char::is_whitespace))
+    {
+        let end = after.find([';', '\n']).unwrap_or(after.len());
+        database = Some(after[..end].trim().to_string());
+        rest = after[end..].trim_start_matches(';').trim();
+    }
+
+    let rest = rest.trim_end_matches(|c: char| c == ';' || c.is_whitespace());
+    if rest.starts_with('{') {
+        return Ok(ParsedCommand {
+            database,
+            command: MongoCommand::RunCommand(parse_document(rest)?),
+        });
+    }
+
+    let rest = rest.strip_prefix("db.").ok_or_else(|| {
+        invalid_command("expected `db.<collection>.<method>(...)` or a command document")
+    })?;
+
+    let (collection, rest) = if rest.starts_with("getCollection") {
+        let call = format!(".{}", rest);
+        let (_, args, after) = parse_call(&call)?;
+        let name = match parse_value(&args)? {
+            Bson::String(name) => name,
+            _ => return Err(invalid_command("getCollection expects a name")),
+        };
+        let consumed = rest.len() - after.len();
+        (name, &rest[consumed..])
+    } else {
+        // Collection names may contain dots, so the method is whatever
+        // follows the last dot before the first parenthesis
+        let paren = rest
+            .find('(')
+            .ok_or_else(|| invalid_command("missing method call"))?;
+        let dot = rest[..paren]
+            .rfind('.')
+            .ok_or_else(|| invalid_command("missing method call"))?;
+        (rest[..dot].to_string(), &rest[dot..])
+    };
+
+    let (method, args, mut rest) = parse_call(rest)?;
+    let args = split_args(&args)
+        .into_iter()
+        .map(parse_value)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let document_arg = |index: usize| -> Result<Option<Document>, DatabaseError> {
+        match args.get(index) {
+            None => Ok(None),
+            Some(Bson::Document(doc)) => Ok(Some(doc.clone())),
+            Some(_) => Err(invalid_command(&format!(
+                "argument {} of {} must be a document",
+                index + 1,
+                method
+            ))),
+        }
+    };
+
+    let mut command = match method.as_str() {
+        "find" | "findOne" => MongoCommand::Find {
+            collection,
+            filter: document_arg(0)?.unwrap_or_default(),
+            projection: document_arg(1)?,
+            sort: None,
+            skip: None,
+            limit: (method == "findOne").then_some(1),
+        },
+        "aggregate" => {
+            let pipeline = match args.first() {
+                Some(Bson::Array(stages)) => stages
+                    .iter()
+                    .map(|stage| {
+                        stage
+                            .as_document()
+                            .cloned()
+                            .ok_or_else(|| invalid_command("pipeline stages must be documents"))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?,
+                None => Vec::new(),
+                Some(_) => return Err(invalid_command("aggregate expects an array of stages")),
+            };
+            MongoCommand::Aggregate {
+                collection,
+                pipeline,
+            }
+        }
+        "countDocuments" | "count" => MongoCommand::Count {
+            collection,
+            filter: document_arg(0)?.unwrap_or_default(),
+        },
+        other => {
+            return Err(invalid_command(&format!("unsupported method `{}`", other)));
+        }
+    };
+
+    // Cursor modifiers chained after find()
+    while !rest.trim().is_empty() {
+        let (modifier, args, after) = parse_call(rest)?;
+        let value = if args.trim().is_empty() {
+            None
+        } else {
+            Some(parse_value(&args)?)
+        };
+
+        match (&mut command, modifier.as_str(), value) {
+            (MongoCommand::Find { limit, .. }, "limit", Some(v)) => {
+                *limit = Some(bson_integer(&v)?);
+            }
+            (MongoCommand::Find { skip, .. }, "skip", Some(v)) => {
+                *skip = Some(bson_integer(&v)?.max(0) as u64);
+            }
+            (MongoCommand::Find { sort, .. }, "sort", Some(Bson::Document(doc))) => {
+                *sort = Some(doc);
+            }
+            (MongoCommand::Find { projection, .. }, "projection", Some(Bson::Document(doc))) => {
+                *projection = Some(doc);
+            }
+            (_, "pretty" | "toArray", None) => {}
+            (_, other, _) => {
+                return Err(invalid_command(&format!(
+                    "unsupported modifier `{}`",
+                    other
+                )));
+            }
+        }
+        rest = after;
+    }
+
+    Ok(ParsedCommand { database, command })
+}
+
+fn bson_integer(value: &Bson) -> Result<i64, DatabaseError> {
+    match value {
+        Bson::Int32(i) => Ok((*i).into()),
+        Bson::Int64(i) => Ok(*i),
+        Bson::Double(f) if f.fract() == 0.0 => Ok(*f as i64),
+        _ => Err(invalid_command("expected an integer")),
+    }
+}
+
+/// Split `.method(args)rest` into its parts
+fn parse_call(text: &str) -> Result<(String, String, &str), DatabaseError> {
+    let text = text
+        .trim_start()
+        .strip_prefix('.')
+        .ok_or_else(|| invalid_command("expected `.method(...)`"))?;
+    let open = text
+        .find('(')
+        .ok_or_else(|| invalid_command("expected `(`"))?;
+    let close = matching_bracket(text, open)?;
+    Ok((
+        text[..open].trim().to_string(),
+        text[open + 1..close].to_string(),
+        &text[close + 1..],
+    ))
+}
+
+/// Index of the bracket closing the one at `open`, skipping quoted strings
+fn matching_bracket(text: &str, open: usize) -> Result<usize, DatabaseError> {
+    let mut depth = 0usize;
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+
+    for (i, c) in text[open..].char_indices() {
+        if let Some(q) = quote {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == q {
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            '"' | '\'' => quote = Some(c),
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(open + i);
+                }
+            }
+            _ => {}
+        }
+    }
+    Err(invalid_command("unbalanced brackets"))
+}
+
+/// Split call arguments on top-level commas
+fn split_args(text: &str) -> Vec<&str> {
+    let mut args = Vec::new();
+    let mut depth = 0usize;
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    let mut start = 0;
+
+    for (i, c) in text.char_indices() {
+        if let Some(q) = quote {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == q {
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            '"' | '\'' => quote = Some(c),
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                args.push(text[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+
+    let last = text[start..].trim();
+    if !last.is_empty() || !args.is_empty() {
+        args.push(last);
+    }
+    args
+}
+
+fn parse_document(text: &str) -> Result<Document, DatabaseError> {
+    match parse_value(text)? {
+        Bson::Document(doc) => Ok(doc),
+        _ => Err(invalid_command("expected a document")),
+    }
+}
+
+/// Parse a shell literal as extended JSON
+fn parse_value(text: &str) -> Result<Bson, DatabaseError> {
+    let json: serde_json::Value =
+        serde_json::from_str(&relaxed_json(text)).map_err(|e| invalid_command(&e.to_string()))?;
+    Bson::try_from(json).map_err(|e| invalid_command(&e.to_string()))
+}
+
+/// Rewrite shell syntax into strict JSON: quote bare keys, turn single-quoted
+/// strings into double-quoted ones, and expand `ObjectId("...")` and
+/// `ISODate("...")` into their extended JSON forms.
+fn relaxed_json(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::with_capacity(text.len());
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '"' || c == '\'' {
+            let (literal, next) = read_string(&chars, i);
+            out.push_str(&Bson::String(literal).into_relaxed_extjson().to_string());
+            i = next;
+        } else if c.is_alphabetic() || c == '_' || c == '$' {
+            let start = i;
+            while i < chars.len()
+                && (chars[i].is_alphanumeric() || matches!(chars[i], '_' | '$' | '.'))
+            {
+                i += 1;
+            }
+            let ident: String = chars[start..i].iter().collect();
+            let next = skip_whitespace(&chars, i);
+
+            match (ident.as_str(), chars.get(next)) {
+                (_, Some(':')) => {
+                    out.push_str(&Bson::String(ident).into_relaxed_extjson().to_string());
+                }
+                ("ObjectId" | "ISODate", Some('(')) => {
+                    let arg_start = skip_whitespace(&chars, next + 1);
+                    if matches!(chars.get(arg_start), Some('"' | '\'')) {
+                        let (literal, after) = read_string(&chars, arg_start);
+                        let close = skip_whitespace(&chars, after);
+                        if chars.get(close) == Some(&')') {
+                            let key = if ident == "ObjectId" { "$oid" } else { "$date" };
+                            out.push_str(&format!(
+                                "{{\"{}\": {}}}",
+                                key,
+                                Bson::String(literal).into_relaxed_extjson()
+                            ));
+                            i = close + 1;
+                            continue;
+                        }
+                    }
+                    out.push_str(&ident);
+                }
+                _ => out.push_str(&ident),
+            }
+        } else {
+            out.push(c);
+            i += 1;
+        }
+    }
+    out
+}
+
+fn skip_whitespace(chars: &[char], mut i: usize) -> usize {
+    while i < chars.len() && chars[i].is_whitespace() {
+        i += 1;
+    }
+    i
+}
+
+/// Read a quoted string starting at `start`, returning its contents and the
+/// index just past the closing quote
+fn read_string(chars: &[char], start: usize) -> (String, usize) {
+    let quote = chars[start];
+    let mut literal = String::new();
+    let mut i = start + 1;
+
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if i + 1 < chars.len() => {
+                literal.push(match chars[i + 1] {
+                    'n' => '\n',
+                    't' => '\t',
+                    'r' => '\r',
+                    other => other,
+                });
+                i += 2;
+            }
+            c if c == quote => return (literal, i + 1),
+            c => {
+                literal.push(c);
+                i += 1;
+            }
+        }
+    }
+    (literal, i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_find_with_modifiers() {
+        let parsed = parse_command(
+            "use shop;\ndb.orders.find({ status: 'open', total: { $gt: 10 } }, { total: 1 })\n  .sort({ total: -1 }).skip(5).limit(20);",
+        )
+        .unwrap();
+
+        assert_eq!(parsed.database.as_deref(), Some("shop"));
+        assert_eq!(
+            parsed.command,
+            MongoCommand::Find {
+                collection: "orders".to_string(),
+                filter: doc! { "status": "open", "total": { "$gt": 10 } },
+                projection: Some(doc! { "total": 1 }),
+                sort: Some(doc! { "total": -1 }),
+                skip: Some(5),
+                limit: Some(20),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_aggregate_and_dotted_collection() {
+        let parsed = parse_command(
+            r#"db.order.items.aggregate([{ $match: { _id: ObjectId("64b7f0c2a1b2c3d4e5f60718") } }])"#,
+        )
+        .unwrap();
+
+        match parsed.command {
+            MongoCommand::Aggregate {
+                collection,
+                pipeline,
+            } => {
+                assert_eq!(collection, "order.items");
+                let id = pipeline[0]
+                    .get_document("$match")
+                    .unwrap()
+                    .get_object_id("_id")
+                    .unwrap();
+                assert_eq!(id.to_hex(), "64b7f0c2a1b2c3d4e5f60718");
+            }
+            other => panic!("unexpected command {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_get_collection_and_raw_command() {
+        let parsed = parse_command(r#"db.getCollection("a, b").countDocuments()"#).unwrap();
+        assert_eq!(
+            parsed.command,
+            MongoCommand::Count {
+                collection: "a, b".to_string(),
+                filter: Document::new(),
+            }
+        );
+
+        let parsed = parse_command(r#"{ "listIndexes": "orders" }"#).unwrap();
+        assert_eq!(
+            parsed.command,
+            MongoCommand::RunCommand(doc! { "listIndexes": "orders" })
+        );
+
+        assert!(parse_command("SELECT * FROM orders").is_err());
+        assert!(parse_command("db.orders.drop()").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_document_batches() {
+        let documents = futures::stream::iter([
+            Ok(doc! { "_id": 1, "name": "ada", "tags": ["x"] }),
+            Ok(doc! { "_id": 2, "address": { "city": "Oslo" } }),
+        ]);
+        let result = collect_batches(document_batches(documents.boxed()))
+            .await
+            .unwrap();
+
+        let names: Vec<_> = result.columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["_id", "name", "tags", "address"]);
+        assert!(result.columns[0].is_primary_key);
+        assert!(matches!(result.rows[1][1], CellValue::Null));
+        assert!(matches!(&result.rows[1][3], CellValue::Json(j) if j == r#"{"city":"Oslo"}"#));
+        assert_eq!(result.documents.len(), 2);
+    }
+
+    #[test]
+    fn test_cap_rows() {
+        let limit = |sql: &str| {
+            let mut parsed = parse_command(sql).unwrap();
+            parsed.cap_rows(100);
+            match parsed.command {
+                MongoCommand::Find { limit, .. } => limit,
+                _ => None,
+            }
+        };
+        assert_eq!(limit("db.t.find()"), Some(101));
+        assert_eq!(limit("db.t.find().limit(1000)"), Some(101));
+        assert_eq!(limit("db.t.find().limit(10)"), Some(10));
+        assert_eq!(limit("db.t.countDocuments()"), None);
+    }
+
+    #[tokio::test]
+    async fn test_document_batches_read_lazily() {
+        use crate::models::ResultLimit;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let read = AtomicUsize::new(0);
+        let documents = futures::stream::iter(0..1200)
+            .map(|i| {
+                read.fetch_add(1, Ordering::Relaxed);
+                Ok(match i {
+                    700 => doc! { "_id": i, "late": true },
+                    _ => doc! { "_id": i },
+                })
+            })
+            .boxed();
+        let limits = QueryLimits {
+            max_rows: Some(600),
+            ..Default::default()
+        };
+        let batches: Vec<RowBatch> = limit_batches(document_batches(documents), limits)
+            .try_collect()
+            .await
+            .unwrap();
+
+        assert_eq!(batches.len(), 2);
+        let columns = batches[0].columns.as_ref().unwrap();
+        assert_eq!(columns.len(), 1);
+        assert!(batches[1].columns.is_none());
+        assert!(batches.iter().all(|b| b.rows.iter().all(|r| r.len() == 1)));
+        assert_eq!(batches[1].rows.len(), 100);
+        assert_eq!(batches[1].limit, Some(ResultLimit::MaxRows(600)));
+        // The rest of the cursor is never read
+        assert_eq!(read.load(Ordering::Relaxed), 1000);
+    }
+
+    #[test]
+    fn test_decimal128_is_exact() {
+        let big: ::mongodb::bson::Decimal128 = "12345678901234567890.000000001".parse().unwrap();
+        assert!(matches!(
+            bson_to_cell(&Bson::Decimal128(big)),
+            CellValue::Decimal(d) if d == "12345678901234567890.000000001"
+        ));
+        let scientific: ::mongodb::bson::Decimal128 = "1.5E+3".parse().unwrap();
+        assert!(matches!(
+            bson_to_cell(&Bson::Decimal128(scientific)),
+            CellValue::Decimal(d) if d == "1500"
+        ));
+    }
+}
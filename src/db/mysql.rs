@@ -1,27 +1,136 @@
+use crate::db::quote::{bind_params, quote_ident, quote_qualified};
 use crate::db::{
-    ColumnDetails, DatabaseConnection, DatabaseError, DatabaseInfo, TableInfo, ViewInfo,
+    batch_rows, bit_string, collect_batches, ddl_script, decode_cell, decode_cell_unchecked,
+    described_columns, limit_batches, with_timeout, wkb, ColumnDetails, DatabaseConnection,
+    DatabaseError, DatabaseInfo, ForeignKeyInfo, IndexInfo, ObjectKind, QueryId, RowBatch,
+    RunningQueries, RunningQuery, SchemaObject, SqlConnection, SqlSession, TableInfo, TriggerInfo,
+    ViewInfo,
 };
-use crate::models::{CellValue, ColumnInfo, ConnectionConfig, QueryResult};
+use crate::models::{
+    CellValue, ColumnInfo, ConnectionConfig, Interval, ParamValue, QueryLimits, QueryResult,
+};
+use crate::sql::Dialect;
 use async_trait::async_trait;
-use sqlx::mysql::{MySqlPool, MySqlPoolOptions, MySqlRow};
-use sqlx::{Column, Row, TypeInfo};
-use std::time::Instant;
+use chrono::{DateTime, NaiveTime, Utc};
+use futures::stream::{BoxStream, StreamExt};
+use sqlx::mysql::types::MySqlTime;
+use sqlx::mysql::{MySql, MySqlPool, MySqlPoolOptions, MySqlRow};
+use sqlx::pool::PoolConnection;
+use sqlx::types::BigDecimal;
+use sqlx::{Column, Executor, Row, TypeInfo};
+use uuid::Uuid;
 
 pub struct MySqlConnection {
     pool: MySqlPool,
+    running: RunningQueries,
+    limits: QueryLimits,
+    /// The schema pooled connections start on, empty when there is none
+    database: String,
 }
 
 impl MySqlConnection {
     pub async fn connect(config: &ConnectionConfig) -> Result<Self, DatabaseError> {
         let url = config.connection_string();
 
+        let timeout_secs = config.limits.statement_timeout_secs;
+
         let pool = MySqlPoolOptions::new()
             .max_connections(5)
+            .after_connect(move |conn, _meta| {
+                Box::pin(async move {
+                    if let Some(secs) = timeout_secs {
+                        // Let the server abort long SELECTs on its own. MySQL
+                        // counts in milliseconds, MariaDB in seconds; the
+                        // client-side deadline covers servers with neither.
+                        let mysql = format!("SET SESSION max_execution_time = {}", secs * 1000);
+                        if conn.execute(mysql.as_str()).await.is_err() {
+                            let mariadb = format!("SET SESSION max_statement_time = {}", secs);
+                            let _ = conn.execute(mariadb.as_str()).await;
+                        }
+                    }
+                    Ok(())
+                })
+            })
             .connect(&url)
             .await
             .map_err(|e| DatabaseError::ConnectionFailed(e.to_string()))?;
 
-        Ok(Self { pool })
+        Ok(Self {
+            pool,
+            running: RunningQueries::default(),
+            limits: config.limits,
+            database: config.database.clone(),
+        })
+    }
+
+    /// Stop whatever `session` is running. The session itself is busy, so
+    /// this goes through another pooled connection.
+    async fn kill_query(&self, session: i64) -> Result<(), DatabaseError> {
+        sqlx::query(&format!("KILL QUERY {}", session))
+            .execute(&self.pool)
+            .await
+            .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+
+        Ok(())
+    }
+
+    async fn open_session(
+        &self,
+        database: Option<&str>,
+        id: QueryId,
+    ) -> Result<MySqlSession<'_>, DatabaseError> {
+        let (conn, thread_id) = self.acquire_session(database).await?;
+        Ok(MySqlSession {
+            owner: self,
+            conn,
+            thread_id,
+            _running: self.running.track(id, thread_id),
+        })
+    }
+
+    /// Take a connection from the pool along with its server thread id,
+    /// which `KILL QUERY` needs to find it again. A connection switched to
+    /// another `database` is closed once done rather than pooled.
+    async fn acquire_session(
+        &self,
+        database: Option<&str>,
+    ) -> Result<(PoolConnection<MySql>, i64), DatabaseError> {
+        let mut conn = self
+            .pool
+            .acquire()
+            .await
+            .map_err(|e| DatabaseError::ConnectionFailed(e.to_string()))?;
+        if let Some(database) = database.filter(|db| !db.is_empty() && *db != self.database) {
+            conn.close_on_drop();
+            let sql = format!("USE {}", quote_ident(database, Dialect::MySql));
+            conn.execute(sql.as_str())
+                .await
+                .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+        }
+        let session: u64 = sqlx::query_scalar("SELECT CONNECTION_ID()")
+            .fetch_one(&mut *conn)
+            .await
+            .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+
+        Ok((conn, session as i64))
+    }
+
+    /// Read a whole result within the connection's limits. A connection
+    /// left with unread rows is closed rather than handed back to the pool.
+    async fn fetch(&self, sql: &str) -> Result<QueryResult, DatabaseError> {
+        let mut conn = self
+            .pool
+            .acquire()
+            .await
+            .map_err(|e| DatabaseError::ConnectionFailed(e.to_string()))?;
+        let columns = describe_columns(&mut conn, sql).await;
+        let rows = sqlx::query(sql).fetch(&mut *conn);
+        let batches = limit_batches(batch_rows(rows, columns, Self::row_to_values), self.limits);
+        let result = collect_batches(batches).await?;
+        if result.limit.is_some() {
+            conn.close_on_drop();
+        }
+        Ok(result)
     }
 
     fn row_to_values(row: &MySqlRow) -> Vec<CellValue> {
@@ -31,39 +140,47 @@ impl MySqlConnection {
             let type_name = col.type_info().name();
 
             let value = match type_name {
-                "BOOLEAN" | "BOOL" => row
-                    .try_get::<bool, _>(i)
-                    .map(CellValue::Bool)
-                    .unwrap_or(CellValue::Null),
-                "TINYINT" | "SMALLINT" | "INT" | "MEDIUMINT" | "BIGINT" => row
-                    .try_get::<i64, _>(i)
-                    .map(CellValue::Int)
-                    .unwrap_or(CellValue::Null),
+                "BOOLEAN" | "BOOL" => decode_cell(row, i, CellValue::Bool),
+                "TINYINT" | "SMALLINT" | "INT" | "MEDIUMINT" | "BIGINT" => {
+                    decode_cell(row, i, CellValue::Int)
+                }
                 "TINYINT UNSIGNED" | "SMALLINT UNSIGNED" | "INT UNSIGNED"
-                | "MEDIUMINT UNSIGNED" | "BIGINT UNSIGNED" => row
-                    .try_get::<i64, _>(i)
-                    .map(CellValue::Int)
-                    .unwrap_or(CellValue::Null),
-                "FLOAT" | "DOUBLE" | "DECIMAL" => row
-                    .try_get::<f64, _>(i)
-                    .map(CellValue::Float)
-                    .unwrap_or(CellValue::Null),
-                "DATE" | "TIME" | "DATETIME" | "TIMESTAMP" => row
-                    .try_get::<String, _>(i)
-                    .map(CellValue::DateTime)
-                    .unwrap_or(CellValue::Null),
-                "JSON" => row
-                    .try_get::<String, _>(i)
-                    .map(CellValue::Json)
-                    .unwrap_or(CellValue::Null),
-                "BLOB" | "BINARY" | "VARBINARY" | "TINYBLOB" | "MEDIUMBLOB" | "LONGBLOB" => row
-                    .try_get::<Vec<u8>, _>(i)
-                    .map(CellValue::Bytes)
-                    .unwrap_or(CellValue::Null),
-                _ => row
-                    .try_get::<String, _>(i)
-                    .map(CellValue::String)
-                    .unwrap_or(CellValue::Null),
+                | "MEDIUMINT UNSIGNED" => decode_cell(row, i, CellValue::Int),
+                "BIGINT UNSIGNED" => decode_cell(row, i, CellValue::UInt),
+                "FLOAT" | "DOUBLE" => decode_cell(row, i, CellValue::Float),
+                "DECIMAL" => decode_cell(row, i, |v: BigDecimal| {
+                    CellValue::Decimal(v.to_plain_string())
+                }),
+                "DATE" => decode_cell(row, i, CellValue::Date),
+                "DATETIME" => decode_cell(row, i, CellValue::DateTime),
+                // The session runs in UTC, so TIMESTAMP values arrive in UTC
+                "TIMESTAMP" => decode_cell(row, i, |v: DateTime<Utc>| {
+                    CellValue::DateTimeTz(v.fixed_offset())
+                }),
+                "TIME" => decode_cell(row, i, time_to_cell),
+                "YEAR" => decode_cell_unchecked(row, i, |v: u16| CellValue::Int(v.into())),
+                "BIT" => decode_cell_unchecked(row, i, |v: Vec<u8>| {
+                    CellValue::Bits(bit_string(&v, v.len() * 8))
+                }),
+                "ENUM" => decode_cell(row, i, CellValue::Enum),
+                "GEOMETRY" => decode_cell_unchecked(row, i, |v: Vec<u8>| {
+                    wkb::from_mysql(&v)
+                        .unwrap_or_else(|| CellValue::Undecodable(type_name.to_string()))
+                }),
+                "JSON" => decode_cell(row, i, CellValue::Json),
+                // BINARY(16) is the usual way to store a UUID
+                "BINARY" => decode_cell(row, i, |v: Vec<u8>| match Uuid::from_slice(&v) {
+                    Ok(uuid) => CellValue::Uuid(uuid),
+                    Err(_) => CellValue::Bytes(v),
+                }),
+                "BLOB" | "VARBINARY" | "TINYBLOB" | "MEDIUMBLOB" | "LONGBLOB" => {
+                    decode_cell(row, i, CellValue::Bytes)
+                }
+                "SET" => decode_cell(row, i, |v: String| {
+                    let members = v.split(',').filter(|m| !m.is_empty());
+                    CellValue::Set(members.map(String::from).collect())
+                }),
+                _ => decode_cell(row, i, CellValue::String),
             };
             values.push(value);
         }
@@ -71,6 +188,35 @@ impl MySqlConnection {
     }
 }
 
+/// A TIME value, which MySQL also uses for durations: anything outside a
+/// day, or negative, becomes an interval
+fn time_to_cell(time: MySqlTime) -> CellValue {
+    if time.is_valid_time_of_day() {
+        if let Ok(time) = NaiveTime::try_from(time) {
+            return CellValue::Time(time);
+        }
+    }
+    let micros = (i64::from(time.hours()) * 3600
+        + i64::from(time.minutes()) * 60
+        + i64::from(time.seconds()))
+        * 1_000_000
+        + i64::from(time.microseconds());
+    CellValue::Interval(Interval {
+        months: 0,
+        days: 0,
+        microseconds: if time.is_negative() { -micros } else { micros },
+    })
+}
+
+/// Column metadata from the server's description of `sql`, or `None` for
+/// statements it can't prepare, whose columns then come from the first row.
+/// sqlx doesn't say which table a column comes from, so primary keys are
+/// left unmarked.
+async fn describe_columns(conn: &mut PoolConnection<MySql>, sql: &str) -> Option<Vec<ColumnInfo>> {
+    let describe = conn.describe(sql).await.ok()?;
+    Some(described_columns(&describe))
+}
+
 #[async_trait]
 impl DatabaseConnection for MySqlConnection {
     async fn test_connection(&self) -> Result<(), DatabaseError> {
@@ -101,17 +247,15 @@ impl DatabaseConnection for MySqlConnection {
     }
 
     async fn list_tables(&self, database: &str) -> Result<Vec<TableInfo>, DatabaseError> {
-        let query = format!(
+        let rows: Vec<MySqlRow> = sqlx::query(
             "SELECT TABLE_NAME, ENGINE, TABLE_ROWS, DATA_LENGTH 
              FROM information_schema.TABLES 
-             WHERE TABLE_SCHEMA = '{}' AND TABLE_TYPE = 'BASE TABLE'",
-            database
-        );
-
-        let rows: Vec<MySqlRow> = sqlx::query(&query)
-            .fetch_all(&self.pool)
-            .await
-            .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+             WHERE TABLE_SCHEMA = ? AND TABLE_TYPE = 'BASE TABLE'",
+        )
+        .bind(database)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
 
         let tables = rows
             .iter()
@@ -119,8 +263,11 @@ impl DatabaseConnection for MySqlConnection {
                 let name: String = row.try_get(0).ok()?;
                 Some(TableInfo {
                     name,
+                    schema: None,
                     database: database.to_string(),
                     engine: row.try_get(1).ok(),
+                    character_set: None,
+                    collation: None,
                     row_count: row.try_get::<i64, _>(2).ok().map(|v| v as u64),
                     data_size: row.try_get::<i64, _>(3).ok().map(|v| v as u64),
                     columns: Vec::new(),
@@ -132,17 +279,15 @@ impl DatabaseConnection for MySqlConnection {
     }
 
     async fn list_views(&self, database: &str) -> Result<Vec<ViewInfo>, DatabaseError> {
-        let query = format!(
+        let rows: Vec<MySqlRow> = sqlx::query(
             "SELECT TABLE_NAME, VIEW_DEFINITION 
              FROM information_schema.VIEWS 
-             WHERE TABLE_SCHEMA = '{}'",
-            database
-        );
-
-        let rows: Vec<MySqlRow> = sqlx::query(&query)
-            .fetch_all(&self.pool)
-            .await
-            .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+             WHERE TABLE_SCHEMA = ?",
+        )
+        .bind(database)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
 
         let views = rows
             .iter()
@@ -150,6 +295,7 @@ impl DatabaseConnection for MySqlConnection {
                 let name: String = row.try_get(0).ok()?;
                 Some(ViewInfo {
                     name,
+                    schema: None,
                     definition: row.try_get(1).ok(),
                 })
             })
@@ -158,20 +304,24 @@ impl DatabaseConnection for MySqlConnection {
         Ok(views)
     }
 
-    async fn describe_table(&self, database: &str, table: &str) -> Result<TableInfo, DatabaseError> {
-        let query = format!(
+    async fn describe_table(
+        &self,
+        database: &str,
+        _schema: Option<&str>,
+        table: &str,
+    ) -> Result<TableInfo, DatabaseError> {
+        let rows: Vec<MySqlRow> = sqlx::query(
             "SELECT COLUMN_NAME, COLUMN_TYPE, IS_NULLABLE, COLUMN_DEFAULT, 
                     COLUMN_KEY, EXTRA, COLUMN_COMMENT
              FROM information_schema.COLUMNS 
-             WHERE TABLE_SCHEMA = '{}' AND TABLE_NAME = '{}'
+             WHERE TABLE_SCHEMA = ? AND TABLE_NAME = ?
              ORDER BY ORDINAL_POSITION",
-            database, table
-        );
-
-        let rows: Vec<MySqlRow> = sqlx::query(&query)
-            .fetch_all(&self.pool)
-            .await
-            .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+        )
+        .bind(database)
+        .bind(table)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
 
         let columns = rows
             .iter()
@@ -188,89 +338,394 @@ impl DatabaseConnection for MySqlConnection {
                     nullable: nullable == "YES",
                     default_value: row.try_get(3).ok(),
                     is_primary_key: column_key == "PRI",
+                    is_unique: column_key == "UNI",
                     is_auto_increment: extra.contains("auto_increment"),
                     comment: row.try_get(6).ok(),
                 })
             })
             .collect();
 
+        let status: Option<MySqlRow> = sqlx::query(
+            "SELECT t.ENGINE, c.CHARACTER_SET_NAME, t.TABLE_COLLATION, t.TABLE_ROWS, t.DATA_LENGTH
+             FROM information_schema.TABLES t
+             LEFT JOIN information_schema.COLLATIONS c ON c.COLLATION_NAME = t.TABLE_COLLATION
+             WHERE t.TABLE_SCHEMA = ? AND t.TABLE_NAME = ?",
+        )
+        .bind(database)
+        .bind(table)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+        let text = |i: usize| status.as_ref().and_then(|row| row.try_get(i).ok());
+        let size = |i: usize| {
+            status
+                .as_ref()
+                .and_then(|row| row.try_get::<u64, _>(i).ok())
+        };
+
         Ok(TableInfo {
             name: table.to_string(),
+            schema: None,
             database: database.to_string(),
-            engine: None,
-            row_count: None,
-            data_size: None,
+            engine: text(0),
+            character_set: text(1),
+            collation: text(2),
+            row_count: size(3),
+            data_size: size(4),
             columns,
         })
     }
 
-    async fn execute_query(&self, sql: &str) -> Result<QueryResult, DatabaseError> {
-        let start = Instant::now();
-
-        let rows: Vec<MySqlRow> = sqlx::query(sql)
-            .fetch_all(&self.pool)
-            .await
-            .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
-
-        let execution_time_ms = start.elapsed().as_millis() as u64;
+    async fn list_indexes(
+        &self,
+        database: &str,
+        _schema: Option<&str>,
+        table: &str,
+    ) -> Result<Vec<IndexInfo>, DatabaseError> {
+        // Functional key parts have no column name; MySQL 8 exposes their
+        // expression in a column older servers lack, so they show as such
+        let rows: Vec<MySqlRow> = sqlx::query(
+            "SELECT INDEX_NAME, COALESCE(COLUMN_NAME, '(expression)'),
+                    CAST(NON_UNIQUE AS SIGNED), INDEX_TYPE
+             FROM information_schema.STATISTICS
+             WHERE TABLE_SCHEMA = ? AND TABLE_NAME = ?
+             ORDER BY INDEX_NAME <> 'PRIMARY', INDEX_NAME, SEQ_IN_INDEX",
+        )
+        .bind(database)
+        .bind(table)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+
+        let mut indexes: Vec<IndexInfo> = Vec::new();
+        for row in &rows {
+            let Ok(name) = row.try_get::<String, _>(0) else {
+                continue;
+            };
+            let column: String = row.try_get(1).unwrap_or_default();
+            match indexes.last_mut() {
+                Some(index) if index.name == name => index.columns.push(column),
+                _ => indexes.push(IndexInfo {
+                    is_primary: name == "PRIMARY",
+                    is_unique: row.try_get::<i64, _>(2).map(|v| v == 0).unwrap_or(false),
+                    method: row.try_get(3).ok(),
+                    columns: vec![column],
+                    name,
+                }),
+            }
+        }
+        Ok(indexes)
+    }
 
-        if rows.is_empty() {
-            return Ok(QueryResult {
-                columns: Vec::new(),
-                rows: Vec::new(),
-                affected_rows: None,
-                execution_time_ms,
-            });
+    async fn list_foreign_keys(
+        &self,
+        database: &str,
+        _schema: Option<&str>,
+        table: &str,
+    ) -> Result<Vec<ForeignKeyInfo>, DatabaseError> {
+        let rows: Vec<MySqlRow> = sqlx::query(
+            "SELECT k.CONSTRAINT_NAME, k.COLUMN_NAME, k.REFERENCED_TABLE_SCHEMA,
+                    k.REFERENCED_TABLE_NAME, k.REFERENCED_COLUMN_NAME,
+                    r.UPDATE_RULE, r.DELETE_RULE
+             FROM information_schema.KEY_COLUMN_USAGE k
+             JOIN information_schema.REFERENTIAL_CONSTRAINTS r
+               ON r.CONSTRAINT_SCHEMA = k.CONSTRAINT_SCHEMA
+              AND r.CONSTRAINT_NAME = k.CONSTRAINT_NAME
+              AND r.TABLE_NAME = k.TABLE_NAME
+             WHERE k.TABLE_SCHEMA = ? AND k.TABLE_NAME = ?
+               AND k.REFERENCED_TABLE_NAME IS NOT NULL
+             ORDER BY k.CONSTRAINT_NAME, k.ORDINAL_POSITION",
+        )
+        .bind(database)
+        .bind(table)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+
+        let mut keys: Vec<ForeignKeyInfo> = Vec::new();
+        for row in &rows {
+            let Ok(name) = row.try_get::<String, _>(0) else {
+                continue;
+            };
+            let column: String = row.try_get(1).unwrap_or_default();
+            let referenced: String = row.try_get(4).unwrap_or_default();
+            match keys.last_mut() {
+                Some(key) if key.name.as_deref() == Some(name.as_str()) => {
+                    key.columns.push(column);
+                    key.referenced_columns.push(referenced);
+                }
+                _ => {
+                    let schema: String = row.try_get(2).unwrap_or_default();
+                    let referenced_table: String = row.try_get(3).unwrap_or_default();
+                    keys.push(ForeignKeyInfo {
+                        name: Some(name),
+                        columns: vec![column],
+                        referenced_table: if schema == database {
+                            referenced_table
+                        } else {
+                            format!("{}.{}", schema, referenced_table)
+                        },
+                        referenced_columns: vec![referenced],
+                        on_update: row.try_get(5).ok(),
+                        on_delete: row.try_get(6).ok(),
+                    });
+                }
+            }
         }
+        Ok(keys)
+    }
 
-        // Extract column info from first row
-        let columns: Vec<ColumnInfo> = rows[0]
-            .columns()
+    async fn list_triggers(
+        &self,
+        database: &str,
+        _schema: Option<&str>,
+        table: &str,
+    ) -> Result<Vec<TriggerInfo>, DatabaseError> {
+        let rows: Vec<MySqlRow> = sqlx::query(
+            "SELECT TRIGGER_NAME, ACTION_TIMING, EVENT_MANIPULATION, ACTION_STATEMENT
+             FROM information_schema.TRIGGERS
+             WHERE EVENT_OBJECT_SCHEMA = ? AND EVENT_OBJECT_TABLE = ?
+             ORDER BY ACTION_TIMING, EVENT_MANIPULATION, ACTION_ORDER",
+        )
+        .bind(database)
+        .bind(table)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+
+        Ok(rows
             .iter()
-            .map(|col| ColumnInfo {
-                name: col.name().to_string(),
-                data_type: col.type_info().name().to_string(),
-                nullable: true,
-                is_primary_key: false,
+            .filter_map(|row| {
+                Some(TriggerInfo {
+                    name: row.try_get(0).ok()?,
+                    timing: row.try_get(1).unwrap_or_default(),
+                    event: row.try_get(2).unwrap_or_default(),
+                    statement: row.try_get(3).ok(),
+                })
             })
-            .collect();
-
-        let data_rows: Vec<Vec<CellValue>> =
-            rows.iter().map(|row| Self::row_to_values(row)).collect();
+            .collect())
+    }
 
-        Ok(QueryResult {
-            columns,
-            rows: data_rows,
-            affected_rows: None,
-            execution_time_ms,
-        })
+    async fn list_routines(&self, database: &str) -> Result<Vec<SchemaObject>, DatabaseError> {
+        let rows: Vec<MySqlRow> = sqlx::query(
+            "SELECT kind, name, target FROM (
+                 SELECT ROUTINE_TYPE AS kind, ROUTINE_NAME AS name, NULL AS target
+                 FROM information_schema.ROUTINES WHERE ROUTINE_SCHEMA = ?
+                 UNION ALL
+                 SELECT 'TRIGGER', TRIGGER_NAME, EVENT_OBJECT_TABLE
+                 FROM information_schema.TRIGGERS WHERE TRIGGER_SCHEMA = ?
+                 UNION ALL
+                 SELECT 'EVENT', EVENT_NAME, NULL
+                 FROM information_schema.EVENTS WHERE EVENT_SCHEMA = ?
+             ) AS objects
+             ORDER BY FIELD(kind, 'PROCEDURE', 'FUNCTION', 'TRIGGER', 'EVENT'), name",
+        )
+        .bind(database)
+        .bind(database)
+        .bind(database)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+
+        Ok(rows
+            .iter()
+            .filter_map(|row| {
+                let kind = match row.try_get::<String, _>(0).ok()?.as_str() {
+                    "PROCEDURE" => ObjectKind::Procedure,
+                    "FUNCTION" => ObjectKind::Function,
+                    "TRIGGER" => ObjectKind::Trigger,
+                    "EVENT" => ObjectKind::Event,
+                    _ => return None,
+                };
+                let name: String = row.try_get(1).ok()?;
+                Some(SchemaObject {
+                    table: row.try_get(2).ok(),
+                    ..SchemaObject::new(kind, None, &name)
+                })
+            })
+            .collect())
     }
 
-    async fn execute_statement(&self, sql: &str) -> Result<u64, DatabaseError> {
-        let result = sqlx::query(sql)
-            .execute(&self.pool)
+    async fn get_ddl(
+        &self,
+        database: &str,
+        object: &SchemaObject,
+    ) -> Result<String, DatabaseError> {
+        let column = match object.kind {
+            ObjectKind::Table => "Create Table",
+            ObjectKind::View => "Create View",
+            ObjectKind::Procedure => "Create Procedure",
+            ObjectKind::Function => "Create Function",
+            ObjectKind::Trigger => "SQL Original Statement",
+            ObjectKind::Event => "Create Event",
+        };
+        // Not every SHOW CREATE can be prepared
+        let sql = format!(
+            "SHOW CREATE {} {}",
+            object.kind.keyword(),
+            quote_qualified(&[database, &object.name], Dialect::MySql)
+        );
+        let row = sqlx::raw_sql(&sql)
+            .fetch_one(&self.pool)
             .await
             .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
 
-        Ok(result.rows_affected())
+        let ddl = match row.try_get::<Option<String>, _>(column) {
+            Ok(ddl) => ddl,
+            Err(_) => row
+                .try_get::<Option<Vec<u8>>, _>(column)
+                .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?
+                .map(|bytes| String::from_utf8_lossy(&bytes).into_owned()),
+        };
+        // Routines show without a body to those who may not see it
+        match ddl {
+            Some(ddl) => Ok(ddl_script([ddl])),
+            None => Err(DatabaseError::QueryFailed(format!(
+                "Not allowed to see the definition of {}",
+                object.name
+            ))),
+        }
+    }
+
+    async fn execute_query(&self, sql: &str) -> Result<QueryResult, DatabaseError> {
+        with_timeout(&self.limits, self.fetch(sql)).await
+    }
+
+    fn stream_query<'a>(
+        &'a self,
+        database: Option<&'a str>,
+        sql: &'a str,
+        params: &'a [ParamValue],
+        id: QueryId,
+    ) -> BoxStream<'a, Result<RowBatch, DatabaseError>> {
+        Box::pin(async_stream::try_stream! {
+            let (mut conn, session) = self.acquire_session(database).await?;
+            let _running = self.running.track(id, session);
+            let columns = describe_columns(&mut conn, sql).await;
+            let rows = bind_params(sqlx::query(sql), params).fetch(&mut *conn);
+            let batches = batch_rows(rows, columns, Self::row_to_values);
+            let mut batches = limit_batches(batches, self.limits);
+            while let Some(batch) = batches.next().await {
+                let batch = batch?;
+                if batch.limit.is_some() {
+                    // Nobody will read the rest of the result
+                    self.kill_query(session).await?;
+                }
+                yield batch;
+            }
+        })
     }
 
     async fn get_table_data(
         &self,
         database: &str,
+        _schema: Option<&str>,
         table: &str,
         limit: u32,
         offset: u32,
     ) -> Result<QueryResult, DatabaseError> {
         let sql = format!(
-            "SELECT * FROM `{}`.`{}` LIMIT {} OFFSET {}",
-            database, table, limit, offset
+            "SELECT * FROM {} LIMIT {} OFFSET {}",
+            quote_qualified(&[database, table], Dialect::MySql),
+            limit,
+            offset
         );
         self.execute_query(&sql).await
     }
 
+    async fn cancel(&self, id: QueryId) -> Result<(), DatabaseError> {
+        match self.running.session(id) {
+            Some(session) => self.kill_query(session).await,
+            None => Ok(()),
+        }
+    }
+
     async fn close(&self) -> Result<(), DatabaseError> {
         self.pool.close().await;
         Ok(())
     }
+
+    fn as_sql(&self) -> Option<&dyn SqlConnection> {
+        Some(self)
+    }
+}
+
+#[async_trait]
+impl SqlConnection for MySqlConnection {
+    async fn execute_statement(
+        &self,
+        database: Option<&str>,
+        sql: &str,
+        params: &[ParamValue],
+        id: QueryId,
+    ) -> Result<u64, DatabaseError> {
+        self.open_session(database, id)
+            .await?
+            .execute(sql, params)
+            .await
+    }
+
+    async fn session(
+        &self,
+        database: Option<&str>,
+        id: QueryId,
+    ) -> Result<Box<dyn SqlSession + '_>, DatabaseError> {
+        let mut session = self.open_session(database, id).await?;
+        // A script may leave a transaction open or session variables set,
+        // so its connection is not handed back to the pool
+        session.conn.close_on_drop();
+        Ok(Box::new(session))
+    }
+}
+
+struct MySqlSession<'a> {
+    owner: &'a MySqlConnection,
+    conn: PoolConnection<MySql>,
+    thread_id: i64,
+    _running: RunningQuery<'a>,
+}
+
+#[async_trait]
+impl SqlSession for MySqlSession<'_> {
+    async fn execute(&mut self, sql: &str, params: &[ParamValue]) -> Result<u64, DatabaseError> {
+        let result = with_timeout(&self.owner.limits, async {
+            bind_params(sqlx::query(sql), params)
+                .execute(&mut *self.conn)
+                .await
+                .map_err(|e| DatabaseError::QueryFailed(e.to_string()))
+        })
+        .await;
+        if let Err(DatabaseError::Timeout(_)) = result {
+            // max_execution_time only covers SELECT, so stop the rest here
+            self.owner.kill_query(self.thread_id).await?;
+        }
+
+        Ok(result?.rows_affected())
+    }
+
+    async fn execute_unprepared(&mut self, sql: &str) -> Result<u64, DatabaseError> {
+        let result = (&mut *self.conn)
+            .execute(sql)
+            .await
+            .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+        Ok(result.rows_affected())
+    }
+
+    async fn fetch(
+        &mut self,
+        sql: &str,
+        params: &[ParamValue],
+    ) -> Result<QueryResult, DatabaseError> {
+        let columns = describe_columns(&mut self.conn, sql).await;
+        let rows = bind_params(sqlx::query(sql), params).fetch(&mut *self.conn);
+        let batches = limit_batches(
+            batch_rows(rows, columns, MySqlConnection::row_to_values),
+            self.owner.limits,
+        );
+        let result = collect_batches(batches).await?;
+        if result.limit.is_some() {
+            self.owner.kill_query(self.thread_id).await?;
+        }
+        Ok(result)
+    }
 }
@@ -0,0 +1,1123 @@
+use crate::db::quote::{bind_params, quote_ident, quote_literal, quote_qualified};
+use crate::db::{
+    batch_rows, bit_string, collect_batches, ddl_script, decode_cell, decode_cell_unchecked,
+    described_columns, limit_batches, with_timeout, wkb, ColumnDetails, DatabaseConnection,
+    DatabaseError, DatabaseInfo, ForeignKeyInfo, IndexInfo, ObjectKind, QueryId, RowBatch,
+    RunningQueries, RunningQuery, SchemaObject, SqlConnection, SqlSession, TableInfo, TriggerInfo,
+    ViewInfo,
+};
+use crate::models::{CellValue, ColumnInfo, ConnectionConfig, Interval, ParamValue, QueryResult};
+use crate::sql::Dialect;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::stream::{BoxStream, StreamExt};
+use sqlx::pool::PoolConnection;
+use sqlx::postgres::types::{
+    Oid, PgBox, PgCircle, PgInterval, PgLSeg, PgLine, PgPath, PgPoint, PgPolygon, PgTimeTz,
+};
+use sqlx::postgres::{PgPool, PgPoolOptions, PgRow, PgTypeKind, Postgres};
+use sqlx::types::BigDecimal;
+use sqlx::{Column, Executor, Row, TypeInfo};
+use std::collections::HashMap;
+use tokio::sync::Mutex;
+
+/// Schemas that belong to PostgreSQL itself and are hidden from the browser
+const SYSTEM_SCHEMAS: &str = "('pg_catalog', 'information_schema')";
+
+pub struct PostgresConnection {
+    config: ConnectionConfig,
+    pool: PgPool,
+    /// A PostgreSQL session is bound to a single database, so browsing any
+    /// other database needs its own pool. These are opened lazily.
+    database_pools: Mutex<HashMap<String, PgPool>>,
+    running: RunningQueries,
+}
+
+impl PostgresConnection {
+    pub async fn connect(config: &ConnectionConfig) -> Result<Self, DatabaseError> {
+        let pool = Self::open_pool(config).await?;
+
+        Ok(Self {
+            config: config.clone(),
+            pool,
+            database_pools: Mutex::new(HashMap::new()),
+            running: RunningQueries::default(),
+        })
+    }
+
+    /// Stop whatever the backend `pid` is running
+    async fn cancel_backend(&self, pid: i64) -> Result<(), DatabaseError> {
+        sqlx::query("SELECT pg_cancel_backend($1)")
+            .bind(pid as i32)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+
+        Ok(())
+    }
+
+    async fn open_session(
+        &self,
+        database: Option<&str>,
+        id: QueryId,
+    ) -> Result<PostgresSession<'_>, DatabaseError> {
+        let (conn, pid) = self.acquire_session(database).await?;
+        Ok(PostgresSession {
+            owner: self,
+            conn,
+            pid,
+            _running: self.running.track(id, pid),
+        })
+    }
+
+    /// Take a connection to `database` along with its backend pid, which
+    /// `pg_cancel_backend` needs to find it again
+    async fn acquire_session(
+        &self,
+        database: Option<&str>,
+    ) -> Result<(PoolConnection<Postgres>, i64), DatabaseError> {
+        let mut conn = self
+            .pool_for(database.unwrap_or_default())
+            .await?
+            .acquire()
+            .await
+            .map_err(|e| DatabaseError::ConnectionFailed(e.to_string()))?;
+        let pid: i32 = sqlx::query_scalar("SELECT pg_backend_pid()")
+            .fetch_one(&mut *conn)
+            .await
+            .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+
+        Ok((conn, pid as i64))
+    }
+
+    async fn open_pool(config: &ConnectionConfig) -> Result<PgPool, DatabaseError> {
+        let timeout_secs = config.limits.statement_timeout_secs;
+        PgPoolOptions::new()
+            .max_connections(5)
+            .after_connect(move |conn, _meta| {
+                Box::pin(async move {
+                    // Let the server abort long statements on its own
+                    if let Some(secs) = timeout_secs {
+                        conn.execute(format!("SET statement_timeout = '{}s'", secs).as_str())
+                            .await?;
+                    }
+                    Ok(())
+                })
+            })
+            .connect(&config.connection_string())
+            .await
+            .map_err(|e| DatabaseError::ConnectionFailed(e.to_string()))
+    }
+
+    /// Get a pool connected to `database`, reusing the main pool when it
+    /// already points there.
+    async fn pool_for(&self, database: &str) -> Result<PgPool, DatabaseError> {
+        if database.is_empty() || database == self.current_database() {
+            return Ok(self.pool.clone());
+        }
+
+        let mut pools = self.database_pools.lock().await;
+        if let Some(pool) = pools.get(database) {
+            return Ok(pool.clone());
+        }
+
+        let mut config = self.config.clone();
+        config.database = database.to_string();
+        let pool = Self::open_pool(&config).await?;
+        pools.insert(database.to_string(), pool.clone());
+        Ok(pool)
+    }
+
+    /// The database the main pool is connected to. PostgreSQL falls back to
+    /// the user name when none is given.
+    fn current_database(&self) -> &str {
+        if self.config.database.is_empty() {
+            &self.config.username
+        } else {
+            &self.config.database
+        }
+    }
+
+    /// CREATE TABLE rebuilt from the catalog with the table's constraints,
+    /// followed by its other indexes and its comments
+    async fn table_ddl(pool: &PgPool, schema: &str, table: &str) -> Result<String, DatabaseError> {
+        let found: Option<(Oid, Option<String>)> = sqlx::query_as(
+            "SELECT c.oid, obj_description(c.oid, 'pg_class')
+             FROM pg_class c
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             WHERE c.relkind IN ('r', 'p') AND n.nspname = $1 AND c.relname = $2",
+        )
+        .bind(schema)
+        .bind(table)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+        let Some((oid, comment)) = found else {
+            return Err(DatabaseError::QueryFailed(format!(
+                "No table named {}.{}",
+                schema, table
+            )));
+        };
+
+        let columns: Vec<PgRow> = sqlx::query(
+            "SELECT a.attname::TEXT,
+                    format_type(a.atttypid, a.atttypmod),
+                    a.attnotnull,
+                    pg_get_expr(d.adbin, d.adrelid),
+                    a.attidentity::TEXT,
+                    a.attgenerated::TEXT,
+                    col_description(a.attrelid, a.attnum)
+             FROM pg_attribute a
+             LEFT JOIN pg_attrdef d ON d.adrelid = a.attrelid AND d.adnum = a.attnum
+             WHERE a.attrelid = $1 AND a.attnum > 0 AND NOT a.attisdropped
+             ORDER BY a.attnum",
+        )
+        .bind(oid)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+
+        // Newer servers also keep NOT NULL as constraints, which the columns
+        // already say
+        let constraints: Vec<(String, String)> = sqlx::query_as(
+            "SELECT conname::TEXT, pg_get_constraintdef(oid, true)
+             FROM pg_constraint
+             WHERE conrelid = $1 AND contype <> 'n'
+             ORDER BY contype <> 'p', contype, conname",
+        )
+        .bind(oid)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+
+        let indexes: Vec<String> = sqlx::query_scalar(
+            "SELECT pg_get_indexdef(x.indexrelid)
+             FROM pg_index x
+             JOIN pg_class ic ON ic.oid = x.indexrelid
+             WHERE x.indrelid = $1
+               AND NOT EXISTS (
+                   SELECT 1 FROM pg_constraint con
+                   WHERE con.conrelid = x.indrelid AND con.conindid = x.indexrelid
+               )
+             ORDER BY ic.relname",
+        )
+        .bind(oid)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+
+        let name = quote_qualified(&[schema, table], Dialect::Postgres);
+        let mut lines = Vec::new();
+        let mut comments = Vec::new();
+        for row in &columns {
+            let column: String = row.try_get(0).unwrap_or_default();
+            let mut line = format!(
+                "{} {}",
+                quote_ident(&column, Dialect::Postgres),
+                row.try_get::<String, _>(1).unwrap_or_default()
+            );
+            let default: Option<String> = row.try_get(3).unwrap_or_default();
+            let identity: String = row.try_get(4).unwrap_or_default();
+            let generated: String = row.try_get(5).unwrap_or_default();
+            match (identity.as_str(), generated.as_str(), default) {
+                ("a", _, _) => line.push_str(" GENERATED ALWAYS AS IDENTITY"),
+                ("d", _, _) => line.push_str(" GENERATED BY DEFAULT AS IDENTITY"),
+                (_, "s", Some(expression)) => {
+                    line.push_str(&format!(" GENERATED ALWAYS AS ({}) STORED", expression))
+                }
+                (_, _, Some(expression)) => line.push_str(&format!(" DEFAULT {}", expression)),
+                _ => {}
+            }
+            if row.try_get(2).unwrap_or(false) {
+                line.push_str(" NOT NULL");
+            }
+            lines.push(line);
+
+            if let Ok(Some(comment)) = row.try_get::<Option<String>, _>(6) {
+                comments.push(format!(
+                    "COMMENT ON COLUMN {}.{} IS {}",
+                    name,
+                    quote_ident(&column, Dialect::Postgres),
+                    quote_literal(&comment, Dialect::Postgres)
+                ));
+            }
+        }
+        for (constraint, definition) in &constraints {
+            lines.push(format!(
+                "CONSTRAINT {} {}",
+                quote_ident(constraint, Dialect::Postgres),
+                definition
+            ));
+        }
+
+        let mut statements = vec![format!(
+            "CREATE TABLE {} (\n    {}\n)",
+            name,
+            lines.join(",\n    ")
+        )];
+        statements.extend(indexes);
+        if let Some(comment) = comment {
+            statements.push(format!(
+                "COMMENT ON TABLE {} IS {}",
+                name,
+                quote_literal(&comment, Dialect::Postgres)
+            ));
+        }
+        statements.extend(comments);
+        Ok(ddl_script(statements))
+    }
+
+    /// Read a whole result within the connection's limits. A connection
+    /// left with unread rows is closed rather than handed back to the pool.
+    async fn fetch(&self, pool: &PgPool, sql: &str) -> Result<QueryResult, DatabaseError> {
+        let mut conn = pool
+            .acquire()
+            .await
+            .map_err(|e| DatabaseError::ConnectionFailed(e.to_string()))?;
+        let columns = describe_columns(&mut conn, sql).await;
+        let rows = sqlx::query(sql).fetch(&mut *conn);
+        let batches = limit_batches(
+            batch_rows(rows, columns, Self::row_to_values),
+            self.config.limits,
+        );
+        let result = collect_batches(batches).await?;
+        if result.limit.is_some() {
+            conn.close_on_drop();
+        }
+        Ok(result)
+    }
+
+    fn row_to_values(row: &PgRow) -> Vec<CellValue> {
+        let mut values = Vec::new();
+        for i in 0..row.len() {
+            let col = row.column(i);
+            let type_name = col.type_info().name();
+
+            let value = match type_name {
+                "BOOL" => decode_cell(row, i, CellValue::Bool),
+                "INT2" => decode_cell(row, i, |v: i16| CellValue::Int(v.into())),
+                "INT4" => decode_cell(row, i, |v: i32| CellValue::Int(v.into())),
+                "INT8" => decode_cell(row, i, CellValue::Int),
+                "OID" => decode_cell(row, i, |v: Oid| CellValue::Int(v.0.into())),
+                "FLOAT4" => decode_cell(row, i, |v: f32| CellValue::Float(v.into())),
+                "FLOAT8" => decode_cell(row, i, CellValue::Float),
+                "NUMERIC" => decode_cell(row, i, |v: BigDecimal| {
+                    CellValue::Decimal(v.to_plain_string())
+                }),
+                "DATE" => decode_cell(row, i, CellValue::Date),
+                "TIME" => decode_cell(row, i, CellValue::Time),
+                "TIMETZ" => decode_cell(row, i, |v: PgTimeTz| CellValue::TimeTz(v.time, v.offset)),
+                "TIMESTAMP" => decode_cell(row, i, CellValue::DateTime),
+                "TIMESTAMPTZ" => decode_cell(row, i, |v: DateTime<Utc>| {
+                    CellValue::DateTimeTz(v.fixed_offset())
+                }),
+                "INTERVAL" => decode_cell(row, i, |v: PgInterval| {
+                    CellValue::Interval(Interval {
+                        months: v.months,
+                        days: v.days,
+                        microseconds: v.microseconds,
+                    })
+                }),
+                "UUID" => decode_cell(row, i, CellValue::Uuid),
+                // The length in bits, then the bits themselves
+                "BIT" | "VARBIT" => {
+                    decode_cell_unchecked(row, i, |v: Vec<u8>| match v.split_first_chunk::<4>() {
+                        Some((len, bits)) => {
+                            CellValue::Bits(bit_string(bits, u32::from_be_bytes(*len) as usize))
+                        }
+                        None => CellValue::Undecodable(type_name.to_string()),
+                    })
+                }
+                "POINT" => decode_cell(row, i, |v: PgPoint| {
+                    geometry(format!("POINT({})", coordinates(&[v])))
+                }),
+                "LSEG" => decode_cell(row, i, |v: PgLSeg| {
+                    let ends = [
+                        PgPoint {
+                            x: v.start_x,
+                            y: v.start_y,
+                        },
+                        PgPoint {
+                            x: v.end_x,
+                            y: v.end_y,
+                        },
+                    ];
+                    geometry(format!("LINESTRING({})", coordinates(&ends)))
+                }),
+                "BOX" => decode_cell(row, i, |v: PgBox| {
+                    let (x1, y1, x2, y2) = (
+                        v.lower_left_x,
+                        v.lower_left_y,
+                        v.upper_right_x,
+                        v.upper_right_y,
+                    );
+                    let corners = [
+                        PgPoint { x: x1, y: y1 },
+                        PgPoint { x: x2, y: y1 },
+                        PgPoint { x: x2, y: y2 },
+                        PgPoint { x: x1, y: y2 },
+                        PgPoint { x: x1, y: y1 },
+                    ];
+                    geometry(format!("POLYGON(({}))", coordinates(&corners)))
+                }),
+                "PATH" => decode_cell(row, i, |v: PgPath| {
+                    if v.closed {
+                        geometry(format!("POLYGON(({}))", ring(v.points)))
+                    } else {
+                        geometry(format!("LINESTRING({})", coordinates(&v.points)))
+                    }
+                }),
+                "POLYGON" => decode_cell(row, i, |v: PgPolygon| {
+                    geometry(format!("POLYGON(({}))", ring(v.points)))
+                }),
+                // No WKT equivalent, so these keep PostgreSQL's notation
+                "LINE" => decode_cell(row, i, |v: PgLine| {
+                    CellValue::String(format!("{{{},{},{}}}", v.a, v.b, v.c))
+                }),
+                "CIRCLE" => decode_cell(row, i, |v: PgCircle| {
+                    CellValue::String(format!("<({},{}),{}>", v.x, v.y, v.radius))
+                }),
+                // PostGIS types, sent as extended WKB
+                "geometry" | "geography" => decode_cell_unchecked(row, i, |v: Vec<u8>| {
+                    wkb::from_ewkb(&v)
+                        .unwrap_or_else(|| CellValue::Undecodable(type_name.to_string()))
+                }),
+                "JSON" | "JSONB" => decode_cell(row, i, |v: serde_json::Value| {
+                    CellValue::Json(v.to_string())
+                }),
+                "BYTEA" => decode_cell(row, i, CellValue::Bytes),
+                // Enums are sent as their label
+                _ if matches!(col.type_info().kind(), PgTypeKind::Enum(_)) => {
+                    decode_cell_unchecked(row, i, CellValue::Enum)
+                }
+                _ => decode_cell(row, i, CellValue::String),
+            };
+            values.push(value);
+        }
+        values
+    }
+}
+
+/// Names of the columns of `relation` numbered in the `keys` array, in
+/// order, as a SQL array expression
+fn column_names(relation: &str, keys: &str) -> String {
+    format!(
+        "ARRAY(
+            SELECT a.attname::TEXT
+            FROM unnest({keys}) WITH ORDINALITY AS k(attnum, position)
+            JOIN pg_attribute a ON a.attrelid = {relation} AND a.attnum = k.attnum
+            ORDER BY k.position
+        )"
+    )
+}
+
+/// SQL spelling of a foreign key action stored as a `pg_constraint` code
+fn referential_action(column: &str) -> String {
+    format!(
+        "CASE {column}
+            WHEN 'a' THEN 'NO ACTION'
+            WHEN 'r' THEN 'RESTRICT'
+            WHEN 'c' THEN 'CASCADE'
+            WHEN 'n' THEN 'SET NULL'
+            WHEN 'd' THEN 'SET DEFAULT'
+        END"
+    )
+}
+
+/// Timing and events of a trigger from the bit flags in `pg_trigger.tgtype`
+fn trigger_type(flags: i32) -> (String, String) {
+    let timing = if flags & 2 != 0 {
+        "BEFORE"
+    } else if flags & 64 != 0 {
+        "INSTEAD OF"
+    } else {
+        "AFTER"
+    };
+    let events: Vec<&str> = [
+        (4, "INSERT"),
+        (16, "UPDATE"),
+        (8, "DELETE"),
+        (32, "TRUNCATE"),
+    ]
+    .into_iter()
+    .filter(|(flag, _)| flags & flag != 0)
+    .map(|(_, event)| event)
+    .collect();
+    (timing.to_string(), events.join(" OR "))
+}
+
+/// Column metadata from the server's description of `sql`, with primary
+/// keys looked up for the columns that come straight from a table. `None`
+/// if the statement can't be described, and its columns then come from the
+/// first row.
+async fn describe_columns(
+    conn: &mut PoolConnection<Postgres>,
+    sql: &str,
+) -> Option<Vec<ColumnInfo>> {
+    let describe = conn.describe(sql).await.ok()?;
+    let mut columns = described_columns(&describe);
+
+    let mut tables: Vec<Oid> = describe
+        .columns()
+        .iter()
+        .filter_map(|col| col.relation_id())
+        .collect();
+    tables.sort_by_key(|oid| oid.0);
+    tables.dedup();
+    if tables.is_empty() {
+        return Some(columns);
+    }
+
+    let keys: Vec<(Oid, i16)> = sqlx::query_as(
+        "SELECT indrelid, unnest(indkey::int2[]) FROM pg_index
+         WHERE indisprimary AND indrelid = ANY($1)",
+    )
+    .bind(tables)
+    .fetch_all(&mut **conn)
+    .await
+    .unwrap_or_default();
+    for (info, col) in columns.iter_mut().zip(describe.columns()) {
+        let origin = col.relation_id().zip(col.relation_attribute_no());
+        info.is_primary_key = origin.is_some_and(|origin| keys.contains(&origin));
+    }
+    Some(columns)
+}
+
+fn geometry(wkt: String) -> CellValue {
+    CellValue::Geometry { srid: None, wkt }
+}
+
+/// `x y,x y,...`
+fn coordinates(points: &[PgPoint]) -> String {
+    points
+        .iter()
+        .map(|p| format!("{} {}", p.x, p.y))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// A ring of points, closed as WKT expects by repeating the first one
+fn ring(mut points: Vec<PgPoint>) -> String {
+    if let Some(first) = points.first().cloned() {
+        points.push(first);
+    }
+    coordinates(&points)
+}
+
+#[async_trait]
+impl DatabaseConnection for PostgresConnection {
+    async fn test_connection(&self) -> Result<(), DatabaseError> {
+        sqlx::query("SELECT 1")
+            .execute(&self.pool)
+            .await
+            .map_err(|e| DatabaseError::ConnectionFailed(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn list_databases(&self) -> Result<Vec<DatabaseInfo>, DatabaseError> {
+        let rows: Vec<PgRow> = sqlx::query(
+            "SELECT datname, pg_encoding_to_char(encoding)
+             FROM pg_database
+             WHERE datallowconn AND NOT datistemplate
+             ORDER BY datname",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+
+        let databases = rows
+            .iter()
+            .filter_map(|row| {
+                row.try_get::<String, _>(0).ok().map(|name| DatabaseInfo {
+                    name,
+                    character_set: row.try_get(1).ok(),
+                })
+            })
+            .collect();
+
+        Ok(databases)
+    }
+
+    async fn list_tables(&self, database: &str) -> Result<Vec<TableInfo>, DatabaseError> {
+        let pool = self.pool_for(database).await?;
+        let query = format!(
+            "SELECT n.nspname, c.relname, c.reltuples::BIGINT, pg_total_relation_size(c.oid)
+             FROM pg_class c
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             WHERE c.relkind IN ('r', 'p')
+               AND n.nspname NOT IN {}
+               AND n.nspname NOT LIKE 'pg\\_toast%'
+             ORDER BY n.nspname, c.relname",
+            SYSTEM_SCHEMAS
+        );
+
+        let rows: Vec<PgRow> = sqlx::query(&query)
+            .fetch_all(&pool)
+            .await
+            .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+
+        let tables = rows
+            .iter()
+            .filter_map(|row| {
+                let schema: String = row.try_get(0).ok()?;
+                let name: String = row.try_get(1).ok()?;
+                Some(TableInfo {
+                    name,
+                    schema: Some(schema),
+                    database: database.to_string(),
+                    engine: None,
+                    character_set: None,
+                    collation: None,
+                    // reltuples is -1 until the table has been analyzed
+                    row_count: row
+                        .try_get::<i64, _>(2)
+                        .ok()
+                        .filter(|v| *v >= 0)
+                        .map(|v| v as u64),
+                    data_size: row.try_get::<i64, _>(3).ok().map(|v| v as u64),
+                    columns: Vec::new(),
+                })
+            })
+            .collect();
+
+        Ok(tables)
+    }
+
+    async fn list_views(&self, database: &str) -> Result<Vec<ViewInfo>, DatabaseError> {
+        let pool = self.pool_for(database).await?;
+        let query = format!(
+            "SELECT table_schema::TEXT, table_name::TEXT, view_definition::TEXT
+             FROM information_schema.views
+             WHERE table_schema NOT IN {}
+             ORDER BY table_schema, table_name",
+            SYSTEM_SCHEMAS
+        );
+
+        let rows: Vec<PgRow> = sqlx::query(&query)
+            .fetch_all(&pool)
+            .await
+            .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+
+        let views = rows
+            .iter()
+            .filter_map(|row| {
+                let schema: String = row.try_get(0).ok()?;
+                let name: String = row.try_get(1).ok()?;
+                Some(ViewInfo {
+                    name,
+                    schema: Some(schema),
+                    definition: row.try_get(2).ok(),
+                })
+            })
+            .collect();
+
+        Ok(views)
+    }
+
+    async fn describe_table(
+        &self,
+        database: &str,
+        schema: Option<&str>,
+        table: &str,
+    ) -> Result<TableInfo, DatabaseError> {
+        let pool = self.pool_for(database).await?;
+        let schema = schema.unwrap_or("public");
+
+        let rows: Vec<PgRow> = sqlx::query(
+            "SELECT a.attname::TEXT,
+                    format_type(a.atttypid, a.atttypmod),
+                    NOT a.attnotnull,
+                    pg_get_expr(d.adbin, d.adrelid),
+                    EXISTS (
+                        SELECT 1 FROM pg_index i
+                        WHERE i.indrelid = c.oid AND i.indisprimary
+                          AND a.attnum = ANY(i.indkey)
+                    ),
+                    a.attidentity <> ''
+                        OR COALESCE(pg_get_expr(d.adbin, d.adrelid) LIKE 'nextval(%', FALSE),
+                    col_description(c.oid, a.attnum),
+                    EXISTS (
+                        SELECT 1 FROM pg_index i
+                        WHERE i.indrelid = c.oid AND i.indisunique AND i.indnatts = 1
+                          AND i.indkey[0] = a.attnum AND i.indpred IS NULL
+                    )
+             FROM pg_attribute a
+             JOIN pg_class c ON c.oid = a.attrelid
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             LEFT JOIN pg_attrdef d ON d.adrelid = a.attrelid AND d.adnum = a.attnum
+             WHERE n.nspname = $1 AND c.relname = $2
+               AND a.attnum > 0 AND NOT a.attisdropped
+             ORDER BY a.attnum",
+        )
+        .bind(schema)
+        .bind(table)
+        .fetch_all(&pool)
+        .await
+        .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+
+        let columns = rows
+            .iter()
+            .filter_map(|row| {
+                Some(ColumnDetails {
+                    name: row.try_get(0).ok()?,
+                    data_type: row.try_get(1).ok()?,
+                    nullable: row.try_get(2).unwrap_or(true),
+                    default_value: row.try_get(3).ok(),
+                    is_primary_key: row.try_get(4).unwrap_or(false),
+                    is_unique: row.try_get(7).unwrap_or(false),
+                    is_auto_increment: row.try_get(5).unwrap_or(false),
+                    comment: row.try_get(6).ok(),
+                })
+            })
+            .collect();
+
+        // The access method stands in for the engine; encoding and collation
+        // belong to the database rather than the table
+        let stats: Option<PgRow> = sqlx::query(
+            "SELECT c.reltuples::BIGINT, pg_total_relation_size(c.oid), am.amname::TEXT,
+                    pg_encoding_to_char(db.encoding)::TEXT, db.datcollate::TEXT
+             FROM pg_class c
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             LEFT JOIN pg_am am ON am.oid = c.relam
+             JOIN pg_database db ON db.datname = current_database()
+             WHERE n.nspname = $1 AND c.relname = $2",
+        )
+        .bind(schema)
+        .bind(table)
+        .fetch_optional(&pool)
+        .await
+        .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+
+        Ok(TableInfo {
+            name: table.to_string(),
+            schema: Some(schema.to_string()),
+            database: database.to_string(),
+            engine: stats.as_ref().and_then(|row| row.try_get(2).ok()),
+            character_set: stats.as_ref().and_then(|row| row.try_get(3).ok()),
+            collation: stats.as_ref().and_then(|row| row.try_get(4).ok()),
+            row_count: stats
+                .as_ref()
+                .and_then(|row| row.try_get::<i64, _>(0).ok())
+                .filter(|v| *v >= 0)
+                .map(|v| v as u64),
+            data_size: stats
+                .as_ref()
+                .and_then(|row| row.try_get::<i64, _>(1).ok())
+                .map(|v| v as u64),
+            columns,
+        })
+    }
+
+    async fn list_indexes(
+        &self,
+        database: &str,
+        schema: Option<&str>,
+        table: &str,
+    ) -> Result<Vec<IndexInfo>, DatabaseError> {
+        let pool = self.pool_for(database).await?;
+        let schema = schema.unwrap_or("public");
+
+        let rows: Vec<PgRow> = sqlx::query(
+            "SELECT ic.relname::TEXT,
+                    ARRAY(
+                        SELECT pg_get_indexdef(x.indexrelid, k, true)
+                        FROM generate_series(1, x.indnatts) AS k
+                        ORDER BY k
+                    ),
+                    x.indisunique,
+                    x.indisprimary,
+                    am.amname::TEXT
+             FROM pg_index x
+             JOIN pg_class c ON c.oid = x.indrelid
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             JOIN pg_class ic ON ic.oid = x.indexrelid
+             JOIN pg_am am ON am.oid = ic.relam
+             WHERE n.nspname = $1 AND c.relname = $2
+             ORDER BY x.indisprimary DESC, ic.relname",
+        )
+        .bind(schema)
+        .bind(table)
+        .fetch_all(&pool)
+        .await
+        .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+
+        Ok(rows
+            .iter()
+            .filter_map(|row| {
+                Some(IndexInfo {
+                    name: row.try_get(0).ok()?,
+                    columns: row.try_get(1).unwrap_or_default(),
+                    is_unique: row.try_get(2).unwrap_or(false),
+                    is_primary: row.try_get(3).unwrap_or(false),
+                    method: row.try_get(4).ok(),
+                })
+            })
+            .collect())
+    }
+
+    async fn list_foreign_keys(
+        &self,
+        database: &str,
+        schema: Option<&str>,
+        table: &str,
+    ) -> Result<Vec<ForeignKeyInfo>, DatabaseError> {
+        let pool = self.pool_for(database).await?;
+        let schema = schema.unwrap_or("public");
+
+        let query = format!(
+            "SELECT con.conname::TEXT,
+                    {},
+                    fn.nspname::TEXT,
+                    fc.relname::TEXT,
+                    {},
+                    {},
+                    {}
+             FROM pg_constraint con
+             JOIN pg_class c ON c.oid = con.conrelid
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             JOIN pg_class fc ON fc.oid = con.confrelid
+             JOIN pg_namespace fn ON fn.oid = fc.relnamespace
+             WHERE con.contype = 'f' AND n.nspname = $1 AND c.relname = $2
+             ORDER BY con.conname",
+            column_names("con.conrelid", "con.conkey"),
+            column_names("con.confrelid", "con.confkey"),
+            referential_action("con.confupdtype"),
+            referential_action("con.confdeltype"),
+        );
+
+        let rows: Vec<PgRow> = sqlx::query(&query)
+            .bind(schema)
+            .bind(table)
+            .fetch_all(&pool)
+            .await
+            .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+
+        Ok(rows
+            .iter()
+            .filter_map(|row| {
+                let referenced_schema: String = row.try_get(2).ok()?;
+                let referenced_table: String = row.try_get(3).ok()?;
+                Some(ForeignKeyInfo {
+                    name: row.try_get(0).ok(),
+                    columns: row.try_get(1).unwrap_or_default(),
+                    referenced_table: if referenced_schema == schema {
+                        referenced_table
+                    } else {
+                        format!("{}.{}", referenced_schema, referenced_table)
+                    },
+                    referenced_columns: row.try_get(4).unwrap_or_default(),
+                    on_update: row.try_get(5).ok(),
+                    on_delete: row.try_get(6).ok(),
+                })
+            })
+            .collect())
+    }
+
+    async fn list_triggers(
+        &self,
+        database: &str,
+        schema: Option<&str>,
+        table: &str,
+    ) -> Result<Vec<TriggerInfo>, DatabaseError> {
+        let pool = self.pool_for(database).await?;
+        let schema = schema.unwrap_or("public");
+
+        let rows: Vec<PgRow> = sqlx::query(
+            "SELECT t.tgname::TEXT, t.tgtype::INT4, pg_get_triggerdef(t.oid, true)
+             FROM pg_trigger t
+             JOIN pg_class c ON c.oid = t.tgrelid
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             WHERE NOT t.tgisinternal AND n.nspname = $1 AND c.relname = $2
+             ORDER BY t.tgname",
+        )
+        .bind(schema)
+        .bind(table)
+        .fetch_all(&pool)
+        .await
+        .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+
+        Ok(rows
+            .iter()
+            .filter_map(|row| {
+                let (timing, event) = trigger_type(row.try_get(1).ok()?);
+                Some(TriggerInfo {
+                    name: row.try_get(0).ok()?,
+                    timing,
+                    event,
+                    statement: row.try_get(2).ok(),
+                })
+            })
+            .collect())
+    }
+
+    async fn list_routines(&self, database: &str) -> Result<Vec<SchemaObject>, DatabaseError> {
+        let pool = self.pool_for(database).await?;
+        // Routines that come with an extension are left to the extension
+        let query = format!(
+            "SELECT kind, schema, name, arguments, target FROM (
+                 SELECT CASE p.prokind WHEN 'p' THEN 'PROCEDURE' ELSE 'FUNCTION' END AS kind,
+                        n.nspname::TEXT AS schema,
+                        p.proname::TEXT AS name,
+                        pg_get_function_identity_arguments(p.oid) AS arguments,
+                        NULL::TEXT AS target
+                 FROM pg_proc p
+                 JOIN pg_namespace n ON n.oid = p.pronamespace
+                 WHERE p.prokind IN ('f', 'p')
+                   AND n.nspname NOT IN {0}
+                   AND NOT EXISTS (
+                       SELECT 1 FROM pg_depend d
+                       WHERE d.classid = 'pg_proc'::regclass
+                         AND d.objid = p.oid
+                         AND d.deptype = 'e'
+                   )
+                 UNION ALL
+                 SELECT 'TRIGGER', n.nspname::TEXT, t.tgname::TEXT, NULL, c.relname::TEXT
+                 FROM pg_trigger t
+                 JOIN pg_class c ON c.oid = t.tgrelid
+                 JOIN pg_namespace n ON n.oid = c.relnamespace
+                 WHERE NOT t.tgisinternal AND n.nspname NOT IN {0}
+             ) AS objects
+             ORDER BY array_position(ARRAY['PROCEDURE', 'FUNCTION', 'TRIGGER'], kind),
+                      schema, name, arguments",
+            SYSTEM_SCHEMAS
+        );
+
+        let rows: Vec<PgRow> = sqlx::query(&query)
+            .fetch_all(&pool)
+            .await
+            .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+
+        Ok(rows
+            .iter()
+            .filter_map(|row| {
+                let kind = match row.try_get::<String, _>(0).ok()?.as_str() {
+                    "PROCEDURE" => ObjectKind::Procedure,
+                    "FUNCTION" => ObjectKind::Function,
+                    _ => ObjectKind::Trigger,
+                };
+                let name: String = row.try_get(2).ok()?;
+                Some(SchemaObject {
+                    arguments: row.try_get(3).ok(),
+                    table: row.try_get(4).ok(),
+                    ..SchemaObject::new(kind, row.try_get(1).ok(), &name)
+                })
+            })
+            .collect())
+    }
+
+    /// PostgreSQL has no SHOW CREATE: routines, triggers and views have
+    /// catalog functions that print them, tables are rebuilt from parts
+    async fn get_ddl(
+        &self,
+        database: &str,
+        object: &SchemaObject,
+    ) -> Result<String, DatabaseError> {
+        let pool = self.pool_for(database).await?;
+        let schema = object.schema.as_deref().unwrap_or("public");
+        let name = object.name.as_str();
+
+        let ddl: Option<String> = match object.kind {
+            ObjectKind::Table => return Self::table_ddl(&pool, schema, name).await,
+            ObjectKind::View => {
+                sqlx::query_scalar(
+                    "SELECT format('CREATE VIEW %I.%I AS\n%s', n.nspname, c.relname,
+                               pg_get_viewdef(c.oid, true))
+                 FROM pg_class c
+                 JOIN pg_namespace n ON n.oid = c.relnamespace
+                 WHERE c.relkind = 'v' AND n.nspname = $1 AND c.relname = $2",
+                )
+                .bind(schema)
+                .bind(name)
+                .fetch_optional(&pool)
+                .await
+            }
+            ObjectKind::Procedure | ObjectKind::Function => {
+                sqlx::query_scalar(
+                    "SELECT pg_get_functiondef(p.oid)
+                 FROM pg_proc p
+                 JOIN pg_namespace n ON n.oid = p.pronamespace
+                 WHERE n.nspname = $1 AND p.proname = $2
+                   AND pg_get_function_identity_arguments(p.oid) = $3",
+                )
+                .bind(schema)
+                .bind(name)
+                .bind(object.arguments.as_deref().unwrap_or_default())
+                .fetch_optional(&pool)
+                .await
+            }
+            ObjectKind::Trigger => {
+                sqlx::query_scalar(
+                    "SELECT pg_get_triggerdef(t.oid)
+                 FROM pg_trigger t
+                 JOIN pg_class c ON c.oid = t.tgrelid
+                 JOIN pg_namespace n ON n.oid = c.relnamespace
+                 WHERE n.nspname = $1 AND c.relname = $2 AND t.tgname = $3",
+                )
+                .bind(schema)
+                .bind(object.table.as_deref().unwrap_or_default())
+                .bind(name)
+                .fetch_optional(&pool)
+                .await
+            }
+            ObjectKind::Event => {
+                return Err(DatabaseError::NotSupported("DDL of an event".to_string()));
+            }
+        }
+        .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+
+        match ddl {
+            Some(ddl) => Ok(ddl_script([ddl])),
+            None => Err(DatabaseError::QueryFailed(format!(
+                "No {} named {}",
+                object.kind.keyword().to_lowercase(),
+                object.display_name()
+            ))),
+        }
+    }
+
+    async fn execute_query(&self, sql: &str) -> Result<QueryResult, DatabaseError> {
+        with_timeout(&self.config.limits, self.fetch(&self.pool, sql)).await
+    }
+
+    fn stream_query<'a>(
+        &'a self,
+        database: Option<&'a str>,
+        sql: &'a str,
+        params: &'a [ParamValue],
+        id: QueryId,
+    ) -> BoxStream<'a, Result<RowBatch, DatabaseError>> {
+        Box::pin(async_stream::try_stream! {
+            let (mut conn, pid) = self.acquire_session(database).await?;
+            let _running = self.running.track(id, pid);
+            let columns = describe_columns(&mut conn, sql).await;
+            let rows = bind_params(sqlx::query(sql), params).fetch(&mut *conn);
+            let batches = batch_rows(rows, columns, Self::row_to_values);
+            let mut batches = limit_batches(batches, self.config.limits);
+            while let Some(batch) = batches.next().await {
+                let batch = batch?;
+                if batch.limit.is_some() {
+                    // Nobody will read the rest of the result
+                    self.cancel_backend(pid).await?;
+                }
+                yield batch;
+            }
+        })
+    }
+
+    async fn get_table_data(
+        &self,
+        database: &str,
+        schema: Option<&str>,
+        table: &str,
+        limit: u32,
+        offset: u32,
+    ) -> Result<QueryResult, DatabaseError> {
+        let pool = self.pool_for(database).await?;
+        let schema = schema.unwrap_or("public");
+        let sql = format!(
+            "SELECT * FROM {} LIMIT {} OFFSET {}",
+            quote_qualified(&[schema, table], Dialect::Postgres),
+            limit,
+            offset
+        );
+        self.fetch(&pool, &sql).await
+    }
+
+    async fn cancel(&self, id: QueryId) -> Result<(), DatabaseError> {
+        match self.running.session(id) {
+            Some(pid) => self.cancel_backend(pid).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn close(&self) -> Result<(), DatabaseError> {
+        self.pool.close().await;
+        for pool in self.database_pools.lock().await.values() {
+            pool.close().await;
+        }
+        Ok(())
+    }
+
+    fn as_sql(&self) -> Option<&dyn SqlConnection> {
+        Some(self)
+    }
+}
+
+#[async_trait]
+impl SqlConnection for PostgresConnection {
+    async fn execute_statement(
+        &self,
+        database: Option<&str>,
+        sql: &str,
+        params: &[ParamValue],
+        id: QueryId,
+    ) -> Result<u64, DatabaseError> {
+        self.open_session(database, id)
+            .await?
+            .execute(sql, params)
+            .await
+    }
+
+    async fn session(
+        &self,
+        database: Option<&str>,
+        id: QueryId,
+    ) -> Result<Box<dyn SqlSession + '_>, DatabaseError> {
+        let mut session = self.open_session(database, id).await?;
+        // A script may leave a transaction open or settings changed, so its
+        // connection is not handed back to the pool
+        session.conn.close_on_drop();
+        Ok(Box::new(session))
+    }
+}
+
+struct PostgresSession<'a> {
+    owner: &'a PostgresConnection,
+    conn: PoolConnection<Postgres>,
+    pid: i64,
+    _running: RunningQuery<'a>,
+}
+
+#[async_trait]
+impl SqlSession for PostgresSession<'_> {
+    async fn execute(&mut self, sql: &str, params: &[ParamValue]) -> Result<u64, DatabaseError> {
+        let result = with_timeout(&self.owner.config.limits, async {
+            bind_params(sqlx::query(sql), params)
+                .execute(&mut *self.conn)
+                .await
+                .map_err(|e| DatabaseError::QueryFailed(e.to_string()))
+        })
+        .await;
+        if let Err(DatabaseError::Timeout(_)) = result {
+            self.owner.cancel_backend(self.pid).await?;
+        }
+
+        Ok(result?.rows_affected())
+    }
+
+    async fn execute_unprepared(&mut self, sql: &str) -> Result<u64, DatabaseError> {
+        let result = (&mut *self.conn)
+            .execute(sql)
+            .await
+            .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+        Ok(result.rows_affected())
+    }
+
+    async fn fetch(
+        &mut self,
+        sql: &str,
+        params: &[ParamValue],
+    ) -> Result<QueryResult, DatabaseError> {
+        let columns = describe_columns(&mut self.conn, sql).await;
+        let rows = bind_params(sqlx::query(sql), params).fetch(&mut *self.conn);
+        let batches = limit_batches(
+            batch_rows(rows, columns, PostgresConnection::row_to_values),
+            self.owner.config.limits,
+        );
+        let result = collect_batches(batches).await?;
+        if result.limit.is_some() {
+            self.owner.cancel_backend(self.pid).await?;
+        }
+        Ok(result)
+    }
+}
@@ -0,0 +1,111 @@
+use crate::models::ParamValue;
+use crate::sql::Dialect;
+use chrono::NaiveDate;
+use sqlx::encode::IsNull;
+use sqlx::error::BoxDynError;
+use sqlx::mysql::{MySql, MySqlTypeInfo};
+use sqlx::postgres::types::Oid;
+use sqlx::postgres::{PgTypeInfo, Postgres};
+use sqlx::query::Query;
+use sqlx::sqlite::{Sqlite, SqliteTypeInfo};
+use sqlx::{Database, Encode, Type};
+
+/// Quote an identifier, doubling any quote characters inside it. Names
+/// can't be bound as parameters, so this is the only way they go into SQL.
+pub fn quote_ident(ident: &str, dialect: Dialect) -> String {
+    match dialect {
+        Dialect::MySql => format!("`{}`", ident.replace('`', "``")),
+        Dialect::Postgres | Dialect::Sqlite => format!("\"{}\"", ident.replace('"', "\"\"")),
+    }
+}
+
+/// Quote a string literal, for the few places that take no parameters,
+/// such as DDL. MySQL treats backslashes as escapes unless told otherwise.
+pub fn quote_literal(text: &str, dialect: Dialect) -> String {
+    match dialect {
+        Dialect::MySql => format!("'{}'", text.replace('\\', "\\\\").replace('\'', "''")),
+        Dialect::Postgres | Dialect::Sqlite => format!("'{}'", text.replace('\'', "''")),
+    }
+}
+
+/// `a.b.c` with every part quoted
+pub fn quote_qualified(parts: &[&str], dialect: Dialect) -> String {
+    parts
+        .iter()
+        .map(|part| quote_ident(part, dialect))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Bind `params` to a query's placeholders, in order
+pub fn bind_params<'q, DB>(
+    query: Query<'q, DB, DB::Arguments<'q>>,
+    params: &'q [ParamValue],
+) -> Query<'q, DB, DB::Arguments<'q>>
+where
+    DB: Database,
+    Null: Type<DB>,
+    i64: Encode<'q, DB> + Type<DB>,
+    &'q str: Encode<'q, DB> + Type<DB>,
+    NaiveDate: Encode<'q, DB> + Type<DB>,
+{
+    params.iter().fold(query, |query, param| match param {
+        ParamValue::Null => query.bind(Null),
+        ParamValue::Int(i) => query.bind(*i),
+        ParamValue::String(s) => query.bind(s.as_str()),
+        ParamValue::Date(d) => query.bind(*d),
+    })
+}
+
+/// A NULL parameter. PostgreSQL gets it with no declared type, so the
+/// server infers one from context as it would for a literal NULL; a typed
+/// NULL fails wherever its type doesn't fit, e.g. a text NULL compared
+/// with an integer column.
+pub struct Null;
+
+impl<'q, DB: Database> Encode<'q, DB> for Null {
+    fn encode_by_ref(&self, _buf: &mut DB::ArgumentBuffer<'q>) -> Result<IsNull, BoxDynError> {
+        Ok(IsNull::Yes)
+    }
+}
+
+impl Type<Postgres> for Null {
+    fn type_info() -> PgTypeInfo {
+        PgTypeInfo::with_oid(Oid(0))
+    }
+}
+
+impl Type<MySql> for Null {
+    fn type_info() -> MySqlTypeInfo {
+        <str as Type<MySql>>::type_info()
+    }
+}
+
+impl Type<Sqlite> for Null {
+    fn type_info() -> SqliteTypeInfo {
+        <str as Type<Sqlite>>::type_info()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote_ident() {
+        assert_eq!(quote_ident("o'brien", Dialect::MySql), "`o'brien`");
+        assert_eq!(quote_ident("a`b", Dialect::MySql), "`a``b`");
+        assert_eq!(quote_ident("a\"b", Dialect::Postgres), "\"a\"\"b\"");
+        assert_eq!(
+            quote_qualified(&["main", "my table"], Dialect::Sqlite),
+            "\"main\".\"my table\""
+        );
+    }
+
+    #[test]
+    fn test_quote_literal() {
+        assert_eq!(quote_literal("o'brien", Dialect::Postgres), "'o''brien'");
+        assert_eq!(quote_literal("a\\b'", Dialect::MySql), "'a\\\\b'''");
+        assert_eq!(quote_literal("a\\b", Dialect::Sqlite), "'a\\b'");
+    }
+}
@@ -0,0 +1,214 @@
+use crate::db::stream::cancel_task;
+use crate::db::{DatabaseConnection, QueryId};
+use crate::models::{ParamValue, QueryResult};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::runtime::Handle;
+use tokio::sync::mpsc;
+use tokio::task::AbortHandle;
+
+/// A statement of a script, as split and classified by the editor
+#[derive(Debug, Clone)]
+pub struct ScriptStatement {
+    pub sql: String,
+    /// Values for the placeholders of `sql`
+    pub params: Vec<ParamValue>,
+    pub returns_rows: bool,
+}
+
+/// Progress of a script, as seen by the UI
+#[derive(Debug)]
+pub enum ScriptEvent {
+    Started(usize),
+    Finished(usize, Result<QueryResult, String>),
+}
+
+/// A script running statement by statement in a single session on the
+/// runtime. Dropping the handle cancels it.
+pub struct ScriptRun {
+    events: mpsc::UnboundedReceiver<ScriptEvent>,
+    runtime: Handle,
+    conn: Arc<dyn DatabaseConnection>,
+    id: QueryId,
+    task: AbortHandle,
+    pub finished: bool,
+}
+
+impl ScriptRun {
+    pub fn spawn(
+        runtime: &Handle,
+        conn: Arc<dyn DatabaseConnection>,
+        database: Option<String>,
+        statements: Vec<ScriptStatement>,
+        id: QueryId,
+        stop_on_error: bool,
+    ) -> Self {
+        let (events_tx, events) = mpsc::unbounded_channel();
+        let task = runtime
+            .spawn(run(
+                conn.clone(),
+                database,
+                statements,
+                id,
+                stop_on_error,
+                events_tx,
+            ))
+            .abort_handle();
+
+        Self {
+            events,
+            runtime: runtime.clone(),
+            conn,
+            id,
+            task,
+            finished: false,
+        }
+    }
+
+    /// Drain the events that arrived since the last poll
+    pub fn poll(&mut self) -> Vec<ScriptEvent> {
+        let mut events = Vec::new();
+        loop {
+            match self.events.try_recv() {
+                Ok(event) => events.push(event),
+                Err(mpsc::error::TryRecvError::Empty) => break,
+                Err(mpsc::error::TryRecvError::Disconnected) => {
+                    self.finished = true;
+                    break;
+                }
+            }
+        }
+        events
+    }
+
+    /// Stop the running statement on the server and skip the rest
+    pub fn cancel(&mut self) {
+        if !self.finished {
+            self.finished = true;
+            cancel_task(&self.runtime, self.conn.clone(), self.id, self.task.clone());
+        }
+    }
+}
+
+impl Drop for ScriptRun {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+async fn run(
+    conn: Arc<dyn DatabaseConnection>,
+    database: Option<String>,
+    statements: Vec<ScriptStatement>,
+    id: QueryId,
+    stop_on_error: bool,
+    events: mpsc::UnboundedSender<ScriptEvent>,
+) {
+    let session = match conn.as_sql() {
+        Some(sql_conn) => sql_conn
+            .session(database.as_deref(), id)
+            .await
+            .map_err(|e| e.to_string()),
+        None => Err("Scripts are only supported on SQL connections".to_string()),
+    };
+    let mut session = match session {
+        Ok(session) => session,
+        Err(e) => {
+            let _ = events.send(ScriptEvent::Finished(0, Err(e)));
+            return;
+        }
+    };
+
+    for (index, statement) in statements.iter().enumerate() {
+        if events.send(ScriptEvent::Started(index)).is_err() {
+            return;
+        }
+
+        let start = Instant::now();
+        let result = if statement.returns_rows {
+            session.fetch(&statement.sql, &statement.params).await
+        } else {
+            session
+                .execute(&statement.sql, &statement.params)
+                .await
+                .map(|affected| QueryResult {
+                    affected_rows: Some(affected),
+                    ..Default::default()
+                })
+        };
+        let result = result
+            .map(|mut result| {
+                result.execution_time_ms = start.elapsed().as_millis() as u64;
+                result
+            })
+            .map_err(|e| e.to_string());
+
+        let failed = result.is_err();
+        if events.send(ScriptEvent::Finished(index, result)).is_err() {
+            return;
+        }
+        if failed && stop_on_error {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::sqlite::{SqliteConnection, MEMORY_DATABASE};
+    use crate::models::{ConnectionConfig, DatabaseType};
+
+    async fn run_script(stop_on_error: bool) -> Vec<Result<QueryResult, String>> {
+        let config = ConnectionConfig {
+            db_type: DatabaseType::SQLite,
+            database: MEMORY_DATABASE.to_string(),
+            ..Default::default()
+        };
+        let conn = SqliteConnection::connect(&config).await.unwrap();
+        let statements = [
+            ("CREATE TABLE t (a INTEGER)", false),
+            ("INSERT INTO t VALUES (1), (2)", false),
+            ("INSERT INTO missing VALUES (1)", false),
+            ("SELECT a FROM t", true),
+        ]
+        .into_iter()
+        .map(|(sql, returns_rows)| ScriptStatement {
+            sql: sql.to_string(),
+            params: Vec::new(),
+            returns_rows,
+        })
+        .collect();
+
+        let mut script = ScriptRun::spawn(
+            &Handle::current(),
+            Arc::new(conn),
+            None,
+            statements,
+            QueryId::new(),
+            stop_on_error,
+        );
+        let mut results = Vec::new();
+        while !script.finished {
+            for event in script.poll() {
+                if let ScriptEvent::Finished(_, result) = event {
+                    results.push(result);
+                }
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+        }
+        results
+    }
+
+    #[tokio::test]
+    async fn test_error_handling() {
+        let results = run_script(true).await;
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[1].as_ref().unwrap().affected_rows, Some(2));
+        assert!(results[2].is_err());
+
+        let results = run_script(false).await;
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[3].as_ref().unwrap().rows.len(), 2);
+    }
+}
@@ -0,0 +1,1020 @@
+use crate::db::quote::{bind_params, quote_ident, quote_qualified};
+use crate::db::{
+    batch_rows, collect_batches, ddl_script, decode_cell, described_columns, limit_batches,
+    with_timeout, ColumnDetails, DatabaseConnection, DatabaseError, DatabaseInfo, ForeignKeyInfo,
+    IndexInfo, ObjectKind, QueryId, RowBatch, SchemaObject, SqlConnection, SqlSession, TableInfo,
+    TriggerInfo, ViewInfo,
+};
+use crate::models::{
+    CellValue, ColumnInfo, ConnectionConfig, ParamValue, QueryLimits, QueryResult,
+};
+use crate::sql::lexer::{tokenize, Token};
+use crate::sql::Dialect;
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime};
+use futures::stream::{BoxStream, StreamExt};
+use sqlx::pool::PoolConnection;
+use sqlx::sqlite::{Sqlite, SqliteConnectOptions, SqlitePool, SqlitePoolOptions, SqliteRow};
+use sqlx::{Column, Executor, Row, TypeInfo, ValueRef};
+
+/// Path that opens a private in-memory database instead of a file
+pub const MEMORY_DATABASE: &str = ":memory:";
+
+pub struct SqliteConnection {
+    pool: SqlitePool,
+    limits: QueryLimits,
+}
+
+impl SqliteConnection {
+    pub async fn connect(config: &ConnectionConfig) -> Result<Self, DatabaseError> {
+        let path = config.database.trim();
+        if path.is_empty() {
+            return Err(DatabaseError::ConnectionFailed(
+                "No database file given".to_string(),
+            ));
+        }
+
+        let is_memory = path == MEMORY_DATABASE;
+        let options = if is_memory {
+            SqliteConnectOptions::new().in_memory(true)
+        } else {
+            SqliteConnectOptions::new()
+                .filename(path)
+                .read_only(config.read_only)
+        };
+
+        // Every connection to :memory: gets its own empty database, so the
+        // pool must never open a second one or drop the only one it has.
+        let pool_options = if is_memory {
+            SqlitePoolOptions::new()
+                .max_connections(1)
+                .min_connections(1)
+                .idle_timeout(None)
+                .max_lifetime(None)
+        } else {
+            SqlitePoolOptions::new().max_connections(5)
+        };
+
+        let pool = pool_options
+            .connect_with(options)
+            .await
+            .map_err(|e| DatabaseError::ConnectionFailed(e.to_string()))?;
+
+        Ok(Self {
+            pool,
+            limits: config.limits,
+        })
+    }
+
+    /// Read a whole result within the connection's limits
+    async fn fetch(&self, sql: &str) -> Result<QueryResult, DatabaseError> {
+        let mut conn = self.acquire().await?;
+        let columns = describe_columns(&mut conn, sql).await;
+        let rows = sqlx::query(sql).fetch(&mut *conn);
+        collect_batches(limit_batches(
+            batch_rows(rows, columns, Self::row_to_values),
+            self.limits,
+        ))
+        .await
+    }
+
+    async fn acquire(&self) -> Result<PoolConnection<Sqlite>, DatabaseError> {
+        self.pool
+            .acquire()
+            .await
+            .map_err(|e| DatabaseError::ConnectionFailed(e.to_string()))
+    }
+
+    /// SQLite is dynamically typed, so each value is decoded by its own
+    /// storage class. The declared column type only refines how an INTEGER
+    /// or TEXT value is presented.
+    fn row_to_values(row: &SqliteRow) -> Vec<CellValue> {
+        let mut values = Vec::new();
+        for i in 0..row.len() {
+            let declared = row.column(i).type_info().name().to_uppercase();
+            let storage = match row.try_get_raw(i) {
+                Ok(raw) if !raw.is_null() => raw.type_info().name().to_string(),
+                _ => {
+                    values.push(CellValue::Null);
+                    continue;
+                }
+            };
+
+            let value = match storage.as_str() {
+                "INTEGER" if declared.contains("BOOL") => {
+                    decode_cell(row, i, |v: i64| CellValue::Bool(v != 0))
+                }
+                "INTEGER" => decode_cell(row, i, CellValue::Int),
+                "REAL" => decode_cell(row, i, CellValue::Float),
+                "BLOB" => decode_cell(row, i, CellValue::Bytes),
+                _ => decode_cell(row, i, |s: String| text_to_cell(&declared, s)),
+            };
+            values.push(value);
+        }
+        values
+    }
+}
+
+/// SQLite stores dates and times as text, so parse them by the column's
+/// declared type, leaving anything that doesn't parse as it is
+fn text_to_cell(declared: &str, text: String) -> CellValue {
+    let parsed = match declared {
+        "DATE" => NaiveDate::parse_from_str(&text, "%Y-%m-%d")
+            .ok()
+            .map(CellValue::Date),
+        "TIME" => NaiveTime::parse_from_str(&text, "%H:%M:%S%.f")
+            .ok()
+            .map(CellValue::Time),
+        "DATETIME" | "TIMESTAMP" => ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"]
+            .iter()
+            .find_map(|format| NaiveDateTime::parse_from_str(&text, format).ok())
+            .map(CellValue::DateTime)
+            .or_else(|| {
+                DateTime::parse_from_rfc3339(&text)
+                    .ok()
+                    .map(CellValue::DateTimeTz)
+            }),
+        "JSON" => return CellValue::Json(text),
+        _ => None,
+    };
+    parsed.unwrap_or(CellValue::String(text))
+}
+
+/// Timing and event of a trigger, read from the head of its `CREATE
+/// TRIGGER` statement since SQLite keeps nothing else. Timing defaults to
+/// BEFORE, as SQLite does.
+fn trigger_header(sql: &str) -> (String, String) {
+    let tokens: Vec<Token> = tokenize(sql, Dialect::Sqlite)
+        .into_iter()
+        .filter(|t| !t.is_trivia())
+        .collect();
+    let start = tokens
+        .iter()
+        .position(|t| t.is_keyword("TRIGGER"))
+        .map_or(0, |i| i + 1);
+
+    let mut timing = "BEFORE";
+    let mut event = "";
+    for token in tokens[start..].iter().take_while(|t| !t.is_keyword("ON")) {
+        if let Some(keyword) = ["BEFORE", "AFTER", "INSTEAD"]
+            .into_iter()
+            .find(|k| token.is_keyword(k))
+        {
+            timing = if keyword == "INSTEAD" {
+                "INSTEAD OF"
+            } else {
+                keyword
+            };
+        } else if let Some(keyword) = ["INSERT", "UPDATE", "DELETE"]
+            .into_iter()
+            .find(|k| token.is_keyword(k))
+        {
+            event = keyword;
+        }
+    }
+    (timing.to_string(), event.to_string())
+}
+
+/// Column metadata from SQLite's description of `sql`, or `None` if it
+/// can't be described, and its columns then come from the first row. sqlx
+/// doesn't say which table a column comes from, so primary keys are left
+/// unmarked.
+async fn describe_columns(conn: &mut PoolConnection<Sqlite>, sql: &str) -> Option<Vec<ColumnInfo>> {
+    let describe = conn.describe(sql).await.ok()?;
+    Some(described_columns(&describe))
+}
+
+#[async_trait]
+impl DatabaseConnection for SqliteConnection {
+    async fn test_connection(&self) -> Result<(), DatabaseError> {
+        sqlx::query("SELECT 1")
+            .execute(&self.pool)
+            .await
+            .map_err(|e| DatabaseError::ConnectionFailed(e.to_string()))?;
+        Ok(())
+    }
+
+    /// The main database plus anything attached to it
+    async fn list_databases(&self) -> Result<Vec<DatabaseInfo>, DatabaseError> {
+        let rows: Vec<SqliteRow> = sqlx::query("SELECT name FROM pragma_database_list")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+
+        let databases = rows
+            .iter()
+            .filter_map(|row| {
+                row.try_get::<String, _>(0).ok().map(|name| DatabaseInfo {
+                    name,
+                    character_set: None,
+                })
+            })
+            .collect();
+
+        Ok(databases)
+    }
+
+    async fn list_tables(&self, database: &str) -> Result<Vec<TableInfo>, DatabaseError> {
+        let query = format!(
+            "SELECT name FROM {}.sqlite_master
+             WHERE type = 'table' AND name NOT LIKE 'sqlite\\_%' ESCAPE '\\'
+             ORDER BY name",
+            quote_ident(database, Dialect::Sqlite)
+        );
+
+        let rows: Vec<SqliteRow> = sqlx::query(&query)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+
+        let tables = rows
+            .iter()
+            .filter_map(|row| {
+                let name: String = row.try_get(0).ok()?;
+                Some(TableInfo {
+                    name,
+                    schema: None,
+                    database: database.to_string(),
+                    engine: None,
+                    character_set: None,
+                    collation: None,
+                    row_count: None,
+                    data_size: None,
+                    columns: Vec::new(),
+                })
+            })
+            .collect();
+
+        Ok(tables)
+    }
+
+    async fn list_views(&self, database: &str) -> Result<Vec<ViewInfo>, DatabaseError> {
+        let query = format!(
+            "SELECT name, sql FROM {}.sqlite_master WHERE type = 'view' ORDER BY name",
+            quote_ident(database, Dialect::Sqlite)
+        );
+
+        let rows: Vec<SqliteRow> = sqlx::query(&query)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+
+        let views = rows
+            .iter()
+            .filter_map(|row| {
+                let name: String = row.try_get(0).ok()?;
+                Some(ViewInfo {
+                    name,
+                    schema: None,
+                    definition: row.try_get(1).ok(),
+                })
+            })
+            .collect();
+
+        Ok(views)
+    }
+
+    async fn describe_table(
+        &self,
+        database: &str,
+        _schema: Option<&str>,
+        table: &str,
+    ) -> Result<TableInfo, DatabaseError> {
+        // A column is unique if it is the only column of a unique index
+        // that covers every row
+        let rows: Vec<SqliteRow> = sqlx::query(
+            "SELECT t.name, t.type, t.\"notnull\", t.dflt_value, t.pk,
+                    EXISTS (
+                        SELECT 1 FROM pragma_index_list(?1, ?2) AS l
+                        WHERE l.\"unique\" AND NOT l.partial
+                          AND (SELECT count(*) FROM pragma_index_info(l.name, ?2)) = 1
+                          AND (SELECT i.name FROM pragma_index_info(l.name, ?2) AS i) = t.name
+                    )
+             FROM pragma_table_info(?1, ?2) AS t",
+        )
+        .bind(table)
+        .bind(database)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+
+        // A lone INTEGER PRIMARY KEY is an alias for the rowid, which SQLite
+        // assigns automatically
+        let pk_count = rows
+            .iter()
+            .filter(|row| row.try_get::<i64, _>(4).unwrap_or(0) > 0)
+            .count();
+
+        let columns = rows
+            .iter()
+            .filter_map(|row| {
+                let name: String = row.try_get(0).ok()?;
+                let data_type: String = row.try_get(1).unwrap_or_default();
+                let is_primary_key = row.try_get::<i64, _>(4).unwrap_or(0) > 0;
+
+                Some(ColumnDetails {
+                    name,
+                    is_auto_increment: is_primary_key
+                        && pk_count == 1
+                        && data_type.eq_ignore_ascii_case("INTEGER"),
+                    data_type,
+                    nullable: row.try_get::<i64, _>(2).unwrap_or(0) == 0,
+                    default_value: row.try_get(3).ok(),
+                    is_primary_key,
+                    is_unique: row.try_get(5).unwrap_or(false),
+                    comment: None,
+                })
+            })
+            .collect();
+
+        // Text encoding is set once for the whole database file
+        let encoding: Option<String> = sqlx::query_scalar(&format!(
+            "PRAGMA {}.encoding",
+            quote_ident(database, Dialect::Sqlite)
+        ))
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+
+        Ok(TableInfo {
+            name: table.to_string(),
+            schema: None,
+            database: database.to_string(),
+            engine: None,
+            character_set: encoding,
+            collation: None,
+            row_count: None,
+            data_size: None,
+            columns,
+        })
+    }
+
+    async fn list_indexes(
+        &self,
+        database: &str,
+        _schema: Option<&str>,
+        table: &str,
+    ) -> Result<Vec<IndexInfo>, DatabaseError> {
+        let rows: Vec<SqliteRow> = sqlx::query(
+            "SELECT name, \"unique\", origin = 'pk' FROM pragma_index_list(?1, ?2)
+             ORDER BY origin = 'pk' DESC, name",
+        )
+        .bind(table)
+        .bind(database)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+
+        let mut indexes = Vec::new();
+        for row in &rows {
+            let Ok(name) = row.try_get::<String, _>(0) else {
+                continue;
+            };
+            // Expressions have no column name
+            let columns: Vec<Option<String>> =
+                sqlx::query_scalar("SELECT name FROM pragma_index_info(?1, ?2) ORDER BY seqno")
+                    .bind(&name)
+                    .bind(database)
+                    .fetch_all(&self.pool)
+                    .await
+                    .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+
+            indexes.push(IndexInfo {
+                columns: columns
+                    .into_iter()
+                    .map(|column| column.unwrap_or_else(|| "(expression)".to_string()))
+                    .collect(),
+                is_unique: row.try_get(1).unwrap_or(false),
+                is_primary: row.try_get(2).unwrap_or(false),
+                method: None,
+                name,
+            });
+        }
+        Ok(indexes)
+    }
+
+    async fn list_foreign_keys(
+        &self,
+        database: &str,
+        _schema: Option<&str>,
+        table: &str,
+    ) -> Result<Vec<ForeignKeyInfo>, DatabaseError> {
+        let rows: Vec<SqliteRow> = sqlx::query(
+            "SELECT id, \"table\", \"from\", \"to\", on_update, on_delete
+             FROM pragma_foreign_key_list(?1, ?2)
+             ORDER BY id, seq",
+        )
+        .bind(table)
+        .bind(database)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+
+        let mut keys: Vec<(i64, ForeignKeyInfo)> = Vec::new();
+        for row in &rows {
+            let id: i64 = row.try_get(0).unwrap_or_default();
+            let column: String = row.try_get(2).unwrap_or_default();
+            // NULL when the key refers to the parent's primary key implicitly
+            let referenced: Option<String> = row.try_get(3).unwrap_or_default();
+            match keys.last_mut() {
+                Some((last, key)) if *last == id => {
+                    key.columns.push(column);
+                    key.referenced_columns.extend(referenced);
+                }
+                _ => keys.push((
+                    id,
+                    ForeignKeyInfo {
+                        name: None,
+                        columns: vec![column],
+                        referenced_table: row.try_get(1).unwrap_or_default(),
+                        referenced_columns: referenced.into_iter().collect(),
+                        on_update: row.try_get(4).ok(),
+                        on_delete: row.try_get(5).ok(),
+                    },
+                )),
+            }
+        }
+        Ok(keys.into_iter().map(|(_, key)| key).collect())
+    }
+
+    async fn list_triggers(
+        &self,
+        database: &str,
+        _schema: Option<&str>,
+        table: &str,
+    ) -> Result<Vec<TriggerInfo>, DatabaseError> {
+        let query = format!(
+            "SELECT name, sql FROM {}.sqlite_master
+             WHERE type = 'trigger' AND tbl_name = ?
+             ORDER BY name",
+            quote_ident(database, Dialect::Sqlite)
+        );
+
+        let rows: Vec<SqliteRow> = sqlx::query(&query)
+            .bind(table)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+
+        Ok(rows
+            .iter()
+            .filter_map(|row| {
+                let statement: String = row.try_get(1).ok()?;
+                let (timing, event) = trigger_header(&statement);
+                Some(TriggerInfo {
+                    name: row.try_get(0).ok()?,
+                    timing,
+                    event,
+                    statement: Some(statement),
+                })
+            })
+            .collect())
+    }
+
+    async fn list_routines(&self, database: &str) -> Result<Vec<SchemaObject>, DatabaseError> {
+        let query = format!(
+            "SELECT name, tbl_name FROM {}.sqlite_master WHERE type = 'trigger' ORDER BY name",
+            quote_ident(database, Dialect::Sqlite)
+        );
+
+        let rows: Vec<SqliteRow> = sqlx::query(&query)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+
+        Ok(rows
+            .iter()
+            .filter_map(|row| {
+                let name: String = row.try_get(0).ok()?;
+                Some(SchemaObject {
+                    table: row.try_get(1).ok(),
+                    ..SchemaObject::new(ObjectKind::Trigger, None, &name)
+                })
+            })
+            .collect())
+    }
+
+    /// The statement SQLite keeps for the object, followed for a table by
+    /// those of its indexes
+    async fn get_ddl(
+        &self,
+        database: &str,
+        object: &SchemaObject,
+    ) -> Result<String, DatabaseError> {
+        let kind = match object.kind {
+            ObjectKind::Table => "table",
+            ObjectKind::View => "view",
+            ObjectKind::Trigger => "trigger",
+            ObjectKind::Procedure | ObjectKind::Function | ObjectKind::Event => {
+                let kind = object.kind.keyword().to_lowercase();
+                return Err(DatabaseError::NotSupported(format!("DDL of a {}", kind)));
+            }
+        };
+        // Indexes SQLite makes for constraints have no statement
+        let query = format!(
+            "SELECT sql FROM {}.sqlite_master
+             WHERE sql IS NOT NULL
+               AND ((type = ? AND name = ?) OR (? = 'table' AND type = 'index' AND tbl_name = ?))
+             ORDER BY type <> ?, name",
+            quote_ident(database, Dialect::Sqlite)
+        );
+
+        let statements: Vec<String> = sqlx::query_scalar(&query)
+            .bind(kind)
+            .bind(&object.name)
+            .bind(kind)
+            .bind(&object.name)
+            .bind(kind)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+
+        if statements.is_empty() {
+            return Err(DatabaseError::QueryFailed(format!(
+                "No {} named {}",
+                kind, object.name
+            )));
+        }
+        Ok(ddl_script(statements))
+    }
+
+    async fn execute_query(&self, sql: &str) -> Result<QueryResult, DatabaseError> {
+        with_timeout(&self.limits, self.fetch(sql)).await
+    }
+
+    /// SQLite runs in-process, so dropping the stream is all cancelling or
+    /// enforcing a limit takes. Attached databases are reached through
+    /// qualified names, so there is no database to switch to.
+    fn stream_query<'a>(
+        &'a self,
+        _database: Option<&'a str>,
+        sql: &'a str,
+        params: &'a [ParamValue],
+        _id: QueryId,
+    ) -> BoxStream<'a, Result<RowBatch, DatabaseError>> {
+        let batches = Box::pin(async_stream::try_stream! {
+            let mut conn = self.acquire().await?;
+            let columns = describe_columns(&mut conn, sql).await;
+            let rows = bind_params(sqlx::query(sql), params).fetch(&mut *conn);
+            let mut batches = batch_rows(rows, columns, Self::row_to_values);
+            while let Some(batch) = batches.next().await {
+                yield batch?;
+            }
+        });
+        limit_batches(batches, self.limits)
+    }
+
+    async fn get_table_data(
+        &self,
+        database: &str,
+        _schema: Option<&str>,
+        table: &str,
+        limit: u32,
+        offset: u32,
+    ) -> Result<QueryResult, DatabaseError> {
+        let sql = format!(
+            "SELECT * FROM {} LIMIT {} OFFSET {}",
+            quote_qualified(&[database, table], Dialect::Sqlite),
+            limit,
+            offset
+        );
+        self.fetch(&sql).await
+    }
+
+    async fn close(&self) -> Result<(), DatabaseError> {
+        self.pool.close().await;
+        Ok(())
+    }
+
+    fn as_sql(&self) -> Option<&dyn SqlConnection> {
+        Some(self)
+    }
+}
+
+#[async_trait]
+impl SqlConnection for SqliteConnection {
+    async fn execute_statement(
+        &self,
+        _database: Option<&str>,
+        sql: &str,
+        params: &[ParamValue],
+        _id: QueryId,
+    ) -> Result<u64, DatabaseError> {
+        let result = with_timeout(&self.limits, async {
+            bind_params(sqlx::query(sql), params)
+                .execute(&self.pool)
+                .await
+                .map_err(|e| DatabaseError::QueryFailed(e.to_string()))
+        })
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Scripts keep their connection so that a `:memory:` database, which
+    /// lives and dies with its only connection, survives them
+    async fn session(
+        &self,
+        _database: Option<&str>,
+        _id: QueryId,
+    ) -> Result<Box<dyn SqlSession + '_>, DatabaseError> {
+        let conn = self.acquire().await?;
+        Ok(Box::new(SqliteSession {
+            conn,
+            limits: self.limits,
+        }))
+    }
+}
+
+struct SqliteSession {
+    conn: PoolConnection<Sqlite>,
+    limits: QueryLimits,
+}
+
+#[async_trait]
+impl SqlSession for SqliteSession {
+    async fn execute(&mut self, sql: &str, params: &[ParamValue]) -> Result<u64, DatabaseError> {
+        let result = with_timeout(&self.limits, async {
+            bind_params(sqlx::query(sql), params)
+                .execute(&mut *self.conn)
+                .await
+                .map_err(|e| DatabaseError::QueryFailed(e.to_string()))
+        })
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn execute_unprepared(&mut self, sql: &str) -> Result<u64, DatabaseError> {
+        let result = (&mut *self.conn)
+            .execute(sql)
+            .await
+            .map_err(|e| DatabaseError::QueryFailed(e.to_string()))?;
+        Ok(result.rows_affected())
+    }
+
+    async fn fetch(
+        &mut self,
+        sql: &str,
+        params: &[ParamValue],
+    ) -> Result<QueryResult, DatabaseError> {
+        let columns = describe_columns(&mut self.conn, sql).await;
+        let rows = bind_params(sqlx::query(sql), params).fetch(&mut *self.conn);
+        collect_batches(limit_batches(
+            batch_rows(rows, columns, SqliteConnection::row_to_values),
+            self.limits,
+        ))
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{DatabaseType, ResultLimit};
+
+    async fn memory_connection() -> SqliteConnection {
+        let config = ConnectionConfig {
+            db_type: DatabaseType::SQLite,
+            database: MEMORY_DATABASE.to_string(),
+            ..Default::default()
+        };
+        let conn = SqliteConnection::connect(&config).await.unwrap();
+        conn.execute_statement(
+            None,
+            "CREATE TABLE users (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL DEFAULT 'anon',
+                active BOOLEAN,
+                score REAL,
+                avatar BLOB
+            )",
+            &[],
+            QueryId::new(),
+        )
+        .await
+        .unwrap();
+        conn.execute_statement(
+            None,
+            "CREATE VIEW active_users AS SELECT * FROM users WHERE active",
+            &[],
+            QueryId::new(),
+        )
+        .await
+        .unwrap();
+        conn
+    }
+
+    #[tokio::test]
+    async fn test_catalog() {
+        let conn = memory_connection().await;
+
+        let databases = conn.list_databases().await.unwrap();
+        assert_eq!(databases[0].name, "main");
+
+        let tables = conn.list_tables("main").await.unwrap();
+        assert_eq!(tables.len(), 1);
+        assert_eq!(tables[0].name, "users");
+
+        let views = conn.list_views("main").await.unwrap();
+        assert_eq!(views[0].name, "active_users");
+        assert!(views[0]
+            .definition
+            .as_deref()
+            .unwrap()
+            .contains("WHERE active"));
+
+        let info = conn.describe_table("main", None, "users").await.unwrap();
+        let id = &info.columns[0];
+        assert!(id.is_primary_key && id.is_auto_increment);
+        let name = &info.columns[1];
+        assert!(!name.nullable && !name.is_unique);
+        assert_eq!(name.default_value.as_deref(), Some("'anon'"));
+
+        conn.execute_statement(
+            None,
+            "CREATE TABLE tags (label TEXT UNIQUE, note TEXT, UNIQUE (label, note))",
+            &[],
+            QueryId::new(),
+        )
+        .await
+        .unwrap();
+        let info = conn.describe_table("main", None, "tags").await.unwrap();
+        assert!(info.columns[0].is_unique && !info.columns[1].is_unique);
+    }
+
+    #[tokio::test]
+    async fn test_structure() {
+        let conn = memory_connection().await;
+        for sql in [
+            "CREATE TABLE orders (
+                id INTEGER,
+                region TEXT,
+                user_id INTEGER REFERENCES users ON DELETE CASCADE,
+                PRIMARY KEY (id, region)
+            )",
+            "CREATE INDEX orders_by_user ON orders (user_id, lower(region))",
+            "CREATE TRIGGER orders_audit AFTER UPDATE OF user_id ON orders
+             BEGIN SELECT 1; END",
+        ] {
+            conn.execute_statement(None, sql, &[], QueryId::new())
+                .await
+                .unwrap();
+        }
+
+        let structure = conn.table_structure("main", None, "orders").await.unwrap();
+        assert_eq!(structure.table.character_set.as_deref(), Some("UTF-8"));
+
+        let indexes = &structure.indexes;
+        assert_eq!(indexes.len(), 2);
+        assert!(indexes[0].is_primary && indexes[0].is_unique);
+        assert_eq!(indexes[0].columns, ["id", "region"]);
+        assert_eq!(indexes[1].name, "orders_by_user");
+        assert_eq!(indexes[1].columns, ["user_id", "(expression)"]);
+        assert!(!indexes[1].is_unique);
+
+        let key = &structure.foreign_keys[0];
+        assert_eq!(key.columns, ["user_id"]);
+        assert_eq!(key.referenced_table, "users");
+        assert!(key.referenced_columns.is_empty());
+        assert_eq!(key.on_delete.as_deref(), Some("CASCADE"));
+
+        let trigger = &structure.triggers[0];
+        assert_eq!(trigger.name, "orders_audit");
+        assert_eq!(
+            (trigger.timing.as_str(), trigger.event.as_str()),
+            ("AFTER", "UPDATE")
+        );
+
+        let users = conn.table_structure("main", None, "users").await.unwrap();
+        assert!(users.indexes.is_empty() && users.foreign_keys.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_ddl() {
+        let conn = memory_connection().await;
+        for sql in [
+            "CREATE INDEX users_by_name ON users (name)",
+            "CREATE TRIGGER users_audit AFTER DELETE ON users BEGIN SELECT 1; END",
+        ] {
+            conn.execute_statement(None, sql, &[], QueryId::new())
+                .await
+                .unwrap();
+        }
+
+        let table = SchemaObject::new(ObjectKind::Table, None, "users");
+        let ddl = conn.get_ddl("main", &table).await.unwrap();
+        assert!(ddl.starts_with("CREATE TABLE users ("));
+        assert!(ddl.ends_with(";\n\nCREATE INDEX users_by_name ON users (name);"));
+
+        let view = &conn.list_views("main").await.unwrap()[0];
+        let ddl = conn
+            .get_ddl("main", &SchemaObject::view(view))
+            .await
+            .unwrap();
+        assert_eq!(ddl, format!("{};", view.definition.as_deref().unwrap()));
+
+        let routines = conn.list_routines("main").await.unwrap();
+        assert_eq!(routines.len(), 1);
+        assert_eq!(routines[0].table.as_deref(), Some("users"));
+        let ddl = conn.get_ddl("main", &routines[0]).await.unwrap();
+        assert!(ddl.ends_with("BEGIN SELECT 1; END;"));
+
+        let missing = SchemaObject::new(ObjectKind::Table, None, "missing");
+        assert!(conn.get_ddl("main", &missing).await.is_err());
+        let event = SchemaObject::new(ObjectKind::Event, None, "nightly");
+        assert!(matches!(
+            conn.get_ddl("main", &event).await,
+            Err(DatabaseError::NotSupported(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_dynamic_typing() {
+        let conn = memory_connection().await;
+        conn.execute_statement(
+            None,
+            "INSERT INTO users (name, active, score, avatar)
+             VALUES ('ada', 1, 9.5, x'0102'), ('bob', NULL, 'n/a', NULL)",
+            &[],
+            QueryId::new(),
+        )
+        .await
+        .unwrap();
+
+        let result = conn
+            .get_table_data("main", None, "users", 10, 0)
+            .await
+            .unwrap();
+        assert_eq!(result.columns.len(), 5);
+
+        let ada = &result.rows[0];
+        assert!(matches!(ada[0], CellValue::Int(1)));
+        assert!(matches!(ada[2], CellValue::Bool(true)));
+        assert!(matches!(ada[3], CellValue::Float(f) if f == 9.5));
+        assert!(matches!(&ada[4], CellValue::Bytes(b) if b == &[1, 2]));
+
+        // A REAL column happily stores text
+        let bob = &result.rows[1];
+        assert!(matches!(bob[2], CellValue::Null));
+        assert!(matches!(&bob[3], CellValue::String(s) if s == "n/a"));
+    }
+
+    #[tokio::test]
+    async fn test_table_data_within_limits() {
+        let mut conn = memory_connection().await;
+        conn.execute_statement(
+            None,
+            "INSERT INTO users (name) VALUES ('ada'), ('bob')",
+            &[],
+            QueryId::new(),
+        )
+        .await
+        .unwrap();
+        conn.limits.max_rows = Some(1);
+        let result = conn
+            .get_table_data("main", None, "users", 10, 0)
+            .await
+            .unwrap();
+        assert_eq!(result.rows.len(), 1);
+        assert_eq!(result.limit, Some(ResultLimit::MaxRows(1)));
+    }
+
+    #[tokio::test]
+    async fn test_typed_text() {
+        let conn = memory_connection().await;
+        conn.execute_statement(
+            None,
+            "CREATE TABLE events (day DATE, at DATETIME, note TIME)",
+            &[],
+            QueryId::new(),
+        )
+        .await
+        .unwrap();
+        conn.execute_statement(
+            None,
+            "INSERT INTO events VALUES
+                ('2024-02-29', '2024-02-29T12:30:00+02:00', 'teatime')",
+            &[],
+            QueryId::new(),
+        )
+        .await
+        .unwrap();
+
+        let result = conn
+            .get_table_data("main", None, "events", 10, 0)
+            .await
+            .unwrap();
+        let row = &result.rows[0];
+        assert!(matches!(row[0], CellValue::Date(_)));
+        assert_eq!(row[1].to_string(), "2024-02-29 12:30:00+02:00");
+        // Text that doesn't parse as its declared type is kept as it is
+        assert!(matches!(&row[2], CellValue::String(s) if s == "teatime"));
+    }
+
+    #[tokio::test]
+    async fn test_bound_params() {
+        let conn = memory_connection().await;
+        let params = [
+            ParamValue::String("it's".to_string()),
+            ParamValue::Int(1),
+            ParamValue::Null,
+        ];
+        conn.execute_statement(
+            None,
+            "INSERT INTO users (name, active, score) VALUES (?, ?, ?)",
+            &params,
+            QueryId::new(),
+        )
+        .await
+        .unwrap();
+
+        let mut session = conn.session(None, QueryId::new()).await.unwrap();
+        let date = ParamValue::Date(chrono::NaiveDate::from_ymd_opt(2024, 2, 29).unwrap());
+        let result = session
+            .fetch(
+                "SELECT name, score IS NULL, ? FROM users WHERE active = ?",
+                &[date, ParamValue::Int(1)],
+            )
+            .await
+            .unwrap();
+        let row = &result.rows[0];
+        assert!(matches!(&row[0], CellValue::String(s) if s == "it's"));
+        assert!(matches!(row[1], CellValue::Int(1)));
+        assert!(matches!(&row[2], CellValue::String(s) if s == "2024-02-29"));
+    }
+
+    #[tokio::test]
+    async fn test_empty_result_columns() {
+        let conn = memory_connection().await;
+
+        let result = conn
+            .execute_query("SELECT id, name, score FROM users WHERE name = 'nobody'")
+            .await
+            .unwrap();
+        assert!(result.rows.is_empty());
+        let names: Vec<_> = result.columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["id", "name", "score"]);
+        assert!(!result.columns[1].nullable);
+        assert!(result.columns[2].nullable);
+
+        let mut rows = conn.stream_query(None, "SELECT name FROM users", &[], QueryId::new());
+        let batch = rows.next().await.unwrap().unwrap();
+        assert_eq!(batch.columns.unwrap()[0].name, "name");
+        assert!(rows.next().await.is_none());
+
+        // Statements without a result set have no columns
+        let result = conn.execute_query("DELETE FROM users").await.unwrap();
+        assert!(result.columns.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_result_limits() {
+        use crate::models::ResultLimit;
+        use futures::TryStreamExt;
+
+        let config = ConnectionConfig {
+            db_type: DatabaseType::SQLite,
+            database: MEMORY_DATABASE.to_string(),
+            limits: QueryLimits {
+                max_rows: Some(1000),
+                max_result_bytes: Some(4000),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let conn = SqliteConnection::connect(&config).await.unwrap();
+        let count = |n: u32| {
+            format!(
+                "WITH RECURSIVE n(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM n WHERE x < {})
+                 SELECT x FROM n",
+                n
+            )
+        };
+        let run = |sql: String| {
+            let conn = &conn;
+            async move {
+                let batches: Vec<RowBatch> = conn
+                    .stream_query(None, &sql, &[], QueryId::new())
+                    .try_collect()
+                    .await
+                    .unwrap();
+                let rows: usize = batches.iter().map(|b| b.rows.len()).sum();
+                (rows, batches.last().and_then(|b| b.limit))
+            }
+        };
+
+        // Integers count as 8 bytes each, and filling the limit exactly is
+        // not a truncation
+        assert_eq!(run(count(500)).await, (500, None));
+        assert_eq!(
+            run(count(600)).await,
+            (500, Some(ResultLimit::MaxBytes(4000)))
+        );
+
+        let text = "WITH RECURSIVE n(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM n WHERE x < 5000)
+                    SELECT '' FROM n";
+        let (rows, limit) = run(text.to_string()).await;
+        assert_eq!((rows, limit), (1000, Some(ResultLimit::MaxRows(1000))));
+    }
+}
@@ -0,0 +1,234 @@
+use crate::db::{DatabaseConnection, DatabaseError, QueryId, RowBatch};
+use crate::models::ParamValue;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::runtime::Handle;
+use tokio::sync::mpsc;
+use tokio::task::AbortHandle;
+
+/// Batches buffered between the query task and the UI
+const CHANNEL_CAPACITY: usize = 16;
+
+/// Progress of a streamed query, as seen by the UI
+#[derive(Debug)]
+pub enum StreamEvent {
+    Batch(RowBatch),
+    /// The page of rows has been delivered and the query is waiting for
+    /// `fetch_more`
+    Paused,
+    Finished,
+    Failed(String),
+}
+
+/// A query running on the runtime that delivers its rows in pages. The
+/// server-side cursor stays open while paused, and dropping the handle
+/// cancels the query.
+pub struct QueryStream {
+    events: mpsc::Receiver<StreamEvent>,
+    more: mpsc::UnboundedSender<usize>,
+    runtime: Handle,
+    conn: Arc<dyn DatabaseConnection>,
+    id: QueryId,
+    task: AbortHandle,
+    started: Instant,
+    pub rows_received: usize,
+    pub paused: bool,
+    pub finished: bool,
+}
+
+impl QueryStream {
+    pub fn spawn(
+        runtime: &Handle,
+        conn: Arc<dyn DatabaseConnection>,
+        database: Option<String>,
+        sql: String,
+        params: Vec<ParamValue>,
+        id: QueryId,
+        page_size: usize,
+    ) -> Self {
+        let (events_tx, events) = mpsc::channel(CHANNEL_CAPACITY);
+        let (more, more_rx) = mpsc::unbounded_channel();
+        let task = runtime
+            .spawn({
+                let conn = conn.clone();
+                async move {
+                    let stream = conn.stream_query(database.as_deref(), &sql, &params, id);
+                    pump(stream, page_size.max(1), events_tx, more_rx).await;
+                }
+            })
+            .abort_handle();
+
+        Self {
+            events,
+            more,
+            runtime: runtime.clone(),
+            conn,
+            id,
+            task,
+            started: Instant::now(),
+            rows_received: 0,
+            paused: false,
+            finished: false,
+        }
+    }
+
+    /// Drain the events that arrived since the last poll
+    pub fn poll(&mut self) -> Vec<StreamEvent> {
+        let mut events = Vec::new();
+        while let Ok(event) = self.events.try_recv() {
+            match &event {
+                StreamEvent::Batch(batch) => self.rows_received += batch.rows.len(),
+                StreamEvent::Paused => self.paused = true,
+                StreamEvent::Finished | StreamEvent::Failed(_) => self.finished = true,
+            }
+            events.push(event);
+        }
+        events
+    }
+
+    /// Resume a paused stream for another `rows` rows
+    pub fn fetch_more(&mut self, rows: usize) {
+        if self.paused && self.more.send(rows.max(1)).is_ok() {
+            self.paused = false;
+        }
+    }
+
+    pub fn elapsed_ms(&self) -> u64 {
+        self.started.elapsed().as_millis() as u64
+    }
+
+    /// Stop the query on the server, then drop it on the client. Rows
+    /// already polled stay with the caller.
+    pub fn cancel(&mut self) {
+        if self.finished {
+            return;
+        }
+        self.finished = true;
+        self.paused = false;
+
+        cancel_task(&self.runtime, self.conn.clone(), self.id, self.task.clone());
+    }
+}
+
+impl Drop for QueryStream {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+/// Cancel query `id` on the server, then abort the task running it. The
+/// server needs the session id, which is forgotten once the task is
+/// dropped, so the abort waits for the cancel to go through.
+pub fn cancel_task(
+    runtime: &Handle,
+    conn: Arc<dyn DatabaseConnection>,
+    id: QueryId,
+    task: AbortHandle,
+) {
+    runtime.spawn(async move {
+        if let Err(e) = conn.cancel(id).await {
+            tracing::warn!("Failed to cancel query on the server: {}", e);
+        }
+        task.abort();
+    });
+}
+
+async fn pump(
+    mut stream: BoxStream<'_, Result<RowBatch, DatabaseError>>,
+    page_size: usize,
+    events: mpsc::Sender<StreamEvent>,
+    mut more: mpsc::UnboundedReceiver<usize>,
+) {
+    let mut budget = page_size;
+    // Rows read past the end of a page, delivered once the next one is asked for
+    let mut carry: Option<RowBatch> = None;
+
+    loop {
+        if carry.is_none() {
+            carry = match stream.next().await {
+                Some(Ok(batch)) => Some(batch),
+                Some(Err(e)) => {
+                    let _ = events.send(StreamEvent::Failed(e.to_string())).await;
+                    return;
+                }
+                None => {
+                    let _ = events.send(StreamEvent::Finished).await;
+                    return;
+                }
+            };
+        }
+
+        // Only pause once we know there are more rows to come
+        if budget == 0 && carry.as_ref().is_some_and(|batch| !batch.rows.is_empty()) {
+            if events.send(StreamEvent::Paused).await.is_err() {
+                return;
+            }
+            match more.recv().await {
+                Some(rows) => budget = rows,
+                None => return,
+            }
+        }
+
+        let Some(mut batch) = carry.take() else {
+            continue;
+        };
+        if batch.rows.len() > budget {
+            carry = Some(batch.split_off(budget));
+        }
+        budget -= batch.rows.len();
+
+        if events.send(StreamEvent::Batch(batch)).await.is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::sqlite::{SqliteConnection, MEMORY_DATABASE};
+    use crate::models::{ConnectionConfig, DatabaseType};
+
+    /// Poll until the stream pauses or ends, returning the rows received
+    async fn settle(stream: &mut QueryStream) -> usize {
+        loop {
+            stream.poll();
+            if stream.paused || stream.finished {
+                return stream.rows_received;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pages_rows() {
+        let config = ConnectionConfig {
+            db_type: DatabaseType::SQLite,
+            database: MEMORY_DATABASE.to_string(),
+            ..Default::default()
+        };
+        let conn = SqliteConnection::connect(&config).await.unwrap();
+        let sql = "WITH RECURSIVE n(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM n WHERE x < 1200)
+                   SELECT x FROM n";
+
+        let mut stream = QueryStream::spawn(
+            &tokio::runtime::Handle::current(),
+            Arc::new(conn),
+            None,
+            sql.to_string(),
+            Vec::new(),
+            QueryId::new(),
+            700,
+        );
+
+        assert_eq!(settle(&mut stream).await, 700);
+        assert!(stream.paused);
+
+        stream.fetch_more(500);
+        assert_eq!(settle(&mut stream).await, 1200);
+        // Exactly one page was left, so the stream ends instead of pausing
+        assert!(stream.finished && !stream.paused);
+    }
+}
@@ -0,0 +1,237 @@
+use crate::models::CellValue;
+
+const EWKB_Z: u32 = 0x8000_0000;
+const EWKB_M: u32 = 0x4000_0000;
+const EWKB_SRID: u32 = 0x2000_0000;
+
+/// A PostGIS geometry in extended WKB, which carries the SRID in its header
+pub fn from_ewkb(bytes: &[u8]) -> Option<CellValue> {
+    let mut reader = Reader { bytes, pos: 0 };
+    let (wkt, srid) = reader.geometry()?;
+    Some(CellValue::Geometry {
+        srid: srid.filter(|&srid| srid != 0),
+        wkt,
+    })
+}
+
+/// A MySQL geometry: a little-endian SRID followed by plain WKB
+pub fn from_mysql(bytes: &[u8]) -> Option<CellValue> {
+    let srid = u32::from_le_bytes(bytes.get(..4)?.try_into().ok()?);
+    let mut reader = Reader {
+        bytes: &bytes[4..],
+        pos: 0,
+    };
+    let (wkt, _) = reader.geometry()?;
+    Some(CellValue::Geometry {
+        srid: Some(srid).filter(|&srid| srid != 0),
+        wkt,
+    })
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+/// Byte order and coordinate count of the geometry being read
+#[derive(Clone, Copy)]
+struct Header {
+    little_endian: bool,
+    dims: usize,
+}
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let bytes = self.bytes.get(self.pos..self.pos + N)?.try_into().ok()?;
+        self.pos += N;
+        Some(bytes)
+    }
+
+    fn u32(&mut self, header: Header) -> Option<u32> {
+        let bytes = self.take()?;
+        Some(if header.little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    fn f64(&mut self, header: Header) -> Option<f64> {
+        let bytes = self.take()?;
+        Some(if header.little_endian {
+            f64::from_le_bytes(bytes)
+        } else {
+            f64::from_be_bytes(bytes)
+        })
+    }
+
+    /// One geometry as WKT, with the SRID if the header had one
+    fn geometry(&mut self) -> Option<(String, Option<u32>)> {
+        let [order] = self.take()?;
+        let mut header = Header {
+            little_endian: order == 1,
+            dims: 2,
+        };
+        let raw = self.u32(header)?;
+
+        // EWKB flags the extra dimensions in the high bits, ISO WKB adds
+        // 1000 (Z), 2000 (M) or 3000 (ZM) to the type
+        let (kind, z, m) = match raw & 0xFFFF {
+            kind if raw & (EWKB_Z | EWKB_M) != 0 => (kind, raw & EWKB_Z != 0, raw & EWKB_M != 0),
+            kind => (
+                kind % 1000,
+                matches!(kind / 1000, 1 | 3),
+                matches!(kind / 1000, 2 | 3),
+            ),
+        };
+        header.dims += usize::from(z) + usize::from(m);
+        let srid = if raw & EWKB_SRID != 0 {
+            Some(self.u32(header)?)
+        } else {
+            None
+        };
+
+        let (name, body) = match kind {
+            1 => ("POINT", self.point(header)?),
+            2 => ("LINESTRING", self.points(header)?),
+            3 => ("POLYGON", self.rings(header)?),
+            4 => ("MULTIPOINT", self.members(header)?),
+            5 => ("MULTILINESTRING", self.members(header)?),
+            6 => ("MULTIPOLYGON", self.members(header)?),
+            7 => ("GEOMETRYCOLLECTION", self.collection(header)?),
+            _ => return None,
+        };
+        let dims = match (z, m) {
+            (true, true) => " ZM ",
+            (true, false) => " Z ",
+            (false, true) => " M ",
+            (false, false) => "",
+        };
+        let wkt = match body {
+            Some(body) if dims.is_empty() => format!("{}{}", name, body),
+            Some(body) => format!("{}{}{}", name, dims, body),
+            None => format!("{} EMPTY", name),
+        };
+        Some((wkt, srid))
+    }
+
+    /// `x y [z [m]]`, or `None` for the empty point, whose coordinates are NaN
+    fn coordinates(&mut self, header: Header) -> Option<Option<String>> {
+        let coordinates = (0..header.dims)
+            .map(|_| self.f64(header))
+            .collect::<Option<Vec<f64>>>()?;
+        if coordinates.iter().all(|c| c.is_nan()) {
+            return Some(None);
+        }
+        let text = coordinates
+            .iter()
+            .map(f64::to_string)
+            .collect::<Vec<_>>()
+            .join(" ");
+        Some(Some(text))
+    }
+
+    fn point(&mut self, header: Header) -> Option<Option<String>> {
+        Some(self.coordinates(header)?.map(|c| format!("({})", c)))
+    }
+
+    fn points(&mut self, header: Header) -> Option<Option<String>> {
+        let count = self.u32(header)?;
+        let points = (0..count)
+            .map(|_| self.coordinates(header).map(Option::unwrap_or_default))
+            .collect::<Option<Vec<String>>>()?;
+        Some(group(points))
+    }
+
+    fn rings(&mut self, header: Header) -> Option<Option<String>> {
+        let count = self.u32(header)?;
+        let rings = (0..count)
+            .map(|_| self.points(header).map(Option::unwrap_or_default))
+            .collect::<Option<Vec<String>>>()?;
+        Some(group(rings))
+    }
+
+    /// The parts of a MULTI* geometry, each a full WKB geometry of its own,
+    /// written without their type names
+    fn members(&mut self, header: Header) -> Option<Option<String>> {
+        let count = self.u32(header)?;
+        let members = (0..count)
+            .map(|_| {
+                let (wkt, _) = self.geometry()?;
+                let body = wkt.find('(').map_or("EMPTY", |start| &wkt[start..]);
+                Some(body.to_string())
+            })
+            .collect::<Option<Vec<String>>>()?;
+        Some(group(members))
+    }
+
+    fn collection(&mut self, header: Header) -> Option<Option<String>> {
+        let count = self.u32(header)?;
+        let members = (0..count)
+            .map(|_| self.geometry().map(|(wkt, _)| wkt))
+            .collect::<Option<Vec<String>>>()?;
+        Some(group(members))
+    }
+}
+
+/// `(a,b,c)`, or `None` for an empty list
+fn group(items: Vec<String>) -> Option<String> {
+    if items.is_empty() {
+        None
+    } else {
+        Some(format!("({})", items.join(",")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wkt(value: Option<CellValue>) -> String {
+        value.unwrap().to_string()
+    }
+
+    fn hex(text: &str) -> Vec<u8> {
+        (0..text.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&text[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_ewkb() {
+        // SELECT 'SRID=4326;POINT(1 2)'::geometry
+        let point = hex("0101000020E6100000000000000000F03F0000000000000040");
+        assert_eq!(wkt(from_ewkb(&point)), "SRID=4326;POINT(1 2)");
+
+        // LINESTRING Z (0 0 1,1 1 2), big-endian
+        let line = hex("0080000002000000020000000000000000000000000000000\
+             03FF00000000000003FF00000000000003FF00000000000004000000000000000");
+        assert_eq!(wkt(from_ewkb(&line)), "LINESTRING Z (0 0 1,1 1 2)");
+
+        assert!(from_ewkb(&point[..10]).is_none());
+    }
+
+    #[test]
+    fn test_mysql() {
+        // ST_GeomFromText('MULTIPOINT((1 2),(3 4))')
+        let mut bytes = vec![0, 0, 0, 0];
+        bytes.extend(hex(
+            "0104000000020000000101000000000000000000F03F0000000000000040\
+             010100000000000000000008400000000000001040",
+        ));
+        assert_eq!(wkt(from_mysql(&bytes)), "MULTIPOINT((1 2),(3 4))");
+
+        // ST_GeomFromText('POLYGON((0 0,1 0,0 1,0 0))', 3857)
+        let mut bytes = 3857u32.to_le_bytes().to_vec();
+        bytes.extend(hex(
+            "0103000000010000000400000000000000000000000000000000000000\
+             000000000000F03F000000000000000000000000000000000000000000\
+             00F03F00000000000000000000000000000000",
+        ));
+        assert_eq!(
+            wkt(from_mysql(&bytes)),
+            "SRID=3857;POLYGON((0 0,1 0,0 1,0 0))"
+        );
+    }
+}
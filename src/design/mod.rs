@@ -0,0 +1,1177 @@
+use crate::db::quote::{quote_ident, quote_literal, quote_qualified};
+use crate::db::{DatabaseConnection, QueryId, TableStructure};
+use crate::sql::Dialect;
+use std::sync::Arc;
+
+/// Foreign key actions offered for ON UPDATE and ON DELETE
+pub const REFERENTIAL_ACTIONS: [&str; 5] = [
+    "NO ACTION",
+    "RESTRICT",
+    "CASCADE",
+    "SET NULL",
+    "SET DEFAULT",
+];
+
+/// A column as laid out in the designer
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ColumnDesign {
+    /// Position of the column in the table as loaded, or `None` for a new
+    /// one, so that renames aren't taken for a drop and an add
+    pub origin: Option<usize>,
+    pub name: String,
+    pub data_type: String,
+    pub nullable: bool,
+    /// A SQL expression such as `0`, `'none'` or `CURRENT_TIMESTAMP`;
+    /// empty for no default
+    pub default_value: String,
+    pub is_primary_key: bool,
+    pub is_auto_increment: bool,
+    /// Empty for no comment
+    pub comment: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct IndexDesign {
+    /// Position of the index in the table as loaded, or `None` for a new one
+    pub origin: Option<usize>,
+    pub name: String,
+    /// Column names in key order. Entries that name no column are taken as
+    /// expressions.
+    pub columns: Vec<String>,
+    pub is_unique: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ForeignKeyDesign {
+    /// Position of the key in the table as loaded, or `None` for a new one
+    pub origin: Option<usize>,
+    /// Constraint name; left empty the engine picks one
+    pub name: String,
+    pub columns: Vec<String>,
+    /// Qualified when it lives in another schema or database
+    pub referenced_table: String,
+    /// Comma-separated, as the other table's columns aren't loaded. Empty
+    /// refers to its primary key.
+    pub referenced_columns: String,
+    /// `None` leaves the engine's default, which is NO ACTION
+    pub on_update: Option<String>,
+    pub on_delete: Option<String>,
+}
+
+/// The parts of a table the designer can change
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TableLayout {
+    pub name: String,
+    pub columns: Vec<ColumnDesign>,
+    pub indexes: Vec<IndexDesign>,
+    pub foreign_keys: Vec<ForeignKeyDesign>,
+}
+
+/// A new table, or changes to an existing one, to be turned into a
+/// CREATE TABLE or ALTER TABLE script
+#[derive(Debug, Clone)]
+pub struct TableDesign {
+    pub dialect: Dialect,
+    /// The database in MySQL, the schema elsewhere
+    pub qualifier: Option<String>,
+    pub table: TableLayout,
+    /// The table as loaded, or `None` when creating one
+    original: Option<TableLayout>,
+    /// Name of the primary key constraint, which PostgreSQL drops by name
+    primary_key_name: Option<String>,
+}
+
+impl TableDesign {
+    /// A new table, starting with an auto-incremented `id` key
+    pub fn new(dialect: Dialect, qualifier: Option<String>) -> Self {
+        let id = ColumnDesign {
+            name: "id".to_string(),
+            data_type: match dialect {
+                Dialect::MySql => "INT",
+                Dialect::Postgres | Dialect::Sqlite => "INTEGER",
+            }
+            .to_string(),
+            is_primary_key: true,
+            is_auto_increment: true,
+            ..Default::default()
+        };
+        Self {
+            dialect,
+            qualifier,
+            table: TableLayout {
+                columns: vec![id],
+                ..Default::default()
+            },
+            original: None,
+            primary_key_name: None,
+        }
+    }
+
+    /// Start from an existing table, as the Structure view reads it
+    pub fn from_structure(structure: &TableStructure, dialect: Dialect) -> Self {
+        let info = &structure.table;
+        let columns = info
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| ColumnDesign {
+                origin: Some(i),
+                name: column.name.clone(),
+                data_type: column.data_type.clone(),
+                nullable: column.nullable,
+                default_value: column
+                    .default_value
+                    .as_deref()
+                    .map(|value| default_expression(value, dialect))
+                    .unwrap_or_default(),
+                is_primary_key: column.is_primary_key,
+                is_auto_increment: column.is_auto_increment,
+                comment: column.comment.clone().unwrap_or_default(),
+            })
+            .collect();
+        let indexes = structure
+            .indexes
+            .iter()
+            .filter(|index| !index.is_primary)
+            .enumerate()
+            .map(|(i, index)| IndexDesign {
+                origin: Some(i),
+                name: index.name.clone(),
+                columns: index.columns.iter().map(|c| unquote(c, dialect)).collect(),
+                is_unique: index.is_unique,
+            })
+            .collect();
+        let foreign_keys = structure
+            .foreign_keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| ForeignKeyDesign {
+                origin: Some(i),
+                name: key.name.clone().unwrap_or_default(),
+                columns: key.columns.clone(),
+                referenced_table: key.referenced_table.clone(),
+                referenced_columns: key.referenced_columns.join(", "),
+                on_update: key.on_update.clone(),
+                on_delete: key.on_delete.clone(),
+            })
+            .collect();
+        let table = TableLayout {
+            name: info.name.clone(),
+            columns,
+            indexes,
+            foreign_keys,
+        };
+
+        Self {
+            dialect,
+            qualifier: match dialect {
+                Dialect::Postgres => info.schema.clone(),
+                Dialect::MySql | Dialect::Sqlite => Some(info.database.clone()),
+            },
+            original: Some(table.clone()),
+            table,
+            primary_key_name: structure
+                .indexes
+                .iter()
+                .find(|index| index.is_primary)
+                .map(|index| index.name.clone()),
+        }
+    }
+
+    pub fn is_new(&self) -> bool {
+        self.original.is_none()
+    }
+
+    /// The schema the table is in, for engines that have them (PostgreSQL)
+    pub fn schema(&self) -> Option<&str> {
+        match self.dialect {
+            Dialect::Postgres => self.qualifier.as_deref(),
+            Dialect::MySql | Dialect::Sqlite => None,
+        }
+    }
+
+    /// The table's name as the schema tree shows it, once the script ran
+    pub fn qualified_name(&self) -> String {
+        match self.schema() {
+            Some(schema) => format!("{}.{}", schema, self.table.name),
+            None => self.table.name.clone(),
+        }
+    }
+
+    /// The statements that create the table or bring it in line with the
+    /// design, in order. Empty when nothing changed.
+    pub fn script(&self) -> Result<Vec<String>, String> {
+        self.validate()?;
+        match &self.original {
+            None => self.create_script(),
+            Some(original) => self.alter_script(original),
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let table = &self.table;
+        if table.name.trim().is_empty() {
+            return Err("The table needs a name".to_string());
+        }
+        if table.columns.is_empty() {
+            return Err("The table needs at least one column".to_string());
+        }
+        for (i, column) in table.columns.iter().enumerate() {
+            if column.name.trim().is_empty() {
+                return Err(format!("Column {} needs a name", i + 1));
+            }
+            if column.data_type.trim().is_empty() {
+                return Err(format!("Column {} needs a type", column.name));
+            }
+            let twin = table.columns[..i]
+                .iter()
+                .any(|c| c.name.eq_ignore_ascii_case(&column.name));
+            if twin {
+                return Err(format!("There are two columns named {}", column.name));
+            }
+        }
+        for (i, index) in table.indexes.iter().enumerate() {
+            if index.name.trim().is_empty() {
+                return Err(format!("Index {} needs a name", i + 1));
+            }
+            if index.columns.is_empty() {
+                return Err(format!("Index {} has no columns", index.name));
+            }
+            self.check_columns(&index.columns, &reference("Index", &index.name, i))?;
+        }
+        for (i, key) in table.foreign_keys.iter().enumerate() {
+            let reference = reference("Foreign key", &key.name, i);
+            if key.columns.is_empty() {
+                return Err(format!("{} has no columns", reference));
+            }
+            if key.referenced_table.trim().is_empty() {
+                return Err(format!("{} needs a table to refer to", reference));
+            }
+            let referenced = split_list(&key.referenced_columns).len();
+            if referenced > 0 && referenced != key.columns.len() {
+                return Err(format!(
+                    "{} has {} columns but refers to {}",
+                    reference,
+                    key.columns.len(),
+                    referenced
+                ));
+            }
+            let expression = key.columns.iter().find(|c| self.column_named(c).is_none());
+            if let Some(column) = expression {
+                return Err(format!("{} refers to unknown column {}", reference, column));
+            }
+        }
+        Ok(())
+    }
+
+    /// Key parts must be columns unless they are expressions
+    fn check_columns(&self, columns: &[String], reference: &str) -> Result<(), String> {
+        let unknown = columns
+            .iter()
+            .find(|c| !c.contains('(') && self.column_named(c).is_none());
+        match unknown {
+            Some(column) => Err(format!("{} refers to unknown column {}", reference, column)),
+            None => Ok(()),
+        }
+    }
+
+    /// The column an index or key part names: by its name in the design,
+    /// or by the name it had before being renamed
+    fn column_named(&self, name: &str) -> Option<&ColumnDesign> {
+        let columns = &self.table.columns;
+        columns.iter().find(|c| c.name == name).or_else(|| {
+            let original = self.original.as_ref()?;
+            let position = original.columns.iter().position(|c| c.name == name)?;
+            columns.iter().find(|c| c.origin == Some(position))
+        })
+    }
+
+    fn quote(&self, ident: &str) -> String {
+        quote_ident(ident, self.dialect)
+    }
+
+    /// `name` qualified by the design's database or schema
+    fn qualify(&self, name: &str) -> String {
+        match &self.qualifier {
+            Some(qualifier) => quote_qualified(&[qualifier, name], self.dialect),
+            None => self.quote(name),
+        }
+    }
+
+    /// Quoted key parts, with columns under their names in the design and
+    /// expressions left as written
+    fn key_parts(&self, parts: &[String]) -> String {
+        parts
+            .iter()
+            .map(|part| match self.column_named(part) {
+                Some(column) => self.quote(&column.name),
+                None => part.clone(),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn primary_key(&self) -> Vec<&ColumnDesign> {
+        let columns = self.table.columns.iter();
+        columns.filter(|c| c.is_primary_key).collect()
+    }
+
+    /// SQLite numbers rows by itself for a lone INTEGER PRIMARY KEY, which
+    /// has to be declared on the column
+    fn sqlite_rowid(&self, column: &ColumnDesign) -> Result<bool, String> {
+        if self.dialect != Dialect::Sqlite || !column.is_auto_increment {
+            return Ok(false);
+        }
+        let lone = self.primary_key().len() == 1 && column.is_primary_key;
+        if lone && column.data_type.trim().eq_ignore_ascii_case("INTEGER") {
+            Ok(true)
+        } else {
+            Err(format!(
+                "SQLite only numbers rows automatically for a lone INTEGER PRIMARY KEY, \
+                 which {} isn't",
+                column.name
+            ))
+        }
+    }
+
+    /// The column's name, type and attributes as in CREATE TABLE or ADD
+    /// COLUMN. Keys are declared separately, except for SQLite's rowid.
+    fn column_definition(&self, column: &ColumnDesign) -> Result<String, String> {
+        let mut sql = format!("{} {}", self.quote(&column.name), column.data_type.trim());
+        match self.dialect {
+            Dialect::MySql => {
+                sql.push_str(if column.nullable {
+                    " NULL"
+                } else {
+                    " NOT NULL"
+                });
+            }
+            Dialect::Postgres => {
+                if column.is_auto_increment {
+                    sql.push_str(" GENERATED BY DEFAULT AS IDENTITY");
+                }
+                if !column.nullable {
+                    sql.push_str(" NOT NULL");
+                }
+            }
+            Dialect::Sqlite => {
+                if self.sqlite_rowid(column)? {
+                    sql.push_str(" PRIMARY KEY");
+                }
+                if !column.nullable {
+                    sql.push_str(" NOT NULL");
+                }
+                if !column.comment.is_empty() {
+                    return Err("SQLite doesn't keep column comments".to_string());
+                }
+            }
+        }
+        if let Some(default) = self.default_clause(column) {
+            sql.push(' ');
+            sql.push_str(&default);
+        }
+        if self.dialect == Dialect::MySql {
+            if column.is_auto_increment {
+                sql.push_str(" AUTO_INCREMENT");
+            }
+            if !column.comment.is_empty() {
+                sql.push_str(&format!(
+                    " COMMENT {}",
+                    quote_literal(&column.comment, self.dialect)
+                ));
+            }
+        }
+        Ok(sql)
+    }
+
+    /// `DEFAULT x`, with the parentheses MySQL and SQLite want around
+    /// anything but a literal
+    fn default_clause(&self, column: &ColumnDesign) -> Option<String> {
+        let value = column.default_value.trim();
+        if value.is_empty() {
+            return None;
+        }
+        let wrap =
+            self.dialect != Dialect::Postgres && !value.starts_with('(') && !is_literal(value);
+        Some(if wrap {
+            format!("DEFAULT ({})", value)
+        } else {
+            format!("DEFAULT {}", value)
+        })
+    }
+
+    fn foreign_key_definition(&self, key: &ForeignKeyDesign) -> String {
+        let mut sql = String::new();
+        if !key.name.trim().is_empty() {
+            sql.push_str(&format!("CONSTRAINT {} ", self.quote(key.name.trim())));
+        }
+        let table: Vec<&str> = key.referenced_table.trim().split('.').collect();
+        sql.push_str(&format!(
+            "FOREIGN KEY ({}) REFERENCES {}",
+            self.key_parts(&key.columns),
+            quote_qualified(&table, self.dialect)
+        ));
+        let referenced = split_list(&key.referenced_columns);
+        if !referenced.is_empty() {
+            let quoted: Vec<String> = referenced.iter().map(|c| self.quote(c)).collect();
+            sql.push_str(&format!(" ({})", quoted.join(", ")));
+        }
+        if let Some(action) = &key.on_update {
+            sql.push_str(&format!(" ON UPDATE {}", action));
+        }
+        if let Some(action) = &key.on_delete {
+            sql.push_str(&format!(" ON DELETE {}", action));
+        }
+        sql
+    }
+
+    /// CREATE INDEX on the table as it is called when the statement runs
+    fn create_index(&self, index: &IndexDesign, table: &str) -> String {
+        let unique = if index.is_unique { "UNIQUE " } else { "" };
+        // SQLite puts the schema on the index rather than the table
+        let (name, table) = match self.dialect {
+            Dialect::Sqlite => (self.qualify(&index.name), self.quote(table)),
+            Dialect::MySql | Dialect::Postgres => (self.quote(&index.name), self.qualify(table)),
+        };
+        format!(
+            "CREATE {}INDEX {} ON {} ({})",
+            unique,
+            name,
+            table,
+            self.key_parts(&index.columns)
+        )
+    }
+
+    fn drop_index(&self, name: &str, table: &str) -> String {
+        match self.dialect {
+            Dialect::MySql => format!("DROP INDEX {} ON {}", self.quote(name), self.qualify(table)),
+            Dialect::Postgres | Dialect::Sqlite => format!("DROP INDEX {}", self.qualify(name)),
+        }
+    }
+
+    fn create_script(&self) -> Result<Vec<String>, String> {
+        let table = &self.table;
+        let mut lines = Vec::new();
+        for column in &table.columns {
+            lines.push(self.column_definition(column)?);
+        }
+        let primary_key = self.primary_key();
+        let rowid = match primary_key.first() {
+            Some(column) => self.sqlite_rowid(column)?,
+            None => false,
+        };
+        if !primary_key.is_empty() && !rowid {
+            let names: Vec<String> = primary_key.iter().map(|c| self.quote(&c.name)).collect();
+            lines.push(format!("PRIMARY KEY ({})", names.join(", ")));
+        }
+        for key in &table.foreign_keys {
+            lines.push(self.foreign_key_definition(key));
+        }
+
+        let mut statements = vec![format!(
+            "CREATE TABLE {} (\n    {}\n)",
+            self.qualify(&table.name),
+            lines.join(",\n    ")
+        )];
+        if self.dialect == Dialect::Postgres {
+            for column in table.columns.iter().filter(|c| !c.comment.is_empty()) {
+                statements.push(self.comment_on(&table.name, column));
+            }
+        }
+        for index in &table.indexes {
+            statements.push(self.create_index(index, &table.name));
+        }
+        Ok(statements)
+    }
+
+    fn comment_on(&self, table: &str, column: &ColumnDesign) -> String {
+        let comment = match column.comment.as_str() {
+            "" => "NULL".to_string(),
+            comment => quote_literal(comment, self.dialect),
+        };
+        format!(
+            "COMMENT ON COLUMN {}.{} IS {}",
+            self.qualify(table),
+            self.quote(&column.name),
+            comment
+        )
+    }
+
+    fn alter_script(&self, original: &TableLayout) -> Result<Vec<String>, String> {
+        let table = &self.table;
+        let alter = format!("ALTER TABLE {}", self.qualify(&original.name));
+        let mut statements = Vec::new();
+
+        // Foreign keys and indexes that changed are dropped and made anew
+        let dropped_keys: Vec<&ForeignKeyDesign> = original
+            .foreign_keys
+            .iter()
+            .enumerate()
+            .filter(|(i, key)| {
+                let current = table.foreign_keys.iter().find(|k| k.origin == Some(*i));
+                current != Some(*key)
+            })
+            .map(|(_, key)| key)
+            .collect();
+        let added_keys: Vec<&ForeignKeyDesign> = table
+            .foreign_keys
+            .iter()
+            .filter(|key| key.origin.is_none_or(|i| original.foreign_keys[i] != **key))
+            .collect();
+        let dropped_indexes: Vec<&IndexDesign> = original
+            .indexes
+            .iter()
+            .enumerate()
+            .filter(|(i, index)| {
+                let current = table.indexes.iter().find(|x| x.origin == Some(*i));
+                current != Some(*index)
+            })
+            .map(|(_, index)| index)
+            .collect();
+        let added_indexes: Vec<&IndexDesign> = table
+            .indexes
+            .iter()
+            .filter(|index| index.origin.is_none_or(|i| original.indexes[i] != **index))
+            .collect();
+
+        let dropped_columns: Vec<&ColumnDesign> = original
+            .columns
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !table.columns.iter().any(|c| c.origin == Some(*i)))
+            .map(|(_, column)| column)
+            .collect();
+        let original_key: Vec<Option<usize>> = original
+            .columns
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.is_primary_key)
+            .map(|(i, _)| Some(i))
+            .collect();
+        let primary_key = self.primary_key();
+        let key_changed = primary_key.iter().map(|c| c.origin).collect::<Vec<_>>() != original_key;
+        // Kept columns in their old order, with new ones after them
+        let in_place = {
+            let origins: Vec<Option<usize>> = table.columns.iter().map(|c| c.origin).collect();
+            let kept = origins.iter().take_while(|o| o.is_some()).count();
+            origins[kept..].iter().all(Option::is_none)
+                && origins[..kept].windows(2).all(|pair| pair[0] < pair[1])
+        };
+        let changed = |column: &ColumnDesign| {
+            column.origin.is_some_and(|i| {
+                let before = &original.columns[i];
+                ColumnDesign {
+                    is_primary_key: before.is_primary_key,
+                    ..column.clone()
+                } != *before
+            })
+        };
+
+        if self.dialect == Dialect::Sqlite {
+            if !dropped_keys.is_empty() || !added_keys.is_empty() {
+                return Err("SQLite can't change the foreign keys of an existing table".to_string());
+            }
+            if key_changed {
+                return Err("SQLite can't change the primary key of an existing table".to_string());
+            }
+            if !in_place {
+                return Err("SQLite can't reorder columns; new ones go at the end".to_string());
+            }
+            for column in table.columns.iter().filter(|c| changed(c)) {
+                let before = &original.columns[column.origin.unwrap_or_default()];
+                let renamed = ColumnDesign {
+                    name: before.name.clone(),
+                    ..column.clone()
+                };
+                if renamed != *before {
+                    return Err(format!(
+                        "SQLite can't change the type, nullability, default or comment of \
+                         column {} in place",
+                        column.name
+                    ));
+                }
+            }
+        }
+        if self.dialect == Dialect::Postgres && !in_place {
+            return Err("PostgreSQL can't reorder columns; new ones go at the end".to_string());
+        }
+
+        for key in &dropped_keys {
+            if key.name.is_empty() {
+                return Err("A foreign key without a name can't be dropped".to_string());
+            }
+            statements.push(match self.dialect {
+                Dialect::MySql => format!("{} DROP FOREIGN KEY {}", alter, self.quote(&key.name)),
+                _ => format!("{} DROP CONSTRAINT {}", alter, self.quote(&key.name)),
+            });
+        }
+        for index in &dropped_indexes {
+            statements.push(self.drop_index(&index.name, &original.name));
+        }
+        if key_changed && !original_key.is_empty() {
+            statements.push(match (self.dialect, &self.primary_key_name) {
+                (Dialect::MySql, _) => format!("{} DROP PRIMARY KEY", alter),
+                (_, Some(name)) => format!("{} DROP CONSTRAINT {}", alter, self.quote(name)),
+                (_, None) => return Err("The primary key has no name to drop it by".to_string()),
+            });
+        }
+        for column in &dropped_columns {
+            statements.push(format!(
+                "{} DROP COLUMN {}",
+                alter,
+                self.quote(&column.name)
+            ));
+        }
+
+        match self.dialect {
+            Dialect::MySql => self.alter_mysql_columns(original, &alter, &mut statements)?,
+            Dialect::Postgres | Dialect::Sqlite => {
+                for column in &table.columns {
+                    let Some(i) = column.origin else {
+                        continue;
+                    };
+                    let before = &original.columns[i];
+                    if column.name != before.name {
+                        statements.push(format!(
+                            "{} RENAME COLUMN {} TO {}",
+                            alter,
+                            self.quote(&before.name),
+                            self.quote(&column.name)
+                        ));
+                    }
+                }
+                for column in table.columns.iter().filter(|c| c.origin.is_none()) {
+                    if self.dialect == Dialect::Sqlite && column.is_primary_key {
+                        return Err(format!(
+                            "SQLite can't add {} as a primary key column",
+                            column.name
+                        ));
+                    }
+                    let definition = self.column_definition(column)?;
+                    statements.push(format!("{} ADD COLUMN {}", alter, definition));
+                    if self.dialect == Dialect::Postgres && !column.comment.is_empty() {
+                        statements.push(self.comment_on(&original.name, column));
+                    }
+                }
+                if self.dialect == Dialect::Postgres {
+                    for column in table.columns.iter().filter(|c| changed(c)) {
+                        let before = &original.columns[column.origin.unwrap_or_default()];
+                        statements.extend(self.alter_postgres_column(
+                            &alter,
+                            &original.name,
+                            before,
+                            column,
+                        ));
+                    }
+                }
+            }
+        }
+
+        if key_changed && !primary_key.is_empty() {
+            let names: Vec<String> = primary_key.iter().map(|c| self.quote(&c.name)).collect();
+            statements.push(format!("{} ADD PRIMARY KEY ({})", alter, names.join(", ")));
+        }
+        for index in &added_indexes {
+            statements.push(self.create_index(index, &original.name));
+        }
+        for key in &added_keys {
+            let definition = self.foreign_key_definition(key);
+            statements.push(format!("{} ADD {}", alter, definition));
+        }
+        if table.name != original.name {
+            statements.push(match self.dialect {
+                Dialect::MySql => format!(
+                    "RENAME TABLE {} TO {}",
+                    self.qualify(&original.name),
+                    self.qualify(&table.name)
+                ),
+                _ => format!("{} RENAME TO {}", alter, self.quote(&table.name)),
+            });
+        }
+        Ok(statements)
+    }
+
+    /// MySQL redefines a column whole, name and position included, so each
+    /// column that changed or moved gets one CHANGE COLUMN. Columns are
+    /// placed in order, each after the one before it.
+    fn alter_mysql_columns(
+        &self,
+        original: &TableLayout,
+        alter: &str,
+        statements: &mut Vec<String>,
+    ) -> Result<(), String> {
+        let columns = &self.table.columns;
+        // The table's column order as the statements so far leave it
+        let mut order: Vec<Option<usize>> = (0..original.columns.len())
+            .filter(|i| columns.iter().any(|c| c.origin == Some(*i)))
+            .map(Some)
+            .collect();
+
+        for (position, column) in columns.iter().enumerate() {
+            let place = match position {
+                0 => "FIRST".to_string(),
+                _ => format!("AFTER {}", self.quote(&columns[position - 1].name)),
+            };
+            let definition = self.column_definition(column)?;
+            let Some(i) = column.origin else {
+                statements.push(format!("{} ADD COLUMN {} {}", alter, definition, place));
+                order.insert(position, None);
+                continue;
+            };
+            let before = &original.columns[i];
+            let current = order.iter().position(|o| *o == Some(i)).unwrap_or(position);
+            let moved = current != position;
+            let changed = ColumnDesign {
+                is_primary_key: before.is_primary_key,
+                ..column.clone()
+            } != *before;
+            if !moved && !changed {
+                continue;
+            }
+            let mut sql = format!(
+                "{} CHANGE COLUMN {} {}",
+                alter,
+                self.quote(&before.name),
+                definition
+            );
+            if moved {
+                sql.push(' ');
+                sql.push_str(&place);
+                order.remove(current);
+                order.insert(position, Some(i));
+            }
+            statements.push(sql);
+        }
+        Ok(())
+    }
+
+    /// The ALTER COLUMN actions that turn `before` into `column`, as one
+    /// statement, and a COMMENT if that changed too
+    fn alter_postgres_column(
+        &self,
+        alter: &str,
+        table: &str,
+        before: &ColumnDesign,
+        column: &ColumnDesign,
+    ) -> Vec<String> {
+        let name = self.quote(&column.name);
+        let mut actions = Vec::new();
+        if column.data_type != before.data_type {
+            actions.push(format!("TYPE {}", column.data_type.trim()));
+        }
+        if column.nullable != before.nullable {
+            actions.push(
+                if column.nullable {
+                    "DROP NOT NULL"
+                } else {
+                    "SET NOT NULL"
+                }
+                .into(),
+            );
+        }
+        if column.default_value != before.default_value {
+            actions.push(match column.default_value.trim() {
+                "" => "DROP DEFAULT".to_string(),
+                value => format!("SET DEFAULT {}", value),
+            });
+        }
+        if column.is_auto_increment != before.is_auto_increment {
+            actions.push(if column.is_auto_increment {
+                "ADD GENERATED BY DEFAULT AS IDENTITY".to_string()
+            } else {
+                "DROP IDENTITY IF EXISTS".to_string()
+            });
+        }
+
+        let mut statements = Vec::new();
+        if !actions.is_empty() {
+            let actions: Vec<String> = actions
+                .into_iter()
+                .map(|action| format!("ALTER COLUMN {} {}", name, action))
+                .collect();
+            statements.push(format!("{} {}", alter, actions.join(", ")));
+        }
+        if column.comment != before.comment {
+            statements.push(self.comment_on(table, column));
+        }
+        statements
+    }
+}
+
+/// An index or key by name, or by position if it has none, for messages
+fn reference(kind: &str, name: &str, position: usize) -> String {
+    match name.trim() {
+        "" => format!("{} {}", kind, position + 1),
+        name => format!("{} {}", kind, name),
+    }
+}
+
+/// A default as the catalog reports it, turned into the SQL expression the
+/// designer edits. MySQL reports string defaults without their quotes.
+fn default_expression(value: &str, dialect: Dialect) -> String {
+    let expression = is_literal(value)
+        || value.contains('(')
+        || value.to_ascii_uppercase().starts_with("CURRENT_");
+    match dialect {
+        Dialect::MySql if !expression => quote_literal(value, dialect),
+        _ => value.to_string(),
+    }
+}
+
+/// Whether a default is a plain literal: a number, a string, NULL, a
+/// boolean or one of the CURRENT_ values
+fn is_literal(value: &str) -> bool {
+    let upper = value.to_ascii_uppercase();
+    value.parse::<f64>().is_ok()
+        || value.starts_with('\'')
+        || upper.starts_with("B'")
+        || upper.starts_with("X'")
+        || ["NULL", "TRUE", "FALSE"].contains(&upper.as_str())
+        || ["CURRENT_TIMESTAMP", "CURRENT_DATE", "CURRENT_TIME"]
+            .iter()
+            .any(|keyword| upper.starts_with(keyword))
+}
+
+/// A name as written by PostgreSQL's `pg_get_indexdef`, without quotes
+fn unquote(name: &str, dialect: Dialect) -> String {
+    let quoted = name.len() >= 2 && name.starts_with('"') && name.ends_with('"');
+    if dialect == Dialect::Postgres && quoted {
+        name[1..name.len() - 1].replace("\"\"", "\"")
+    } else {
+        name.to_string()
+    }
+}
+
+/// The non-empty items of a comma-separated list
+fn split_list(list: &str) -> Vec<&str> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// Run a design's script in `database`. PostgreSQL and SQLite run it as one
+/// transaction; MySQL commits each DDL statement as it runs, so a failure
+/// there leaves the statements before it applied.
+pub async fn apply(
+    conn: Arc<dyn DatabaseConnection>,
+    database: Option<String>,
+    statements: Vec<String>,
+    dialect: Dialect,
+    id: QueryId,
+) -> Result<(), String> {
+    let sql = conn.as_sql().ok_or("This connection can't change tables")?;
+    let mut session = sql
+        .session(database.as_deref(), id)
+        .await
+        .map_err(|e| e.to_string())?;
+    let transaction = dialect != Dialect::MySql;
+    if transaction {
+        session
+            .execute_unprepared("BEGIN")
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
+    for (i, statement) in statements.iter().enumerate() {
+        if let Err(e) = session.execute_unprepared(statement).await {
+            if transaction {
+                if let Err(rollback) = session.execute_unprepared("ROLLBACK").await {
+                    tracing::warn!("Failed to roll back table changes: {}", rollback);
+                }
+            } else if i > 0 {
+                return Err(format!(
+                    "{}: {} (the {} statements before it were applied)",
+                    e, statement, i
+                ));
+            }
+            return Err(format!("{}: {}", e, statement));
+        }
+    }
+
+    if transaction {
+        session
+            .execute_unprepared("COMMIT")
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::create_connection;
+    use crate::db::{ColumnDetails, ForeignKeyInfo, IndexInfo, TableInfo};
+    use crate::models::{ConnectionConfig, DatabaseType};
+
+    fn column(name: &str, data_type: &str) -> ColumnDesign {
+        ColumnDesign {
+            name: name.to_string(),
+            data_type: data_type.to_string(),
+            nullable: true,
+            ..Default::default()
+        }
+    }
+
+    fn details(name: &str, data_type: &str, is_primary_key: bool) -> ColumnDetails {
+        ColumnDetails {
+            name: name.to_string(),
+            data_type: data_type.to_string(),
+            nullable: !is_primary_key,
+            default_value: None,
+            is_primary_key,
+            is_unique: false,
+            is_auto_increment: is_primary_key,
+            comment: None,
+        }
+    }
+
+    /// `shop.orders (id, status, user_id)` with an index and a foreign key
+    fn orders(dialect: Dialect) -> TableDesign {
+        let mut status = details("status", "varchar(20)", false);
+        status.default_value = Some("new".to_string());
+        let structure = TableStructure {
+            table: TableInfo {
+                name: "orders".to_string(),
+                schema: (dialect == Dialect::Postgres).then(|| "public".to_string()),
+                database: "shop".to_string(),
+                engine: None,
+                character_set: None,
+                collation: None,
+                row_count: None,
+                data_size: None,
+                columns: vec![
+                    details("id", "int", true),
+                    status,
+                    details("user_id", "int", false),
+                ],
+            },
+            indexes: vec![
+                IndexInfo {
+                    name: "orders_pkey".to_string(),
+                    columns: vec!["id".to_string()],
+                    is_unique: true,
+                    is_primary: true,
+                    method: None,
+                },
+                IndexInfo {
+                    name: "by_status".to_string(),
+                    // PostgreSQL quotes names that need it
+                    columns: vec![match dialect {
+                        Dialect::Postgres => "\"status\"".to_string(),
+                        _ => "status".to_string(),
+                    }],
+                    is_unique: false,
+                    is_primary: false,
+                    method: None,
+                },
+            ],
+            foreign_keys: vec![ForeignKeyInfo {
+                name: Some("orders_user".to_string()),
+                columns: vec!["user_id".to_string()],
+                referenced_table: "users".to_string(),
+                referenced_columns: vec!["id".to_string()],
+                on_update: None,
+                on_delete: Some("CASCADE".to_string()),
+            }],
+            triggers: Vec::new(),
+        };
+        TableDesign::from_structure(&structure, dialect)
+    }
+
+    #[test]
+    fn test_create_table() {
+        let mut design = TableDesign::new(Dialect::MySql, Some("shop".to_string()));
+        design.table.name = "tags".to_string();
+        let mut label = column("label", "varchar(50)");
+        label.nullable = false;
+        label.default_value = "'none'".to_string();
+        label.comment = "Shown to users".to_string();
+        design.table.columns.push(label);
+        design.table.indexes.push(IndexDesign {
+            name: "tags_label".to_string(),
+            columns: vec!["label".to_string()],
+            is_unique: true,
+            ..Default::default()
+        });
+        assert_eq!(
+            design.script().unwrap(),
+            [
+                "CREATE TABLE `shop`.`tags` (\n    \
+                 `id` INT NOT NULL AUTO_INCREMENT,\n    \
+                 `label` varchar(50) NOT NULL DEFAULT 'none' COMMENT 'Shown to users',\n    \
+                 PRIMARY KEY (`id`)\n)",
+                "CREATE UNIQUE INDEX `tags_label` ON `shop`.`tags` (`label`)",
+            ]
+        );
+
+        // SQLite declares its rowid on the column and has no comments
+        let mut design = TableDesign::new(Dialect::Sqlite, Some("main".to_string()));
+        design.table.name = "tags".to_string();
+        let mut created = column("created", "TEXT");
+        created.default_value = "datetime('now')".to_string();
+        design.table.columns.push(created);
+        assert_eq!(
+            design.script().unwrap(),
+            ["CREATE TABLE \"main\".\"tags\" (\n    \
+              \"id\" INTEGER PRIMARY KEY NOT NULL,\n    \
+              \"created\" TEXT DEFAULT (datetime('now'))\n)"]
+        );
+        design.table.columns[1].comment = "When".to_string();
+        assert!(design.script().is_err());
+
+        design.table.columns.push(column("created", "TEXT"));
+        assert_eq!(
+            design.script().unwrap_err(),
+            "There are two columns named created"
+        );
+    }
+
+    #[test]
+    fn test_unchanged_table() {
+        for dialect in [Dialect::MySql, Dialect::Postgres, Dialect::Sqlite] {
+            let design = orders(dialect);
+            assert!(design.script().unwrap().is_empty());
+        }
+        // MySQL reports string defaults without quotes
+        assert_eq!(
+            orders(Dialect::MySql).table.columns[1].default_value,
+            "'new'"
+        );
+        assert_eq!(
+            orders(Dialect::Postgres).table.indexes[0].columns,
+            ["status"]
+        );
+    }
+
+    #[test]
+    fn test_alter_mysql() {
+        let mut design = orders(Dialect::MySql);
+        let columns = &mut design.table.columns;
+        // Rename status and move it first, drop user_id, add a column
+        columns[1].name = "state".to_string();
+        columns.swap(0, 1);
+        columns.remove(2);
+        columns.push(column("note", "text"));
+        design.table.indexes[0].is_unique = true;
+        design.table.foreign_keys.clear();
+        design.table.name = "purchases".to_string();
+
+        assert_eq!(
+            design.script().unwrap(),
+            [
+                "ALTER TABLE `shop`.`orders` DROP FOREIGN KEY `orders_user`",
+                "DROP INDEX `by_status` ON `shop`.`orders`",
+                "ALTER TABLE `shop`.`orders` DROP COLUMN `user_id`",
+                "ALTER TABLE `shop`.`orders` CHANGE COLUMN `status` \
+                 `state` varchar(20) NULL DEFAULT 'new' FIRST",
+                "ALTER TABLE `shop`.`orders` ADD COLUMN `note` text NULL AFTER `id`",
+                "CREATE UNIQUE INDEX `by_status` ON `shop`.`orders` (`state`)",
+                "RENAME TABLE `shop`.`orders` TO `shop`.`purchases`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_alter_postgres() {
+        let mut design = orders(Dialect::Postgres);
+        let columns = &mut design.table.columns;
+        columns[1].name = "state".to_string();
+        columns[1].nullable = false;
+        columns[1].default_value.clear();
+        columns[2].comment = "Buyer".to_string();
+        columns[2].is_primary_key = true;
+        design.table.foreign_keys[0].on_delete = None;
+
+        assert_eq!(
+            design.script().unwrap(),
+            [
+                "ALTER TABLE \"public\".\"orders\" DROP CONSTRAINT \"orders_user\"",
+                "ALTER TABLE \"public\".\"orders\" DROP CONSTRAINT \"orders_pkey\"",
+                "ALTER TABLE \"public\".\"orders\" RENAME COLUMN \"status\" TO \"state\"",
+                "ALTER TABLE \"public\".\"orders\" ALTER COLUMN \"state\" SET NOT NULL, \
+                 ALTER COLUMN \"state\" DROP DEFAULT",
+                "COMMENT ON COLUMN \"public\".\"orders\".\"user_id\" IS 'Buyer'",
+                "ALTER TABLE \"public\".\"orders\" ADD PRIMARY KEY (\"id\", \"user_id\")",
+                "ALTER TABLE \"public\".\"orders\" ADD CONSTRAINT \"orders_user\" \
+                 FOREIGN KEY (\"user_id\") REFERENCES \"users\" (\"id\")",
+            ]
+        );
+
+        let mut design = orders(Dialect::Postgres);
+        design.table.columns.swap(1, 2);
+        assert!(design.script().unwrap_err().contains("can't reorder"));
+    }
+
+    #[test]
+    fn test_sqlite_limits() {
+        let mut design = orders(Dialect::Sqlite);
+        design.table.columns[1].data_type = "TEXT".to_string();
+        assert!(design.script().unwrap_err().contains("in place"));
+
+        let mut design = orders(Dialect::Sqlite);
+        design.table.foreign_keys.clear();
+        assert!(design.script().unwrap_err().contains("foreign keys"));
+    }
+
+    #[tokio::test]
+    async fn test_create_and_alter() {
+        let config = ConnectionConfig {
+            db_type: DatabaseType::SQLite,
+            database: ":memory:".to_string(),
+            ..ConnectionConfig::default()
+        };
+        let conn: Arc<dyn DatabaseConnection> =
+            Arc::from(create_connection(&config).await.unwrap());
+
+        let mut design = TableDesign::new(Dialect::Sqlite, Some("main".to_string()));
+        design.table.name = "notes".to_string();
+        design.table.columns.push(column("body", "TEXT"));
+        let script = design.script().unwrap();
+        apply(conn.clone(), None, script, Dialect::Sqlite, QueryId::new())
+            .await
+            .unwrap();
+
+        let structure = conn.table_structure("main", None, "notes").await.unwrap();
+        let mut design = TableDesign::from_structure(&structure, Dialect::Sqlite);
+        assert!(design.table.columns[0].is_auto_increment);
+        design.table.columns[1].name = "text".to_string();
+        let mut pinned = column("pinned", "BOOLEAN");
+        pinned.nullable = false;
+        pinned.default_value = "0".to_string();
+        design.table.columns.push(pinned);
+        design.table.indexes.push(IndexDesign {
+            name: "notes_pinned".to_string(),
+            columns: vec!["pinned".to_string()],
+            ..Default::default()
+        });
+        let script = design.script().unwrap();
+        assert_eq!(script.len(), 3);
+        apply(conn.clone(), None, script, Dialect::Sqlite, QueryId::new())
+            .await
+            .unwrap();
+
+        let structure = conn.table_structure("main", None, "notes").await.unwrap();
+        let names: Vec<&str> = structure
+            .table
+            .columns
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(names, ["id", "text", "pinned"]);
+        assert_eq!(structure.indexes[0].name, "notes_pinned");
+
+        // A failing statement rolls back the ones before it
+        let script = vec![
+            "ALTER TABLE notes ADD COLUMN extra TEXT".to_string(),
+            "ALTER TABLE missing ADD COLUMN extra TEXT".to_string(),
+        ];
+        let error = apply(conn.clone(), None, script, Dialect::Sqlite, QueryId::new())
+            .await
+            .unwrap_err();
+        assert!(error.contains("missing"));
+        let info = conn.describe_table("main", None, "notes").await.unwrap();
+        assert_eq!(info.columns.len(), 3);
+    }
+}
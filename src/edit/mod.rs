@@ -0,0 +1,713 @@
+use crate::db::quote::{quote_ident, quote_qualified};
+use crate::db::{ColumnDetails, DatabaseConnection, QueryId, TableInfo};
+use crate::models::{CellValue, ColumnInfo, ParamValue};
+use crate::sql::source::TableName;
+use crate::sql::Dialect;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+/// A result whose rows can be written back to its table, having been read
+/// from that table alone
+#[derive(Debug, Clone)]
+pub struct EditTarget {
+    pub table: TableName,
+    pub dialect: Dialect,
+    /// The table column shown in each result column, if any
+    pub columns: Vec<Option<ColumnDetails>>,
+    /// Result columns that pick out a row: the primary key, or else a
+    /// unique column. Without them rows can only be added, and this says
+    /// why.
+    pub key: Result<Vec<usize>, String>,
+}
+
+impl EditTarget {
+    /// Match a result's columns with those of the table it was read from.
+    /// Fails if the table wasn't found.
+    pub fn new(
+        table: TableName,
+        dialect: Dialect,
+        info: &TableInfo,
+        columns: &[ColumnInfo],
+    ) -> Result<Self, String> {
+        if info.columns.is_empty() {
+            return Err(format!("Table {} was not found", table.name));
+        }
+        let columns: Vec<Option<ColumnDetails>> = columns
+            .iter()
+            .map(|column| {
+                let exact = info.columns.iter().find(|c| c.name == column.name);
+                exact
+                    .or_else(|| {
+                        let mut same = info.columns.iter();
+                        same.find(|c| c.name.eq_ignore_ascii_case(&column.name))
+                    })
+                    .cloned()
+            })
+            .collect();
+        let position = |name: &str| {
+            columns
+                .iter()
+                .position(|c| c.as_ref().is_some_and(|c| c.name == name))
+        };
+
+        let primary: Vec<&ColumnDetails> =
+            info.columns.iter().filter(|c| c.is_primary_key).collect();
+        let primary_key: Option<Vec<usize>> = primary.iter().map(|c| position(&c.name)).collect();
+        let unique_key = info
+            .columns
+            .iter()
+            .filter(|c| c.is_unique)
+            .find_map(|c| position(&c.name));
+        let key = match (primary_key, unique_key) {
+            (Some(key), _) if !key.is_empty() => Ok(key),
+            (_, Some(column)) => Ok(vec![column]),
+            _ if primary.is_empty() => {
+                Err(format!("Table {} has no primary or unique key", table.name))
+            }
+            _ => Err(format!(
+                "The result does not include the primary key of {}",
+                table.name
+            )),
+        };
+
+        Ok(Self {
+            table,
+            dialect,
+            columns,
+            key,
+        })
+    }
+
+    /// Whether a cell of an existing row can be changed
+    pub fn is_editable(&self, column: usize, cell: &CellValue) -> bool {
+        self.key.is_ok()
+            && self.columns.get(column).is_some_and(Option::is_some)
+            && edit_text(cell).is_some()
+    }
+
+    /// Values for a new row: each column's default, if it has one or the
+    /// database fills it in, else NULL if it allows it
+    pub fn new_row(&self) -> Vec<NewValue> {
+        self.columns
+            .iter()
+            .map(|column| match column {
+                None => NewValue::Default,
+                Some(c) if c.is_auto_increment || c.default_value.is_some() => NewValue::Default,
+                Some(c) if c.nullable => NewValue::Null,
+                Some(_) => NewValue::Text(String::new()),
+            })
+            .collect()
+    }
+
+    /// Values for a copy of a row. Auto-increment columns, and values that
+    /// can't be written as text, are left to their defaults.
+    pub fn copy_row(&self, cells: &[CellValue]) -> Vec<NewValue> {
+        self.columns
+            .iter()
+            .zip(cells)
+            .map(|(column, cell)| match (column, cell) {
+                (None, _) => NewValue::Default,
+                (Some(c), _) if c.is_auto_increment => NewValue::Default,
+                (Some(_), CellValue::Null) => NewValue::Null,
+                (Some(_), cell) => edit_text(cell).map_or(NewValue::Default, NewValue::Text),
+            })
+            .collect()
+    }
+
+    fn quoted_table(&self) -> String {
+        let mut parts: Vec<&str> = self.table.qualifier.iter().map(String::as_str).collect();
+        parts.push(&self.table.name);
+        quote_qualified(&parts, self.dialect)
+    }
+
+    /// The placeholder for the `n`th parameter, holding a value for
+    /// `column`. Values are bound as text, which PostgreSQL won't convert
+    /// to the column's type unless told to.
+    fn placeholder(&self, n: usize, column: &ColumnDetails) -> String {
+        match self.dialect {
+            Dialect::Postgres => format!("CAST(${} AS {})", n, column.data_type),
+            Dialect::MySql | Dialect::Sqlite => "?".to_string(),
+        }
+    }
+
+    /// The WHERE clause matching a row by its key, adding the key's values
+    /// to `params`
+    fn key_condition(
+        &self,
+        cells: &[CellValue],
+        params: &mut Vec<ParamValue>,
+    ) -> Result<String, String> {
+        let key = self.key.as_ref().map_err(String::clone)?;
+        let mut conditions = Vec::new();
+        for &column in key {
+            let details = self.columns[column]
+                .as_ref()
+                .ok_or("Key can't be matched")?;
+            params.push(key_param(&cells[column]).ok_or_else(|| {
+                format!("The value of {} can't be matched exactly", details.name)
+            })?);
+            conditions.push(format!(
+                "{} = {}",
+                quote_ident(&details.name, self.dialect),
+                self.placeholder(params.len(), details)
+            ));
+        }
+        Ok(conditions.join(" AND "))
+    }
+}
+
+/// A value for a column of a new row
+#[derive(Debug, Clone, PartialEq)]
+pub enum NewValue {
+    /// Left out of the INSERT, for the column's default or next
+    /// auto-increment value
+    Default,
+    Null,
+    Text(String),
+}
+
+/// A statement generated from staged changes, with the values to bind
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangeStatement {
+    pub sql: String,
+    pub params: Vec<ParamValue>,
+}
+
+/// Edits staged on a result, to be reviewed and applied together
+#[derive(Debug, Clone, Default)]
+pub struct ChangeSet {
+    /// New values by row and column; `None` sets NULL
+    updates: BTreeMap<(usize, usize), Option<String>>,
+    /// Rows to add, with a value for each result column
+    inserts: Vec<Vec<NewValue>>,
+    /// Rows to delete
+    deletes: BTreeSet<usize>,
+}
+
+impl ChangeSet {
+    pub fn is_empty(&self) -> bool {
+        self.updates.is_empty() && self.inserts.is_empty() && self.deletes.is_empty()
+    }
+
+    /// Number of cells changed, rows added and rows deleted
+    pub fn len(&self) -> usize {
+        self.updates.len() + self.inserts.len() + self.deletes.len()
+    }
+
+    /// The staged value of a cell, if it was edited
+    pub fn get(&self, row: usize, column: usize) -> Option<&Option<String>> {
+        self.updates.get(&(row, column))
+    }
+
+    /// Stage a new value for a cell. One that puts back the original value
+    /// drops the edit instead, and so does empty text for a NULL cell.
+    pub fn set(&mut self, row: usize, column: usize, value: Option<String>, original: &CellValue) {
+        let unchanged = match (&value, original) {
+            (None, CellValue::Null) => true,
+            (Some(text), CellValue::Null) => text.is_empty(),
+            (None, _) => false,
+            (Some(text), original) => edit_text(original).as_ref() == Some(text),
+        };
+        if unchanged {
+            self.updates.remove(&(row, column));
+        } else {
+            self.updates.insert((row, column), value);
+        }
+    }
+
+    pub fn revert(&mut self, row: usize, column: usize) {
+        self.updates.remove(&(row, column));
+    }
+
+    /// Rows to add, in the order they were added
+    pub fn inserts(&self) -> &[Vec<NewValue>] {
+        &self.inserts
+    }
+
+    pub fn insert(&mut self, values: Vec<NewValue>) {
+        self.inserts.push(values);
+    }
+
+    /// Set a column of the `index`th row to add
+    pub fn set_inserted(&mut self, index: usize, column: usize, value: NewValue) {
+        if let Some(value_of) = self
+            .inserts
+            .get_mut(index)
+            .and_then(|row| row.get_mut(column))
+        {
+            *value_of = value;
+        }
+    }
+
+    pub fn remove_inserted(&mut self, index: usize) {
+        if index < self.inserts.len() {
+            self.inserts.remove(index);
+        }
+    }
+
+    /// Mark a row for deletion, dropping any edits to it
+    pub fn delete(&mut self, row: usize) {
+        self.updates.retain(|(r, _), _| *r != row);
+        self.deletes.insert(row);
+    }
+
+    pub fn undelete(&mut self, row: usize) {
+        self.deletes.remove(&row);
+    }
+
+    pub fn is_deleted(&self, row: usize) -> bool {
+        self.deletes.contains(&row)
+    }
+
+    pub fn clear(&mut self) {
+        self.updates.clear();
+        self.inserts.clear();
+        self.deletes.clear();
+    }
+
+    /// The statements that make the changes: a DELETE for each deleted
+    /// row, then an UPDATE for each edited one, both matching the row by
+    /// its key, then an INSERT for each new row
+    pub fn statements(
+        &self,
+        target: &EditTarget,
+        rows: &[Vec<CellValue>],
+    ) -> Result<Vec<ChangeStatement>, String> {
+        let row_cells = |row: usize| {
+            rows.get(row)
+                .ok_or("The edited row is no longer in the result")
+        };
+        let mut statements = Vec::new();
+
+        for &row in &self.deletes {
+            let mut params = Vec::new();
+            let condition = target.key_condition(row_cells(row)?, &mut params)?;
+            statements.push(ChangeStatement {
+                sql: format!("DELETE FROM {} WHERE {}", target.quoted_table(), condition),
+                params,
+            });
+        }
+
+        let mut by_row: BTreeMap<usize, Vec<(usize, &Option<String>)>> = BTreeMap::new();
+        for ((row, column), value) in &self.updates {
+            by_row.entry(*row).or_default().push((*column, value));
+        }
+        for (row, edits) in by_row {
+            let mut params = Vec::new();
+            let mut assignments = Vec::new();
+            for (column, value) in edits {
+                let details = target.columns[column]
+                    .as_ref()
+                    .ok_or("Column can't be edited")?;
+                params.push(match value {
+                    Some(text) => ParamValue::String(text.clone()),
+                    None => ParamValue::Null,
+                });
+                assignments.push(format!(
+                    "{} = {}",
+                    quote_ident(&details.name, target.dialect),
+                    target.placeholder(params.len(), details)
+                ));
+            }
+            let condition = target.key_condition(row_cells(row)?, &mut params)?;
+            statements.push(ChangeStatement {
+                sql: format!(
+                    "UPDATE {} SET {} WHERE {}",
+                    target.quoted_table(),
+                    assignments.join(", "),
+                    condition
+                ),
+                params,
+            });
+        }
+
+        for values in &self.inserts {
+            let mut params = Vec::new();
+            let mut names = Vec::new();
+            let mut placeholders = Vec::new();
+            for (details, value) in target.columns.iter().zip(values) {
+                let Some(details) = details else {
+                    continue;
+                };
+                params.push(match value {
+                    NewValue::Default => continue,
+                    NewValue::Null => ParamValue::Null,
+                    NewValue::Text(text) => ParamValue::String(text.clone()),
+                });
+                names.push(quote_ident(&details.name, target.dialect));
+                placeholders.push(target.placeholder(params.len(), details));
+            }
+            let sql = match (names.is_empty(), target.dialect) {
+                (true, Dialect::MySql) => {
+                    format!("INSERT INTO {} () VALUES ()", target.quoted_table())
+                }
+                (true, _) => format!("INSERT INTO {} DEFAULT VALUES", target.quoted_table()),
+                (false, _) => format!(
+                    "INSERT INTO {} ({}) VALUES ({})",
+                    target.quoted_table(),
+                    names.join(", "),
+                    placeholders.join(", ")
+                ),
+            };
+            statements.push(ChangeStatement { sql, params });
+        }
+        Ok(statements)
+    }
+
+    /// Show the changes in the result, once they are applied. Added rows
+    /// are not shown, since the values the database filled in are not
+    /// known without reading them back.
+    pub fn apply_to(&self, rows: &mut Vec<Vec<CellValue>>) {
+        for ((row, column), value) in &self.updates {
+            if let Some(cell) = rows.get_mut(*row).and_then(|cells| cells.get_mut(*column)) {
+                *cell = match value {
+                    Some(text) => CellValue::String(text.clone()),
+                    None => CellValue::Null,
+                };
+            }
+        }
+        for &row in self.deletes.iter().rev() {
+            if row < rows.len() {
+                rows.remove(row);
+            }
+        }
+    }
+}
+
+/// A cell's value as text to edit, or `None` if it can't be written back
+/// as text
+pub fn edit_text(cell: &CellValue) -> Option<String> {
+    match cell {
+        CellValue::Null => Some(String::new()),
+        // Accepted by every engine, unlike `true`, which MySQL won't take
+        // for its TINYINT booleans
+        CellValue::Bool(b) => Some(if *b { "1" } else { "0" }.to_string()),
+        CellValue::Bytes(_) | CellValue::Geometry { .. } | CellValue::Undecodable(_) => None,
+        other => Some(other.to_string()),
+    }
+}
+
+/// A key value to match a row by
+fn key_param(cell: &CellValue) -> Option<ParamValue> {
+    match cell {
+        CellValue::Null => None,
+        CellValue::Int(i) => Some(ParamValue::Int(*i)),
+        other => edit_text(other).map(ParamValue::String),
+    }
+}
+
+/// Run statements in `database` in a single transaction. Each must change
+/// exactly one row; otherwise, or if any fails, none of them takes effect.
+pub async fn apply(
+    conn: Arc<dyn DatabaseConnection>,
+    database: Option<String>,
+    statements: Vec<ChangeStatement>,
+    id: QueryId,
+) -> Result<u64, String> {
+    let sql = conn.as_sql().ok_or("This connection can't apply changes")?;
+    let mut session = sql
+        .session(database.as_deref(), id)
+        .await
+        .map_err(|e| e.to_string())?;
+    session
+        .execute_unprepared("BEGIN")
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut changed = 0;
+    for statement in &statements {
+        let result = match session.execute(&statement.sql, &statement.params).await {
+            Ok(1) => Ok(()),
+            Ok(0) => Err(format!(
+                "No row matched, it may have been changed or deleted: {}",
+                statement.sql
+            )),
+            Ok(rows) => Err(format!("{} rows matched: {}", rows, statement.sql)),
+            Err(e) => Err(e.to_string()),
+        };
+        if let Err(e) = result {
+            if let Err(rollback) = session.execute_unprepared("ROLLBACK").await {
+                tracing::warn!("Failed to roll back changes: {}", rollback);
+            }
+            return Err(e);
+        }
+        changed += 1;
+    }
+
+    session
+        .execute_unprepared("COMMIT")
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::create_connection;
+    use crate::models::{ConnectionConfig, DatabaseType};
+
+    fn column(name: &str, data_type: &str, is_primary_key: bool) -> ColumnDetails {
+        ColumnDetails {
+            name: name.to_string(),
+            data_type: data_type.to_string(),
+            nullable: !is_primary_key,
+            default_value: None,
+            is_primary_key,
+            is_unique: false,
+            is_auto_increment: false,
+            comment: None,
+        }
+    }
+
+    fn result_column(name: &str) -> ColumnInfo {
+        ColumnInfo {
+            name: name.to_string(),
+            data_type: String::new(),
+            nullable: true,
+            is_primary_key: false,
+        }
+    }
+
+    fn table_info(columns: Vec<ColumnDetails>) -> TableInfo {
+        TableInfo {
+            name: "users".to_string(),
+            schema: None,
+            database: String::new(),
+            engine: None,
+            character_set: None,
+            collation: None,
+            row_count: None,
+            data_size: None,
+            columns,
+        }
+    }
+
+    fn users(dialect: Dialect) -> EditTarget {
+        let info = table_info(vec![
+            column("id", "integer", true),
+            column("name", "text", false),
+            column("age", "integer", false),
+        ]);
+        let table = TableName {
+            qualifier: Some("app".to_string()),
+            name: "users".to_string(),
+        };
+        let columns = [
+            result_column("name"),
+            result_column("id"),
+            result_column("age"),
+        ];
+        EditTarget::new(table, dialect, &info, &columns).unwrap()
+    }
+
+    #[test]
+    fn test_target() {
+        let target = users(Dialect::MySql);
+        assert_eq!(target.key, Ok(vec![1]));
+        assert!(target.is_editable(0, &CellValue::String("Ann".into())));
+        assert!(!target.is_editable(0, &CellValue::Bytes(vec![1])));
+
+        let mut email = column("email", "text", false);
+        email.is_unique = true;
+        let info = table_info(vec![
+            column("id", "integer", true),
+            column("name", "text", false),
+            email,
+        ]);
+        let table = TableName {
+            qualifier: None,
+            name: "users".to_string(),
+        };
+        let target = |columns: &[ColumnInfo]| {
+            EditTarget::new(table.clone(), Dialect::Sqlite, &info, columns).unwrap()
+        };
+
+        // Without the primary key, a unique column will do
+        let by_email = target(&[result_column("name"), result_column("email")]);
+        assert_eq!(by_email.key, Ok(vec![1]));
+        let no_key = target(&[result_column("name")]);
+        assert!(no_key
+            .key
+            .as_ref()
+            .unwrap_err()
+            .contains("primary key of users"));
+        assert!(!no_key.is_editable(0, &CellValue::Null));
+
+        let computed = target(&[result_column("id"), result_column("upper(name)")]);
+        assert!(computed.columns[1].is_none());
+
+        let info = table_info(vec![column("name", "text", false)]);
+        let target = EditTarget::new(table, Dialect::Sqlite, &info, &[result_column("name")]);
+        let key = target.unwrap().key;
+        assert!(key.unwrap_err().contains("no primary or unique key"));
+    }
+
+    #[test]
+    fn test_update_statements() {
+        let rows = vec![
+            vec![
+                CellValue::String("Ann".into()),
+                CellValue::Int(1),
+                CellValue::Int(30),
+            ],
+            vec![
+                CellValue::String("Bob".into()),
+                CellValue::Int(2),
+                CellValue::Null,
+            ],
+        ];
+        let mut changes = ChangeSet::default();
+        changes.set(0, 0, Some("Anne".into()), &rows[0][0]);
+        changes.set(0, 2, None, &rows[0][2]);
+        changes.set(1, 2, None, &rows[1][2]);
+        // Putting back the original value is no change
+        changes.set(1, 0, Some("Bob".into()), &rows[1][0]);
+        assert_eq!(changes.len(), 2);
+
+        let statements = changes.statements(&users(Dialect::MySql), &rows).unwrap();
+        assert_eq!(
+            statements,
+            [ChangeStatement {
+                sql: "UPDATE `app`.`users` SET `name` = ?, `age` = ? WHERE `id` = ?".to_string(),
+                params: vec![
+                    ParamValue::String("Anne".into()),
+                    ParamValue::Null,
+                    ParamValue::Int(1)
+                ],
+            }]
+        );
+
+        let statements = changes
+            .statements(&users(Dialect::Postgres), &rows)
+            .unwrap();
+        assert_eq!(
+            statements[0].sql,
+            "UPDATE \"app\".\"users\" SET \"name\" = CAST($1 AS text), \"age\" = CAST($2 AS integer) \
+             WHERE \"id\" = CAST($3 AS integer)"
+        );
+    }
+
+    #[test]
+    fn test_insert_and_delete_statements() {
+        let info = table_info(vec![
+            ColumnDetails {
+                is_auto_increment: true,
+                ..column("id", "integer", true)
+            },
+            column("name", "text", false),
+            ColumnDetails {
+                default_value: Some("0".to_string()),
+                ..column("age", "integer", false)
+            },
+        ]);
+        let table = TableName {
+            qualifier: None,
+            name: "users".to_string(),
+        };
+        let columns = [
+            result_column("id"),
+            result_column("name"),
+            result_column("age"),
+        ];
+        let target = EditTarget::new(table, Dialect::Sqlite, &info, &columns).unwrap();
+        let rows = vec![vec![
+            CellValue::Int(7),
+            CellValue::String("Ann".into()),
+            CellValue::Int(30),
+        ]];
+
+        let new_row = target.new_row();
+        assert_eq!(
+            new_row,
+            [NewValue::Default, NewValue::Null, NewValue::Default]
+        );
+        let copy = target.copy_row(&rows[0]);
+        assert_eq!(
+            copy,
+            [
+                NewValue::Default,
+                NewValue::Text("Ann".into()),
+                NewValue::Text("30".into())
+            ]
+        );
+
+        let mut changes = ChangeSet::default();
+        changes.set(0, 1, Some("Anne".into()), &rows[0][1]);
+        changes.insert(copy);
+        changes.insert(vec![NewValue::Default; 3]);
+        changes.set_inserted(1, 1, NewValue::Text("Bob".into()));
+        changes.insert(new_row);
+        changes.remove_inserted(2);
+        // Deleting a row drops its edits
+        changes.delete(0);
+        assert_eq!(changes.len(), 3);
+
+        let statements = changes.statements(&target, &rows).unwrap();
+        let sql: Vec<&str> = statements.iter().map(|s| s.sql.as_str()).collect();
+        assert_eq!(
+            sql,
+            [
+                "DELETE FROM \"users\" WHERE \"id\" = ?",
+                "INSERT INTO \"users\" (\"name\", \"age\") VALUES (?, ?)",
+                "INSERT INTO \"users\" (\"name\") VALUES (?)",
+            ]
+        );
+        assert_eq!(statements[0].params, [ParamValue::Int(7)]);
+
+        let mut defaults = ChangeSet::default();
+        defaults.insert(vec![NewValue::Default; 3]);
+        let statements = defaults.statements(&target, &rows).unwrap();
+        assert_eq!(statements[0].sql, "INSERT INTO \"users\" DEFAULT VALUES");
+
+        let mut rows = rows;
+        changes.apply_to(&mut rows);
+        assert!(rows.is_empty());
+    }
+
+    #[test]
+    fn test_apply_in_one_transaction() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let config = ConnectionConfig {
+            db_type: DatabaseType::SQLite,
+            database: ":memory:".to_string(),
+            ..ConnectionConfig::default()
+        };
+        let conn: Arc<dyn DatabaseConnection> =
+            Arc::from(runtime.block_on(create_connection(&config)).unwrap());
+        let sql = conn.as_sql().unwrap();
+        for statement in [
+            "CREATE TABLE t (id INTEGER PRIMARY KEY, name TEXT)",
+            "INSERT INTO t VALUES (1, 'a'), (2, 'b')",
+        ] {
+            runtime
+                .block_on(sql.execute_statement(None, statement, &[], QueryId::new()))
+                .unwrap();
+        }
+        let update = |name: &str, id: i64| ChangeStatement {
+            sql: "UPDATE t SET name = ? WHERE id = ?".to_string(),
+            params: vec![ParamValue::String(name.to_string()), ParamValue::Int(id)],
+        };
+        let names = || {
+            let result = runtime
+                .block_on(conn.execute_query("SELECT name FROM t ORDER BY id"))
+                .unwrap();
+            result
+                .rows
+                .iter()
+                .map(|row| row[0].to_string())
+                .collect::<Vec<_>>()
+        };
+
+        // The second row is gone, so neither change is kept
+        let statements = vec![update("x", 1), update("y", 3)];
+        let result = runtime.block_on(apply(conn.clone(), None, statements, QueryId::new()));
+        assert!(result.unwrap_err().contains("No row matched"));
+        assert_eq!(names(), ["a", "b"]);
+
+        let statements = vec![update("x", 1), update("y", 2)];
+        let result = runtime.block_on(apply(conn.clone(), None, statements, QueryId::new()));
+        assert_eq!(result, Ok(2));
+        assert_eq!(names(), ["x", "y"]);
+    }
+}
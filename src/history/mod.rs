@@ -0,0 +1,280 @@
+use crate::config::{AppConfig, ConfigError};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Entries kept when the history is loaded; older ones are dropped
+const MAX_ENTRIES: usize = 5000;
+
+/// One executed statement
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub sql: String,
+    /// Key of the connection it ran on, see `AppConfig::connection_key`
+    #[serde(default)]
+    pub connection: Option<String>,
+    #[serde(default)]
+    pub database: Option<String>,
+    pub executed_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    /// Rows returned, for statements that return rows
+    #[serde(default)]
+    pub rows: Option<u64>,
+    #[serde(default)]
+    pub affected_rows: Option<u64>,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl HistoryEntry {
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Which outcomes a history search shows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StatusFilter {
+    #[default]
+    All,
+    Succeeded,
+    Failed,
+}
+
+impl StatusFilter {
+    pub const ALL: [StatusFilter; 3] = [
+        StatusFilter::All,
+        StatusFilter::Succeeded,
+        StatusFilter::Failed,
+    ];
+
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            StatusFilter::All => "All",
+            StatusFilter::Succeeded => "Succeeded",
+            StatusFilter::Failed => "Failed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct HistoryFilter {
+    /// Case-insensitive text the SQL must contain
+    pub text: String,
+    /// Connection key, or `None` for every connection
+    pub connection: Option<String>,
+    pub status: StatusFilter,
+}
+
+impl HistoryFilter {
+    fn matches(&self, entry: &HistoryEntry) -> bool {
+        let status = match self.status {
+            StatusFilter::All => true,
+            StatusFilter::Succeeded => entry.succeeded(),
+            StatusFilter::Failed => !entry.succeeded(),
+        };
+        let text = self.text.trim().to_lowercase();
+        status
+            && (self.connection.is_none() || entry.connection == self.connection)
+            && (text.is_empty() || entry.sql.to_lowercase().contains(&text))
+    }
+}
+
+/// Every statement run from the editor, oldest first, kept in a JSON Lines
+/// file so it survives restarts
+pub struct History {
+    path: Option<PathBuf>,
+    entries: Vec<HistoryEntry>,
+}
+
+impl History {
+    pub fn path() -> Result<PathBuf, ConfigError> {
+        Ok(AppConfig::config_dir()?.join("history.jsonl"))
+    }
+
+    /// Load the history from the config directory. Without one the history
+    /// still works, but only for this run.
+    pub fn load() -> Self {
+        match Self::path() {
+            Ok(path) => Self::open(path).unwrap_or_else(|e| {
+                tracing::warn!("Failed to load query history: {}", e);
+                Self::in_memory()
+            }),
+            Err(e) => {
+                tracing::warn!("Query history is not saved: {}", e);
+                Self::in_memory()
+            }
+        }
+    }
+
+    fn in_memory() -> Self {
+        Self {
+            path: None,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Read the history kept at `path`, skipping lines that don't parse,
+    /// and trim the file down to the most recent entries
+    pub fn open(path: PathBuf) -> Result<Self, ConfigError> {
+        let mut entries = Vec::new();
+        if path.exists() {
+            for line in fs::read_to_string(&path)?.lines() {
+                match serde_json::from_str(line) {
+                    Ok(entry) => entries.push(entry),
+                    Err(e) if !line.trim().is_empty() => {
+                        tracing::warn!("Skipping history entry: {}", e)
+                    }
+                    Err(_) => {}
+                }
+            }
+        }
+
+        let mut history = Self {
+            path: Some(path),
+            entries,
+        };
+        if history.entries.len() > MAX_ENTRIES {
+            let excess = history.entries.len() - MAX_ENTRIES;
+            history.entries.drain(..excess);
+            history.rewrite()?;
+        }
+        Ok(history)
+    }
+
+    fn rewrite(&self) -> Result<(), ConfigError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut content = String::new();
+        for entry in &self.entries {
+            content.push_str(&serde_json::to_string(entry)?);
+            content.push('\n');
+        }
+        fs::write(path, content)?;
+        Ok(())
+    }
+
+    /// Add an entry, appending it to the file
+    pub fn record(&mut self, entry: HistoryEntry) {
+        if let Some(path) = &self.path {
+            if let Err(e) = append(path, &entry) {
+                tracing::warn!("Failed to save query history: {}", e);
+            }
+        }
+        self.entries.push(entry);
+    }
+
+    /// Entries matching `filter`, newest first
+    pub fn search(&self, filter: &HistoryFilter) -> impl Iterator<Item = &HistoryEntry> + '_ {
+        let filter = filter.clone();
+        self.entries
+            .iter()
+            .rev()
+            .filter(move |entry| filter.matches(entry))
+    }
+
+    /// The connections that appear in the history, sorted
+    pub fn connections(&self) -> Vec<String> {
+        let mut connections: Vec<String> = self
+            .entries
+            .iter()
+            .filter_map(|entry| entry.connection.clone())
+            .collect();
+        connections.sort();
+        connections.dedup();
+        connections
+    }
+
+    /// Forget every entry
+    pub fn clear(&mut self) -> Result<(), ConfigError> {
+        self.entries.clear();
+        self.rewrite()
+    }
+}
+
+fn append(path: &Path, entry: &HistoryEntry) -> Result<(), ConfigError> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(line.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(sql: &str, connection: &str, error: Option<&str>) -> HistoryEntry {
+        HistoryEntry {
+            sql: sql.to_string(),
+            connection: Some(connection.to_string()),
+            database: None,
+            executed_at: Utc::now(),
+            duration_ms: 3,
+            rows: error.is_none().then_some(1),
+            affected_rows: None,
+            error: error.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_record_and_reload() {
+        let path =
+            std::env::temp_dir().join(format!("nebula-history-{}.jsonl", uuid::Uuid::new_v4()));
+        let mut history = History::open(path.clone()).unwrap();
+        history.record(entry("SELECT 1", "sqlite.local", None));
+        history.record(entry("SELEC 2", "mysql.local", Some("syntax error")));
+
+        // A line cut short by a crash is skipped
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"sql\": \"SELECT").unwrap();
+
+        let history = History::open(path.clone()).unwrap();
+        let sql: Vec<&str> = history
+            .search(&HistoryFilter::default())
+            .map(|e| e.sql.as_str())
+            .collect();
+        assert_eq!(sql, ["SELEC 2", "SELECT 1"]);
+        assert_eq!(history.connections(), ["mysql.local", "sqlite.local"]);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_filter() {
+        let mut history = History::in_memory();
+        history.record(entry("SELECT * FROM users", "sqlite.local", None));
+        history.record(entry("select name from Users", "mysql.local", None));
+        history.record(entry("DELETE FROM users", "mysql.local", Some("read-only")));
+        history.record(entry("SELECT 1", "mysql.local", None));
+
+        let search = |filter: HistoryFilter| -> Vec<String> {
+            history.search(&filter).map(|e| e.sql.clone()).collect()
+        };
+        let users = HistoryFilter {
+            text: "USERS ".to_string(),
+            ..HistoryFilter::default()
+        };
+        assert_eq!(search(users.clone()).len(), 3);
+        assert_eq!(
+            search(HistoryFilter {
+                connection: Some("mysql.local".to_string()),
+                status: StatusFilter::Succeeded,
+                ..users.clone()
+            }),
+            ["select name from Users"]
+        );
+        assert_eq!(
+            search(HistoryFilter {
+                status: StatusFilter::Failed,
+                ..HistoryFilter::default()
+            }),
+            ["DELETE FROM users"]
+        );
+    }
+}
@@ -0,0 +1,365 @@
+use crate::config::{AppConfig, ConfigError};
+use crate::models::SavedQuery;
+use chrono::Utc;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// Opens and closes the front-matter at the top of a saved query. It is
+/// written as SQL comments so the file still runs in any other tool.
+const FENCE: &str = "-- +++";
+
+/// Saved queries, one `.sql` file each, in a folder tree that can be kept
+/// in version control
+pub struct Library {
+    root: Option<PathBuf>,
+    queries: Vec<SavedQuery>,
+    /// The file each query was read from or last saved to
+    files: HashMap<Uuid, PathBuf>,
+}
+
+impl Library {
+    /// Open the library folder set in the config. Without one the library
+    /// is empty and saving fails.
+    pub fn load(config: &AppConfig) -> Self {
+        match config.queries_dir() {
+            Ok(root) => Self::open(root),
+            Err(e) => {
+                tracing::warn!("Saved queries are not available: {}", e);
+                Self {
+                    root: None,
+                    queries: Vec::new(),
+                    files: HashMap::new(),
+                }
+            }
+        }
+    }
+
+    pub fn open(root: PathBuf) -> Self {
+        let mut library = Self {
+            root: Some(root),
+            queries: Vec::new(),
+            files: HashMap::new(),
+        };
+        library.reload();
+        library
+    }
+
+    /// Read every query in the folder again, picking up changes made
+    /// outside the app
+    pub fn reload(&mut self) {
+        self.queries.clear();
+        self.files.clear();
+        let Some(root) = self.root.clone() else {
+            return;
+        };
+        if let Err(e) = self.read_dir(&root, "") {
+            if e.kind() != io::ErrorKind::NotFound {
+                tracing::warn!("Failed to read saved queries: {}", e);
+            }
+        }
+        self.sort();
+    }
+
+    /// By folder, then name
+    fn sort(&mut self) {
+        self.queries.sort_by(|a, b| {
+            (&a.folder, a.name.to_lowercase()).cmp(&(&b.folder, b.name.to_lowercase()))
+        });
+    }
+
+    fn read_dir(&mut self, dir: &Path, folder: &str) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            // Leaves out .git and the like
+            if name.starts_with('.') {
+                continue;
+            }
+            if path.is_dir() {
+                let folder = match folder {
+                    "" => name.to_string(),
+                    _ => format!("{}/{}", folder, name),
+                };
+                self.read_dir(&path, &folder)?;
+            } else if path.extension().is_some_and(|ext| ext == "sql") {
+                let text = fs::read_to_string(&path);
+                match text
+                    .map_err(|e| e.to_string())
+                    .and_then(|text| parse(&text).map_err(|e| e.to_string()))
+                {
+                    Ok(mut query) => {
+                        if query.name.trim().is_empty() {
+                            let stem = path.file_stem().unwrap_or_default();
+                            query.name = stem.to_string_lossy().to_string();
+                        }
+                        // Copied files keep the id of the original
+                        if self.files.contains_key(&query.id) {
+                            query.id = Uuid::new_v4();
+                        }
+                        query.folder = folder.to_string();
+                        self.files.insert(query.id, path);
+                        self.queries.push(query);
+                    }
+                    Err(e) => tracing::warn!("Skipping {}: {}", path.display(), e),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn root(&self) -> Option<&Path> {
+        self.root.as_deref()
+    }
+
+    pub fn get(&self, id: Uuid) -> Option<&SavedQuery> {
+        self.queries.iter().find(|query| query.id == id)
+    }
+
+    /// Queries usable on `connection`, that is global ones and those scoped
+    /// to it, matching every word of `text`, sorted by folder and name.
+    /// A word starting with `#` must be one of the query's tags; any other
+    /// must appear in its name, folder or SQL.
+    pub fn search(&self, text: &str, connection: Option<&str>) -> Vec<&SavedQuery> {
+        let words: Vec<String> = text.split_whitespace().map(str::to_lowercase).collect();
+        self.queries
+            .iter()
+            .filter(|query| query.connection.is_none() || query.connection.as_deref() == connection)
+            .filter(|query| words.iter().all(|word| matches(query, word)))
+            .collect()
+    }
+
+    /// Every folder that holds a query, sorted
+    pub fn folders(&self) -> Vec<String> {
+        let mut folders: Vec<String> = self
+            .queries
+            .iter()
+            .map(|query| query.folder.clone())
+            .filter(|folder| !folder.is_empty())
+            .collect();
+        folders.dedup();
+        folders
+    }
+
+    /// Write a query to its file, moving the file if the query was renamed
+    /// or put in another folder
+    pub fn save(&mut self, mut query: SavedQuery) -> Result<(), ConfigError> {
+        let root = self.root.clone().ok_or(ConfigError::ConfigDirNotFound)?;
+        query.folder = clean_folder(&query.folder);
+        query.tags = query
+            .tags
+            .iter()
+            .map(|tag| tag.trim().trim_start_matches('#').to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect();
+        query.updated_at = Utc::now();
+
+        let old = self.files.get(&query.id).cloned();
+        let dir = query
+            .folder
+            .split('/')
+            .filter(|part| !part.is_empty())
+            .fold(root, |dir, part| dir.join(part));
+        let stem = file_stem(&query.name);
+        let mut path = dir.join(format!("{}.sql", stem));
+        let mut n = 1;
+        while path.exists() && Some(&path) != old.as_ref() {
+            n += 1;
+            path = dir.join(format!("{}-{}.sql", stem, n));
+        }
+
+        fs::create_dir_all(&dir)?;
+        fs::write(&path, render(&query)?)?;
+        if let Some(old) = old.filter(|old| *old != path) {
+            if let Err(e) = fs::remove_file(&old) {
+                tracing::warn!("Failed to remove {}: {}", old.display(), e);
+            }
+        }
+
+        self.files.insert(query.id, path);
+        self.queries.retain(|q| q.id != query.id);
+        self.queries.push(query);
+        self.sort();
+        Ok(())
+    }
+
+    /// Remove a query and its file
+    pub fn delete(&mut self, id: Uuid) -> Result<(), ConfigError> {
+        if let Some(path) = self.files.remove(&id) {
+            fs::remove_file(path)?;
+        }
+        self.queries.retain(|query| query.id != id);
+        Ok(())
+    }
+}
+
+fn matches(query: &SavedQuery, word: &str) -> bool {
+    match word.strip_prefix('#') {
+        Some(tag) => query.tags.iter().any(|t| t.to_lowercase() == tag),
+        None => [&query.name, &query.folder, &query.sql]
+            .iter()
+            .any(|field| field.to_lowercase().contains(word)),
+    }
+}
+
+/// Split a saved query file into its front-matter and SQL. A file without
+/// front-matter is all SQL.
+fn parse(text: &str) -> Result<SavedQuery, toml::de::Error> {
+    let plain = || SavedQuery {
+        name: String::new(),
+        sql: text.to_string(),
+        ..SavedQuery::default()
+    };
+
+    let mut header = String::new();
+    let mut offset = 0;
+    for (i, line) in text.split_inclusive('\n').enumerate() {
+        offset += line.len();
+        let line = line.trim_end();
+        match i {
+            0 if line != FENCE => return Ok(plain()),
+            0 => {}
+            _ if line == FENCE => {
+                let mut query: SavedQuery = toml::from_str(&header)?;
+                query.sql = text[offset..].trim_end().to_string();
+                return Ok(query);
+            }
+            _ => {
+                let line = line.strip_prefix("--").unwrap_or(line);
+                header.push_str(line.strip_prefix(' ').unwrap_or(line));
+                header.push('\n');
+            }
+        }
+    }
+    // Never closed, so not front-matter after all
+    Ok(plain())
+}
+
+/// The file contents for a query: its front-matter, then its SQL
+fn render(query: &SavedQuery) -> Result<String, toml::ser::Error> {
+    let mut text = format!("{}\n", FENCE);
+    for line in toml::to_string(query)?.lines() {
+        text.push_str(&format!("-- {}\n", line));
+    }
+    text.push_str(FENCE);
+    text.push('\n');
+    text.push_str(query.sql.trim_end());
+    text.push('\n');
+    Ok(text)
+}
+
+/// A folder path without empty, `.` or `..` parts, so a query can't be
+/// written outside the library
+fn clean_folder(folder: &str) -> String {
+    folder
+        .split(['/', '\\'])
+        .map(str::trim)
+        .filter(|part| !part.is_empty() && *part != "." && *part != "..")
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// A file name for a query name: lowercase letters, digits and dashes
+fn file_stem(name: &str) -> String {
+    let mut stem = String::new();
+    for c in name.trim().chars() {
+        if c.is_alphanumeric() || c == '_' {
+            stem.extend(c.to_lowercase());
+        } else if !stem.ends_with('-') {
+            stem.push('-');
+        }
+    }
+    let stem = stem.trim_matches('-');
+    if stem.is_empty() {
+        "query".to_string()
+    } else {
+        stem.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_front_matter_round_trip() {
+        let mut query = SavedQuery::new(
+            "Monthly revenue".to_string(),
+            "SELECT month, sum(total)\nFROM orders\nGROUP BY month;".to_string(),
+        );
+        query.tags = vec!["reports".to_string(), "finance".to_string()];
+        query.connection = Some("postgres.warehouse".to_string());
+
+        let text = render(&query).unwrap();
+        assert!(text.starts_with("-- +++\n-- id = "));
+        assert!(text.ends_with("-- +++\nSELECT month, sum(total)\nFROM orders\nGROUP BY month;\n"));
+
+        let parsed = parse(&text).unwrap();
+        assert_eq!(parsed.id, query.id);
+        assert_eq!(parsed.name, "Monthly revenue");
+        assert_eq!(parsed.tags, ["reports", "finance"]);
+        assert_eq!(parsed.connection.as_deref(), Some("postgres.warehouse"));
+        assert_eq!(parsed.created_at, query.created_at);
+        assert_eq!(parsed.sql, query.sql);
+
+        // A plain SQL file is all query
+        let parsed = parse("-- totals\nSELECT 1").unwrap();
+        assert_eq!(parsed.sql, "-- totals\nSELECT 1");
+        assert!(parsed.tags.is_empty());
+    }
+
+    #[test]
+    fn test_save_search_and_delete() {
+        let root = std::env::temp_dir().join(format!("nebula-library-{}", Uuid::new_v4()));
+        let mut library = Library::open(root.clone());
+
+        let mut revenue = SavedQuery::new("Revenue".to_string(), "SELECT 1".to_string());
+        revenue.folder = "/monthly/./".to_string();
+        revenue.tags = vec!["#Finance".to_string()];
+        library.save(revenue.clone()).unwrap();
+        let mut users = SavedQuery::new("Active users".to_string(), "SELECT 2".to_string());
+        users.connection = Some("mysql.local".to_string());
+        library.save(users.clone()).unwrap();
+        assert!(root.join("monthly/revenue.sql").exists());
+        assert!(root.join("active-users.sql").exists());
+
+        // Renaming moves the file; a plain file dropped in is picked up
+        revenue.name = "Revenue by month".to_string();
+        revenue.folder = "monthly".to_string();
+        library.save(revenue.clone()).unwrap();
+        fs::write(root.join("monthly/ad hoc.sql"), "SELECT 3").unwrap();
+        let mut library = Library::open(root.clone());
+        assert!(!root.join("monthly/revenue.sql").exists());
+
+        let names = |queries: Vec<&SavedQuery>| -> Vec<String> {
+            queries.iter().map(|q| q.name.clone()).collect()
+        };
+        assert_eq!(
+            names(library.search("", None)),
+            ["ad hoc", "Revenue by month"]
+        );
+        assert_eq!(
+            names(library.search("", Some("mysql.local"))),
+            ["Active users", "ad hoc", "Revenue by month"]
+        );
+        assert_eq!(
+            names(library.search("#finance month", None)),
+            ["Revenue by month"]
+        );
+        assert_eq!(library.folders(), ["monthly"]);
+
+        library.delete(revenue.id).unwrap();
+        assert!(!root.join("monthly/revenue-by-month.sql").exists());
+        assert!(library.get(revenue.id).is_none());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_file_names() {
+        assert_eq!(file_stem("Top 10 customers (EU)"), "top-10-customers-eu");
+        assert_eq!(file_stem("  ?? "), "query");
+        assert_eq!(clean_folder("/a//./b\\c/"), "a/b/c");
+    }
+}
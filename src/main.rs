@@ -1,30 +1,57 @@
 mod config;
 mod db;
+mod design;
+mod edit;
+mod history;
+mod library;
 mod models;
+mod session;
+mod sql;
+mod tabs;
 mod theme;
 
+use config::workspace::{StoredTab, Workspace};
 use config::AppConfig;
-use db::{create_connection, DatabaseConnection, DatabaseInfo, TableInfo, ViewInfo};
+use db::quote::quote_qualified;
+use db::{create_connection, ObjectKind, SchemaObject, TableStructure};
+use design::{ColumnDesign, ForeignKeyDesign, IndexDesign, TableDesign, REFERENTIAL_ACTIONS};
 use eframe::egui;
-use models::{ConnectionConfig, ConnectionState, QueryResult};
-use std::collections::{HashMap, HashSet};
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use edit::{ChangeSet, EditTarget, NewValue};
+use history::{History, HistoryEntry, HistoryFilter, StatusFilter};
+use library::Library;
+use models::{
+    CellValue, ConnectionConfig, ConnectionState, DatabaseType, ParamKind, ParamValue, QueryResult,
+    SavedQuery,
+};
+use session::{Pending, Session, SessionEvent, Sessions};
+use sql::Dialect;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tabs::{QueryTab, RunContext, StatementStatus, TableView};
+use uuid::Uuid;
 
 fn main() -> eframe::Result<()> {
     tracing_subscriber::fmt::init();
 
+    let workspace = Workspace::load().unwrap_or_else(|e| {
+        tracing::warn!("Failed to load workspace: {}", e);
+        Workspace::default()
+    });
+
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
-            .with_inner_size([1400.0, 900.0])
-            .with_min_inner_size([800.0, 600.0]),
+            .with_inner_size(workspace.window_size.unwrap_or([1400.0, 900.0]))
+            .with_min_inner_size([800.0, 600.0])
+            .with_drag_and_drop(true),
         ..Default::default()
     };
 
     eframe::run_native(
         "Nebula - Database Client",
         options,
-        Box::new(|cc| Ok(Box::new(NebulaApp::new(cc)))),
+        Box::new(|cc| Ok(Box::new(NebulaApp::new(cc, workspace)))),
     )
 }
 
@@ -32,10 +59,8 @@ struct NebulaApp {
     // Runtime for async operations
     runtime: tokio::runtime::Runtime,
 
-    // Connection state
-    connection: Option<Arc<Mutex<Box<dyn DatabaseConnection>>>>,
-    connection_config: Option<ConnectionConfig>,
-    connection_state: ConnectionState,
+    // Open connections, each with its schema tree
+    sessions: Sessions,
 
     // Config
     app_config: AppConfig,
@@ -44,6 +69,8 @@ struct NebulaApp {
     // UI State
     view_state: ViewState,
     sidebar_width: f32,
+    /// Size of the window as of the last frame, saved with the workspace
+    window_size: Option<egui::Vec2>,
 
     // Connection form
     form_config: ConnectionConfig,
@@ -51,31 +78,81 @@ struct NebulaApp {
     form_test_result: Option<Result<(), String>>,
 
     // Schema browser
-    databases: Vec<DatabaseInfo>,
-    tables: HashMap<String, Vec<TableInfo>>,
-    views: HashMap<String, Vec<ViewInfo>>,
-    expanded_databases: HashSet<String>,
-    selected_database: Option<String>,
-    selected_table: Option<(String, String)>,
-    schema_loading: bool,
-
-    // Query editor
-    query_content: String,
-    query_executing: bool,
-
-    // Results
-    query_result: Option<QueryResult>,
-    result_error: Option<String>,
+    /// Session, database and table last picked in the sidebar
+    selected_table: Option<(Uuid, String, String)>,
+    /// Expanded databases saved for connections that are not open yet, by
+    /// connection key
+    restored_expanded: HashMap<String, Vec<String>>,
+
+    // Query editors
+    tabs: Vec<QueryTab>,
+    active_tab: usize,
+    /// Id of the last tab opened
+    last_tab_id: u64,
+    /// The tab whose name is being edited
+    renaming_tab: Option<u64>,
+    /// Last values bound to each query's placeholders, by query text
+    bound_params: HashMap<String, HashMap<String, ParamValue>>,
+
+    // Query history
+    history: History,
+    show_history: bool,
+    history_filter: HistoryFilter,
+
+    // Saved queries
+    library: Library,
+    library_search: String,
+    save_form: Option<SaveQueryForm>,
+
+    // Files
+    /// The open or save dialog being shown
+    pending_dialog: Option<(FileDialog, Pending<Option<PathBuf>>)>,
+    /// A file tab with unsaved edits that is about to be closed
+    unsaved_prompt: Option<UnsavedPrompt>,
+    /// When tabs last checked their files for changes on disk
+    last_file_check: Instant,
 
     // Async task results (polled each frame)
-    pending_connection: Option<tokio::sync::oneshot::Receiver<Result<Box<dyn DatabaseConnection>, String>>>,
-    pending_databases: Option<tokio::sync::oneshot::Receiver<Result<Vec<DatabaseInfo>, String>>>,
-    pending_tables: Option<(String, tokio::sync::oneshot::Receiver<Result<Vec<TableInfo>, String>>)>,
-    pending_views: Option<(String, tokio::sync::oneshot::Receiver<Result<Vec<ViewInfo>, String>>)>,
-    pending_query: Option<tokio::sync::oneshot::Receiver<Result<QueryResult, String>>>,
-    pending_test: Option<tokio::sync::oneshot::Receiver<Result<(), String>>>,
+    pending_test: Option<Pending<()>>,
+    pending_ddl: Option<PendingDdl>,
+    /// How the last DDL request went, shown under its connection
+    ddl_notice: Option<(Uuid, Result<String, String>)>,
+}
+
+/// What to do with an object's DDL once it has been read
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DdlAction {
+    Copy,
+    OpenTab,
+}
+
+/// An object's DDL on its way from the server
+struct PendingDdl {
+    action: DdlAction,
+    session: Uuid,
+    database: String,
+    /// The object's name as the tree shows it
+    name: String,
+    result: Pending<String>,
+}
+
+/// A native file dialog, by what its answer is for
+#[derive(Debug, Clone, Copy)]
+enum FileDialog {
+    Open,
+    /// Save the tab with this id
+    SaveAs(u64),
 }
 
+const OPEN_FILE: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::O);
+const SAVE_FILE: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::S);
+const SAVE_FILE_AS: egui::KeyboardShortcut = egui::KeyboardShortcut::new(
+    egui::Modifiers::COMMAND.plus(egui::Modifiers::SHIFT),
+    egui::Key::S,
+);
+
 #[derive(Debug, Clone, PartialEq)]
 enum ViewState {
     Welcome,
@@ -83,71 +160,174 @@ enum ViewState {
     Connected,
 }
 
+/// The question asked before closing a file tab with unsaved edits
+struct UnsavedPrompt {
+    tab: u64,
+    /// The whole window is closing, so the tab stays open for the
+    /// workspace and the next unsaved tab is asked about after it
+    window: bool,
+}
+
+/// The dialog that saves the active tab to the library
+struct SaveQueryForm {
+    /// The saved query being updated, or `None` for a new one
+    id: Option<Uuid>,
+    name: String,
+    folder: String,
+    /// Comma-separated
+    tags: String,
+    /// Key of the tab's connection, offered as the query's scope
+    connection: Option<String>,
+    scoped: bool,
+    error: Option<String>,
+}
+
 impl NebulaApp {
-    fn new(_cc: &eframe::CreationContext<'_>) -> Self {
+    fn new(_cc: &eframe::CreationContext<'_>, workspace: Workspace) -> Self {
         let app_config = AppConfig::load().unwrap_or_default();
         let connections = app_config.get_connections();
+        let library = Library::load(&app_config);
 
-        Self {
+        let mut app = Self {
             runtime: tokio::runtime::Runtime::new().unwrap(),
-            connection: None,
-            connection_config: None,
-            connection_state: ConnectionState::Disconnected,
+            sessions: Sessions::default(),
             app_config,
             connections,
             view_state: ViewState::Welcome,
-            sidebar_width: 250.0,
+            sidebar_width: workspace.sidebar_width.unwrap_or(250.0),
+            window_size: None,
             form_config: ConnectionConfig::default(),
             form_testing: false,
             form_test_result: None,
-            databases: Vec::new(),
-            tables: HashMap::new(),
-            views: HashMap::new(),
-            expanded_databases: HashSet::new(),
-            selected_database: None,
             selected_table: None,
-            schema_loading: false,
-            query_content: String::new(),
-            query_executing: false,
-            query_result: None,
-            result_error: None,
-            pending_connection: None,
-            pending_databases: None,
-            pending_tables: None,
-            pending_views: None,
-            pending_query: None,
+            restored_expanded: workspace.expanded_databases,
+            tabs: Vec::new(),
+            active_tab: 0,
+            last_tab_id: 0,
+            renaming_tab: None,
+            bound_params: HashMap::new(),
+            history: History::load(),
+            show_history: false,
+            history_filter: HistoryFilter::default(),
+            library,
+            library_search: String::new(),
+            save_form: None,
+            unsaved_prompt: None,
+            pending_dialog: None,
+            last_file_check: Instant::now(),
             pending_test: None,
+            pending_ddl: None,
+            ddl_notice: None,
+        };
+        app.restore_tabs(workspace.tabs, workspace.active_tab);
+        // Show the restored editors rather than the welcome page
+        if app.tabs.iter().any(|tab| !tab.query_content.is_empty()) {
+            app.view_state = ViewState::Connected;
         }
+
+        if app.app_config.nebula.auto_connect {
+            let last = app.app_config.nebula.last_connection.as_deref();
+            if let Some(config) = last.and_then(|key| app.find_connection(key)) {
+                app.open_session(config);
+            }
+        }
+        app
     }
 
-    fn poll_async_tasks(&mut self) {
-        // Poll connection result
-        if let Some(rx) = &mut self.pending_connection {
-            if let Ok(result) = rx.try_recv() {
-                match result {
-                    Ok(conn) => {
-                        let conn = Arc::new(Mutex::new(conn));
-                        self.connection = Some(conn.clone());
-                        self.connection_state = ConnectionState::Connected;
+    /// Reopen the tabs saved with the workspace, on the same connections
+    fn restore_tabs(&mut self, tabs: Vec<StoredTab>, active: usize) {
+        for stored in tabs {
+            let connection = stored
+                .connection
+                .and_then(|key| self.find_connection(&key))
+                .map(|config| config.id);
+            let saved = stored.saved_query.and_then(|id| self.library.get(id));
+            let saved_content = saved.map(|query| query.sql.clone()).unwrap_or_default();
+            let tab = self.open_tab(connection);
+            if !stored.name.trim().is_empty() {
+                tab.name = stored.name;
+            }
+            tab.database = stored.database;
+            tab.table = stored.table;
+            tab.schema = stored.schema;
+            tab.saved_query = stored.saved_query;
+            tab.query_content = stored.sql;
+            tab.saved_content = saved_content;
+            if let Some(path) = stored.file {
+                tab.restore_file(path);
+            }
+        }
+        if self.tabs.is_empty() {
+            self.open_tab(None);
+        }
+        self.active_tab = active.min(self.tabs.len() - 1);
+    }
+
+    /// The saved connection a connection key refers to
+    fn find_connection(&self, key: &str) -> Option<ConnectionConfig> {
+        self.app_config.find_connection(key, &self.connections).cloned()
+    }
+
+    /// The key of a connection, open or saved, by id
+    fn connection_key(&self, id: Uuid) -> Option<String> {
+        let config = self.sessions.get(id).map(|s| &s.config);
+        config
+            .or_else(|| self.connections.iter().find(|c| c.id == id))
+            .map(AppConfig::connection_key)
+    }
+
+    /// The tabs, trees and layout as they are now, to be restored on the
+    /// next start
+    fn workspace(&self) -> Workspace {
+        let mut expanded_databases = self.restored_expanded.clone();
+        for session in self.sessions.iter() {
+            let mut databases: Vec<String> = session.expanded_databases.iter().cloned().collect();
+            databases.sort();
+            expanded_databases.insert(AppConfig::connection_key(&session.config), databases);
+        }
+
+        Workspace {
+            sidebar_width: Some(self.sidebar_width),
+            window_size: self.window_size.map(|size| [size.x, size.y]),
+            active_tab: self.active_tab,
+            expanded_databases,
+            tabs: self
+                .tabs
+                .iter()
+                .map(|tab| StoredTab {
+                    name: tab.name.clone(),
+                    connection: tab.connection.and_then(|id| self.connection_key(id)),
+                    database: tab.database.clone(),
+                    saved_query: tab.saved_query,
+                    file: tab.file.as_ref().map(|file| file.path.clone()),
+                    table: tab.table.clone(),
+                    schema: tab.schema.clone(),
+                    sql: tab.query_content.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    fn poll_async_tasks(&mut self, ctx: &egui::Context) {
+        for (id, event) in self.sessions.poll(self.runtime.handle()) {
+            match event {
+                SessionEvent::Connected => {
+                    // Leave the form alone if it moved on to another connection
+                    if self.view_state != ViewState::ConnectionForm || self.form_config.id == id {
                         self.view_state = ViewState::Connected;
-                        
-                        // Start loading databases
-                        self.schema_loading = true;
-                        let (tx, rx) = tokio::sync::oneshot::channel();
-                        let conn_clone = conn.clone();
-                        self.runtime.spawn(async move {
-                            let conn = conn_clone.lock().await;
-                            let result = conn.list_databases().await.map_err(|e| e.to_string());
-                            let _ = tx.send(result);
-                        });
-                        self.pending_databases = Some(rx);
                     }
-                    Err(e) => {
-                        self.connection_state = ConnectionState::Error;
+                    if let Some(session) = self.sessions.get(id) {
+                        let key = AppConfig::connection_key(&session.config);
+                        self.app_config.set_last_connection(&key);
+                        let _ = self.app_config.save();
+                    }
+                    self.show_session(id);
+                }
+                SessionEvent::Failed(e) => {
+                    if self.view_state == ViewState::ConnectionForm && self.form_config.id == id {
                         self.form_test_result = Some(Err(e));
                     }
                 }
-                self.pending_connection = None;
             }
         }
 
@@ -160,140 +340,338 @@ impl NebulaApp {
             }
         }
 
-        // Poll databases result
-        if let Some(rx) = &mut self.pending_databases {
+        if let Some((_, rx)) = &mut self.pending_dialog {
             if let Ok(result) = rx.try_recv() {
-                self.schema_loading = false;
-                match result {
-                    Ok(databases) => {
-                        // Filter databases if specific one was configured
-                        let filtered = if let Some(config) = &self.connection_config {
-                            if !config.database.is_empty() {
-                                databases.into_iter()
-                                    .filter(|db| db.name == config.database)
-                                    .collect()
-                            } else {
-                                databases
-                            }
-                        } else {
-                            databases
-                        };
-                        
-                        self.databases = filtered;
-                        
-                        // Auto-expand if single database
-                        if self.databases.len() == 1 {
-                            let db_name = self.databases[0].name.clone();
-                            self.expanded_databases.insert(db_name.clone());
-                            self.selected_database = Some(db_name.clone());
-                            self.load_tables_and_views(&db_name);
+                let dialog = self.pending_dialog.take().map(|(dialog, _)| dialog);
+                match (dialog, result) {
+                    (Some(FileDialog::Open), Ok(Some(path))) => self.open_file(path),
+                    (Some(FileDialog::SaveAs(id)), Ok(Some(path))) => {
+                        if let Some(index) = self.tabs.iter().position(|tab| tab.id == id) {
+                            self.save_file(index, path);
                         }
                     }
-                    Err(e) => {
-                        tracing::error!("Failed to load databases: {}", e);
-                    }
+                    _ => {}
                 }
-                self.pending_databases = None;
             }
         }
 
-        // Poll tables result
-        if let Some((db_name, rx)) = &mut self.pending_tables {
-            if let Ok(result) = rx.try_recv() {
-                self.schema_loading = false;
-                match result {
-                    Ok(tables) => {
-                        self.tables.insert(db_name.clone(), tables);
-                    }
-                    Err(e) => {
-                        tracing::error!("Failed to load tables: {}", e);
-                    }
-                }
-                self.pending_tables = None;
+        let ddl = self.pending_ddl.as_mut().map(|ddl| ddl.result.try_recv());
+        if let Some(Ok(result)) = ddl {
+            if let Some(ddl) = self.pending_ddl.take() {
+                self.finish_ddl(ctx, ddl, result);
             }
         }
 
-        // Poll views result
-        if let Some((db_name, rx)) = &mut self.pending_views {
-            if let Ok(result) = rx.try_recv() {
-                match result {
-                    Ok(views) => {
-                        self.views.insert(db_name.clone(), views);
-                    }
-                    Err(e) => {
-                        tracing::error!("Failed to load views: {}", e);
-                    }
-                }
-                self.pending_views = None;
+        for index in 0..self.tabs.len() {
+            self.tabs[index].poll();
+            self.record_history(index);
+            self.reload_changed_schema(index);
+        }
+
+        if self.last_file_check.elapsed() >= Duration::from_secs(1) {
+            for tab in &mut self.tabs {
+                tab.check_file();
             }
+            self.last_file_check = Instant::now();
         }
+    }
 
-        // Poll query result
-        if let Some(rx) = &mut self.pending_query {
-            if let Ok(result) = rx.try_recv() {
-                self.query_executing = false;
-                match result {
-                    Ok(qr) => {
-                        self.query_result = Some(qr);
-                        self.result_error = None;
-                    }
-                    Err(e) => {
-                        self.query_result = None;
-                        self.result_error = Some(e);
-                    }
-                }
-                self.pending_query = None;
+    /// Open a `.sql` file in an editor, or switch to the tab that has it
+    fn open_file(&mut self, path: PathBuf) {
+        self.view_state = ViewState::Connected;
+        let open = self.tabs.iter().position(|tab| {
+            tab.file.as_ref().is_some_and(|file| file.path == path)
+        });
+        if let Some(index) = open {
+            self.active_tab = index;
+            return;
+        }
+        let tab = self.editor_tab(None, false);
+        match tab.open_file(path.clone()) {
+            Ok(()) => {
+                self.app_config.add_recent_file(&path);
+                let _ = self.app_config.save();
             }
+            Err(e) => tab.result_error = Some(format!("Failed to open {}: {}", path.display(), e)),
         }
     }
 
-    fn load_tables_and_views(&mut self, db_name: &str) {
-        if let Some(conn) = &self.connection {
-            self.schema_loading = true;
-            
-            // Load tables
-            let (tx, rx) = tokio::sync::oneshot::channel();
-            let conn_clone = conn.clone();
-            let db = db_name.to_string();
-            self.runtime.spawn(async move {
-                let conn = conn_clone.lock().await;
-                let result = conn.list_tables(&db).await.map_err(|e| e.to_string());
-                let _ = tx.send(result);
-            });
-            self.pending_tables = Some((db_name.to_string(), rx));
-
-            // Load views
-            let (tx, rx) = tokio::sync::oneshot::channel();
-            let conn_clone = conn.clone();
-            let db = db_name.to_string();
-            self.runtime.spawn(async move {
-                let conn = conn_clone.lock().await;
-                let result = conn.list_views(&db).await.map_err(|e| e.to_string());
-                let _ = tx.send(result);
+    /// Write a tab to `path`
+    fn save_file(&mut self, index: usize, path: PathBuf) {
+        let tab = &mut self.tabs[index];
+        match tab.save_file(path.clone()) {
+            Ok(()) => {
+                self.app_config.add_recent_file(&path);
+                let _ = self.app_config.save();
+            }
+            Err(e) => tab.result_error = Some(format!("Failed to save {}: {}", path.display(), e)),
+        }
+    }
+
+    /// Save the active tab to its file, asking for one if it has none yet
+    fn save_active_tab(&mut self, save_as: bool) {
+        let tab = &self.tabs[self.active_tab];
+        match tab.file.as_ref().filter(|_| !save_as) {
+            Some(file) => self.save_file(self.active_tab, file.path.clone()),
+            None => self.show_file_dialog(FileDialog::SaveAs(tab.id)),
+        }
+    }
+
+    /// Ask for a file to open or save to. The answer arrives in
+    /// `poll_async_tasks`.
+    fn show_file_dialog(&mut self, dialog: FileDialog) {
+        if self.pending_dialog.is_some() {
+            return;
+        }
+        let tab = &self.tabs[self.active_tab];
+        let directory = match &tab.file {
+            Some(file) => file.path.parent().map(Path::to_path_buf),
+            None => self.library.root().map(Path::to_path_buf),
+        };
+        let file_name = match &tab.file {
+            Some(file) => file.name(),
+            None => format!("{}.sql", tab.name),
+        };
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        self.runtime.spawn(async move {
+            let mut picker = rfd::AsyncFileDialog::new()
+                .add_filter("SQL", &["sql"])
+                .add_filter("All files", &["*"]);
+            if let Some(directory) = directory.filter(|dir| dir.is_dir()) {
+                picker = picker.set_directory(directory);
+            }
+            let file = match dialog {
+                FileDialog::Open => picker.pick_file().await,
+                FileDialog::SaveAs(_) => picker.set_file_name(file_name).save_file().await,
+            };
+            let _ = tx.send(Ok(file.map(|file| file.path().to_path_buf())));
+        });
+        self.pending_dialog = Some((dialog, rx));
+    }
+
+    /// Read an object's DDL, to copy or open once it arrives
+    fn request_ddl(
+        &mut self,
+        session: Uuid,
+        database: String,
+        object: SchemaObject,
+        action: DdlAction,
+    ) {
+        let Some(conn) = self.sessions.get(session).and_then(Session::connected) else {
+            return;
+        };
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let db = database.clone();
+        let name = object.display_name();
+        self.runtime.spawn(async move {
+            let result = conn.get_ddl(&db, &object).await.map_err(|e| e.to_string());
+            let _ = tx.send(result);
+        });
+        self.ddl_notice = None;
+        self.pending_ddl = Some(PendingDdl {
+            action,
+            session,
+            database,
+            name,
+            result: rx,
+        });
+    }
+
+    fn finish_ddl(&mut self, ctx: &egui::Context, ddl: PendingDdl, result: Result<String, String>) {
+        let notice = match (ddl.action, result) {
+            (_, Err(e)) => Err(e),
+            (DdlAction::Copy, Ok(text)) => {
+                ctx.copy_text(text);
+                Ok(format!("Copied the DDL of {}", ddl.name))
+            }
+            (DdlAction::OpenTab, Ok(text)) => {
+                let tab = self.open_tab(Some(ddl.session));
+                tab.name = format!("{} DDL", ddl.name);
+                tab.database = Some(ddl.database);
+                tab.query_content = text;
+                return;
+            }
+        };
+        self.ddl_notice = Some((ddl.session, notice));
+    }
+
+    /// Reload the tables of a database a tab created or changed a table in
+    fn reload_changed_schema(&mut self, index: usize) {
+        let tab = &mut self.tabs[index];
+        if !tab.take_schema_changed() {
+            return;
+        }
+        let (Some(id), Some(database)) = (tab.connection, tab.database.clone()) else {
+            return;
+        };
+        if let Some(session) = self.sessions.get_mut(id) {
+            session.load_tables_and_views(self.runtime.handle(), &database);
+        }
+    }
+
+    /// Add the statements a tab finished to the history
+    fn record_history(&mut self, index: usize) {
+        let tab = &mut self.tabs[index];
+        let entries = tab.take_finished();
+        if entries.is_empty() {
+            return;
+        }
+        let connection = tab.connection.and_then(|id| self.connection_key(id));
+        for entry in entries {
+            self.history.record(HistoryEntry {
+                connection: connection.clone(),
+                ..entry
             });
-            self.pending_views = Some((db_name.to_string(), rx));
         }
     }
 
     fn connect(&mut self) {
         let config = self.form_config.clone();
-        self.connections.push(config.clone());
+        match self.connections.iter_mut().find(|c| c.id == config.id) {
+            Some(saved) => *saved = config.clone(),
+            None => self.connections.push(config.clone()),
+        }
         self.app_config.save_connection(&config);
         let _ = self.app_config.save();
-        
-        self.connection_config = Some(config.clone());
-        self.connection_state = ConnectionState::Connecting;
 
-        let (tx, rx) = tokio::sync::oneshot::channel();
-        self.runtime.spawn(async move {
-            let result = async {
-                let conn = create_connection(&config).await.map_err(|e| e.to_string())?;
-                conn.test_connection().await.map_err(|e| e.to_string())?;
-                Ok(conn)
-            }.await;
-            let _ = tx.send(result);
-        });
-        self.pending_connection = Some(rx);
+        self.form_test_result = None;
+        self.open_session(config);
+    }
+
+    /// Connect to a saved connection, or just switch to it if it is open
+    /// already
+    fn open_session(&mut self, config: ConnectionConfig) {
+        let key = AppConfig::connection_key(&config);
+        let id = self.sessions.open(self.runtime.handle(), config);
+        if let Some(databases) = self.restored_expanded.remove(&key) {
+            if let Some(session) = self.sessions.get_mut(id) {
+                session.restore_expanded(databases);
+            }
+        }
+        if self.sessions.get(id).is_some_and(|s| s.state == ConnectionState::Connected) {
+            self.view_state = ViewState::Connected;
+            self.show_session(id);
+        }
+    }
+
+    /// Give a newly opened connection an editor, unless a tab is already
+    /// bound to it: the active tab if it has no connection, else a new tab
+    fn show_session(&mut self, id: Uuid) {
+        if self.tabs.iter().any(|tab| tab.connection == Some(id)) {
+            return;
+        }
+        let tab = &mut self.tabs[self.active_tab];
+        if tab.connection.is_none() && !tab.query_executing {
+            tab.connection = Some(id);
+        } else {
+            self.open_tab(Some(id));
+        }
+    }
+
+    /// Disconnect a session. Tabs bound to it stop what they are running
+    /// and are left without a connection.
+    fn close_session(&mut self, id: Uuid) {
+        for index in 0..self.tabs.len() {
+            if self.tabs[index].connection == Some(id) {
+                if self.tabs[index].query_executing {
+                    self.tabs[index].cancel(self.runtime.handle());
+                    self.record_history(index);
+                }
+                self.tabs[index].connection = None;
+            }
+        }
+        if self.selected_table.as_ref().is_some_and(|(session, _, _)| *session == id) {
+            self.selected_table = None;
+        }
+        self.sessions.close(self.runtime.handle(), id);
+        if self.sessions.is_empty() && self.view_state == ViewState::Connected {
+            self.view_state = ViewState::Welcome;
+        }
+    }
+
+    /// Open an empty tab on `connection` and switch to it
+    fn open_tab(&mut self, connection: Option<Uuid>) -> &mut QueryTab {
+        self.last_tab_id += 1;
+        let name = format!("Query {}", self.last_tab_id);
+        self.tabs.push(QueryTab::new(self.last_tab_id, name, connection));
+        self.active_tab = self.tabs.len() - 1;
+        &mut self.tabs[self.active_tab]
+    }
+
+    /// A tab to put a query in: the active one if it is empty and idle,
+    /// else a new one. It is bound to `connection` if given.
+    fn editor_tab(&mut self, connection: Option<Uuid>, new_tab: bool) -> &mut QueryTab {
+        let active = &self.tabs[self.active_tab];
+        if new_tab || !active.query_content.trim().is_empty() || active.is_busy() {
+            let current = active.connection;
+            return self.open_tab(connection.or(current));
+        }
+        let tab = &mut self.tabs[self.active_tab];
+        tab.connection = connection.or(tab.connection);
+        tab
+    }
+
+    /// Close a tab, stopping whatever it is running. The last tab is
+    /// replaced by an empty one on the same connection.
+    fn close_tab(&mut self, index: usize) {
+        let tab = &self.tabs[index];
+        if tab.file.is_some() && tab.is_dirty() {
+            self.unsaved_prompt = Some(UnsavedPrompt { tab: tab.id, window: false });
+            return;
+        }
+        self.remove_tab(index);
+    }
+
+    /// Close a tab without asking about unsaved edits
+    fn remove_tab(&mut self, index: usize) {
+        if self.tabs[index].query_executing {
+            self.tabs[index].cancel(self.runtime.handle());
+            self.record_history(index);
+        }
+        let tab = self.tabs.remove(index);
+        if self.active_tab > index || self.active_tab == self.tabs.len() {
+            self.active_tab = self.active_tab.saturating_sub(1);
+        }
+        if self.tabs.is_empty() {
+            self.open_tab(tab.connection);
+        }
+    }
+
+    /// Move a tab to another position, keeping the same tab active
+    fn move_tab(&mut self, from: usize, to: usize) {
+        let active = self.tabs[self.active_tab].id;
+        let tab = self.tabs.remove(from);
+        self.tabs.insert(to.min(self.tabs.len()), tab);
+        self.active_tab = self.tabs.iter().position(|t| t.id == active).unwrap_or(0);
+    }
+
+    /// The session a tab is bound to
+    fn tab_session(&self, tab: &QueryTab) -> Option<&Session> {
+        tab.connection.and_then(|id| self.sessions.get(id))
+    }
+
+    /// Hand the active tab what it needs to run a query, if its connection
+    /// is open
+    fn with_run_context(&mut self, run: impl FnOnce(&mut QueryTab, &mut RunContext<'_>)) {
+        let tab = &mut self.tabs[self.active_tab];
+        let Some(session) = tab.connection.and_then(|id| self.sessions.get(id)) else {
+            return;
+        };
+        let Some(conn) = session.connected() else {
+            return;
+        };
+        let mut cx = RunContext {
+            runtime: self.runtime.handle(),
+            conn,
+            dialect: Dialect::for_database(session.config.db_type),
+            read_only: session.config.read_only,
+            database: Some(session.config.database.clone()).filter(|db| !db.is_empty()),
+            stop_on_error: self.app_config.nebula.stop_on_error,
+            fetch_size: self.app_config.nebula.fetch_size,
+            bound_params: &mut self.bound_params,
+        };
+        run(tab, &mut cx);
     }
 
     fn test_connection(&mut self) {
@@ -314,60 +692,62 @@ impl NebulaApp {
         self.pending_test = Some(rx);
     }
 
-    fn execute_query(&mut self) {
-        if let Some(conn) = &self.connection {
-            let sql = self.query_content.clone();
-            self.query_executing = true;
-
-            let (tx, rx) = tokio::sync::oneshot::channel();
-            let conn_clone = conn.clone();
-            
-            let is_select = sql.trim().to_uppercase().starts_with("SELECT")
-                || sql.trim().to_uppercase().starts_with("SHOW")
-                || sql.trim().to_uppercase().starts_with("DESCRIBE")
-                || sql.trim().to_uppercase().starts_with("EXPLAIN");
-
-            self.runtime.spawn(async move {
-                let conn = conn_clone.lock().await;
-                let result = if is_select {
-                    conn.execute_query(&sql).await.map_err(|e| e.to_string())
-                } else {
-                    match conn.execute_statement(&sql).await {
-                        Ok(affected) => Ok(QueryResult {
-                            columns: vec![],
-                            rows: vec![],
-                            affected_rows: Some(affected),
-                            execution_time_ms: 0,
-                        }),
-                        Err(e) => Err(e.to_string()),
-                    }
-                };
-                let _ = tx.send(result);
-            });
-            self.pending_query = Some(rx);
-        }
-    }
 }
 
 impl eframe::App for NebulaApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         // Poll async tasks
-        self.poll_async_tasks();
+        self.poll_async_tasks(ctx);
 
         // Request repaint if we have pending tasks
-        if self.pending_connection.is_some()
-            || self.pending_databases.is_some()
-            || self.pending_tables.is_some()
-            || self.pending_views.is_some()
-            || self.pending_query.is_some()
+        if self.sessions.is_busy()
             || self.pending_test.is_some()
+            || self.pending_dialog.is_some()
+            || self.pending_ddl.is_some()
+            || self.tabs.iter().any(QueryTab::is_busy)
         {
             ctx.request_repaint();
+        } else if self.tabs.iter().any(|tab| tab.file.is_some()) {
+            // Keep checking open files for changes on disk
+            ctx.request_repaint_after(Duration::from_secs(1));
+        }
+
+        // Files dropped onto the window
+        let dropped: Vec<PathBuf> = ctx.input(|i| {
+            i.raw.dropped_files.iter().filter_map(|file| file.path.clone()).collect()
+        });
+        for path in dropped {
+            if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("sql")) {
+                self.open_file(path);
+            }
+        }
+
+        // Checked before Save, which it would match too
+        if ctx.input_mut(|i| i.consume_shortcut(&SAVE_FILE_AS)) {
+            self.save_active_tab(true);
+        } else if ctx.input_mut(|i| i.consume_shortcut(&SAVE_FILE)) {
+            self.save_active_tab(false);
+        } else if ctx.input_mut(|i| i.consume_shortcut(&OPEN_FILE)) {
+            self.show_file_dialog(FileDialog::Open);
+        }
+
+        // Ask about unsaved files one at a time before letting the window go
+        if ctx.input(|i| i.viewport().close_requested()) {
+            if let Some(tab) = self.unsaved_tab() {
+                ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+                let prompt = self.unsaved_prompt.get_or_insert(UnsavedPrompt { tab, window: true });
+                prompt.window = true;
+            }
+        }
+
+        if let Some(rect) = ctx.input(|i| i.viewport().inner_rect) {
+            self.window_size = Some(rect.size());
         }
 
         // Apply dark theme
         ctx.set_visuals(theme::dark_visuals());
 
+        self.render_menu_bar(ctx);
         match self.view_state {
             ViewState::Welcome | ViewState::ConnectionForm => {
                 self.render_sidebar(ctx);
@@ -381,17 +761,80 @@ impl eframe::App for NebulaApp {
             }
             ViewState::Connected => {
                 self.render_sidebar(ctx);
+                if self.show_history {
+                    self.render_history(ctx);
+                }
                 egui::CentralPanel::default().show(ctx, |ui| {
                     self.render_main_content(ui);
                 });
+                self.render_document_window(ctx);
+                self.render_bind_prompt(ctx);
+                self.render_save_form(ctx);
+                self.render_change_review(ctx);
+                self.render_design_review(ctx);
             }
         }
+        self.render_unsaved_prompt(ctx);
+    }
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        if let Err(e) = self.workspace().save() {
+            tracing::warn!("Failed to save workspace: {}", e);
+        }
     }
 }
 
 impl NebulaApp {
+    fn render_menu_bar(&mut self, ctx: &egui::Context) {
+        let recent = self.app_config.nebula.recent_files.clone();
+        let (mut open, mut open_path, mut save, mut save_as) = (false, None, false, false);
+        let mut clear_recent = false;
+
+        egui::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
+            egui::MenuBar::new().ui(ui, |ui| {
+                ui.menu_button("File", |ui| {
+                    let shortcut = |shortcut| ctx.format_shortcut(&shortcut);
+                    open = ui
+                        .add(egui::Button::new("Open…").shortcut_text(shortcut(OPEN_FILE)))
+                        .clicked();
+                    ui.add_enabled_ui(!recent.is_empty(), |ui| {
+                        ui.menu_button("Open Recent", |ui| {
+                            for path in &recent {
+                                if ui.button(path.display().to_string()).clicked() {
+                                    open_path = Some(path.clone());
+                                }
+                            }
+                            ui.separator();
+                            clear_recent = ui.button("Clear Recent").clicked();
+                        });
+                    });
+                    ui.separator();
+                    save = ui
+                        .add(egui::Button::new("Save").shortcut_text(shortcut(SAVE_FILE)))
+                        .clicked();
+                    save_as = ui
+                        .add(egui::Button::new("Save As…").shortcut_text(shortcut(SAVE_FILE_AS)))
+                        .clicked();
+                });
+            });
+        });
+
+        if open {
+            self.show_file_dialog(FileDialog::Open);
+        }
+        if let Some(path) = open_path {
+            self.open_file(path);
+        }
+        if save || save_as {
+            self.save_active_tab(save_as);
+        }
+        if clear_recent {
+            self.app_config.nebula.recent_files.clear();
+            let _ = self.app_config.save();
+        }
+    }
+
     fn render_sidebar(&mut self, ctx: &egui::Context) {
-        egui::SidePanel::left("sidebar")
+        let panel = egui::SidePanel::left("sidebar")
             .resizable(true)
             .default_width(self.sidebar_width)
             .min_width(150.0)
@@ -404,12 +847,13 @@ impl NebulaApp {
                 ui.add_space(10.0);
                 ui.separator();
 
-                if self.view_state == ViewState::Connected {
-                    self.render_schema_browser(ui);
-                } else {
+                egui::ScrollArea::vertical().show(ui, |ui| {
                     self.render_connections_list(ui);
-                }
+                    self.render_sessions(ui);
+                    self.render_saved_queries(ui);
+                });
             });
+        self.sidebar_width = panel.response.rect.width();
     }
 
     fn render_connections_list(&mut self, ui: &mut egui::Ui) {
@@ -430,13 +874,28 @@ impl NebulaApp {
             ui.label(egui::RichText::new("Create a new connection to get started").color(theme::TEXT_MUTED).small());
         } else {
             let connections = self.connections.clone();
+            let mut connect = None;
             for (idx, conn) in connections.iter().enumerate() {
                 ui.horizontal(|ui| {
-                    let btn = ui.button(format!("{} {}", conn.db_type.icon(), conn.name));
-                    if btn.clicked() {
+                    let name = format!("{} {}", conn.db_type.icon(), conn.name);
+                    let mut label = egui::RichText::new(name);
+                    if let Some(color) = connection_color(conn) {
+                        label = label.color(color);
+                    }
+                    if ui.button(label).clicked() {
                         self.form_config = conn.clone();
+                        self.form_test_result = None;
                         self.view_state = ViewState::ConnectionForm;
                     }
+
+                    let open = self.sessions.get(conn.id).is_some();
+                    if ui
+                        .add_enabled(!open, egui::Button::new("▶").small())
+                        .on_hover_text("Connect")
+                        .clicked()
+                    {
+                        connect = Some(conn.clone());
+                    }
                     
                     if ui.small_button("✕").clicked() {
                         self.connections.remove(idx);
@@ -444,56 +903,238 @@ impl NebulaApp {
                         let _ = self.app_config.save();
                     }
                 });
-                ui.label(egui::RichText::new(format!("{}:{}", conn.host, conn.port)).color(theme::TEXT_MUTED).small());
+                let location = if conn.db_type == DatabaseType::SQLite {
+                    conn.database.clone()
+                } else {
+                    format!("{}:{}", conn.host, conn.port)
+                };
+                ui.label(egui::RichText::new(location).color(theme::TEXT_MUTED).small());
                 ui.add_space(5.0);
             }
+            if let Some(config) = connect {
+                self.open_session(config);
+            }
+
+            if ui
+                .checkbox(&mut self.app_config.nebula.auto_connect, "Reconnect on start")
+                .on_hover_text("Open the last connection used when Nebula starts")
+                .changed()
+            {
+                let _ = self.app_config.save();
+            }
         }
     }
 
-    fn render_schema_browser(&mut self, ui: &mut egui::Ui) {
-        ui.add_space(10.0);
-        
+    /// One schema tree per open connection
+    fn render_sessions(&mut self, ui: &mut egui::Ui) {
+        if self.sessions.is_empty() {
+            return;
+        }
+
+        ui.add_space(5.0);
+        ui.separator();
+        ui.label(egui::RichText::new("Open").color(theme::TEXT_MUTED).small());
+        ui.add_space(5.0);
+
+        let ids: Vec<Uuid> = self.sessions.iter().map(Session::id).collect();
+        for id in ids {
+            self.render_schema_browser(ui, id);
+            ui.add_space(5.0);
+        }
+    }
+
+    /// The query library: global queries and those scoped to the active
+    /// tab's connection, by folder
+    fn render_saved_queries(&mut self, ui: &mut egui::Ui) {
+        let tab = &self.tabs[self.active_tab];
+        let connection = tab.connection.and_then(|id| self.connection_key(id));
+        let current = tab.saved_query;
+
+        ui.add_space(5.0);
+        ui.separator();
         ui.horizontal(|ui| {
-            if ui.button("↻ Refresh").clicked() && self.connection.is_some() {
-                self.schema_loading = true;
-                let conn = self.connection.as_ref().unwrap().clone();
-                let (tx, rx) = tokio::sync::oneshot::channel();
-                self.runtime.spawn(async move {
-                    let conn = conn.lock().await;
-                    let result = conn.list_databases().await.map_err(|e| e.to_string());
-                    let _ = tx.send(result);
-                });
-                self.pending_databases = Some(rx);
+            let label =
+                ui.label(egui::RichText::new("Saved queries").color(theme::TEXT_MUTED).small());
+            if let Some(root) = self.library.root() {
+                label.on_hover_text(root.display().to_string());
+            }
+            if ui.small_button("↻").on_hover_text("Reload from disk").clicked() {
+                self.library.reload();
             }
         });
+        ui.add(
+            egui::TextEdit::singleline(&mut self.library_search)
+                .hint_text("Search, #tag")
+                .desired_width(f32::INFINITY),
+        );
+        ui.add_space(5.0);
 
-        ui.add_space(10.0);
+        let searching = !self.library_search.trim().is_empty();
+        let queries = self.library.search(&self.library_search, connection.as_deref());
+        if queries.is_empty() {
+            let text = if searching { "No matches" } else { "No saved queries" };
+            ui.label(egui::RichText::new(text).color(theme::TEXT_MUTED).small());
+            return;
+        }
 
-        if self.schema_loading && self.databases.is_empty() {
-            ui.horizontal(|ui| {
-                ui.spinner();
-                ui.label("Loading databases...");
+        let mut open = None;
+        let mut delete = None;
+        let mut row = |ui: &mut egui::Ui, query: &SavedQuery| {
+            let response = ui
+                .horizontal(|ui| {
+                    let name = format!("📄 {}", query.name);
+                    let response = ui.selectable_label(current == Some(query.id), name);
+                    if query.connection.is_some() {
+                        ui.label(egui::RichText::new("🔗").small())
+                            .on_hover_text("Only for this connection");
+                    }
+                    if !query.tags.is_empty() {
+                        let tags: Vec<String> =
+                            query.tags.iter().map(|tag| format!("#{}", tag)).collect();
+                        ui.label(
+                            egui::RichText::new(tags.join(" ")).color(theme::TEXT_MUTED).small(),
+                        );
+                    }
+                    response
+                })
+                .inner
+                .on_hover_text(egui::RichText::new(&query.sql).monospace());
+            if response.clicked() {
+                open = Some((query.id, false));
+            }
+            response.context_menu(|ui| {
+                if ui.button("Open").clicked() {
+                    open = Some((query.id, false));
+                    ui.close();
+                }
+                if ui.button("Open in new tab").clicked() {
+                    open = Some((query.id, true));
+                    ui.close();
+                }
+                ui.separator();
+                if ui.button("Delete").clicked() {
+                    delete = Some(query.id);
+                    ui.close();
+                }
             });
-        } else if self.databases.is_empty() {
-            ui.label(egui::RichText::new("No databases").color(theme::TEXT_MUTED));
-        } else {
-            // Clone data to avoid borrow issues
-            let databases = self.databases.clone();
-            let tables = self.tables.clone();
-            let views = self.views.clone();
-            let expanded = self.expanded_databases.clone();
-            let selected_table = self.selected_table.clone();
-            
-            // Collect actions to perform after rendering
-            let mut expand_db: Option<String> = None;
-            let mut collapse_db: Option<String> = None;
-            let mut select_table: Option<(String, String)> = None;
-            let mut load_table_data: Option<(String, String)> = None;
-            let mut set_query: Option<String> = None;
-            
-            egui::ScrollArea::vertical().show(ui, |ui| {
-                for db in &databases {
-                    let is_expanded = expanded.contains(&db.name);
+        };
+        for folder in queries.chunk_by(|a, b| a.folder == b.folder) {
+            let name = &folder[0].folder;
+            if name.is_empty() {
+                for query in folder {
+                    row(ui, query);
+                }
+                continue;
+            }
+            egui::CollapsingHeader::new(format!("📂 {}", name))
+                .id_salt(("query_folder", name))
+                .open(searching.then_some(true))
+                .show(ui, |ui| {
+                    for query in folder {
+                        row(ui, query);
+                    }
+                });
+        }
+
+        if let Some((id, new_tab)) = open {
+            self.open_saved_query(id, new_tab);
+        }
+        if let Some(id) = delete {
+            if let Err(e) = self.library.delete(id) {
+                tracing::warn!("Failed to delete saved query: {}", e);
+            }
+            for tab in &mut self.tabs {
+                if tab.saved_query == Some(id) {
+                    tab.saved_query = None;
+                }
+            }
+        }
+    }
+
+    fn render_schema_browser(&mut self, ui: &mut egui::Ui, id: Uuid) {
+        let Some(session) = self.sessions.get(id) else {
+            return;
+        };
+        let color = connection_color(&session.config);
+        let selected_table = self.selected_table.clone();
+
+        // Collect actions to perform after rendering
+        let mut refresh = false;
+        let mut reconnect = false;
+        let mut disconnect = false;
+        let mut expand_db: Option<String> = None;
+        let mut collapse_db: Option<String> = None;
+        let mut select_table: Option<(String, String)> = None;
+        // Database, name and query of an object to open in a new tab
+        let mut open_query: Option<(String, String, String)> = None;
+        // Database, table and query of a table to open in a new tab
+        let mut load_table_data: Option<(String, SchemaObject, String)> = None;
+        let mut open_table: Option<(String, SchemaObject, String)> = None;
+        let mut show_structure: Option<(String, SchemaObject, String)> = None;
+        let mut new_table: Option<String> = None;
+        let mut ddl: Option<(String, SchemaObject, DdlAction)> = None;
+        let mut dismiss_notice = false;
+        let notice = self.ddl_notice.as_ref().filter(|(session, _)| *session == id);
+        let ddl_loading = self.pending_ddl.as_ref().is_some_and(|ddl| ddl.session == id);
+
+        egui::Frame::group(ui.style())
+            .stroke(egui::Stroke::new(1.0, color.unwrap_or(theme::BORDER)))
+            .show(ui, |ui| {
+                ui.set_width(ui.available_width());
+                ui.horizontal(|ui| {
+                    let config = &session.config;
+                    let name = format!("{} {}", config.db_type.icon(), config.name);
+                    let label = egui::RichText::new(name)
+                        .strong()
+                        .color(color.unwrap_or(theme::TEXT_PRIMARY));
+                    ui.label(label);
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        disconnect = ui.small_button("✕").on_hover_text("Disconnect").clicked();
+                        match session.state {
+                            ConnectionState::Connected => {
+                                refresh = ui.small_button("↻").on_hover_text("Refresh").clicked();
+                            }
+                            ConnectionState::Error => {
+                                reconnect =
+                                    ui.small_button("↻").on_hover_text("Reconnect").clicked();
+                            }
+                            _ => {}
+                        }
+                        let connecting = session.state == ConnectionState::Connecting;
+                        if connecting || session.schema_loading() || ddl_loading {
+                            ui.spinner();
+                        }
+                    });
+                });
+
+                if let Some((_, notice)) = notice {
+                    ui.horizontal(|ui| {
+                        let text = match notice {
+                            Ok(text) => egui::RichText::new(text).color(theme::TEXT_MUTED),
+                            Err(e) => egui::RichText::new(e).color(theme::DANGER),
+                        };
+                        ui.label(text.small());
+                        dismiss_notice = ui.small_button("✕").clicked();
+                    });
+                }
+
+                if let Some(error) = &session.error {
+                    ui.label(egui::RichText::new(error).color(theme::DANGER).small());
+                    return;
+                }
+                if session.state != ConnectionState::Connected {
+                    return;
+                }
+                if session.databases.is_empty() {
+                    if !session.schema_loading() {
+                        ui.label(egui::RichText::new("No databases").color(theme::TEXT_MUTED));
+                    }
+                    return;
+                }
+
+                let db_type = session.config.db_type;
+                for db in &session.databases {
+                    let is_expanded = session.expanded_databases.contains(&db.name);
                     ui.horizontal(|ui| {
                         let icon = if is_expanded { "▼" } else { "▶" };
                         if ui.small_button(icon).clicked() {
@@ -504,74 +1145,211 @@ impl NebulaApp {
                             }
                         }
                         ui.label("🗄");
-                        ui.label(&db.name);
-                    });
+                        let label = ui.label(&db.name);
+                        if let Some(charset) = &db.character_set {
+                            label.on_hover_text(format!("Character set: {}", charset));
+                        }
+                        let sql = db_type != DatabaseType::MongoDB;
+                        if sql && ui.small_button("➕").on_hover_text("New table").clicked() {
+                            new_table = Some(db.name.clone());
+                        }
+                    });
 
                     if is_expanded {
-                        ui.indent(&db.name, |ui| {
+                        ui.indent((id, &db.name), |ui| {
                             // Tables
-                            if let Some(db_tables) = tables.get(&db.name) {
+                            if let Some(db_tables) = session.tables.get(&db.name) {
                                 for table in db_tables {
                                     ui.horizontal(|ui| {
-                                        ui.label("  📋");
+                                        ui.label(format!("  {}", ObjectKind::Table.icon()));
+                                        let table_name = table.qualified_name();
                                         let selected = selected_table.as_ref()
-                                            .map(|(d, t)| d == &db.name && t == &table.name)
+                                            .map(|(s, d, t)| {
+                                                *s == id && d == &db.name && t == &table_name
+                                            })
                                             .unwrap_or(false);
+                                        let query = || {
+                                            let schema = table.schema.as_deref();
+                                            preview_query(db_type, &db.name, schema, &table.name)
+                                        };
                                         
-                                        if ui.selectable_label(selected, &table.name).clicked() {
-                                            select_table = Some((db.name.clone(), table.name.clone()));
-                                            set_query = Some(format!(
-                                                "SELECT * FROM `{}`.`{}` LIMIT 100",
-                                                db.name, table.name
-                                            ));
+                                        let label = ui.selectable_label(selected, &table_name);
+                                        let object = || SchemaObject::table(table);
+                                        if label.clicked() {
+                                            select_table = Some((db.name.clone(), table_name.clone()));
+                                            open_table = Some((db.name.clone(), object(), query()));
                                         }
+                                        label.context_menu(|ui| {
+                                            ddl_menu(ui, &db.name, object(), &mut ddl);
+                                        });
                                         
                                         if ui.small_button("▶").on_hover_text("Load data").clicked() {
-                                            load_table_data = Some((db.name.clone(), table.name.clone()));
+                                            load_table_data =
+                                                Some((db.name.clone(), object(), query()));
+                                        }
+                                        if ui.small_button("🏗").on_hover_text("Structure").clicked() {
+                                            show_structure =
+                                                Some((db.name.clone(), object(), query()));
                                         }
                                     });
                                 }
                             }
 
                             // Views
-                            if let Some(db_views) = views.get(&db.name) {
+                            if let Some(db_views) = session.views.get(&db.name) {
                                 for view in db_views {
                                     ui.horizontal(|ui| {
-                                        ui.label("  👁");
-                                        if ui.link(&view.name).clicked() {
-                                            set_query = Some(format!(
-                                                "SELECT * FROM `{}`.`{}` LIMIT 100",
-                                                db.name, view.name
-                                            ));
+                                        ui.label(format!("  {}", ObjectKind::View.icon()));
+                                        let view_name = view.qualified_name();
+                                        let mut link = ui.link(&view_name);
+                                        if let Some(definition) = &view.definition {
+                                            let text = egui::RichText::new(definition.trim());
+                                            link = link.on_hover_text(text.monospace());
+                                        }
+                                        link.context_menu(|ui| {
+                                            let object = SchemaObject::view(view);
+                                            ddl_menu(ui, &db.name, object, &mut ddl);
+                                        });
+                                        if link.clicked() {
+                                            let query = preview_query(
+                                                db_type,
+                                                &db.name,
+                                                view.schema.as_deref(),
+                                                &view.name,
+                                            );
+                                            open_query = Some((db.name.clone(), view_name, query));
                                         }
                                     });
                                 }
                             }
+
+                            // Procedures, functions, triggers and events
+                            for routine in session.routines.get(&db.name).into_iter().flatten() {
+                                ui.horizontal(|ui| {
+                                    ui.label(format!("  {}", routine.kind.icon()));
+                                    let kind = routine.kind.keyword().to_lowercase();
+                                    let hover = match &routine.table {
+                                        Some(table) => format!("{} on {}", kind, table),
+                                        None => kind,
+                                    };
+                                    let link = ui.link(routine.display_name()).on_hover_text(hover);
+                                    if link.clicked() {
+                                        let object = routine.clone();
+                                        ddl = Some((db.name.clone(), object, DdlAction::OpenTab));
+                                    }
+                                    link.context_menu(|ui| {
+                                        ddl_menu(ui, &db.name, routine.clone(), &mut ddl);
+                                    });
+                                });
+                            }
                         });
                     }
                 }
             });
-            
-            // Apply actions after rendering
-            if let Some(db_name) = expand_db {
-                self.expanded_databases.insert(db_name.clone());
-                self.selected_database = Some(db_name.clone());
-                if !self.tables.contains_key(&db_name) {
-                    self.load_tables_and_views(&db_name);
-                }
-            }
-            if let Some(db_name) = collapse_db {
-                self.expanded_databases.remove(&db_name);
-            }
-            if let Some((db, table)) = select_table {
-                self.selected_table = Some((db, table));
-            }
-            if let Some(query) = set_query {
-                self.query_content = query;
+
+        // Apply actions after rendering
+        let runtime = self.runtime.handle().clone();
+        if disconnect {
+            self.close_session(id);
+            return;
+        }
+        let Some(session) = self.sessions.get_mut(id) else {
+            return;
+        };
+        if refresh {
+            session.refresh(&runtime);
+        }
+        if reconnect {
+            session.reconnect(&runtime);
+        }
+        if let Some(db_name) = expand_db {
+            session.expand(&runtime, &db_name);
+        }
+        if let Some(db_name) = collapse_db {
+            session.expanded_databases.remove(&db_name);
+        }
+
+        // Objects open in a tab of their own, leaving the current one alone
+        if let Some((db, table)) = select_table {
+            self.selected_table = Some((id, db, table));
+        }
+        if let Some((db, name, query)) = open_query {
+            self.open_object_tab(id, db, name, query);
+        }
+        if let Some((db, table, query)) = open_table {
+            self.open_table_tab(id, db, table, query);
+        }
+        if let Some((db, table, query)) = load_table_data {
+            self.open_table_tab(id, db.clone(), table.clone(), query);
+            self.tabs[self.active_tab].view = TableView::Results;
+            if !self.tabs[self.active_tab].query_executing {
+                let schema = table.schema.as_deref();
+                self.with_run_context(|tab, cx| tab.load_table_data(cx, &db, schema, &table.name));
             }
-            if let Some((db, table)) = load_table_data {
-                self.query_content = format!("SELECT * FROM `{}`.`{}` LIMIT 100", db, table);
-                self.execute_query();
+        }
+        if let Some((db, table, query)) = show_structure {
+            self.open_table_tab(id, db, table, query);
+            self.tabs[self.active_tab].view = TableView::Structure;
+        }
+        if let Some(db) = new_table {
+            self.open_new_table_tab(id, db);
+        }
+        if dismiss_notice {
+            self.ddl_notice = None;
+        }
+        if let Some((db, object, action)) = ddl {
+            self.request_ddl(id, db, object, action);
+        }
+    }
+
+    /// A tab with the designer laid out for a new table in `database`
+    fn open_new_table_tab(&mut self, session: Uuid, database: String) {
+        let Some(session_state) = self.sessions.get(session) else {
+            return;
+        };
+        let Some(dialect) = Dialect::for_database(session_state.config.db_type) else {
+            return;
+        };
+        let qualifier = match dialect {
+            // PostgreSQL lists databases, not schemas, in the tree
+            Dialect::Postgres => "public".to_string(),
+            Dialect::MySql | Dialect::Sqlite => database.clone(),
+        };
+        let tab = self.open_tab(Some(session));
+        tab.name = "New table".to_string();
+        tab.database = Some(database);
+        tab.new_table(dialect, Some(qualifier));
+    }
+
+    /// `open_object_tab` for a table, which the tab can show the structure of
+    fn open_table_tab(
+        &mut self,
+        session: Uuid,
+        database: String,
+        table: SchemaObject,
+        query: String,
+    ) {
+        self.open_object_tab(session, database, table.qualified_name(), query);
+        let tab = &mut self.tabs[self.active_tab];
+        tab.schema = table.schema;
+        tab.table = Some(table.name);
+    }
+
+    /// Switch to the tab showing a table or view, opening one with `query`
+    /// if there is none
+    fn open_object_tab(&mut self, session: Uuid, database: String, name: String, query: String) {
+        let open = self.tabs.iter().position(|tab| {
+            tab.connection == Some(session)
+                && tab.database.as_deref() == Some(database.as_str())
+                && tab.name == name
+        });
+        match open {
+            Some(index) => self.active_tab = index,
+            None => {
+                let tab = self.open_tab(Some(session));
+                tab.name = name;
+                tab.database = Some(database);
+                tab.query_content = query;
             }
         }
     }
@@ -600,6 +1378,47 @@ impl NebulaApp {
                 ui.text_edit_singleline(&mut self.form_config.name);
                 ui.end_row();
 
+                ui.label("Type:");
+                let previous_type = self.form_config.db_type;
+                egui::ComboBox::from_id_salt("connection_type")
+                    .selected_text(format!(
+                        "{} {}",
+                        self.form_config.db_type.icon(),
+                        self.form_config.db_type
+                    ))
+                    .show_ui(ui, |ui| {
+                        for db_type in [
+                            DatabaseType::MySQL,
+                            DatabaseType::PostgreSQL,
+                            DatabaseType::SQLite,
+                            DatabaseType::MongoDB,
+                        ] {
+                            ui.selectable_value(
+                                &mut self.form_config.db_type,
+                                db_type,
+                                format!("{} {}", db_type.icon(), db_type),
+                            );
+                        }
+                    });
+                if self.form_config.db_type != previous_type {
+                    self.form_config.port = self.form_config.db_type.default_port();
+                }
+                ui.end_row();
+
+                if self.form_config.db_type == DatabaseType::SQLite {
+                    ui.label("File:");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.form_config.database)
+                            .hint_text("/path/to/database.db or :memory:"),
+                    );
+                    ui.end_row();
+
+                    ui.label("");
+                    ui.checkbox(&mut self.form_config.read_only, "Read only");
+                    ui.end_row();
+                    return;
+                }
+
                 ui.label("Host:");
                 ui.text_edit_singleline(&mut self.form_config.host);
                 ui.end_row();
@@ -626,6 +1445,42 @@ impl NebulaApp {
                 ui.end_row();
             });
 
+        ui.add_space(10.0);
+        ui.horizontal(|ui| {
+            ui.label("Color:");
+            let mut color = connection_color(&self.form_config).unwrap_or(theme::PRIMARY);
+            if ui.color_edit_button_srgba(&mut color).changed() {
+                self.form_config.color = Some(theme::to_hex(color));
+            }
+            if self.form_config.color.is_some()
+                && ui.small_button("✕").on_hover_text("No color").clicked()
+            {
+                self.form_config.color = None;
+            }
+        });
+
+        ui.add_space(10.0);
+        ui.collapsing("Query limits", |ui| {
+            egui::Grid::new("connection_limits")
+                .num_columns(2)
+                .spacing([20.0, 10.0])
+                .show(ui, |ui| {
+                    let limits = &mut self.form_config.limits;
+
+                    ui.label("Statement timeout (s):");
+                    optional_number_edit(ui, &mut limits.statement_timeout_secs);
+                    ui.end_row();
+
+                    ui.label("Max rows:");
+                    optional_number_edit(ui, &mut limits.max_rows);
+                    ui.end_row();
+
+                    ui.label("Max result bytes:");
+                    optional_number_edit(ui, &mut limits.max_result_bytes);
+                    ui.end_row();
+                });
+        });
+
         ui.add_space(20.0);
 
         ui.horizontal(|ui| {
@@ -661,59 +1516,274 @@ impl NebulaApp {
     }
 
     fn render_main_content(&mut self, ui: &mut egui::Ui) {
+        ui.add_space(5.0);
+        self.render_tab_bar(ui);
+        ui.separator();
+
         // Query editor at top
-        ui.add_space(10.0);
+        let tab = &self.tabs[self.active_tab];
+        let session = self.tab_session(tab);
+        let dialect = session.and_then(|s| Dialect::for_database(s.config.db_type));
+        let color = session.and_then(|s| connection_color(&s.config));
+        let connected = session.is_some_and(|s| s.connected().is_some());
+        let executing = tab.query_executing;
+        // A restored tab whose connection is not open yet
+        let closed = match session {
+            Some(_) => None,
+            None => tab.connection.and_then(|id| self.connections.iter().find(|c| c.id == id)),
+        }
+        .cloned();
+        let (mut execute, mut cancel, mut connect) = (false, false, false);
         ui.horizontal(|ui| {
-            ui.label("Query:");
-            if self.query_executing {
+            ui.label("Query on");
+            self.render_connection_picker(ui);
+            self.render_database_picker(ui);
+            if closed.is_some() {
+                connect = ui.button("Connect").clicked();
+            }
+            if executing {
                 ui.spinner();
+                cancel = ui.button("■ Cancel").clicked();
             } else {
-                if ui.button("▶ Execute").clicked() {
-                    self.execute_query();
-                }
+                execute = ui
+                    .add_enabled(connected, egui::Button::new("▶ Execute"))
+                    .on_hover_text("Run everything. Ctrl+Enter runs the selection or the statement at the cursor.")
+                    .clicked();
             }
             if ui.button("Clear").clicked() {
-                self.query_content.clear();
+                self.tabs[self.active_tab].query_content.clear();
+            }
+            if ui
+                .checkbox(&mut self.app_config.nebula.stop_on_error, "Stop on error")
+                .on_hover_text("Stop a multi-statement script at the first failing statement")
+                .changed()
+            {
+                let _ = self.app_config.save();
             }
+            if ui.button("💾 Save").on_hover_text("Save to the query library").clicked() {
+                self.open_save_form();
+            }
+            ui.toggle_value(&mut self.show_history, "🕘 History");
         });
+        if execute {
+            self.with_run_context(|tab, cx| tab.execute_query(None, cx));
+        }
+        if cancel {
+            self.tabs[self.active_tab].cancel(self.runtime.handle());
+        }
+        if let Some(config) = closed.filter(|_| connect) {
+            self.open_session(config);
+        }
+
+        let tab = &mut self.tabs[self.active_tab];
+        if let Some(file) = tab.file.as_ref().filter(|file| file.changed_on_disk) {
+            let message = if tab.is_dirty() {
+                format!("⚠ {} changed on disk. Reloading discards your edits.", file.name())
+            } else {
+                format!("⚠ {} changed on disk.", file.name())
+            };
+            ui.horizontal(|ui| {
+                ui.label(egui::RichText::new(message).color(theme::WARNING));
+                if ui.button("Reload").clicked() {
+                    if let Err(e) = tab.reload_file() {
+                        tab.result_error = Some(format!("Failed to reload: {}", e));
+                    }
+                }
+                if ui.button("Keep mine").clicked() {
+                    tab.ignore_file_change();
+                }
+            });
+        }
         
         ui.add_space(5.0);
         
+        let fetch_size = self.app_config.nebula.fetch_size;
+        let tab = &mut self.tabs[self.active_tab];
+        // Taken before the editor sees it, which would insert a newline
+        let run_current = connected
+            && !tab.query_executing
+            && tab.bind_prompt.is_none()
+            && ui.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::Enter));
+
         let editor_height = 150.0;
-        egui::ScrollArea::vertical()
+        let editor = egui::ScrollArea::vertical()
+            .id_salt(("query_editor", tab.id))
             .max_height(editor_height)
             .show(ui, |ui| {
-                ui.add(
-                    egui::TextEdit::multiline(&mut self.query_content)
-                        .font(egui::TextStyle::Monospace)
-                        .desired_width(f32::INFINITY)
-                        .desired_rows(8)
-                );
+                // Dim whatever the last partial run left out
+                let executed_ranges = tab.executed_ranges.clone();
+                let mut layouter = |ui: &egui::Ui, text: &dyn egui::TextBuffer, wrap_width: f32| {
+                    let text = text.as_str();
+                    let font_id = egui::TextStyle::Monospace.resolve(ui.style());
+                    let mut job = egui::text::LayoutJob::default();
+                    let append = |job: &mut egui::text::LayoutJob, range: Range<usize>, color| {
+                        if !range.is_empty() {
+                            let format = egui::TextFormat::simple(font_id.clone(), color);
+                            job.append(&text[range], 0.0, format);
+                        }
+                    };
+                    let mut pos = 0;
+                    for range in &executed_ranges {
+                        let range = range.start.min(text.len())..range.end.min(text.len());
+                        append(&mut job, pos..range.start.max(pos), theme::TEXT_MUTED);
+                        append(&mut job, range.start.max(pos)..range.end, ui.visuals().text_color());
+                        pos = pos.max(range.end);
+                    }
+                    let rest = if executed_ranges.is_empty() {
+                        ui.visuals().text_color()
+                    } else {
+                        theme::TEXT_MUTED
+                    };
+                    append(&mut job, pos..text.len(), rest);
+                    job.wrap.max_width = wrap_width;
+                    ui.fonts_mut(|f| f.layout_job(job))
+                };
+
+                let output = egui::TextEdit::multiline(&mut tab.query_content)
+                    .font(egui::TextStyle::Monospace)
+                    .desired_width(f32::INFINITY)
+                    .desired_rows(8)
+                    .layouter(&mut layouter)
+                    .show(ui);
+                if output.response.changed() {
+                    tab.executed_ranges.clear();
+                }
+                if let Some(range) = output.cursor_range {
+                    tab.editor_cursor = Some((range.secondary.index, range.primary.index));
+                }
             });
+        // Tint the editor with its connection's color
+        if let Some(color) = color {
+            ui.painter().rect_stroke(
+                editor.inner_rect.expand(1.0),
+                2.0,
+                egui::Stroke::new(1.5, color),
+                egui::StrokeKind::Outside,
+            );
+        }
+
+        if run_current {
+            self.with_run_context(|tab, cx| tab.execute_current(cx));
+        }
 
         ui.separator();
 
+        let tab = &mut self.tabs[self.active_tab];
+        if tab.table.is_some() || tab.design.is_some() {
+            let mut reload = false;
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut tab.view, TableView::Results, "▦ Results");
+                if tab.table.is_some() {
+                    ui.selectable_value(&mut tab.view, TableView::Structure, "🏗 Structure");
+                }
+                if dialect.is_some() {
+                    ui.selectable_value(&mut tab.view, TableView::Design, "✏ Design");
+                }
+                if tab.is_loading_structure() {
+                    ui.spinner();
+                } else if tab.view == TableView::Structure {
+                    reload = ui.small_button("↻").on_hover_text("Reload").clicked();
+                }
+            });
+            // Read the structure when first needed, and again once a design
+            // changed it
+            let tab = &self.tabs[self.active_tab];
+            let needed = tab.view != TableView::Results && tab.design.is_none();
+            let missing = tab.table.is_some() && tab.structure.is_none();
+            if reload || (needed && missing && !tab.is_loading_structure()) {
+                self.with_run_context(|tab, cx| tab.load_structure(cx));
+            }
+            ui.add_space(5.0);
+
+            let tab = &mut self.tabs[self.active_tab];
+            if let (TableView::Design, Some(dialect)) = (tab.view, dialect) {
+                tab.start_design(dialect);
+            }
+            if tab.view != TableView::Results {
+                let loading = tab.is_loading_structure();
+                match (&tab.structure, &mut tab.design) {
+                    (_, Some(design)) if tab.view == TableView::Design => {
+                        let (mut review, mut discard) = (false, false);
+                        ui.horizontal(|ui| {
+                            review = ui.button("Review SQL").clicked();
+                            discard = ui.button("Discard").on_hover_text("Start over").clicked();
+                        });
+                        render_table_design(ui, tab.id, design);
+                        if discard {
+                            tab.discard_design();
+                        }
+                        if review {
+                            tab.design_error = None;
+                            tab.reviewing_design = true;
+                        }
+                    }
+                    (Some(Ok(structure)), _) => render_table_structure(ui, tab.id, structure),
+                    (Some(Err(error)), _) => {
+                        let error = format!("Error: {}", error);
+                        ui.label(egui::RichText::new(error).color(theme::DANGER));
+                    }
+                    (None, _) if loading => {}
+                    (None, _) => {
+                        let hint = "Connect to read the structure";
+                        ui.label(egui::RichText::new(hint).color(theme::TEXT_MUTED));
+                    }
+                }
+                return;
+            }
+        }
+
+        let tab = &mut self.tabs[self.active_tab];
         // Results table
-        if let Some(error) = &self.result_error {
+        if !tab.statement_tabs.is_empty() {
+            render_statement_tabs(ui, tab);
+        } else if let Some(error) = &tab.result_error {
             ui.label(egui::RichText::new(format!("Error: {}", error)).color(theme::DANGER));
-        } else if let Some(result) = &self.query_result {
+        } else if let Some(result) = &tab.query_result {
+            let mut fetch_more = false;
             ui.horizontal(|ui| {
-                ui.label(format!(
-                    "{} rows × {} columns | {} ms",
-                    result.rows.len(),
-                    result.columns.len(),
-                    result.execution_time_ms
-                ));
-                if let Some(affected) = result.affected_rows {
-                    ui.label(format!("| {} rows affected", affected));
+                render_result_summary(ui, result);
+                if tab.query_cancelled {
+                    ui.label(egui::RichText::new("| cancelled").color(theme::WARNING));
+                }
+                if let Some(stream) = &tab.query_stream {
+                    if stream.paused {
+                        ui.label(egui::RichText::new("| more rows available").color(theme::WARNING));
+                        fetch_more = ui.button("Fetch more").clicked();
+                    } else {
+                        ui.spinner();
+                        ui.label(egui::RichText::new("Streaming…").color(theme::TEXT_MUTED));
+                    }
                 }
             });
+            if fetch_more {
+                tab.fetch_more(fetch_size);
+            }
+            render_edit_bar(ui, tab);
             
             ui.add_space(5.0);
             
-            if !result.columns.is_empty() {
-                self.render_results_table(ui, result);
+            if let Some(result) = &tab.query_result {
+                if !result.columns.is_empty() {
+                    let edit = match &tab.editable {
+                        Some(Ok(target)) if !tab.is_applying() => Some(GridEdit {
+                            target,
+                            changes: &mut tab.changes,
+                            editing: &mut tab.editing_cell,
+                        }),
+                        _ => None,
+                    };
+                    if let Some(row) = render_results_table(ui, result, edit) {
+                        tab.viewing_document = Some(row);
+                    }
+                }
             }
+        } else if tab.query_stream.is_some() {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label(egui::RichText::new("Waiting for rows…").color(theme::TEXT_MUTED));
+            });
+        } else if tab.query_cancelled {
+            ui.label(egui::RichText::new("Query cancelled").color(theme::WARNING));
         } else {
             ui.centered_and_justified(|ui| {
                 ui.label(egui::RichText::new("Execute a query to see results").color(theme::TEXT_MUTED));
@@ -721,37 +1791,1654 @@ impl NebulaApp {
         }
     }
 
-    fn render_results_table(&self, ui: &mut egui::Ui, result: &QueryResult) {
-        use egui_extras::{Column, TableBuilder};
+    /// One entry per open tab, and a button for a new one. Tabs are
+    /// renamed by double-clicking and reordered by dragging.
+    fn render_tab_bar(&mut self, ui: &mut egui::Ui) {
+        let mut activate = None;
+        let mut close = None;
+        let mut rename = None;
+        let mut renamed = false;
+        let mut moved = None;
+        let mut dropped = None;
+        let mut rects = Vec::new();
 
-        let available_height = ui.available_height();
-        
-        TableBuilder::new(ui)
-            .striped(true)
-            .resizable(true)
-            .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
-            .columns(Column::auto().at_least(80.0).resizable(true), result.columns.len())
-            .min_scrolled_height(0.0)
-            .max_scroll_height(available_height)
-            .header(25.0, |mut header| {
-                for col in &result.columns {
-                    header.col(|ui| {
-                        ui.strong(&col.name);
-                    });
+        ui.horizontal_wrapped(|ui| {
+            let count = self.tabs.len();
+            for (index, tab) in self.tabs.iter_mut().enumerate() {
+                let session = tab.connection.and_then(|id| self.sessions.get(id));
+                if self.renaming_tab == Some(tab.id) {
+                    let response = ui.add(
+                        egui::TextEdit::singleline(&mut tab.name)
+                            .id(egui::Id::new(("tab_name", tab.id)))
+                            .desired_width(120.0),
+                    );
+                    if response.lost_focus() {
+                        renamed = true;
+                    } else if !response.has_focus() {
+                        response.request_focus();
+                    }
+                    rects.push(response.rect);
+                    continue;
                 }
-            })
-            .body(|body| {
-                body.rows(22.0, result.rows.len(), |mut row| {
-                    let row_idx = row.index();
-                    if let Some(data_row) = result.rows.get(row_idx) {
-                        for cell in data_row {
-                            row.col(|ui| {
-                                let text = cell.display_string();
-                                ui.label(&text);
+
+                let mut label = tab.name.clone();
+                if tab.is_dirty() {
+                    label.push_str(" ●");
+                }
+                if tab.query_executing {
+                    label = format!("⟳ {}", label);
+                }
+                let mut text = egui::RichText::new(label);
+                if let Some(color) = session.and_then(|s| connection_color(&s.config)) {
+                    text = text.color(color);
+                }
+                let mut hover = match (session, &tab.database) {
+                    (Some(session), Some(database)) => {
+                        format!("{} / {}", session.config.name, database)
+                    }
+                    (Some(session), None) => session.config.name.clone(),
+                    (None, _) => "No connection".to_string(),
+                };
+                if let Some(file) = &tab.file {
+                    hover = format!("{}\n{}", file.path.display(), hover);
+                }
+                let response = ui
+                    .selectable_label(index == self.active_tab, text)
+                    .interact(egui::Sense::drag())
+                    .on_hover_text(hover);
+                if response.clicked() {
+                    activate = Some(index);
+                }
+                if response.double_clicked() {
+                    rename = Some(tab.id);
+                }
+                if response.drag_stopped() {
+                    dropped = ui.ctx().pointer_interact_pos().map(|pos| (index, pos));
+                }
+                response.context_menu(|ui| {
+                    if ui.button("Rename").clicked() {
+                        rename = Some(tab.id);
+                        ui.close();
+                    }
+                    if ui.add_enabled(index > 0, egui::Button::new("Move left")).clicked() {
+                        moved = Some((index, index - 1));
+                        ui.close();
+                    }
+                    let last = index + 1 == count;
+                    if ui.add_enabled(!last, egui::Button::new("Move right")).clicked() {
+                        moved = Some((index, index + 1));
+                        ui.close();
+                    }
+                    if ui.button("Close").clicked() {
+                        close = Some(index);
+                        ui.close();
+                    }
+                });
+                rects.push(response.rect);
+
+                if ui.small_button("✕").on_hover_text("Close tab").clicked() {
+                    close = Some(index);
+                }
+            }
+            if ui.small_button("+").on_hover_text("New tab").clicked() {
+                let connection = self.tabs[self.active_tab].connection;
+                self.open_tab(connection);
+            }
+        });
+
+        // Apply actions after rendering
+        if renamed {
+            if let Some(tab) = self.tabs.iter_mut().find(|t| Some(t.id) == self.renaming_tab) {
+                if tab.name.trim().is_empty() {
+                    tab.name = format!("Query {}", tab.id);
+                }
+            }
+            self.renaming_tab = None;
+        }
+        if rename.is_some() {
+            self.renaming_tab = rename;
+        }
+        if let Some((from, pos)) = dropped {
+            if let Some(to) = rects.iter().position(|rect| rect.contains(pos)) {
+                moved = Some((from, to));
+            }
+        }
+        if let Some((from, to)) = moved {
+            self.move_tab(from, to);
+        }
+        if let Some(index) = activate {
+            self.active_tab = index;
+        }
+        if let Some(index) = close {
+            self.close_tab(index);
+        }
+    }
+
+    /// Choose which open connection the editor runs on
+    fn render_connection_picker(&mut self, ui: &mut egui::Ui) {
+        let label = |config: &ConnectionConfig| {
+            let text = egui::RichText::new(format!("{} {}", config.db_type.icon(), config.name));
+            match connection_color(config) {
+                Some(color) => text.color(color),
+                None => text,
+            }
+        };
+        let tab = &self.tabs[self.active_tab];
+        let config = match self.tab_session(tab) {
+            Some(session) => Some(&session.config),
+            None => tab.connection.and_then(|id| self.connections.iter().find(|c| c.id == id)),
+        };
+        let selected = match config {
+            Some(config) => label(config),
+            None => egui::RichText::new("No connection").color(theme::TEXT_MUTED),
+        };
+
+        let mut connection = tab.connection;
+        ui.add_enabled_ui(!tab.query_executing, |ui| {
+            egui::ComboBox::from_id_salt("editor_connection")
+                .selected_text(selected)
+                .show_ui(ui, |ui| {
+                    for session in self.sessions.iter() {
+                        if session.state == ConnectionState::Connected {
+                            let id = Some(session.id());
+                            ui.selectable_value(&mut connection, id, label(&session.config));
+                        }
+                    }
+                });
+        });
+        let tab = &mut self.tabs[self.active_tab];
+        if connection != tab.connection {
+            tab.connection = connection;
+            tab.database = None;
+            tab.executed_ranges.clear();
+        }
+    }
+
+    /// Choose which of the connection's databases the editor runs in
+    fn render_database_picker(&mut self, ui: &mut egui::Ui) {
+        let tab = &self.tabs[self.active_tab];
+        let Some(session) = self.tab_session(tab) else {
+            return;
+        };
+        if session.databases.is_empty() {
+            return;
+        }
+        let selected = match &tab.database {
+            Some(database) => egui::RichText::new(database),
+            None => egui::RichText::new("Default database").color(theme::TEXT_MUTED),
+        };
+
+        let mut database = tab.database.clone();
+        ui.add_enabled_ui(!tab.query_executing, |ui| {
+            egui::ComboBox::from_id_salt("editor_database")
+                .selected_text(selected)
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut database, None, "Default database");
+                    for info in &session.databases {
+                        ui.selectable_value(&mut database, Some(info.name.clone()), &info.name);
+                    }
+                });
+        });
+        let tab = &mut self.tabs[self.active_tab];
+        if database != tab.database {
+            tab.database = database;
+            tab.executed_ranges.clear();
+        }
+    }
+
+    /// The first file tab with unsaved edits
+    fn unsaved_tab(&self) -> Option<u64> {
+        self.tabs.iter().find(|tab| tab.file.is_some() && tab.is_dirty()).map(|tab| tab.id)
+    }
+
+    /// Ask whether to save a file tab's edits before it closes
+    fn render_unsaved_prompt(&mut self, ctx: &egui::Context) {
+        let Some(prompt) = &self.unsaved_prompt else {
+            return;
+        };
+        let Some(index) = self.tabs.iter().position(|tab| tab.id == prompt.tab) else {
+            self.unsaved_prompt = None;
+            return;
+        };
+        let window = prompt.window;
+        let tab = &self.tabs[index];
+
+        let (mut save, mut discard, mut cancel) = (false, false, false);
+        let modal = egui::Modal::new(egui::Id::new("unsaved_changes")).show(ctx, |ui| {
+            ui.heading("Unsaved changes");
+            ui.add_space(5.0);
+            ui.label(format!("Save the changes to {} before closing?", tab.name));
+            ui.add_space(5.0);
+            ui.horizontal(|ui| {
+                save = ui.button("💾 Save").clicked();
+                discard = ui.button("Discard").clicked();
+                cancel = ui.button("Cancel").clicked();
+            });
+        });
+
+        if save {
+            if let Some(file) = &self.tabs[index].file {
+                self.save_file(index, file.path.clone());
+            }
+            // A failed save leaves its error on the tab, which stays open
+            if self.tabs[index].is_dirty() {
+                self.unsaved_prompt = None;
+                return;
+            }
+        } else if discard {
+            if window {
+                // Otherwise the workspace would bring the edits back
+                let tab = &mut self.tabs[index];
+                tab.query_content = tab.saved_content.clone();
+            }
+        } else {
+            if cancel || modal.should_close() {
+                self.unsaved_prompt = None;
+            }
+            return;
+        }
+
+        self.unsaved_prompt = None;
+        if !window {
+            self.remove_tab(index);
+        } else if let Some(tab) = self.unsaved_tab() {
+            self.unsaved_prompt = Some(UnsavedPrompt { tab, window: true });
+        } else {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+        }
+    }
+
+    fn render_bind_prompt(&mut self, ctx: &egui::Context) {
+        let Some(prompt) = &mut self.tabs[self.active_tab].bind_prompt else {
+            return;
+        };
+
+        let mut run = false;
+        let mut cancel = false;
+        let modal = egui::Modal::new(egui::Id::new("bind_prompt")).show(ctx, |ui| {
+            ui.heading("Query parameters");
+            ui.add_space(5.0);
+
+            egui::Grid::new("bind_params")
+                .num_columns(3)
+                .spacing([10.0, 6.0])
+                .show(ui, |ui| {
+                    for (i, input) in prompt.inputs.iter_mut().enumerate() {
+                        ui.label(egui::RichText::new(&input.name).monospace());
+                        egui::ComboBox::from_id_salt(("param_kind", i))
+                            .selected_text(input.kind.display_name())
+                            .show_ui(ui, |ui| {
+                                for kind in ParamKind::ALL {
+                                    ui.selectable_value(&mut input.kind, kind, kind.display_name());
+                                }
                             });
+                        let hint = match input.kind {
+                            ParamKind::Date => "YYYY-MM-DD",
+                            _ => "",
+                        };
+                        let response = ui.add_enabled(
+                            input.kind != ParamKind::Null,
+                            egui::TextEdit::singleline(&mut input.text).hint_text(hint),
+                        );
+                        if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                            run = true;
+                        }
+                        ui.end_row();
+                    }
+                });
+
+            if let Some(error) = &prompt.error {
+                ui.label(egui::RichText::new(error).color(theme::DANGER));
+            }
+
+            ui.add_space(5.0);
+            ui.horizontal(|ui| {
+                if ui.button("▶ Run").clicked() {
+                    run = true;
+                }
+                if ui.button("Cancel").clicked() {
+                    cancel = true;
+                }
+            });
+        });
+
+        if run {
+            self.with_run_context(|tab, cx| tab.submit_bind_prompt(cx));
+        } else if cancel || modal.should_close() {
+            self.tabs[self.active_tab].bind_prompt = None;
+        }
+    }
+
+    fn render_document_window(&mut self, ctx: &egui::Context) {
+        let tab = &mut self.tabs[self.active_tab];
+        let Some(row) = tab.viewing_document else {
+            return;
+        };
+        let Some(document) = tab
+            .query_result
+            .as_ref()
+            .and_then(|result| result.documents.get(row))
+        else {
+            tab.viewing_document = None;
+            return;
+        };
+
+        let mut open = true;
+        egui::Window::new(format!("Document {}", row + 1))
+            .open(&mut open)
+            .default_size([500.0, 400.0])
+            .show(ctx, |ui| {
+                if ui.button("Copy").clicked() {
+                    ctx.copy_text(document.clone());
+                }
+                egui::ScrollArea::both().show(ui, |ui| {
+                    ui.add(
+                        egui::TextEdit::multiline(&mut document.as_str())
+                            .font(egui::TextStyle::Monospace)
+                            .desired_width(f32::INFINITY),
+                    );
+                });
+            });
+
+        if !open {
+            tab.viewing_document = None;
+        }
+    }
+
+    /// Past statements, newest first, filtered by text, connection and outcome
+    fn render_history(&mut self, ctx: &egui::Context) {
+        // Enough to scroll through without laying out the whole history
+        const SHOWN: usize = 500;
+        let open_keys: Vec<String> = self
+            .sessions
+            .iter()
+            .filter(|s| s.connected().is_some())
+            .map(|s| AppConfig::connection_key(&s.config))
+            .collect();
+        let mut open = None;
+
+        egui::SidePanel::right("history")
+            .resizable(true)
+            .default_width(320.0)
+            .min_width(200.0)
+            .show(ctx, |ui| {
+                ui.add_space(10.0);
+                ui.horizontal(|ui| {
+                    ui.heading("History");
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui.small_button("✕").clicked() {
+                            self.show_history = false;
+                        }
+                    });
+                });
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.history_filter.text)
+                        .hint_text("Search SQL")
+                        .desired_width(f32::INFINITY),
+                );
+                ui.horizontal(|ui| {
+                    let filter = &mut self.history_filter;
+                    egui::ComboBox::from_id_salt("history_connection")
+                        .selected_text(filter.connection.as_deref().unwrap_or("All connections"))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut filter.connection, None, "All connections");
+                            for key in self.history.connections() {
+                                let label = key.clone();
+                                ui.selectable_value(&mut filter.connection, Some(key), label);
+                            }
+                        });
+                    egui::ComboBox::from_id_salt("history_status")
+                        .selected_text(filter.status.display_name())
+                        .show_ui(ui, |ui| {
+                            for status in StatusFilter::ALL {
+                                ui.selectable_value(
+                                    &mut filter.status,
+                                    status,
+                                    status.display_name(),
+                                );
+                            }
+                        });
+                    if ui.button("Clear").on_hover_text("Forget every entry").clicked() {
+                        if let Err(e) = self.history.clear() {
+                            tracing::warn!("Failed to clear query history: {}", e);
+                        }
+                    }
+                });
+                ui.separator();
+
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    let mut shown = 0;
+                    for entry in self.history.search(&self.history_filter).take(SHOWN) {
+                        shown += 1;
+                        ui.horizontal(|ui| {
+                            let time = entry.executed_at.with_timezone(&chrono::Local);
+                            let time = time.format("%Y-%m-%d %H:%M:%S").to_string();
+                            ui.label(egui::RichText::new(time).color(theme::TEXT_MUTED).small());
+                            let connection = entry.connection.as_deref().unwrap_or("-");
+                            let mut location = connection.to_string();
+                            if let Some(database) = &entry.database {
+                                location = format!("{} / {}", location, database);
+                            }
+                            ui.label(
+                                egui::RichText::new(location).color(theme::TEXT_MUTED).small(),
+                            );
+                        });
+
+                        let mut preview: String = entry
+                            .sql
+                            .split_whitespace()
+                            .collect::<Vec<_>>()
+                            .join(" ");
+                        if preview.chars().count() > 80 {
+                            preview = preview.chars().take(80).collect::<String>() + "…";
                         }
+                        ui.label(egui::RichText::new(preview).monospace())
+                            .on_hover_text(egui::RichText::new(&entry.sql).monospace());
+
+                        ui.horizontal(|ui| {
+                            let outcome = match (&entry.error, entry.rows, entry.affected_rows) {
+                                (Some(e), _, _) => {
+                                    egui::RichText::new(format!("✕ {}", e)).color(theme::DANGER)
+                                }
+                                (None, _, Some(affected)) => egui::RichText::new(format!(
+                                    "✓ {} rows affected | {} ms",
+                                    affected, entry.duration_ms
+                                )),
+                                (None, Some(rows), None) => egui::RichText::new(format!(
+                                    "✓ {} rows | {} ms",
+                                    rows, entry.duration_ms
+                                )),
+                                (None, None, None) => {
+                                    egui::RichText::new(format!("✓ {} ms", entry.duration_ms))
+                                }
+                            };
+                            ui.label(outcome.small());
+                            ui.with_layout(
+                                egui::Layout::right_to_left(egui::Align::Center),
+                                |ui| {
+                                    let connected = entry
+                                        .connection
+                                        .as_ref()
+                                        .is_some_and(|key| open_keys.contains(key));
+                                    if ui
+                                        .add_enabled(connected, egui::Button::new("▶").small())
+                                        .on_hover_text("Run again in a new tab")
+                                        .clicked()
+                                    {
+                                        open = Some((entry.clone(), true));
+                                    }
+                                    if ui
+                                        .small_button("Open")
+                                        .on_hover_text("Open in an editor tab")
+                                        .clicked()
+                                    {
+                                        open = Some((entry.clone(), false));
+                                    }
+                                },
+                            );
+                        });
+                        ui.separator();
+                    }
+                    if shown == 0 {
+                        ui.label(egui::RichText::new("No queries").color(theme::TEXT_MUTED));
                     }
                 });
             });
+
+        if let Some((entry, run)) = open {
+            self.open_history_entry(entry, run);
+        }
+    }
+
+    /// Put a statement from the history back in an editor: the active tab if
+    /// it is empty, else a new tab, on the connection it last ran on
+    fn open_history_entry(&mut self, entry: HistoryEntry, run: bool) {
+        let connection = entry
+            .connection
+            .as_deref()
+            .and_then(|key| self.find_connection(key))
+            .map(|config| config.id);
+        let tab = self.editor_tab(connection, false);
+        tab.database = entry.database;
+        tab.query_content = entry.sql;
+        tab.executed_ranges.clear();
+        if run {
+            self.with_run_context(|tab, cx| tab.execute_query(None, cx));
+        }
+    }
+
+    /// Open a saved query in an editor, on its connection if it has one.
+    /// A tab that already has it open is brought forward instead.
+    fn open_saved_query(&mut self, id: Uuid, new_tab: bool) {
+        let Some(query) = self.library.get(id).cloned() else {
+            return;
+        };
+        if !new_tab {
+            if let Some(index) = self.tabs.iter().position(|tab| tab.saved_query == Some(id)) {
+                self.active_tab = index;
+                return;
+            }
+        }
+        let connection = query
+            .connection
+            .as_deref()
+            .and_then(|key| self.find_connection(key))
+            .map(|config| config.id);
+        let tab = self.editor_tab(connection, new_tab);
+        tab.name = query.name;
+        tab.saved_query = Some(id);
+        tab.query_content = query.sql;
+        tab.mark_saved();
+        tab.executed_ranges.clear();
+    }
+
+    /// Start saving the active tab, as an update if it came from the library
+    fn open_save_form(&mut self) {
+        let tab = &self.tabs[self.active_tab];
+        let connection = tab.connection.and_then(|id| self.connection_key(id));
+        self.save_form = Some(match tab.saved_query.and_then(|id| self.library.get(id)) {
+            Some(query) => SaveQueryForm {
+                id: Some(query.id),
+                name: query.name.clone(),
+                folder: query.folder.clone(),
+                tags: query.tags.join(", "),
+                scoped: query.connection.is_some(),
+                connection: query.connection.clone().or(connection),
+                error: None,
+            },
+            None => SaveQueryForm {
+                id: None,
+                name: tab.name.clone(),
+                folder: String::new(),
+                tags: String::new(),
+                connection,
+                scoped: false,
+                error: None,
+            },
+        });
+    }
+
+    fn render_save_form(&mut self, ctx: &egui::Context) {
+        let Some(form) = &mut self.save_form else {
+            return;
+        };
+
+        let folders = self.library.folders();
+        let (mut save, mut save_as_new, mut cancel) = (false, false, false);
+        let modal = egui::Modal::new(egui::Id::new("save_query")).show(ctx, |ui| {
+            ui.heading(if form.id.is_some() { "Update saved query" } else { "Save query" });
+            ui.add_space(5.0);
+
+            egui::Grid::new("save_query_fields")
+                .num_columns(2)
+                .spacing([10.0, 6.0])
+                .show(ui, |ui| {
+                    ui.label("Name");
+                    ui.text_edit_singleline(&mut form.name);
+                    ui.end_row();
+
+                    ui.label("Folder");
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            egui::TextEdit::singleline(&mut form.folder)
+                                .hint_text("reports/monthly"),
+                        );
+                        ui.add_enabled_ui(!folders.is_empty(), |ui| {
+                            ui.menu_button("▾", |ui| {
+                                for folder in &folders {
+                                    if ui.button(folder).clicked() {
+                                        form.folder = folder.clone();
+                                        ui.close();
+                                    }
+                                }
+                            });
+                        });
+                    });
+                    ui.end_row();
+
+                    ui.label("Tags");
+                    ui.add(egui::TextEdit::singleline(&mut form.tags).hint_text("finance, daily"));
+                    ui.end_row();
+
+                    ui.label("Scope");
+                    match &form.connection {
+                        Some(connection) => {
+                            ui.checkbox(&mut form.scoped, format!("Only for {}", connection))
+                        }
+                        None => ui.label(
+                            egui::RichText::new("All connections").color(theme::TEXT_MUTED),
+                        ),
+                    };
+                    ui.end_row();
+                });
+
+            if let Some(error) = &form.error {
+                ui.label(egui::RichText::new(error).color(theme::DANGER));
+            }
+
+            ui.add_space(5.0);
+            ui.horizontal(|ui| {
+                let named = !form.name.trim().is_empty();
+                save = ui.add_enabled(named, egui::Button::new("💾 Save")).clicked();
+                if form.id.is_some() {
+                    save_as_new = ui.add_enabled(named, egui::Button::new("Save as new")).clicked();
+                }
+                cancel = ui.button("Cancel").clicked();
+            });
+        });
+
+        if save || save_as_new {
+            let tab = &mut self.tabs[self.active_tab];
+            let existing = form.id.filter(|_| !save_as_new).and_then(|id| self.library.get(id));
+            let mut query = existing.cloned().unwrap_or_default();
+            query.name = form.name.trim().to_string();
+            query.folder = form.folder.clone();
+            query.tags = form.tags.split(',').map(str::to_string).collect();
+            query.connection = form.connection.clone().filter(|_| form.scoped);
+            query.sql = tab.query_content.clone();
+            let (id, name) = (query.id, query.name.clone());
+            match self.library.save(query) {
+                Ok(()) => {
+                    tab.saved_query = Some(id);
+                    tab.name = name;
+                    tab.mark_saved();
+                    self.save_form = None;
+                }
+                Err(e) => form.error = Some(e.to_string()),
+            }
+        } else if cancel || modal.should_close() {
+            self.save_form = None;
+        }
+    }
+
+    /// The statements that would apply the active tab's staged edits, to
+    /// be run as one transaction once confirmed
+    fn render_design_review(&mut self, ctx: &egui::Context) {
+        let tab = &self.tabs[self.active_tab];
+        let Some(design) = tab.design.as_ref().filter(|_| tab.reviewing_design) else {
+            return;
+        };
+
+        let script = design.script();
+        let (mut apply, mut cancel) = (false, false);
+        let applying = tab.is_applying_design();
+        let modal = egui::Modal::new(egui::Id::new("review_design")).show(ctx, |ui| {
+            ui.heading(if design.is_new() { "Create table" } else { "Alter table" });
+            ui.add_space(5.0);
+
+            match &script {
+                Ok(statements) if statements.is_empty() => {
+                    ui.label(egui::RichText::new("Nothing has changed").color(theme::TEXT_MUTED));
+                }
+                Ok(statements) => {
+                    egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                        for statement in statements {
+                            let sql = format!("{};", statement);
+                            ui.label(egui::RichText::new(sql).monospace());
+                            ui.add_space(4.0);
+                        }
+                    });
+                    let note = match design.dialect {
+                        Dialect::MySql => {
+                            "MySQL commits each statement as it runs, so one that fails \
+                             leaves those before it applied."
+                        }
+                        _ => "All statements run in a single transaction.",
+                    };
+                    ui.label(egui::RichText::new(note).color(theme::TEXT_MUTED).small());
+                }
+                Err(e) => {
+                    ui.label(egui::RichText::new(e).color(theme::DANGER));
+                }
+            }
+            if let Some(error) = &tab.design_error {
+                ui.label(egui::RichText::new(format!("Error: {}", error)).color(theme::DANGER));
+            }
+
+            ui.add_space(5.0);
+            ui.horizontal(|ui| {
+                let ready = script.as_ref().is_ok_and(|s| !s.is_empty()) && !applying;
+                apply = ui.add_enabled(ready, egui::Button::new("▶ Apply")).clicked();
+                cancel = ui.add_enabled(!applying, egui::Button::new("Cancel")).clicked();
+                if applying {
+                    ui.spinner();
+                }
+            });
+        });
+
+        if apply {
+            self.with_run_context(|tab, cx| tab.apply_design(cx));
+        } else if cancel || (modal.should_close() && !applying) {
+            self.tabs[self.active_tab].reviewing_design = false;
+        }
+    }
+
+    fn render_change_review(&mut self, ctx: &egui::Context) {
+        let tab = &self.tabs[self.active_tab];
+        if !tab.reviewing_changes {
+            return;
+        }
+
+        let statements = tab.change_statements();
+        let (mut apply, mut cancel) = (false, false);
+        let modal = egui::Modal::new(egui::Id::new("review_changes")).show(ctx, |ui| {
+            ui.heading("Review changes");
+            ui.add_space(5.0);
+
+            match &statements {
+                Ok(statements) => {
+                    egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                        for statement in statements {
+                            let sql = format!("{};", statement.sql);
+                            ui.label(egui::RichText::new(sql).monospace());
+                            let params: Vec<String> = statement
+                                .params
+                                .iter()
+                                .map(|param| match param {
+                                    ParamValue::Null => "NULL".to_string(),
+                                    ParamValue::String(text) => format!("'{}'", text),
+                                    other => other.input_text(),
+                                })
+                                .collect();
+                            ui.label(
+                                egui::RichText::new(format!("-- {}", params.join(", ")))
+                                    .monospace()
+                                    .color(theme::TEXT_MUTED),
+                            );
+                            ui.add_space(4.0);
+                        }
+                    });
+                    ui.label(
+                        egui::RichText::new("All statements run in a single transaction.")
+                            .color(theme::TEXT_MUTED)
+                            .small(),
+                    );
+                }
+                Err(e) => {
+                    ui.label(egui::RichText::new(e).color(theme::DANGER));
+                }
+            }
+            if let Some(error) = &tab.apply_error {
+                ui.label(egui::RichText::new(format!("Error: {}", error)).color(theme::DANGER));
+            }
+
+            ui.add_space(5.0);
+            ui.horizontal(|ui| {
+                let ready = statements.is_ok() && !tab.is_applying();
+                apply = ui.add_enabled(ready, egui::Button::new("▶ Apply")).clicked();
+                cancel = ui.add_enabled(!tab.is_applying(), egui::Button::new("Cancel")).clicked();
+                if tab.is_applying() {
+                    ui.spinner();
+                }
+            });
+        });
+
+        if apply {
+            self.with_run_context(|tab, cx| tab.apply_changes(cx));
+        } else if cancel || (modal.should_close() && !tab.is_applying()) {
+            self.tabs[self.active_tab].reviewing_changes = false;
+        }
+    }
+}
+
+/// "Copy DDL" and "Open DDL in new tab" for a node of the schema tree
+fn ddl_menu(
+    ui: &mut egui::Ui,
+    database: &str,
+    object: SchemaObject,
+    ddl: &mut Option<(String, SchemaObject, DdlAction)>,
+) {
+    for (label, action) in [
+        ("Copy DDL", DdlAction::Copy),
+        ("Open DDL in new tab", DdlAction::OpenTab),
+    ] {
+        if ui.button(label).clicked() {
+            *ddl = Some((database.to_string(), object.clone(), action));
+            ui.close();
+        }
+    }
+}
+
+/// What moving or removing a row of the designer's lists does
+#[derive(Clone, Copy)]
+enum RowAction {
+    Up,
+    Down,
+    Remove,
+}
+
+fn apply_row_action<T>(items: &mut Vec<T>, action: Option<(usize, RowAction)>) {
+    match action {
+        Some((i, RowAction::Up)) if i > 0 => items.swap(i - 1, i),
+        Some((i, RowAction::Down)) if i + 1 < items.len() => items.swap(i, i + 1),
+        Some((i, RowAction::Remove)) => {
+            items.remove(i);
+        }
+        _ => {}
+    }
+}
+
+/// Buttons to move a row of the designer up or down, or remove it
+fn render_row_buttons(
+    ui: &mut egui::Ui,
+    i: usize,
+    count: usize,
+    movable: bool,
+    action: &mut Option<(usize, RowAction)>,
+) {
+    ui.horizontal(|ui| {
+        if movable {
+            if ui.add_enabled(i > 0, egui::Button::new("⬆").small()).clicked() {
+                *action = Some((i, RowAction::Up));
+            }
+            if ui.add_enabled(i + 1 < count, egui::Button::new("⬇").small()).clicked() {
+                *action = Some((i, RowAction::Down));
+            }
+        }
+        if ui.small_button("🗑").on_hover_text("Remove").clicked() {
+            *action = Some((i, RowAction::Remove));
+        }
+    });
+}
+
+/// A menu of the table's columns to pick a key's columns from, in the
+/// order they are picked
+fn render_column_picker(ui: &mut egui::Ui, picked: &mut Vec<String>, names: &[String]) {
+    let label = if picked.is_empty() {
+        "Choose…".to_string()
+    } else {
+        picked.join(", ")
+    };
+    ui.menu_button(label, |ui| {
+        for name in names.iter().filter(|name| !name.is_empty()) {
+            let mut checked = picked.contains(name);
+            if ui.checkbox(&mut checked, name).changed() {
+                if checked {
+                    picked.push(name.clone());
+                } else {
+                    picked.retain(|c| c != name);
+                }
+            }
+        }
+    });
+}
+
+/// ON UPDATE or ON DELETE, or the engine's default
+fn render_action_picker(ui: &mut egui::Ui, id: impl std::hash::Hash, action: &mut Option<String>) {
+    egui::ComboBox::from_id_salt(id)
+        .selected_text(action.as_deref().unwrap_or("default"))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(action, None, "default");
+            for name in REFERENTIAL_ACTIONS {
+                ui.selectable_value(action, Some(name.to_string()), name);
+            }
+        });
+}
+
+/// The designer: the table's name, then its columns, indexes and foreign
+/// keys as editable rows
+fn render_table_design(ui: &mut egui::Ui, tab_id: u64, design: &mut TableDesign) {
+    let qualifier = design.qualifier.clone();
+    let movable = design.dialect == Dialect::MySql || design.is_new();
+    let table = &mut design.table;
+    let names: Vec<String> = table.columns.iter().map(|c| c.name.clone()).collect();
+    let muted = |text: &str| egui::RichText::new(text).color(theme::TEXT_MUTED);
+
+    egui::ScrollArea::both()
+        .id_salt(("table_design", tab_id))
+        .auto_shrink(false)
+        .show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Table");
+                if let Some(qualifier) = &qualifier {
+                    ui.label(muted(&format!("{}.", qualifier)));
+                }
+                let name = egui::TextEdit::singleline(&mut table.name).hint_text("name");
+                ui.add(name.desired_width(200.0));
+            });
+
+            ui.add_space(10.0);
+            ui.label(egui::RichText::new("Columns").strong());
+            let mut action = None;
+            let count = table.columns.len();
+            egui::Grid::new(("design_columns", tab_id))
+                .striped(true)
+                .num_columns(8)
+                .show(ui, |ui| {
+                    for header in [
+                        "",
+                        "Name",
+                        "Type",
+                        "Nullable",
+                        "Default",
+                        "Primary key",
+                        "Auto increment",
+                        "Comment",
+                    ] {
+                        ui.label(muted(header));
+                    }
+                    ui.end_row();
+                    for (i, column) in table.columns.iter_mut().enumerate() {
+                        render_row_buttons(ui, i, count, movable, &mut action);
+                        let name = egui::TextEdit::singleline(&mut column.name);
+                        ui.add(name.desired_width(140.0));
+                        let data_type = egui::TextEdit::singleline(&mut column.data_type)
+                            .font(egui::TextStyle::Monospace)
+                            .hint_text("type");
+                        ui.add(data_type.desired_width(120.0));
+                        ui.checkbox(&mut column.nullable, "");
+                        let default = egui::TextEdit::singleline(&mut column.default_value)
+                            .font(egui::TextStyle::Monospace)
+                            .hint_text("none");
+                        ui.add(default.desired_width(120.0))
+                            .on_hover_text("A SQL expression, such as 0, 'text' or NULL");
+                        ui.checkbox(&mut column.is_primary_key, "");
+                        ui.checkbox(&mut column.is_auto_increment, "");
+                        let comment = egui::TextEdit::singleline(&mut column.comment);
+                        ui.add(comment.desired_width(160.0));
+                        ui.end_row();
+                    }
+                });
+            apply_row_action(&mut table.columns, action);
+            if ui.button("➕ Add column").clicked() {
+                table.columns.push(ColumnDesign {
+                    nullable: true,
+                    ..Default::default()
+                });
+            }
+
+            ui.add_space(10.0);
+            ui.label(egui::RichText::new("Indexes").strong());
+            let mut action = None;
+            let count = table.indexes.len();
+            egui::Grid::new(("design_indexes", tab_id))
+                .striped(true)
+                .num_columns(4)
+                .show(ui, |ui| {
+                    for header in ["", "Name", "Columns", "Unique"] {
+                        ui.label(muted(header));
+                    }
+                    ui.end_row();
+                    for (i, index) in table.indexes.iter_mut().enumerate() {
+                        render_row_buttons(ui, i, count, false, &mut action);
+                        let name = egui::TextEdit::singleline(&mut index.name).hint_text("name");
+                        ui.add(name.desired_width(160.0));
+                        render_column_picker(ui, &mut index.columns, &names);
+                        ui.checkbox(&mut index.is_unique, "");
+                        ui.end_row();
+                    }
+                });
+            apply_row_action(&mut table.indexes, action);
+            if ui.button("➕ Add index").clicked() {
+                table.indexes.push(IndexDesign::default());
+            }
+
+            ui.add_space(10.0);
+            ui.label(egui::RichText::new("Foreign keys").strong());
+            let mut action = None;
+            let count = table.foreign_keys.len();
+            egui::Grid::new(("design_foreign_keys", tab_id))
+                .striped(true)
+                .num_columns(7)
+                .show(ui, |ui| {
+                    for header in [
+                        "",
+                        "Name",
+                        "Columns",
+                        "References",
+                        "Referenced columns",
+                        "On update",
+                        "On delete",
+                    ] {
+                        ui.label(muted(header));
+                    }
+                    ui.end_row();
+                    for (i, key) in table.foreign_keys.iter_mut().enumerate() {
+                        render_row_buttons(ui, i, count, false, &mut action);
+                        let name = egui::TextEdit::singleline(&mut key.name).hint_text("auto");
+                        ui.add(name.desired_width(140.0));
+                        render_column_picker(ui, &mut key.columns, &names);
+                        let referenced = egui::TextEdit::singleline(&mut key.referenced_table)
+                            .hint_text("table");
+                        ui.add(referenced.desired_width(140.0));
+                        let columns = egui::TextEdit::singleline(&mut key.referenced_columns)
+                            .hint_text("primary key");
+                        ui.add(columns.desired_width(140.0))
+                            .on_hover_text("Comma-separated");
+                        render_action_picker(ui, (tab_id, "on_update", i), &mut key.on_update);
+                        render_action_picker(ui, (tab_id, "on_delete", i), &mut key.on_delete);
+                        ui.end_row();
+                    }
+                });
+            apply_row_action(&mut table.foreign_keys, action);
+            if ui.button("➕ Add foreign key").clicked() {
+                table.foreign_keys.push(ForeignKeyDesign::default());
+            }
+        });
+}
+
+/// One tab per statement of a script, with its outcome and rows
+fn render_statement_tabs(ui: &mut egui::Ui, tab: &mut QueryTab) {
+    ui.horizontal_wrapped(|ui| {
+        for (index, statement) in tab.statement_tabs.iter().enumerate() {
+            let (marker, color) = match &statement.status {
+                StatementStatus::Pending if tab.script.is_some() => ("…", theme::TEXT_MUTED),
+                StatementStatus::Pending => ("–", theme::TEXT_MUTED),
+                StatementStatus::Running => ("⟳", theme::WARNING),
+                StatementStatus::Done(_) => ("✓", theme::SUCCESS),
+                StatementStatus::Failed(_) => ("✗", theme::DANGER),
+            };
+            let label = egui::RichText::new(format!("{} {}", marker, index + 1)).color(color);
+            if ui
+                .selectable_label(tab.selected_statement == index, label)
+                .on_hover_text(&statement.sql)
+                .clicked()
+            {
+                tab.selected_statement = index;
+            }
+        }
+    });
+    ui.add_space(5.0);
+
+    let Some(statement) = tab.statement_tabs.get(tab.selected_statement) else {
+        return;
+    };
+    ui.label(egui::RichText::new(&statement.sql).monospace().color(theme::TEXT_MUTED));
+    ui.add_space(5.0);
+
+    match &statement.status {
+        StatementStatus::Pending if tab.script.is_some() => {
+            ui.label(egui::RichText::new("Waiting to run").color(theme::TEXT_MUTED));
+        }
+        StatementStatus::Pending => {
+            ui.label(
+                egui::RichText::new("Not run: the script stopped before this statement")
+                    .color(theme::TEXT_MUTED),
+            );
+        }
+        StatementStatus::Running => {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label(egui::RichText::new("Running…").color(theme::TEXT_MUTED));
+            });
+        }
+        StatementStatus::Failed(error) => {
+            ui.label(egui::RichText::new(format!("Error: {}", error)).color(theme::DANGER));
+        }
+        StatementStatus::Done(result) => {
+            ui.horizontal(|ui| render_result_summary(ui, result));
+            ui.add_space(5.0);
+            if !result.columns.is_empty() {
+                render_results_table(ui, result, None);
+            }
+        }
+    }
+}
+
+/// Staged edits and pending changes of a result that can be written back, or
+/// the reason it can't
+fn render_edit_bar(ui: &mut egui::Ui, tab: &mut QueryTab) {
+    let applying = tab.is_applying();
+    match &tab.editable {
+        Some(Ok(target)) => {
+            ui.horizontal(|ui| {
+                if ui.add_enabled(!applying, egui::Button::new("➕ Add row")).clicked() {
+                    tab.changes.insert(target.new_row());
+                }
+                if tab.changes.is_empty() {
+                    let hint = match &target.key {
+                        Ok(_) => "Double-click a cell to edit it".to_string(),
+                        Err(reason) => format!("Rows can only be added: {}", reason),
+                    };
+                    ui.label(egui::RichText::new(hint).color(theme::TEXT_MUTED).small());
+                } else {
+                    let count = tab.changes.len();
+                    let label = if count == 1 { "change" } else { "changes" };
+                    ui.label(
+                        egui::RichText::new(format!("{} pending {}", count, label))
+                            .color(theme::WARNING),
+                    );
+                    if ui.button("Review SQL").clicked() {
+                        tab.apply_error = None;
+                        tab.reviewing_changes = true;
+                    }
+                    if ui.add_enabled(!applying, egui::Button::new("Discard")).clicked() {
+                        tab.changes.clear();
+                        tab.editing_cell = None;
+                    }
+                }
+                if let Some(notice) = &tab.edit_notice {
+                    ui.label(egui::RichText::new(notice).color(theme::TEXT_MUTED).small());
+                }
+            });
+        }
+        Some(Err(reason)) => {
+            ui.label(
+                egui::RichText::new(format!("Read-only: {}", reason))
+                    .color(theme::TEXT_MUTED)
+                    .small(),
+            );
+        }
+        None => {}
+    }
+}
+
+/// What the result grid needs to edit a result. Rows being added are shown
+/// after the result's own.
+struct GridEdit<'a> {
+    target: &'a EditTarget,
+    changes: &'a mut ChangeSet,
+    /// The cell being edited, as in `QueryTab::editing_cell`
+    editing: &'a mut Option<(usize, usize, String)>,
+}
+
+/// Draw the result grid. Returns the row whose source document was
+/// requested, for results that come from a document store. With `edit`,
+/// cells can be edited and show their staged values.
+fn render_results_table(
+    ui: &mut egui::Ui,
+    result: &QueryResult,
+    mut edit: Option<GridEdit<'_>>,
+) -> Option<usize> {
+    use egui_extras::{Column, TableBuilder};
+
+    let available_height = ui.available_height();
+    let has_documents = !result.documents.is_empty();
+    let mut open_document = None;
+    
+    let mut table = TableBuilder::new(ui)
+        .striped(true)
+        .resizable(true)
+        .cell_layout(egui::Layout::left_to_right(egui::Align::Center));
+    if has_documents {
+        table = table.column(Column::exact(28.0));
+    }
+    table
+        .columns(Column::auto().at_least(80.0).resizable(true), result.columns.len())
+        .min_scrolled_height(0.0)
+        .max_scroll_height(available_height)
+        .header(25.0, |mut header| {
+            if has_documents {
+                header.col(|_| {});
+            }
+            for col in &result.columns {
+                header.col(|ui| {
+                    let name = if col.is_primary_key {
+                        format!("🔑 {}", col.name)
+                    } else {
+                        col.name.clone()
+                    };
+                    let nullability = if col.nullable { "NULL" } else { "NOT NULL" };
+                    ui.strong(name)
+                        .on_hover_text(format!("{} {}", col.data_type, nullability));
+                });
+            }
+        })
+        .body(|body| {
+            let added = edit.as_ref().map_or(0, |edit| edit.changes.inserts().len());
+            body.rows(22.0, result.rows.len() + added, |mut row| {
+                let row_idx = row.index();
+                if has_documents {
+                    row.col(|ui| {
+                        if ui.small_button("{ }").on_hover_text("View document").clicked() {
+                            open_document = Some(row_idx);
+                        }
+                    });
+                }
+                if let Some(data_row) = result.rows.get(row_idx) {
+                    for (col_idx, cell) in data_row.iter().enumerate() {
+                        row.col(|ui| match &mut edit {
+                            Some(edit) => {
+                                render_editable_cell(ui, edit, row_idx, col_idx, data_row)
+                            }
+                            None => {
+                                render_cell(ui, cell, |_| {});
+                            }
+                        });
+                    }
+                } else if let Some(edit) = &mut edit {
+                    let index = row_idx - result.rows.len();
+                    for col_idx in 0..result.columns.len() {
+                        row.col(|ui| render_new_cell(ui, edit, row_idx, index, col_idx));
+                    }
+                }
+            });
+        });
+
+    open_document
+}
+
+/// A cell's value, with a context menu to copy it and whatever `menu` adds
+fn render_cell(
+    ui: &mut egui::Ui,
+    cell: &CellValue,
+    menu: impl FnOnce(&mut egui::Ui),
+) -> egui::Response {
+    let text = cell.display_string();
+    let label = match cell {
+        CellValue::Undecodable(_) => ui.label(egui::RichText::new(&text).color(theme::WARNING)),
+        _ => ui.label(&text),
+    };
+    label.context_menu(|ui| {
+        if ui.button("Copy value").clicked() {
+            ui.ctx().copy_text(text.clone());
+            ui.close();
+        }
+        menu(ui);
+    });
+    label
+}
+
+/// The text field of the cell being edited, if it is this one. Returns the
+/// text entered once editing ends, unless it was cancelled.
+fn render_cell_editor(
+    ui: &mut egui::Ui,
+    editing: &mut Option<(usize, usize, String)>,
+    row: usize,
+    column: usize,
+) -> Option<Option<String>> {
+    let (_, _, text) = editing
+        .as_mut()
+        .filter(|(r, c, _)| (*r, *c) == (row, column))?;
+    let response = ui.add(egui::TextEdit::singleline(text).desired_width(f32::INFINITY));
+    if response.lost_focus() {
+        // Escape drops the edit; Enter or clicking away keeps it
+        let entered = !ui.input(|i| i.key_pressed(egui::Key::Escape));
+        let text = entered.then(|| text.clone());
+        *editing = None;
+        return Some(text);
+    }
+    if !response.has_focus() {
+        response.request_focus();
+    }
+    Some(None)
+}
+
+/// A cell of a result that can be edited: a text field while it is being
+/// edited, otherwise its staged value, if any, highlighted
+fn render_editable_cell(
+    ui: &mut egui::Ui,
+    edit: &mut GridEdit<'_>,
+    row: usize,
+    column: usize,
+    cells: &[CellValue],
+) {
+    let cell = &cells[column];
+    if let Some(entered) = render_cell_editor(ui, edit.editing, row, column) {
+        if let Some(text) = entered {
+            edit.changes.set(row, column, Some(text), cell);
+        }
+        return;
+    }
+
+    if edit.changes.is_deleted(row) {
+        let rect = ui.max_rect().expand(2.0);
+        ui.painter().rect_filled(rect, 0.0, theme::DANGER.gamma_multiply(0.25));
+        let text = egui::RichText::new(cell.display_string()).strikethrough();
+        ui.label(text.color(theme::TEXT_MUTED)).context_menu(|ui| {
+            if ui.button("Undo delete").clicked() {
+                edit.changes.undelete(row);
+                ui.close();
+            }
+        });
+        return;
+    }
+
+    let staged = edit.changes.get(row, column).cloned();
+    let shown = match &staged {
+        Some(Some(text)) => CellValue::String(text.clone()),
+        Some(None) => CellValue::Null,
+        None => cell.clone(),
+    };
+    if staged.is_some() {
+        let rect = ui.max_rect().expand(2.0);
+        ui.painter().rect_filled(rect, 0.0, theme::WARNING.gamma_multiply(0.25));
+    }
+
+    // Columns computed by the query, and values that don't read back as
+    // text, stay as they are
+    let editable = edit.target.is_editable(column, cell);
+    let nullable = edit.target.columns[column].as_ref().is_some_and(|c| c.nullable);
+    let (mut start_editing, mut duplicate) = (false, false);
+    let response = render_cell(ui, &shown, |ui| {
+        ui.separator();
+        if editable {
+            if ui.button("Edit").clicked() {
+                start_editing = true;
+                ui.close();
+            }
+            if nullable && ui.button("Set NULL").clicked() {
+                edit.changes.set(row, column, None, cell);
+                ui.close();
+            }
+            if staged.is_some() && ui.button("Revert").clicked() {
+                edit.changes.revert(row, column);
+                ui.close();
+            }
+            ui.separator();
+        }
+        if ui.button("Duplicate row").clicked() {
+            duplicate = true;
+            ui.close();
+        }
+        let delete = ui.add_enabled(edit.target.key.is_ok(), egui::Button::new("Delete row"));
+        let delete = match &edit.target.key {
+            Err(reason) => delete.on_disabled_hover_text(reason),
+            Ok(_) => delete,
+        };
+        if delete.clicked() {
+            edit.changes.delete(row);
+            ui.close();
+        }
+    });
+
+    if editable && (start_editing || response.double_clicked()) {
+        let text = match staged {
+            Some(value) => value.unwrap_or_default(),
+            None => edit::edit_text(cell).unwrap_or_default(),
+        };
+        *edit.editing = Some((row, column, text));
+    }
+    if duplicate {
+        // The row as it will be, with its edits
+        let cells: Vec<CellValue> = cells
+            .iter()
+            .enumerate()
+            .map(|(c, cell)| match edit.changes.get(row, c) {
+                Some(Some(text)) => CellValue::String(text.clone()),
+                Some(None) => CellValue::Null,
+                None => cell.clone(),
+            })
+            .collect();
+        edit.changes.insert(edit.target.copy_row(&cells));
+    }
+}
+
+/// A cell of a row being added: its value, or the default it will get
+fn render_new_cell(
+    ui: &mut egui::Ui,
+    edit: &mut GridEdit<'_>,
+    grid_row: usize,
+    index: usize,
+    column: usize,
+) {
+    if let Some(entered) = render_cell_editor(ui, edit.editing, grid_row, column) {
+        if let Some(text) = entered {
+            edit.changes.set_inserted(index, column, NewValue::Text(text));
+        }
+        return;
+    }
+
+    let value = edit.changes.inserts().get(index).and_then(|row| row.get(column));
+    let Some(value) = value.cloned() else {
+        return;
+    };
+    let rect = ui.max_rect().expand(2.0);
+    ui.painter().rect_filled(rect, 0.0, theme::SUCCESS.gamma_multiply(0.2));
+
+    let details = edit.target.columns.get(column).and_then(Option::as_ref);
+    let response = match &value {
+        NewValue::Default => {
+            let text = match details {
+                Some(details) if details.is_auto_increment => "auto".to_string(),
+                Some(details) => match &details.default_value {
+                    Some(default) => format!("DEFAULT {}", default),
+                    None => "DEFAULT".to_string(),
+                },
+                None => String::new(),
+            };
+            ui.label(egui::RichText::new(text).italics().color(theme::TEXT_MUTED))
+        }
+        NewValue::Null => ui.label(CellValue::Null.display_string()),
+        NewValue::Text(text) => ui.label(text),
+    };
+
+    // Columns computed by the query are not part of the row
+    let Some(details) = details else {
+        return;
+    };
+    let mut start_editing = response.double_clicked();
+    response.context_menu(|ui| {
+        if ui.button("Edit").clicked() {
+            start_editing = true;
+            ui.close();
+        }
+        if ui.button("Set DEFAULT").clicked() {
+            edit.changes.set_inserted(index, column, NewValue::Default);
+            ui.close();
+        }
+        if details.nullable && ui.button("Set NULL").clicked() {
+            edit.changes.set_inserted(index, column, NewValue::Null);
+            ui.close();
+        }
+        ui.separator();
+        if ui.button("Remove row").clicked() {
+            edit.changes.remove_inserted(index);
+            ui.close();
+        }
+    });
+    if start_editing {
+        let text = match value {
+            NewValue::Text(text) => text,
+            NewValue::Default | NewValue::Null => String::new(),
+        };
+        *edit.editing = Some((grid_row, column, text));
+    }
+}
+
+/// A text field for an optional limit; left empty it means no limit
+fn optional_number_edit<T>(ui: &mut egui::Ui, value: &mut Option<T>)
+where
+    T: std::str::FromStr + std::fmt::Display,
+{
+    let mut text = value.as_ref().map(T::to_string).unwrap_or_default();
+    let response = ui.add(egui::TextEdit::singleline(&mut text).hint_text("unlimited"));
+    if response.changed() {
+        if text.trim().is_empty() {
+            *value = None;
+        } else if let Ok(parsed) = text.trim().parse() {
+            *value = Some(parsed);
+        }
+    }
+}
+
+/// Build the `SELECT` used to preview a table or view in the editor
+fn preview_query(
+    db_type: DatabaseType,
+    database: &str,
+    schema: Option<&str>,
+    name: &str,
+) -> String {
+    let (dialect, parts) = match (db_type, schema) {
+        // PostgreSQL cannot reference other databases, only schemas
+        (DatabaseType::PostgreSQL, Some(schema)) => (Dialect::Postgres, vec![schema, name]),
+        (DatabaseType::PostgreSQL, None) => (Dialect::Postgres, vec![name]),
+        (DatabaseType::MongoDB, _) => {
+            return format!("use {};\ndb.getCollection({:?}).find({{}}).limit(100)", database, name);
+        }
+        (DatabaseType::SQLite, _) => (Dialect::Sqlite, vec![database, name]),
+        (DatabaseType::MySQL, _) => (Dialect::MySql, vec![database, name]),
+    };
+    format!("SELECT * FROM {} LIMIT 100", quote_qualified(&parts, dialect))
+}
+
+/// The color picked for a connection, if it has a valid one
+fn connection_color(config: &ConnectionConfig) -> Option<egui::Color32> {
+    config.color.as_deref().and_then(theme::parse_hex)
+}
+
+/// A table's properties, then its columns, indexes, foreign keys and
+/// triggers, one grid each
+fn render_table_structure(ui: &mut egui::Ui, tab_id: u64, structure: &TableStructure) {
+    let table = &structure.table;
+    let mut properties = Vec::new();
+    if let Some(engine) = &table.engine {
+        properties.push(("Engine", engine.clone()));
+    }
+    if let Some(charset) = &table.character_set {
+        properties.push(("Character set", charset.clone()));
+    }
+    if let Some(collation) = &table.collation {
+        properties.push(("Collation", collation.clone()));
+    }
+    if let Some(rows) = table.row_count {
+        properties.push(("Rows (estimate)", format!("~{}", rows)));
+    }
+    if let Some(size) = table.data_size {
+        properties.push(("Data size", format_bytes(size)));
+    }
+
+    egui::ScrollArea::both()
+        .id_salt(("table_structure", tab_id))
+        .auto_shrink(false)
+        .show(ui, |ui| {
+            if !properties.is_empty() {
+                egui::Grid::new(("structure_properties", tab_id))
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        for (label, value) in properties {
+                            ui.label(egui::RichText::new(label).color(theme::TEXT_MUTED));
+                            ui.label(value);
+                            ui.end_row();
+                        }
+                    });
+            }
+
+            let headers = [
+                "Name",
+                "Type",
+                "Nullable",
+                "Default",
+                "Key",
+                "Auto increment",
+                "Comment",
+            ];
+            let columns = &table.columns;
+            structure_grid(ui, (tab_id, "columns"), "Columns", &headers, columns, |ui, column| {
+                ui.label(&column.name);
+                ui.monospace(&column.data_type);
+                ui.label(if column.nullable { "YES" } else { "NO" });
+                ui.monospace(column.default_value.as_deref().unwrap_or(""));
+                ui.label(if column.is_primary_key {
+                    "PRIMARY"
+                } else if column.is_unique {
+                    "UNIQUE"
+                } else {
+                    ""
+                });
+                ui.label(if column.is_auto_increment { "✔" } else { "" });
+                ui.label(column.comment.as_deref().unwrap_or(""));
+            });
+
+            let headers = ["Name", "Columns", "Kind", "Method"];
+            let indexes = &structure.indexes;
+            structure_grid(ui, (tab_id, "indexes"), "Indexes", &headers, indexes, |ui, index| {
+                ui.label(&index.name);
+                ui.monospace(index.columns.join(", "));
+                ui.label(if index.is_primary {
+                    "PRIMARY"
+                } else if index.is_unique {
+                    "UNIQUE"
+                } else {
+                    ""
+                });
+                ui.label(index.method.as_deref().unwrap_or(""));
+            });
+
+            let headers = ["Name", "Columns", "References", "On update", "On delete"];
+            let keys = &structure.foreign_keys;
+            structure_grid(ui, (tab_id, "foreign_keys"), "Foreign keys", &headers, keys, |ui, key| {
+                ui.label(key.name.as_deref().unwrap_or(""));
+                ui.monospace(key.columns.join(", "));
+                if key.referenced_columns.is_empty() {
+                    ui.monospace(&key.referenced_table);
+                } else {
+                    let columns = key.referenced_columns.join(", ");
+                    ui.monospace(format!("{} ({})", key.referenced_table, columns));
+                }
+                ui.label(key.on_update.as_deref().unwrap_or(""));
+                ui.label(key.on_delete.as_deref().unwrap_or(""));
+            });
+
+            let headers = ["Name", "Timing", "Event", "Statement"];
+            let triggers = &structure.triggers;
+            structure_grid(ui, (tab_id, "triggers"), "Triggers", &headers, triggers, |ui, trigger| {
+                ui.label(&trigger.name);
+                ui.label(&trigger.timing);
+                ui.label(&trigger.event);
+                // The first line in the grid, all of it on hover
+                let statement = trigger.statement.as_deref().unwrap_or("");
+                let first_line = statement.lines().next().unwrap_or("");
+                let shown = if first_line.len() < statement.trim_end().len() {
+                    format!("{} …", first_line)
+                } else {
+                    first_line.to_string()
+                };
+                ui.monospace(shown).on_hover_text(egui::RichText::new(statement).monospace());
+            });
+        });
+}
+
+/// A heading with a count, then a striped grid with one row per item, or a
+/// note that there are none
+fn structure_grid<T>(
+    ui: &mut egui::Ui,
+    id: impl std::hash::Hash,
+    title: &str,
+    headers: &[&str],
+    items: &[T],
+    mut row: impl FnMut(&mut egui::Ui, &T),
+) {
+    ui.add_space(10.0);
+    ui.label(egui::RichText::new(format!("{} ({})", title, items.len())).strong());
+    if items.is_empty() {
+        ui.label(egui::RichText::new("None").color(theme::TEXT_MUTED));
+        return;
+    }
+    egui::Grid::new(id)
+        .striped(true)
+        .num_columns(headers.len())
+        .spacing([16.0, 4.0])
+        .show(ui, |ui| {
+            for header in headers {
+                ui.label(egui::RichText::new(*header).color(theme::TEXT_MUTED));
+            }
+            ui.end_row();
+            for item in items {
+                row(ui, item);
+                ui.end_row();
+            }
+        });
+}
+
+/// A byte count in the largest unit that keeps it at or above 1
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Row count, timing, rows affected and any limit that cut the result short
+fn render_result_summary(ui: &mut egui::Ui, result: &QueryResult) {
+    ui.label(format!(
+        "{} rows × {} columns | {} ms",
+        result.rows.len(),
+        result.columns.len(),
+        result.execution_time_ms
+    ));
+    if let Some(affected) = result.affected_rows {
+        ui.label(format!("| {} rows affected", affected));
+    }
+    if let Some(limit) = result.limit {
+        ui.label(egui::RichText::new(format!("| {}", limit)).color(theme::WARNING));
     }
 }
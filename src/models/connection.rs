@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 use uuid::Uuid;
 
 /// Supported database types
@@ -57,9 +58,30 @@ pub struct ConnectionConfig {
     pub password: String,
     pub database: String,
     pub ssl_enabled: bool,
+    /// Open the database without write access (SQLite)
+    #[serde(default)]
+    pub read_only: bool,
+    #[serde(default)]
+    pub limits: QueryLimits,
     pub color: Option<String>,
 }
 
+/// Limits applied to every query run on a connection; `None` is unlimited
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueryLimits {
+    /// Seconds a statement may run, or wait for its next rows, before it is aborted
+    pub statement_timeout_secs: Option<u64>,
+    pub max_rows: Option<usize>,
+    /// Approximate size of the decoded values in a result
+    pub max_result_bytes: Option<usize>,
+}
+
+impl QueryLimits {
+    pub fn statement_timeout(&self) -> Option<Duration> {
+        self.statement_timeout_secs.map(Duration::from_secs)
+    }
+}
+
 impl Default for ConnectionConfig {
     fn default() -> Self {
         Self {
@@ -72,6 +94,8 @@ impl Default for ConnectionConfig {
             password: String::new(),
             database: String::new(),
             ssl_enabled: false,
+            read_only: false,
+            limits: QueryLimits::default(),
             color: None,
         }
     }
@@ -96,9 +120,15 @@ impl ConnectionConfig {
                 format!("sqlite:{}", self.database)
             }
             DatabaseType::MongoDB => {
+                // Credentials are optional for MongoDB
+                let credentials = if self.username.is_empty() {
+                    String::new()
+                } else {
+                    format!("{}:{}@", self.username, self.password)
+                };
                 format!(
-                    "mongodb://{}:{}@{}:{}/{}",
-                    self.username, self.password, self.host, self.port, self.database
+                    "mongodb://{}{}:{}/{}",
+                    credentials, self.host, self.port, self.database
                 )
             }
         }
@@ -106,7 +136,7 @@ impl ConnectionConfig {
 }
 
 /// Connection state
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub enum ConnectionState {
     #[default]
     Disconnected,
@@ -1,29 +1,35 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-/// A saved query
+/// A saved query. Each one is kept in its own `.sql` file; the SQL and the
+/// folder come from the file itself, the rest from its front-matter.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SavedQuery {
+    #[serde(default = "Uuid::new_v4")]
     pub id: Uuid,
+    #[serde(default)]
     pub name: String,
+    /// Folder within the library, `/`-separated; empty for the top level
+    #[serde(skip)]
+    pub folder: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Key of the connection the query belongs to, see
+    /// `AppConfig::connection_key`; `None` for a query usable anywhere
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connection: Option<String>,
+    #[serde(skip)]
     pub sql: String,
-    pub connection_id: Option<Uuid>,
+    #[serde(default = "Utc::now")]
     pub created_at: DateTime<Utc>,
+    #[serde(default = "Utc::now")]
     pub updated_at: DateTime<Utc>,
 }
 
 impl Default for SavedQuery {
     fn default() -> Self {
-        let now = Utc::now();
-        Self {
-            id: Uuid::new_v4(),
-            name: "Untitled Query".to_string(),
-            sql: String::new(),
-            connection_id: None,
-            created_at: now,
-            updated_at: now,
-        }
+        Self::new("Untitled Query".to_string(), String::new())
     }
 }
 
@@ -33,8 +39,10 @@ impl SavedQuery {
         Self {
             id: Uuid::new_v4(),
             name,
+            folder: String::new(),
+            tags: Vec::new(),
+            connection: None,
             sql,
-            connection_id: None,
             created_at: now,
             updated_at: now,
         }
@@ -48,6 +56,29 @@ pub struct QueryResult {
     pub rows: Vec<Vec<CellValue>>,
     pub affected_rows: Option<u64>,
     pub execution_time_ms: u64,
+    /// Source document of each row as pretty-printed JSON (document stores only)
+    pub documents: Vec<String>,
+    /// The connection limit that cut the result short, if any
+    pub limit: Option<ResultLimit>,
+}
+
+/// A connection limit that stopped a query before all its rows were read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResultLimit {
+    MaxRows(usize),
+    MaxBytes(usize),
+    /// The statement timeout, in seconds, ran out
+    Timeout(u64),
+}
+
+impl std::fmt::Display for ResultLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResultLimit::MaxRows(rows) => write!(f, "truncated at the {} row limit", rows),
+            ResultLimit::MaxBytes(bytes) => write!(f, "truncated at the {} byte limit", bytes),
+            ResultLimit::Timeout(secs) => write!(f, "aborted by the {} s statement timeout", secs),
+        }
+    }
 }
 
 /// Column information
@@ -65,11 +96,81 @@ pub enum CellValue {
     Null,
     Bool(bool),
     Int(i64),
+    /// Unsigned 64-bit integers, which may not fit in `Int`
+    UInt(u64),
     Float(f64),
+    /// An exact decimal number in plain notation, kept as text so that no
+    /// digit is lost on the way to the screen, the clipboard or a file
+    Decimal(String),
     String(String),
     Bytes(Vec<u8>),
-    DateTime(String),
+    Date(NaiveDate),
+    Time(NaiveTime),
+    TimeTz(NaiveTime, FixedOffset),
+    DateTime(NaiveDateTime),
+    DateTimeTz(DateTime<FixedOffset>),
+    Interval(Interval),
+    Uuid(Uuid),
+    /// A bit string as `0` and `1` digits
+    Bits(String),
+    Enum(String),
+    Set(Vec<String>),
+    /// A spatial value as well-known text, with its spatial reference id
+    Geometry {
+        srid: Option<u32>,
+        wkt: String,
+    },
     Json(String),
+    /// A value the client could not decode, with the name of its type. Shown
+    /// as such rather than as NULL, which it isn't.
+    Undecodable(String),
+}
+
+/// A span of time in the calendar units PostgreSQL keeps apart, since a
+/// month or a day has no fixed length
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interval {
+    pub months: i32,
+    pub days: i32,
+    pub microseconds: i64,
+}
+
+/// In PostgreSQL's own format, e.g. `1 year 2 mons 3 days 04:05:06.5`
+impl std::fmt::Display for Interval {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let plural = |n: i32| if n.abs() == 1 { "" } else { "s" };
+        let mut parts = Vec::new();
+        let (years, months) = (self.months / 12, self.months % 12);
+        if years != 0 {
+            parts.push(format!("{} year{}", years, plural(years)));
+        }
+        if months != 0 {
+            parts.push(format!("{} mon{}", months, plural(months)));
+        }
+        if self.days != 0 {
+            parts.push(format!("{} day{}", self.days, plural(self.days)));
+        }
+
+        if self.microseconds != 0 || parts.is_empty() {
+            let sign = if self.microseconds < 0 { "-" } else { "" };
+            let micros = self.microseconds.unsigned_abs();
+            let secs = micros / 1_000_000;
+            let mut time = format!(
+                "{}{:02}:{:02}:{:02}",
+                sign,
+                secs / 3600,
+                secs / 60 % 60,
+                secs % 60
+            );
+            if !micros.is_multiple_of(1_000_000) {
+                let fraction = format!("{:06}", micros % 1_000_000);
+                time.push('.');
+                time.push_str(fraction.trim_end_matches('0'));
+            }
+            parts.push(time);
+        }
+        write!(f, "{}", parts.join(" "))
+    }
 }
 
 impl std::fmt::Display for CellValue {
@@ -78,11 +179,28 @@ impl std::fmt::Display for CellValue {
             CellValue::Null => write!(f, "NULL"),
             CellValue::Bool(b) => write!(f, "{}", b),
             CellValue::Int(i) => write!(f, "{}", i),
+            CellValue::UInt(u) => write!(f, "{}", u),
             CellValue::Float(fl) => write!(f, "{}", fl),
+            CellValue::Decimal(d) => write!(f, "{}", d),
             CellValue::String(s) => write!(f, "{}", s),
             CellValue::Bytes(b) => write!(f, "<{} bytes>", b.len()),
-            CellValue::DateTime(dt) => write!(f, "{}", dt),
+            CellValue::Date(d) => write!(f, "{}", d.format("%Y-%m-%d")),
+            CellValue::Time(t) => write!(f, "{}", t.format("%H:%M:%S%.f")),
+            CellValue::TimeTz(t, offset) => write!(f, "{}{}", t.format("%H:%M:%S%.f"), offset),
+            CellValue::DateTime(dt) => write!(f, "{}", dt.format("%Y-%m-%d %H:%M:%S%.f")),
+            CellValue::DateTimeTz(dt) => write!(f, "{}", dt.format("%Y-%m-%d %H:%M:%S%.f%:z")),
+            CellValue::Interval(i) => write!(f, "{}", i),
+            CellValue::Uuid(u) => write!(f, "{}", u),
+            CellValue::Bits(b) => write!(f, "{}", b),
+            CellValue::Enum(e) => write!(f, "{}", e),
+            CellValue::Set(members) => write!(f, "{}", members.join(",")),
+            CellValue::Geometry {
+                srid: Some(srid),
+                wkt,
+            } => write!(f, "SRID={};{}", srid, wkt),
+            CellValue::Geometry { srid: None, wkt } => write!(f, "{}", wkt),
             CellValue::Json(j) => write!(f, "{}", j),
+            CellValue::Undecodable(type_name) => write!(f, "⚠ undecodable {}", type_name),
         }
     }
 }
@@ -91,4 +209,101 @@ impl CellValue {
     pub fn display_string(&self) -> String {
         self.to_string()
     }
+
+    /// Rough number of bytes the value takes up, for result size limits
+    pub fn approx_size(&self) -> usize {
+        match self {
+            CellValue::Null | CellValue::Bool(_) | CellValue::Undecodable(_) => 1,
+            CellValue::Int(_)
+            | CellValue::UInt(_)
+            | CellValue::Float(_)
+            | CellValue::Date(_)
+            | CellValue::Time(_)
+            | CellValue::TimeTz(..)
+            | CellValue::DateTime(_)
+            | CellValue::DateTimeTz(_) => 8,
+            CellValue::Interval(_) | CellValue::Uuid(_) => 16,
+            CellValue::Decimal(s)
+            | CellValue::String(s)
+            | CellValue::Bits(s)
+            | CellValue::Enum(s)
+            | CellValue::Json(s)
+            | CellValue::Geometry { wkt: s, .. } => s.len(),
+            CellValue::Set(members) => members.iter().map(String::len).sum(),
+            CellValue::Bytes(b) => b.len(),
+        }
+    }
+}
+
+/// A value bound to a query placeholder
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParamValue {
+    Null,
+    Int(i64),
+    String(String),
+    Date(NaiveDate),
+}
+
+impl ParamValue {
+    pub fn kind(&self) -> ParamKind {
+        match self {
+            ParamValue::Null => ParamKind::Null,
+            ParamValue::Int(_) => ParamKind::Int,
+            ParamValue::String(_) => ParamKind::String,
+            ParamValue::Date(_) => ParamKind::Date,
+        }
+    }
+
+    /// The value as typed into the bind form
+    pub fn input_text(&self) -> String {
+        match self {
+            ParamValue::Null => String::new(),
+            ParamValue::Int(i) => i.to_string(),
+            ParamValue::String(s) => s.clone(),
+            ParamValue::Date(d) => d.format("%Y-%m-%d").to_string(),
+        }
+    }
+}
+
+/// The type a placeholder value is entered as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamKind {
+    Int,
+    String,
+    Date,
+    Null,
+}
+
+impl ParamKind {
+    pub const ALL: [ParamKind; 4] = [
+        ParamKind::Int,
+        ParamKind::String,
+        ParamKind::Date,
+        ParamKind::Null,
+    ];
+
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            ParamKind::Int => "Integer",
+            ParamKind::String => "String",
+            ParamKind::Date => "Date",
+            ParamKind::Null => "NULL",
+        }
+    }
+
+    /// Read a value typed into the bind form
+    pub fn parse(&self, text: &str) -> Result<ParamValue, String> {
+        match self {
+            ParamKind::Int => text
+                .trim()
+                .parse()
+                .map(ParamValue::Int)
+                .map_err(|_| format!("'{}' is not an integer", text)),
+            ParamKind::String => Ok(ParamValue::String(text.to_string())),
+            ParamKind::Date => NaiveDate::parse_from_str(text.trim(), "%Y-%m-%d")
+                .map(ParamValue::Date)
+                .map_err(|_| format!("'{}' is not a YYYY-MM-DD date", text)),
+            ParamKind::Null => Ok(ParamValue::Null),
+        }
+    }
 }
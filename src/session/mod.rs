@@ -0,0 +1,421 @@
+use crate::db::{
+    create_connection, DatabaseConnection, DatabaseInfo, SchemaObject, TableInfo, ViewInfo,
+};
+use crate::models::{ConnectionConfig, ConnectionState, DatabaseType};
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::sync::Arc;
+use tokio::runtime::Handle;
+use tokio::sync::oneshot::error::TryRecvError;
+use uuid::Uuid;
+
+/// Receiver for the result of a task spawned on the runtime
+pub type Pending<T> = tokio::sync::oneshot::Receiver<Result<T, String>>;
+
+/// Run `task` on the runtime, handing its result to the returned receiver
+fn spawn_pending<T: Send + 'static>(
+    runtime: &Handle,
+    task: impl Future<Output = Result<T, String>> + Send + 'static,
+) -> Pending<T> {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    runtime.spawn(async move {
+        let _ = tx.send(task.await);
+    });
+    rx
+}
+
+/// Take the results that have arrived for a list of keyed tasks, dropping
+/// any task that went away without one
+fn take_finished<T>(pending: &mut Vec<(String, Pending<T>)>) -> Vec<(String, Result<T, String>)> {
+    let mut finished = Vec::new();
+    pending.retain_mut(|(key, rx)| match rx.try_recv() {
+        Ok(result) => {
+            finished.push((key.clone(), result));
+            false
+        }
+        Err(TryRecvError::Empty) => true,
+        Err(TryRecvError::Closed) => false,
+    });
+    finished
+}
+
+/// What happened to a session since it was last polled
+#[derive(Debug)]
+pub enum SessionEvent {
+    Connected,
+    Failed(String),
+}
+
+/// A live connection to one server, with its schema tree
+pub struct Session {
+    pub config: ConnectionConfig,
+    pub state: ConnectionState,
+    pub connection: Option<Arc<dyn DatabaseConnection>>,
+    /// Why the connection could not be opened
+    pub error: Option<String>,
+    pub databases: Vec<DatabaseInfo>,
+    pub tables: HashMap<String, Vec<TableInfo>>,
+    pub views: HashMap<String, Vec<ViewInfo>>,
+    /// Procedures, functions, triggers and events, by database
+    pub routines: HashMap<String, Vec<SchemaObject>>,
+    pub expanded_databases: HashSet<String>,
+    pending_connection: Option<Pending<Box<dyn DatabaseConnection>>>,
+    pending_databases: Option<Pending<Vec<DatabaseInfo>>>,
+    pending_tables: Vec<(String, Pending<Vec<TableInfo>>)>,
+    pending_views: Vec<(String, Pending<Vec<ViewInfo>>)>,
+    pending_routines: Vec<(String, Pending<Vec<SchemaObject>>)>,
+    /// Databases to expand once the database list arrives
+    restore_expanded: Vec<String>,
+}
+
+impl Session {
+    /// Start connecting to `config`
+    fn open(runtime: &Handle, config: ConnectionConfig) -> Self {
+        let mut session = Self {
+            config,
+            state: ConnectionState::Disconnected,
+            connection: None,
+            error: None,
+            databases: Vec::new(),
+            tables: HashMap::new(),
+            views: HashMap::new(),
+            routines: HashMap::new(),
+            expanded_databases: HashSet::new(),
+            pending_connection: None,
+            pending_databases: None,
+            pending_tables: Vec::new(),
+            pending_views: Vec::new(),
+            pending_routines: Vec::new(),
+            restore_expanded: Vec::new(),
+        };
+        session.reconnect(runtime);
+        session
+    }
+
+    pub fn id(&self) -> Uuid {
+        self.config.id
+    }
+
+    /// Try again after a failed connection attempt
+    pub fn reconnect(&mut self, runtime: &Handle) {
+        let config = self.config.clone();
+        self.state = ConnectionState::Connecting;
+        self.error = None;
+        self.pending_connection = Some(spawn_pending(runtime, async move {
+            let conn = create_connection(&config)
+                .await
+                .map_err(|e| e.to_string())?;
+            conn.test_connection().await.map_err(|e| e.to_string())?;
+            Ok(conn)
+        }));
+    }
+
+    /// The connection, once it is open
+    pub fn connected(&self) -> Option<Arc<dyn DatabaseConnection>> {
+        match self.state {
+            ConnectionState::Connected => self.connection.clone(),
+            _ => None,
+        }
+    }
+
+    /// Whether a task is still running for this session
+    pub fn is_busy(&self) -> bool {
+        self.pending_connection.is_some()
+            || self.pending_databases.is_some()
+            || !self.pending_tables.is_empty()
+            || !self.pending_views.is_empty()
+            || !self.pending_routines.is_empty()
+    }
+
+    /// Whether the database list or a table list is still loading
+    pub fn schema_loading(&self) -> bool {
+        self.pending_databases.is_some() || !self.pending_tables.is_empty()
+    }
+
+    /// Reload the list of databases
+    pub fn refresh(&mut self, runtime: &Handle) {
+        let Some(conn) = self.connected() else {
+            return;
+        };
+        self.pending_databases = Some(spawn_pending(runtime, async move {
+            conn.list_databases().await.map_err(|e| e.to_string())
+        }));
+    }
+
+    /// Load everything the tree shows inside a database
+    pub fn load_tables_and_views(&mut self, runtime: &Handle, database: &str) {
+        let Some(conn) = self.connected() else {
+            return;
+        };
+
+        let (tables_conn, db) = (conn.clone(), database.to_string());
+        let tables = spawn_pending(runtime, async move {
+            tables_conn
+                .list_tables(&db)
+                .await
+                .map_err(|e| e.to_string())
+        });
+        self.pending_tables.push((database.to_string(), tables));
+
+        let (views_conn, db) = (conn.clone(), database.to_string());
+        let views = spawn_pending(runtime, async move {
+            views_conn.list_views(&db).await.map_err(|e| e.to_string())
+        });
+        self.pending_views.push((database.to_string(), views));
+
+        let db = database.to_string();
+        let routines = spawn_pending(runtime, async move {
+            conn.list_routines(&db).await.map_err(|e| e.to_string())
+        });
+        self.pending_routines.push((database.to_string(), routines));
+    }
+
+    pub fn expand(&mut self, runtime: &Handle, database: &str) {
+        self.expanded_databases.insert(database.to_string());
+        if !self.tables.contains_key(database) {
+            self.load_tables_and_views(runtime, database);
+        }
+    }
+
+    /// Expand these databases, as they were in an earlier run, once the
+    /// database list has loaded
+    pub fn restore_expanded(&mut self, databases: Vec<String>) {
+        self.restore_expanded = databases;
+    }
+
+    /// Collect the results of finished tasks
+    fn poll(&mut self, runtime: &Handle) -> Option<SessionEvent> {
+        let mut event = None;
+
+        if let Some(rx) = &mut self.pending_connection {
+            if let Ok(result) = rx.try_recv() {
+                self.pending_connection = None;
+                match result {
+                    Ok(conn) => {
+                        self.connection = Some(Arc::from(conn));
+                        self.state = ConnectionState::Connected;
+                        self.refresh(runtime);
+                        event = Some(SessionEvent::Connected);
+                    }
+                    Err(e) => {
+                        self.state = ConnectionState::Error;
+                        self.error = Some(e.clone());
+                        event = Some(SessionEvent::Failed(e));
+                    }
+                }
+            }
+        }
+
+        if let Some(rx) = &mut self.pending_databases {
+            if let Ok(result) = rx.try_recv() {
+                self.pending_databases = None;
+                match result {
+                    Ok(databases) => self.set_databases(runtime, databases),
+                    Err(e) => tracing::error!("Failed to load databases: {}", e),
+                }
+            }
+        }
+
+        for (database, result) in take_finished(&mut self.pending_tables) {
+            match result {
+                Ok(tables) => {
+                    self.tables.insert(database, tables);
+                }
+                Err(e) => tracing::error!("Failed to load tables: {}", e),
+            }
+        }
+        for (database, result) in take_finished(&mut self.pending_views) {
+            match result {
+                Ok(views) => {
+                    self.views.insert(database, views);
+                }
+                Err(e) => tracing::error!("Failed to load views: {}", e),
+            }
+        }
+        for (database, result) in take_finished(&mut self.pending_routines) {
+            match result {
+                Ok(routines) => {
+                    self.routines.insert(database, routines);
+                }
+                Err(e) => tracing::error!("Failed to load routines: {}", e),
+            }
+        }
+
+        event
+    }
+
+    fn set_databases(&mut self, runtime: &Handle, databases: Vec<DatabaseInfo>) {
+        // Only show the configured database, if there is one. For SQLite the
+        // configured database is a file path.
+        let config = &self.config;
+        self.databases = if !config.database.is_empty() && config.db_type != DatabaseType::SQLite {
+            databases
+                .into_iter()
+                .filter(|db| db.name == config.database)
+                .collect()
+        } else {
+            databases
+        };
+
+        for name in std::mem::take(&mut self.restore_expanded) {
+            if self.databases.iter().any(|db| db.name == name) {
+                self.expand(runtime, &name);
+            }
+        }
+
+        // Auto-expand if single database
+        if let [db] = self.databases.as_slice() {
+            let name = db.name.clone();
+            self.expand(runtime, &name);
+        }
+    }
+}
+
+/// Every connection the user has open, in the order they were opened
+#[derive(Default)]
+pub struct Sessions {
+    sessions: Vec<Session>,
+}
+
+impl Sessions {
+    /// Connect to `config`, unless a session for it is already open or on
+    /// its way. Returns the session's id.
+    pub fn open(&mut self, runtime: &Handle, config: ConnectionConfig) -> Uuid {
+        let id = config.id;
+        match self.get_mut(id) {
+            Some(session) if session.state == ConnectionState::Error => {
+                session.config = config;
+                session.reconnect(runtime);
+            }
+            Some(_) => {}
+            None => self.sessions.push(Session::open(runtime, config)),
+        }
+        id
+    }
+
+    /// Disconnect and forget a session
+    pub fn close(&mut self, runtime: &Handle, id: Uuid) {
+        let Some(index) = self.sessions.iter().position(|s| s.id() == id) else {
+            return;
+        };
+        let session = self.sessions.remove(index);
+        if let Some(conn) = session.connection {
+            runtime.spawn(async move {
+                if let Err(e) = conn.close().await {
+                    tracing::warn!("Failed to close connection: {}", e);
+                }
+            });
+        }
+    }
+
+    pub fn get(&self, id: Uuid) -> Option<&Session> {
+        self.sessions.iter().find(|s| s.id() == id)
+    }
+
+    pub fn get_mut(&mut self, id: Uuid) -> Option<&mut Session> {
+        self.sessions.iter_mut().find(|s| s.id() == id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Session> {
+        self.sessions.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    pub fn is_busy(&self) -> bool {
+        self.sessions.iter().any(Session::is_busy)
+    }
+
+    /// Poll every session, returning what happened to which
+    pub fn poll(&mut self, runtime: &Handle) -> Vec<(Uuid, SessionEvent)> {
+        self.sessions
+            .iter_mut()
+            .filter_map(|session| Some((session.id(), session.poll(runtime)?)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    fn sqlite_config(name: &str) -> ConnectionConfig {
+        ConnectionConfig {
+            name: name.to_string(),
+            db_type: DatabaseType::SQLite,
+            database: ":memory:".to_string(),
+            ..ConnectionConfig::default()
+        }
+    }
+
+    /// Poll until no session has work left, collecting the events
+    fn settle(runtime: &Handle, sessions: &mut Sessions) -> Vec<(Uuid, SessionEvent)> {
+        let started = Instant::now();
+        let mut events = sessions.poll(runtime);
+        while sessions.is_busy() {
+            assert!(
+                started.elapsed() < Duration::from_secs(10),
+                "sessions never settled"
+            );
+            std::thread::sleep(Duration::from_millis(5));
+            events.extend(sessions.poll(runtime));
+        }
+        events
+    }
+
+    #[test]
+    fn test_sessions() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let handle = runtime.handle();
+        let mut sessions = Sessions::default();
+
+        let first = sessions.open(handle, sqlite_config("first"));
+        let second = sessions.open(handle, sqlite_config("second"));
+        // Opening an open connection again does not start another session
+        assert_eq!(
+            sessions.open(handle, sessions.get(first).unwrap().config.clone()),
+            first
+        );
+        assert_eq!(sessions.iter().count(), 2);
+
+        let events = settle(handle, &mut sessions);
+        assert_eq!(events.len(), 2);
+        assert!(events
+            .iter()
+            .all(|(_, event)| matches!(event, SessionEvent::Connected)));
+
+        // Each session has its own tree, with the only database expanded
+        let session = sessions.get(second).unwrap();
+        assert_eq!(session.databases[0].name, "main");
+        assert!(session.expanded_databases.contains("main"));
+        assert!(session.tables.contains_key("main"));
+        assert!(session.routines.contains_key("main"));
+
+        sessions.close(handle, first);
+        assert!(sessions.get(first).is_none());
+        assert!(sessions.get(second).unwrap().connected().is_some());
+    }
+
+    #[test]
+    fn test_failed_session() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let handle = runtime.handle();
+        let mut sessions = Sessions::default();
+
+        let mut config = sqlite_config("missing");
+        config.database = "/nonexistent/nebula.db".to_string();
+        config.read_only = true;
+        let id = sessions.open(handle, config);
+
+        let events = settle(handle, &mut sessions);
+        assert!(
+            matches!(events.as_slice(), [(event_id, SessionEvent::Failed(_))] if *event_id == id)
+        );
+        let session = sessions.get(id).unwrap();
+        assert_eq!(session.state, ConnectionState::Error);
+        assert!(session.error.is_some() && session.connected().is_none());
+    }
+}
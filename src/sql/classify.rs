@@ -0,0 +1,357 @@
+use crate::sql::lexer::{tokenize, Token, TokenKind};
+use crate::sql::Dialect;
+
+/// What a statement does, as far as can be told without asking the server
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StatementClass {
+    /// Produces a result set, so it should be fetched rather than executed
+    pub returns_rows: bool,
+    pub modifies_data: bool,
+    /// DDL and privilege changes
+    pub changes_schema: bool,
+    /// BEGIN, COMMIT, ROLLBACK, SAVEPOINT and friends
+    pub transaction_control: bool,
+    /// Not a statement the classifier knows, so it may do anything
+    pub unrecognized: bool,
+}
+
+impl StatementClass {
+    const ROWS: Self = Self {
+        returns_rows: true,
+        modifies_data: false,
+        changes_schema: false,
+        transaction_control: false,
+        unrecognized: false,
+    };
+
+    const UNRECOGNIZED: Self = Self {
+        returns_rows: false,
+        modifies_data: false,
+        changes_schema: false,
+        transaction_control: false,
+        unrecognized: true,
+    };
+
+    /// Whether running the statement can change anything on the server.
+    /// Statements that can't be told apart are assumed to.
+    pub fn is_write(&self) -> bool {
+        self.modifies_data || self.changes_schema || self.unrecognized
+    }
+}
+
+/// Classify a single SQL statement
+pub fn classify(sql: &str, dialect: Dialect) -> StatementClass {
+    let tokens: Vec<Token> = tokenize(sql, dialect)
+        .into_iter()
+        .filter(|t| !t.is_trivia())
+        .collect();
+    classify_tokens(&tokens, dialect)
+}
+
+fn classify_tokens(tokens: &[Token], dialect: Dialect) -> StatementClass {
+    // `(SELECT ...) UNION (SELECT ...)`
+    let start = tokens
+        .iter()
+        .position(|t| !t.is_symbol('('))
+        .unwrap_or(tokens.len());
+    let tokens = &tokens[start..];
+    let Some(first) = tokens.first() else {
+        return StatementClass::default();
+    };
+    if first.kind != TokenKind::Word {
+        return StatementClass::UNRECOGNIZED;
+    }
+    let rest = &tokens[1..];
+
+    match first.text.to_ascii_uppercase().as_str() {
+        "SELECT" => classify_select(rest, dialect),
+        // `PRAGMA name = value` changes a setting, often of the file itself
+        "PRAGMA" if rest.iter().any(|t| t.is_symbol('=')) => StatementClass {
+            returns_rows: true,
+            modifies_data: true,
+            ..Default::default()
+        },
+        "WITH" => classify_with(rest, dialect),
+        "VALUES" | "TABLE" | "SHOW" | "DESCRIBE" | "DESC" | "PRAGMA" | "HELP" => {
+            StatementClass::ROWS
+        }
+        "EXPLAIN" => classify_explain(rest, dialect),
+        "INSERT" | "UPDATE" | "DELETE" | "MERGE" | "REPLACE" | "UPSERT" => StatementClass {
+            returns_rows: find_top_level(rest, "RETURNING").is_some(),
+            modifies_data: true,
+            ..Default::default()
+        },
+        "COPY" | "LOAD" => StatementClass {
+            modifies_data: true,
+            ..Default::default()
+        },
+        "CREATE" | "ALTER" | "DROP" | "RENAME" | "COMMENT" | "GRANT" | "REVOKE" => StatementClass {
+            changes_schema: true,
+            ..Default::default()
+        },
+        "TRUNCATE" => StatementClass {
+            modifies_data: true,
+            changes_schema: true,
+            ..Default::default()
+        },
+        "BEGIN" | "START" | "COMMIT" | "ROLLBACK" | "SAVEPOINT" | "RELEASE" | "END" | "ABORT" => {
+            StatementClass {
+                transaction_control: true,
+                ..Default::default()
+            }
+        }
+        "SET" if rest.first().is_some_and(|t| t.is_keyword("TRANSACTION")) => StatementClass {
+            transaction_control: true,
+            ..Default::default()
+        },
+        // Settings of the session only; MySQL's GLOBAL and PERSIST outlive it
+        "SET" | "RESET" | "USE"
+            if !rest.first().is_some_and(|t| {
+                ["GLOBAL", "PERSIST", "PERSIST_ONLY"]
+                    .iter()
+                    .any(|keyword| t.is_keyword(keyword))
+            }) =>
+        {
+            StatementClass::default()
+        }
+        // A procedure may return result sets and write, so assume both
+        "CALL" | "EXEC" | "EXECUTE" | "DO" => StatementClass {
+            returns_rows: true,
+            modifies_data: true,
+            ..Default::default()
+        },
+        // MySQL's table maintenance statements report a status table
+        "ANALYZE" | "CHECK" | "CHECKSUM" | "OPTIMIZE" | "REPAIR" if dialect == Dialect::MySql => {
+            StatementClass::ROWS
+        }
+        _ => StatementClass::UNRECOGNIZED,
+    }
+}
+
+/// `SELECT ... INTO` stores its result instead of returning it. PostgreSQL
+/// creates a table from it, MySQL fills variables or a file.
+fn classify_select(rest: &[Token], dialect: Dialect) -> StatementClass {
+    if find_top_level(rest, "INTO").is_none() {
+        return StatementClass::ROWS;
+    }
+    let creates_table = dialect == Dialect::Postgres;
+    StatementClass {
+        modifies_data: creates_table,
+        changes_schema: creates_table,
+        ..Default::default()
+    }
+}
+
+/// `WITH [RECURSIVE] name [(columns)] AS [[NOT] MATERIALIZED] (body), ...`
+/// followed by the main statement. PostgreSQL allows the bodies to modify
+/// data too.
+fn classify_with(tokens: &[Token], dialect: Dialect) -> StatementClass {
+    let mut i = usize::from(tokens.first().is_some_and(|t| t.is_keyword("RECURSIVE")));
+    let mut modifies_data = false;
+
+    loop {
+        // Past the CTE name, then its optional column list
+        i += 1;
+        if tokens.get(i).is_some_and(|t| t.is_symbol('(')) {
+            i = skip_group(tokens, i);
+        }
+        if !tokens.get(i).is_some_and(|t| t.is_keyword("AS")) {
+            break;
+        }
+        i += 1;
+        while tokens
+            .get(i)
+            .is_some_and(|t| t.is_keyword("NOT") || t.is_keyword("MATERIALIZED"))
+        {
+            i += 1;
+        }
+        if !tokens.get(i).is_some_and(|t| t.is_symbol('(')) {
+            break;
+        }
+        let end = skip_group(tokens, i);
+        let body = &tokens[i + 1..end.saturating_sub(1).max(i + 1)];
+        modifies_data |= classify_tokens(body, dialect).modifies_data;
+        i = end;
+
+        if !tokens.get(i).is_some_and(|t| t.is_symbol(',')) {
+            break;
+        }
+        // On to the next CTE's name
+        i += 1;
+    }
+
+    let mut class = classify_tokens(tokens.get(i..).unwrap_or_default(), dialect);
+    class.modifies_data |= modifies_data;
+    class
+}
+
+/// EXPLAIN returns the plan, but with ANALYZE the statement really runs
+fn classify_explain(rest: &[Token], dialect: Dialect) -> StatementClass {
+    let analyze = match rest.first() {
+        Some(t) if t.is_keyword("ANALYZE") => true,
+        // PostgreSQL's `EXPLAIN (ANALYZE, BUFFERS) ...`
+        Some(t) if t.is_symbol('(') => rest[..skip_group(rest, 0)]
+            .iter()
+            .any(|t| t.is_keyword("ANALYZE")),
+        _ => false,
+    };
+
+    let inner = rest
+        .iter()
+        .position(|t| {
+            [
+                "SELECT", "WITH", "INSERT", "UPDATE", "DELETE", "MERGE", "REPLACE", "VALUES",
+                "TABLE",
+            ]
+            .iter()
+            .any(|keyword| t.is_keyword(keyword))
+        })
+        .map(|start| classify_tokens(&rest[start..], dialect))
+        .unwrap_or_default();
+
+    StatementClass {
+        returns_rows: true,
+        modifies_data: analyze && inner.modifies_data,
+        ..Default::default()
+    }
+}
+
+/// Index just past the parenthesised group opening at `open`
+fn skip_group(tokens: &[Token], open: usize) -> usize {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        if token.is_symbol('(') {
+            depth += 1;
+        } else if token.is_symbol(')') {
+            depth -= 1;
+            if depth == 0 {
+                return i + 1;
+            }
+        }
+    }
+    tokens.len()
+}
+
+/// Position of `keyword` outside any parentheses
+fn find_top_level(tokens: &[Token], keyword: &str) -> Option<usize> {
+    let mut depth = 0i32;
+    tokens.iter().position(|token| {
+        if token.is_symbol('(') {
+            depth += 1;
+        } else if token.is_symbol(')') {
+            depth -= 1;
+        }
+        depth <= 0 && token.is_keyword(keyword)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(sql: &str, dialect: Dialect) -> bool {
+        classify(sql, dialect).returns_rows
+    }
+
+    #[test]
+    fn test_returns_rows() {
+        use Dialect::*;
+
+        assert!(rows("select 1", MySql));
+        assert!(rows("-- latest\n/* all */ SELECT * FROM t", Postgres));
+        assert!(rows("# note\nSELECT 1", MySql));
+        assert!(rows("(SELECT 1) UNION (SELECT 2)", MySql));
+        assert!(rows(
+            "WITH RECURSIVE n(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM n WHERE x < 5) SELECT x FROM n",
+            Sqlite
+        ));
+        assert!(rows(
+            "WITH a AS (SELECT 1), b AS (SELECT 2) SELECT * FROM a, b",
+            MySql
+        ));
+        assert!(rows("VALUES (1), (2)", Postgres));
+        assert!(rows("TABLE users", Postgres));
+        assert!(rows("SHOW TABLES", MySql));
+        assert!(rows("PRAGMA table_info(users)", Sqlite));
+        assert!(rows("INSERT INTO t (a) VALUES (1) RETURNING id", Postgres));
+
+        assert!(!rows("INSERT INTO t SELECT * FROM u", MySql));
+        assert!(!rows(
+            "UPDATE t SET a = (SELECT 1) WHERE id IN (SELECT 1)",
+            MySql
+        ));
+        assert!(!rows("SELECT a INTO @a FROM t", MySql));
+        assert!(!rows("-- SELECT\nDELETE FROM t", Postgres));
+    }
+
+    #[test]
+    fn test_side_effects() {
+        use Dialect::*;
+
+        let class = classify("SELECT * FROM t WHERE name = 'DROP TABLE x'", MySql);
+        assert!(!class.is_write());
+
+        let class = classify(
+            "WITH gone AS (DELETE FROM t WHERE old RETURNING *) SELECT count(*) FROM gone",
+            Postgres,
+        );
+        assert!(class.returns_rows && class.modifies_data);
+
+        let class = classify(
+            "WITH a AS (SELECT 1), b (x) AS (DELETE FROM t RETURNING id) SELECT * FROM b",
+            Postgres,
+        );
+        assert!(class.returns_rows && class.modifies_data);
+
+        let class = classify(
+            "WITH a AS (SELECT 1), b AS (SELECT 2), c AS (UPDATE t SET a = 1 RETURNING a) \
+             SELECT * FROM a, b, c",
+            Postgres,
+        );
+        assert!(class.returns_rows && class.modifies_data);
+
+        let class = classify("WITH x AS (SELECT 1) UPDATE t SET a = 1", Postgres);
+        assert!(!class.returns_rows && class.modifies_data);
+
+        let class = classify("EXPLAIN (ANALYZE, BUFFERS) DELETE FROM t", Postgres);
+        assert!(class.returns_rows && class.modifies_data);
+        assert!(!classify("EXPLAIN DELETE FROM t", Postgres).modifies_data);
+
+        assert!(classify("SELECT * INTO archive FROM t", Postgres).changes_schema);
+        assert!(classify("create index i on t (a)", Sqlite).changes_schema);
+
+        let class = classify("TRUNCATE t", MySql);
+        assert!(class.modifies_data && class.changes_schema);
+
+        assert!(classify("BEGIN", Sqlite).transaction_control);
+        assert!(classify("start transaction", MySql).transaction_control);
+        assert!(
+            classify("SET TRANSACTION ISOLATION LEVEL SERIALIZABLE", Postgres).transaction_control
+        );
+        assert_eq!(
+            classify("SET search_path = app", Postgres),
+            StatementClass::default()
+        );
+        assert!(!classify("USE shop", MySql).is_write());
+        assert!(!classify("PRAGMA table_info(users)", Sqlite).is_write());
+    }
+
+    #[test]
+    fn test_unrecognized_is_write() {
+        use Dialect::*;
+
+        for (sql, dialect) in [
+            ("VACUUM", Postgres),
+            ("VACUUM", Sqlite),
+            ("PRAGMA journal_mode = WAL", Sqlite),
+            ("SET GLOBAL max_connections = 10", MySql),
+            ("CALL archive()", MySql),
+            ("DO $$ BEGIN DELETE FROM t; END $$", Postgres),
+            ("COPY t FROM '/tmp/t.csv'", Postgres),
+            ("SLECT * FROM t", MySql),
+        ] {
+            assert!(classify(sql, dialect).is_write(), "{}", sql);
+        }
+        assert!(!classify("", Postgres).is_write());
+    }
+}
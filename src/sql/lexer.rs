@@ -0,0 +1,320 @@
+use crate::sql::Dialect;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Whitespace,
+    /// `-- ...`, `/* ... */`, and `# ...` in MySQL
+    Comment,
+    /// A keyword or unquoted identifier
+    Word,
+    QuotedIdent,
+    /// A string literal, including PostgreSQL dollar-quoted strings
+    String,
+    Number,
+    /// Any other single character: punctuation and operators
+    Symbol,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    pub text: &'a str,
+    /// Byte offset of the token in the source
+    pub start: usize,
+}
+
+impl Token<'_> {
+    pub fn end(&self) -> usize {
+        self.start + self.text.len()
+    }
+
+    /// Whitespace and comments, which carry no meaning
+    pub fn is_trivia(&self) -> bool {
+        matches!(self.kind, TokenKind::Whitespace | TokenKind::Comment)
+    }
+
+    pub fn is_keyword(&self, keyword: &str) -> bool {
+        self.kind == TokenKind::Word && self.text.eq_ignore_ascii_case(keyword)
+    }
+
+    pub fn is_symbol(&self, symbol: char) -> bool {
+        self.kind == TokenKind::Symbol && self.text.starts_with(symbol)
+    }
+}
+
+/// Split SQL into tokens. Never fails: unterminated strings and comments
+/// run to the end of the input.
+pub fn tokenize(sql: &str, dialect: Dialect) -> Vec<Token<'_>> {
+    let mut lexer = Lexer {
+        sql,
+        pos: 0,
+        dialect,
+    };
+    let mut tokens = Vec::new();
+    while lexer.pos < sql.len() {
+        let start = lexer.pos;
+        let kind = lexer.next_kind();
+        tokens.push(Token {
+            kind,
+            text: &sql[start..lexer.pos],
+            start,
+        });
+    }
+    tokens
+}
+
+struct Lexer<'a> {
+    sql: &'a str,
+    pos: usize,
+    dialect: Dialect,
+}
+
+impl Lexer<'_> {
+    fn rest(&self) -> &str {
+        &self.sql[self.pos..]
+    }
+
+    fn peek(&self, n: usize) -> Option<char> {
+        self.rest().chars().nth(n)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek(0)?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn bump_while(&mut self, f: impl Fn(char) -> bool) {
+        while self.peek(0).is_some_and(&f) {
+            self.bump();
+        }
+    }
+
+    /// Consume one token and report its kind
+    fn next_kind(&mut self) -> TokenKind {
+        let c = self.peek(0).unwrap_or_default();
+        let next = self.peek(1);
+
+        match c {
+            c if c.is_whitespace() => {
+                self.bump_while(char::is_whitespace);
+                TokenKind::Whitespace
+            }
+            '-' if next == Some('-') && self.line_comment_allowed() => {
+                self.bump_while(|c| c != '\n');
+                TokenKind::Comment
+            }
+            '#' if self.dialect == Dialect::MySql => {
+                self.bump_while(|c| c != '\n');
+                TokenKind::Comment
+            }
+            '/' if next == Some('*') => {
+                self.block_comment();
+                TokenKind::Comment
+            }
+            '\'' => {
+                self.quoted('\'', self.dialect == Dialect::MySql);
+                TokenKind::String
+            }
+            'e' | 'E' if next == Some('\'') && self.dialect == Dialect::Postgres => {
+                self.bump();
+                self.quoted('\'', true);
+                TokenKind::String
+            }
+            '"' if self.dialect == Dialect::MySql => {
+                self.quoted('"', true);
+                TokenKind::String
+            }
+            '"' => {
+                self.quoted('"', false);
+                TokenKind::QuotedIdent
+            }
+            '`' if self.dialect != Dialect::Postgres => {
+                self.quoted('`', false);
+                TokenKind::QuotedIdent
+            }
+            '[' if self.dialect == Dialect::Sqlite => {
+                self.bump();
+                self.bump_while(|c| c != ']');
+                self.bump();
+                TokenKind::QuotedIdent
+            }
+            '$' if self.dialect == Dialect::Postgres && self.dollar_quoted() => TokenKind::String,
+            c if c.is_ascii_digit() || (c == '.' && next.is_some_and(|n| n.is_ascii_digit())) => {
+                self.number();
+                TokenKind::Number
+            }
+            c if is_word_start(c) => {
+                self.bump_while(is_word_char);
+                TokenKind::Word
+            }
+            _ => {
+                self.bump();
+                TokenKind::Symbol
+            }
+        }
+    }
+
+    /// MySQL only treats `--` as a comment when followed by whitespace
+    fn line_comment_allowed(&self) -> bool {
+        self.dialect != Dialect::MySql || self.peek(2).is_none_or(char::is_whitespace)
+    }
+
+    /// `/* ... */`, which PostgreSQL allows to nest
+    fn block_comment(&mut self) {
+        self.pos += 2;
+        let mut depth = 1;
+        while depth > 0 && self.pos < self.sql.len() {
+            if self.rest().starts_with("*/") {
+                self.pos += 2;
+                depth -= 1;
+            } else if self.rest().starts_with("/*") && self.dialect == Dialect::Postgres {
+                self.pos += 2;
+                depth += 1;
+            } else {
+                self.bump();
+            }
+        }
+    }
+
+    /// A quoted string or identifier where a doubled quote stands for
+    /// itself, optionally with backslash escapes
+    fn quoted(&mut self, quote: char, backslash_escapes: bool) {
+        self.bump();
+        while let Some(c) = self.bump() {
+            if c == '\\' && backslash_escapes {
+                self.bump();
+            } else if c == quote {
+                if self.peek(0) == Some(quote) {
+                    self.bump();
+                } else {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// `$tag$ ... $tag$`. Returns false, consuming nothing, if the `$` does
+    /// not open a dollar quote (e.g. a `$1` parameter).
+    fn dollar_quoted(&mut self) -> bool {
+        let rest = self.rest();
+        let tag_len = rest[1..]
+            .find(|c: char| !is_word_char(c) || c == '$')
+            .map(|i| i + 1);
+        let Some(tag_end) = tag_len.filter(|&end| rest[end..].starts_with('$')) else {
+            return false;
+        };
+        if rest[1..].starts_with(|c: char| c.is_ascii_digit()) {
+            return false;
+        }
+
+        let tag = &rest[..=tag_end];
+        let body = tag.len();
+        self.pos += match rest[body..].find(tag) {
+            Some(close) => body + close + tag.len(),
+            None => rest.len(),
+        };
+        true
+    }
+
+    fn number(&mut self) {
+        self.bump_while(|c| c.is_ascii_digit());
+        if self.peek(0) == Some('.') {
+            self.bump();
+            self.bump_while(|c| c.is_ascii_digit());
+        }
+        let exponent = matches!(self.peek(0), Some('e' | 'E'))
+            && match self.peek(1) {
+                Some('+' | '-') => self.peek(2).is_some_and(|c| c.is_ascii_digit()),
+                Some(c) => c.is_ascii_digit(),
+                None => false,
+            };
+        if exponent {
+            self.bump();
+            self.bump();
+            self.bump_while(|c| c.is_ascii_digit());
+        }
+    }
+}
+
+fn is_word_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(sql: &str, dialect: Dialect) -> Vec<(TokenKind, &str)> {
+        tokenize(sql, dialect)
+            .into_iter()
+            .filter(|t| t.kind != TokenKind::Whitespace)
+            .map(|t| (t.kind, t.text))
+            .collect()
+    }
+
+    #[test]
+    fn test_quotes_and_comments() {
+        use TokenKind::*;
+
+        assert_eq!(
+            kinds("SELECT 'it''s', \"a\"\"b\" -- done", Dialect::Postgres),
+            vec![
+                (Word, "SELECT"),
+                (String, "'it''s'"),
+                (Symbol, ","),
+                (QuotedIdent, "\"a\"\"b\""),
+                (Comment, "-- done"),
+            ]
+        );
+        assert_eq!(
+            kinds("SELECT \"a\\\"b\", `t` # note\n", Dialect::MySql),
+            vec![
+                (Word, "SELECT"),
+                (String, "\"a\\\"b\""),
+                (Symbol, ","),
+                (QuotedIdent, "`t`"),
+                (Comment, "# note"),
+            ]
+        );
+        // `--1` is double negation in MySQL
+        assert_eq!(
+            kinds("SELECT 1--1", Dialect::MySql),
+            vec![
+                (Word, "SELECT"),
+                (Number, "1"),
+                (Symbol, "-"),
+                (Symbol, "-"),
+                (Number, "1"),
+            ]
+        );
+        assert_eq!(
+            kinds("/* a /* b */ c */ x", Dialect::Postgres),
+            vec![(Comment, "/* a /* b */ c */"), (Word, "x")]
+        );
+    }
+
+    #[test]
+    fn test_dollar_quotes() {
+        use TokenKind::*;
+
+        assert_eq!(
+            kinds("AS $fn$ SELECT ';' $x$ $fn$ , $1", Dialect::Postgres),
+            vec![
+                (Word, "AS"),
+                (String, "$fn$ SELECT ';' $x$ $fn$"),
+                (Symbol, ","),
+                (Symbol, "$"),
+                (Number, "1"),
+            ]
+        );
+        assert_eq!(
+            kinds("$$ it's $$", Dialect::Postgres),
+            vec![(String, "$$ it's $$")]
+        );
+    }
+}
@@ -0,0 +1,27 @@
+pub mod classify;
+pub mod lexer;
+pub mod params;
+pub mod source;
+pub mod split;
+
+use crate::models::DatabaseType;
+
+/// The flavour of SQL being parsed. Affects quoting and comment syntax.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    MySql,
+    Postgres,
+    Sqlite,
+}
+
+impl Dialect {
+    /// The dialect spoken by a database, or `None` if it doesn't use SQL
+    pub fn for_database(db_type: DatabaseType) -> Option<Self> {
+        match db_type {
+            DatabaseType::MySQL => Some(Dialect::MySql),
+            DatabaseType::PostgreSQL => Some(Dialect::Postgres),
+            DatabaseType::SQLite => Some(Dialect::Sqlite),
+            DatabaseType::MongoDB => None,
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use crate::models::ParamValue;
+use crate::sql::lexer::{tokenize, Token, TokenKind};
+use crate::sql::Dialect;
+use std::collections::HashMap;
+
+/// A placeholder for a bound value: `:name`, `$1`, or `?` outside
+/// PostgreSQL, where `?` is a jsonb operator
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Placeholder {
+    /// As written, except that each `?` is numbered (`?1`, `?2`, ...) since
+    /// they are bound by position
+    pub name: String,
+    /// Byte range of the placeholder in the statement
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Every placeholder in `sql`, in order of appearance
+pub fn placeholders(sql: &str, dialect: Dialect) -> Vec<Placeholder> {
+    placeholders_after(sql, dialect, 0)
+}
+
+/// Placeholders of a statement that follows `question_marks` `?`
+/// placeholders in the same script, which carry on their numbering
+fn placeholders_after(sql: &str, dialect: Dialect, mut question_marks: usize) -> Vec<Placeholder> {
+    let tokens = tokenize(sql, dialect);
+    let mut placeholders = Vec::new();
+    // Inside a PostgreSQL array subscript, where `[lo:hi]` is a slice
+    let mut brackets = 0usize;
+
+    for (i, token) in tokens.iter().enumerate() {
+        let next = tokens.get(i + 1).filter(|next| next.start == token.end());
+        let prev = i.checked_sub(1).map(|p| &tokens[p]);
+
+        if dialect == Dialect::Postgres && token.is_symbol('[') {
+            brackets += 1;
+        } else if dialect == Dialect::Postgres && token.is_symbol(']') {
+            brackets = brackets.saturating_sub(1);
+        } else if token.is_symbol(':') {
+            // Not the second colon of a PostgreSQL `::type` cast
+            let cast = prev.is_some_and(|prev| prev.is_symbol(':') && prev.end() == token.start);
+            let word = next.filter(|next| next.kind == TokenKind::Word);
+            if let Some(word) = word.filter(|_| !cast && brackets == 0) {
+                placeholders.push(placeholder(token, word));
+            }
+        } else if token.is_symbol('$') {
+            if let Some(number) = next.filter(|next| is_position(next)) {
+                placeholders.push(placeholder(token, number));
+            }
+        } else if token.is_symbol('?') && dialect != Dialect::Postgres {
+            question_marks += 1;
+            placeholders.push(Placeholder {
+                name: format!("?{}", question_marks),
+                start: token.start,
+                end: token.end(),
+            });
+        }
+    }
+    placeholders
+}
+
+/// Distinct placeholder names, in order of first appearance
+pub fn param_names(sql: &str, dialect: Dialect) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for placeholder in placeholders(sql, dialect) {
+        if !names.contains(&placeholder.name) {
+            names.push(placeholder.name);
+        }
+    }
+    names
+}
+
+/// Each statement of a script in the dialect's native placeholder form,
+/// with the values to bind to it. `?` is numbered across the whole script,
+/// as `param_names` does for its text.
+pub fn bind_statements<S: AsRef<str>>(
+    statements: &[S],
+    dialect: Dialect,
+    values: &HashMap<String, ParamValue>,
+) -> Vec<(String, Vec<ParamValue>)> {
+    let mut question_marks = 0;
+    statements
+        .iter()
+        .map(|sql| {
+            let sql = sql.as_ref();
+            let bound = bind(sql, dialect, values, question_marks);
+            question_marks += placeholders(sql, dialect)
+                .iter()
+                .filter(|placeholder| placeholder.name.starts_with('?'))
+                .count();
+            bound
+        })
+        .collect()
+}
+
+/// Rewrite the placeholders of `sql` into the dialect's native positional
+/// form and list the values to bind, in order. Names missing from `values`
+/// bind NULL. `?` are numbered from after the first `question_marks`.
+fn bind(
+    sql: &str,
+    dialect: Dialect,
+    values: &HashMap<String, ParamValue>,
+    question_marks: usize,
+) -> (String, Vec<ParamValue>) {
+    let mut rewritten = String::with_capacity(sql.len());
+    let mut params = Vec::new();
+    // PostgreSQL can refer to one parameter several times
+    let mut positions: Vec<String> = Vec::new();
+    let mut pos = 0;
+
+    for placeholder in placeholders_after(sql, dialect, question_marks) {
+        rewritten.push_str(&sql[pos..placeholder.start]);
+        pos = placeholder.end;
+        let value = values
+            .get(&placeholder.name)
+            .cloned()
+            .unwrap_or(ParamValue::Null);
+
+        if dialect == Dialect::Postgres {
+            let index = match positions.iter().position(|name| *name == placeholder.name) {
+                Some(index) => index,
+                None => {
+                    positions.push(placeholder.name);
+                    params.push(value);
+                    positions.len() - 1
+                }
+            };
+            rewritten.push_str(&format!("${}", index + 1));
+        } else {
+            rewritten.push('?');
+            params.push(value);
+        }
+    }
+    rewritten.push_str(&sql[pos..]);
+
+    (rewritten, params)
+}
+
+fn placeholder(sigil: &Token, name: &Token) -> Placeholder {
+    Placeholder {
+        name: format!("{}{}", sigil.text, name.text),
+        start: sigil.start,
+        end: name.end(),
+    }
+}
+
+/// The digits of `$1`
+fn is_position(token: &Token) -> bool {
+    token.kind == TokenKind::Number && token.text.chars().all(|c| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(sql: &str, dialect: Dialect) -> Vec<String> {
+        param_names(sql, dialect)
+    }
+
+    #[test]
+    fn test_placeholders() {
+        assert_eq!(
+            names(
+                "SELECT * FROM t WHERE a = :id AND b = :name OR c = :id",
+                Dialect::MySql
+            ),
+            vec![":id", ":name"]
+        );
+        assert_eq!(
+            names("SELECT $2::int, $1, ':x', \"?\"", Dialect::Postgres),
+            vec!["$2", "$1"]
+        );
+        assert_eq!(
+            names("SELECT data ? 'key' FROM t", Dialect::Postgres),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            names("SELECT ? -- ?\n, ?", Dialect::Sqlite),
+            vec!["?1", "?2"]
+        );
+        assert_eq!(
+            names("SET @a := 1; SELECT x::text", Dialect::MySql),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn test_array_slices() {
+        assert_eq!(
+            names(
+                "SELECT arr[1:n], a[lo:hi], m[1:2][i:j] FROM t WHERE id = :id",
+                Dialect::Postgres
+            ),
+            vec![":id"]
+        );
+    }
+
+    #[test]
+    fn test_bind() {
+        let values = HashMap::from([
+            (":id".to_string(), ParamValue::Int(7)),
+            (":name".to_string(), ParamValue::String("a".to_string())),
+        ]);
+        let sql = "SELECT * FROM t WHERE id = :id AND name = :name OR parent = :id";
+
+        let (rewritten, params) = bind(sql, Dialect::Postgres, &values, 0);
+        assert_eq!(
+            rewritten,
+            "SELECT * FROM t WHERE id = $1 AND name = $2 OR parent = $1"
+        );
+        assert_eq!(params.len(), 2);
+
+        let (rewritten, params) = bind(sql, Dialect::MySql, &values, 0);
+        assert_eq!(
+            rewritten,
+            "SELECT * FROM t WHERE id = ? AND name = ? OR parent = ?"
+        );
+        assert_eq!(params[2], ParamValue::Int(7));
+
+        let (rewritten, params) = bind("SELECT $2, $1", Dialect::Postgres, &HashMap::new(), 0);
+        assert_eq!(rewritten, "SELECT $1, $2");
+        assert_eq!(params, vec![ParamValue::Null, ParamValue::Null]);
+
+        // `?` is numbered across the script, as the bind form asks for it
+        let script = "INSERT INTO t VALUES (?); INSERT INTO t VALUES (?, ?)";
+        assert_eq!(names(script, Dialect::Sqlite), vec!["?1", "?2", "?3"]);
+        let values = HashMap::from([
+            ("?1".to_string(), ParamValue::Int(1)),
+            ("?2".to_string(), ParamValue::Int(2)),
+            ("?3".to_string(), ParamValue::Int(3)),
+        ]);
+        let statements = ["INSERT INTO t VALUES (?)", "INSERT INTO t VALUES (?, ?)"];
+        let bound = bind_statements(&statements, Dialect::Sqlite, &values);
+        assert_eq!(bound[0].1, vec![ParamValue::Int(1)]);
+        assert_eq!(bound[1].1, vec![ParamValue::Int(2), ParamValue::Int(3)]);
+    }
+}
@@ -0,0 +1,191 @@
+use crate::sql::lexer::{tokenize, Token, TokenKind};
+use crate::sql::Dialect;
+
+/// A table as named in a query: the name and whatever qualifies it, which
+/// is the database in MySQL and the schema elsewhere
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableName {
+    pub qualifier: Option<String>,
+    pub name: String,
+}
+
+/// Keywords that may follow the table of a plain single-table SELECT
+const TRAILING_CLAUSES: [&str; 5] = ["WHERE", "ORDER", "LIMIT", "OFFSET", "FETCH"];
+
+/// Keywords that make a SELECT's rows something other than rows of its
+/// table, so they can't be written back
+const DERIVED: [&str; 10] = [
+    "DISTINCT",
+    "DISTINCTROW",
+    "GROUP",
+    "HAVING",
+    "UNION",
+    "INTERSECT",
+    "EXCEPT",
+    "JOIN",
+    "INTO",
+    "WINDOW",
+];
+
+/// The table a query reads its rows from, if it is a plain `SELECT ... FROM
+/// table` whose rows are rows of that table: no joins, grouping, set
+/// operations or subquery in the FROM clause.
+pub fn source_table(sql: &str, dialect: Dialect) -> Option<TableName> {
+    let mut tokens: Vec<Token> = tokenize(sql, dialect)
+        .into_iter()
+        .filter(|t| !t.is_trivia())
+        .collect();
+    while tokens.last().is_some_and(|t| t.is_symbol(';')) {
+        tokens.pop();
+    }
+    if !tokens.first()?.is_keyword("SELECT") {
+        return None;
+    }
+
+    // Only what is outside parentheses says where the rows come from. An
+    // opening parenthesis stands in for the whole group.
+    let mut depth = 0i32;
+    let mut top_level = Vec::new();
+    for token in &tokens[1..] {
+        if token.is_symbol('(') {
+            if depth == 0 {
+                top_level.push(*token);
+            }
+            depth += 1;
+        } else if token.is_symbol(')') {
+            depth -= 1;
+        } else if depth == 0 {
+            top_level.push(*token);
+        }
+        if depth == 0 && DERIVED.iter().any(|keyword| token.is_keyword(keyword)) {
+            return None;
+        }
+    }
+
+    let from = top_level.iter().position(|t| t.is_keyword("FROM"))?;
+    let mut rest = top_level[from + 1..].iter().peekable();
+    let mut parts = vec![identifier(rest.next()?, dialect)?];
+    while rest.next_if(|t| t.is_symbol('.')).is_some() {
+        parts.push(identifier(rest.next()?, dialect)?);
+    }
+
+    // An alias, then nothing but filtering and ordering
+    if rest.next_if(|t| t.is_keyword("AS")).is_some() {
+        rest.next()?;
+    } else {
+        rest.next_if(|t| {
+            matches!(t.kind, TokenKind::Word | TokenKind::QuotedIdent) && !is_clause(t)
+        });
+    }
+    if rest.peek().is_some_and(|t| !is_clause(t)) {
+        return None;
+    }
+
+    let name = parts.pop()?;
+    match parts.len() {
+        0 => Some(TableName {
+            qualifier: None,
+            name,
+        }),
+        1 => Some(TableName {
+            qualifier: parts.pop(),
+            name,
+        }),
+        _ => None,
+    }
+}
+
+fn is_clause(token: &Token) -> bool {
+    TRAILING_CLAUSES
+        .iter()
+        .any(|keyword| token.is_keyword(keyword))
+}
+
+/// The name an identifier token stands for. Unquoted names fold to lower
+/// case in PostgreSQL.
+fn identifier(token: &Token, dialect: Dialect) -> Option<String> {
+    match token.kind {
+        TokenKind::Word if is_clause(token) => None,
+        TokenKind::Word if dialect == Dialect::Postgres => Some(token.text.to_lowercase()),
+        TokenKind::Word => Some(token.text.to_string()),
+        TokenKind::QuotedIdent => {
+            let quote = token.text.chars().next()?;
+            let inner = token.text.get(1..token.text.len().checked_sub(1)?)?;
+            let close = if quote == '[' { ']' } else { quote };
+            Some(inner.replace(&format!("{}{}", close, close), &close.to_string()))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(qualifier: Option<&str>, name: &str) -> Option<TableName> {
+        Some(TableName {
+            qualifier: qualifier.map(str::to_string),
+            name: name.to_string(),
+        })
+    }
+
+    #[test]
+    fn test_single_table() {
+        use Dialect::*;
+
+        assert_eq!(
+            source_table("SELECT * FROM users", MySql),
+            table(None, "users")
+        );
+        assert_eq!(
+            source_table("SELECT * FROM `shop`.`order``s` LIMIT 100;", MySql),
+            table(Some("shop"), "order`s")
+        );
+        assert_eq!(
+            source_table(
+                "select id, (select 1) from Public.\"Users\" u where id in (select 1) order by 1",
+                Postgres
+            ),
+            table(Some("public"), "Users")
+        );
+        assert_eq!(
+            source_table("SELECT * FROM \"main\".\"t\" AS x WHERE x.a = 1", Sqlite),
+            table(Some("main"), "t")
+        );
+        assert_eq!(
+            source_table("SELECT * FROM t WHERE a = 1", Sqlite),
+            table(None, "t")
+        );
+    }
+
+    #[test]
+    fn test_derived_rows() {
+        use Dialect::*;
+
+        assert_eq!(source_table("SELECT 1", MySql), None);
+        assert_eq!(
+            source_table("SELECT * FROM a JOIN b ON a.id = b.id", MySql),
+            None
+        );
+        assert_eq!(source_table("SELECT * FROM a, b", MySql), None);
+        assert_eq!(source_table("SELECT DISTINCT a FROM t", MySql), None);
+        assert_eq!(
+            source_table("SELECT a, count(*) FROM t GROUP BY a", Postgres),
+            None
+        );
+        assert_eq!(source_table("SELECT * FROM (SELECT 1) x", Postgres), None);
+        assert_eq!(
+            source_table("SELECT a FROM t UNION SELECT a FROM u", Sqlite),
+            None
+        );
+        assert_eq!(
+            source_table("SELECT * FROM generate_series(1, 3)", Postgres),
+            None
+        );
+        assert_eq!(
+            source_table("WITH x AS (SELECT 1) SELECT * FROM x", Postgres),
+            None
+        );
+        assert_eq!(source_table("UPDATE t SET a = 1", MySql), None);
+    }
+}
@@ -0,0 +1,196 @@
+use crate::sql::lexer::{tokenize, Token, TokenKind};
+use crate::sql::Dialect;
+
+/// One statement of a script
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Statement<'a> {
+    /// The statement without its delimiter or surrounding whitespace
+    pub text: &'a str,
+    /// Byte range of `text` in the script
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Split a script into statements on `;`, ignoring delimiters inside
+/// quotes, comments, dollar-quoted bodies and SQLite trigger bodies.
+/// MySQL `DELIMITER` lines switch the delimiter, as in the mysql client,
+/// and are not part of any statement.
+pub fn split(sql: &str, dialect: Dialect) -> Vec<Statement<'_>> {
+    let mut statements = Vec::new();
+    let mut delimiter = ";".to_string();
+    // Significant tokens of the statement being read
+    let mut current: Vec<Token> = Vec::new();
+    // BEGIN/CASE ... END nesting inside a SQLite trigger body
+    let mut depth = 0;
+    let mut skip_to = 0;
+
+    let mut finish = |current: &mut Vec<Token>| {
+        if let (Some(first), Some(last)) = (current.first(), current.last()) {
+            statements.push(Statement {
+                text: &sql[first.start..last.end()],
+                start: first.start,
+                end: last.end(),
+            });
+        }
+        current.clear();
+    };
+
+    for token in tokenize(sql, dialect) {
+        if token.start < skip_to || token.is_trivia() {
+            continue;
+        }
+
+        if dialect == Dialect::MySql && current.is_empty() && token.is_keyword("DELIMITER") {
+            let line_end = sql[token.start..]
+                .find('\n')
+                .map_or(sql.len(), |i| token.start + i);
+            if let Some(new) = sql[token.end()..line_end].split_whitespace().next() {
+                delimiter = new.to_string();
+            }
+            skip_to = line_end;
+            continue;
+        }
+
+        if depth == 0 {
+            if let Some(offset) = find_delimiter(sql, &token, &delimiter) {
+                // A custom delimiter can be glued to a word, as in `END$$`
+                if offset > 0 {
+                    current.push(Token {
+                        text: &token.text[..offset],
+                        ..token
+                    });
+                }
+                finish(&mut current);
+                skip_to = token.start + offset + delimiter.len();
+                continue;
+            }
+        }
+
+        if dialect == Dialect::Sqlite && is_trigger(&current) {
+            if token.is_keyword("BEGIN") || token.is_keyword("CASE") {
+                depth += 1;
+            } else if token.is_keyword("END") {
+                depth -= 1;
+            }
+        }
+        current.push(token);
+    }
+    finish(&mut current);
+
+    statements
+}
+
+/// The statement under the cursor at byte `offset`. Between statements
+/// that is the one before the cursor, as after typing `...;` and a newline.
+pub fn statement_at(sql: &str, dialect: Dialect, offset: usize) -> Option<Statement<'_>> {
+    let statements = split(sql, dialect);
+    let index = statements
+        .iter()
+        .rposition(|statement| statement.start <= offset)
+        .unwrap_or(0);
+    statements.into_iter().nth(index)
+}
+
+/// Offset within `token` where the delimiter starts, if it does. The
+/// delimiter may run on into the following tokens.
+fn find_delimiter(sql: &str, token: &Token, delimiter: &str) -> Option<usize> {
+    if matches!(token.kind, TokenKind::String | TokenKind::QuotedIdent) {
+        return None;
+    }
+    token
+        .text
+        .char_indices()
+        .map(|(i, _)| i)
+        .find(|&i| sql[token.start + i..].starts_with(delimiter))
+}
+
+/// `CREATE [TEMP] TRIGGER`, whose body holds `;`-separated statements
+fn is_trigger(tokens: &[Token]) -> bool {
+    tokens.first().is_some_and(|t| t.is_keyword("CREATE"))
+        && tokens
+            .iter()
+            .skip(1)
+            .take(2)
+            .any(|t| t.is_keyword("TRIGGER"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(sql: &str, dialect: Dialect) -> Vec<&str> {
+        split(sql, dialect).into_iter().map(|s| s.text).collect()
+    }
+
+    #[test]
+    fn test_split_statements() {
+        let script = "-- setup\nCREATE TABLE t (a TEXT);\n\
+                      INSERT INTO t VALUES ('a;b'), (\"c;d\"); /* ; */\n\
+                      SELECT 1 ;;\n  ";
+        assert_eq!(
+            texts(script, Dialect::MySql),
+            vec![
+                "CREATE TABLE t (a TEXT)",
+                "INSERT INTO t VALUES ('a;b'), (\"c;d\")",
+                "SELECT 1",
+            ]
+        );
+
+        let statements = split("SELECT 1; SELECT 2", Dialect::Sqlite);
+        assert_eq!((statements[1].start, statements[1].end), (10, 18));
+    }
+
+    #[test]
+    fn test_statement_at() {
+        let script = "SELECT 1;\n\nSELECT 2;\n";
+        let at = |offset| {
+            statement_at(script, Dialect::Postgres, offset)
+                .unwrap()
+                .text
+        };
+        assert_eq!(at(0), "SELECT 1");
+        assert_eq!(at(9), "SELECT 1");
+        assert_eq!(at(10), "SELECT 1");
+        assert_eq!(at(11), "SELECT 2");
+        assert_eq!(at(script.len()), "SELECT 2");
+        assert!(statement_at("  -- nothing", Dialect::Postgres, 0).is_none());
+    }
+
+    #[test]
+    fn test_mysql_delimiter() {
+        let script = "DELIMITER $$\n\
+                      CREATE PROCEDURE p() BEGIN SELECT 1; SELECT 2; END$$\n\
+                      DELIMITER ;\n\
+                      CALL p();";
+        assert_eq!(
+            texts(script, Dialect::MySql),
+            vec![
+                "CREATE PROCEDURE p() BEGIN SELECT 1; SELECT 2; END",
+                "CALL p()",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_postgres_dollar_quotes() {
+        let script = "CREATE FUNCTION f() RETURNS int AS $body$ BEGIN RETURN 1; END; $body$ \
+                      LANGUAGE plpgsql; SELECT f();";
+        assert_eq!(texts(script, Dialect::Postgres).len(), 2);
+    }
+
+    #[test]
+    fn test_sqlite_trigger() {
+        let script = "CREATE TRIGGER t AFTER INSERT ON a BEGIN \
+                      UPDATE b SET n = CASE WHEN n IS NULL THEN 1 ELSE n + 1 END; \
+                      DELETE FROM c; END; SELECT 1;";
+        assert_eq!(
+            texts(script, Dialect::Sqlite),
+            vec![
+                "CREATE TRIGGER t AFTER INSERT ON a BEGIN \
+                 UPDATE b SET n = CASE WHEN n IS NULL THEN 1 ELSE n + 1 END; \
+                 DELETE FROM c; END",
+                "SELECT 1",
+            ]
+        );
+    }
+}
@@ -0,0 +1,1338 @@
+use crate::db::script::{ScriptEvent, ScriptRun, ScriptStatement};
+use crate::db::stream::{cancel_task, QueryStream, StreamEvent};
+use crate::db::{DatabaseConnection, QueryId, TableInfo, TableStructure};
+use crate::design::{self, TableDesign};
+use crate::edit::{self, ChangeSet, ChangeStatement, EditTarget};
+use crate::history::HistoryEntry;
+use crate::models::{ParamKind, ParamValue, QueryResult};
+use crate::session::Pending;
+use crate::sql::classify::classify;
+use crate::sql::params::{bind_statements, param_names};
+use crate::sql::source::{source_table, TableName};
+use crate::sql::split::{split, statement_at};
+use crate::sql::Dialect;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
+use tokio::runtime::Handle;
+use uuid::Uuid;
+
+/// One query editor: its text, the connection it runs on and the outcome
+/// of its last run
+pub struct QueryTab {
+    /// Stable for the life of the tab, to key its widgets
+    pub id: u64,
+    pub name: String,
+    /// The session the tab's queries run on
+    pub connection: Option<Uuid>,
+    /// The database the tab was opened on, if any
+    pub database: Option<String>,
+    /// The saved query the tab was opened from or last saved as
+    pub saved_query: Option<Uuid>,
+    /// The file the tab was opened from or last saved to
+    pub file: Option<TabFile>,
+    pub query_content: String,
+    /// The editor contents as last opened or saved, to tell unsaved edits
+    pub saved_content: String,
+    pub query_executing: bool,
+    /// Selected characters in the editor as of the last frame (both ends
+    /// equal when nothing is selected)
+    pub editor_cursor: Option<(usize, usize)>,
+    /// Byte ranges of the editor text that the last run executed, when it
+    /// ran only part of it
+    pub executed_ranges: Vec<Range<usize>>,
+    /// Values for the placeholders of a query, asked for before it runs
+    pub bind_prompt: Option<BindPrompt>,
+    /// The query behind `pending_query` and the connection it runs on, so
+    /// it can be cancelled
+    running_query: Option<(
+        Arc<dyn DatabaseConnection>,
+        QueryId,
+        tokio::task::AbortHandle,
+    )>,
+    pub query_cancelled: bool,
+
+    // Results
+    pub query_result: Option<QueryResult>,
+    pub result_error: Option<String>,
+    pub viewing_document: Option<usize>,
+    pub query_stream: Option<QueryStream>,
+    pub script: Option<ScriptRun>,
+    /// One result tab per statement of the last script
+    pub statement_tabs: Vec<StatementTab>,
+    pub selected_statement: usize,
+    pending_query: Option<Pending<QueryResult>>,
+    /// The statement running now, for the history
+    run: Option<Run>,
+    /// Statements that finished since the history last took them
+    finished: Vec<HistoryEntry>,
+
+    // Editing
+    /// Whether the result can be written back to its table, once that is
+    /// known: where to, or why not
+    pub editable: Option<Result<EditTarget, String>>,
+    /// Edits to the result waiting to be applied
+    pub changes: ChangeSet,
+    /// The cell being edited, as row, column and the text so far
+    pub editing_cell: Option<(usize, usize, String)>,
+    /// The statements for `changes` are shown for review
+    pub reviewing_changes: bool,
+    pub apply_error: Option<String>,
+    /// What the last applied changes did that the result doesn't show
+    pub edit_notice: Option<String>,
+    /// The table the result was read from, being described
+    pending_table: Option<(TableName, Dialect, Pending<TableInfo>)>,
+    /// The table the result was read from, once described
+    source: Option<(TableName, Dialect, TableInfo)>,
+    pending_apply: Option<Pending<u64>>,
+
+    // Structure
+    /// The table the tab was opened on from the schema tree, if any
+    pub table: Option<String>,
+    /// The schema `table` is in, for engines that have them (PostgreSQL)
+    pub schema: Option<String>,
+    pub view: TableView,
+    pub structure: Option<Result<TableStructure, String>>,
+    pending_structure: Option<Pending<TableStructure>>,
+
+    // Designing
+    /// Changes to the tab's table, or a new table, being laid out
+    pub design: Option<TableDesign>,
+    /// The design's script is shown for review
+    pub reviewing_design: bool,
+    pub design_error: Option<String>,
+    pending_design: Option<Pending<()>>,
+    /// A table was created or changed since the app last looked
+    schema_changed: bool,
+}
+
+/// What a tab opened on a table shows below the editor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TableView {
+    #[default]
+    Results,
+    Structure,
+    Design,
+}
+
+/// A statement as it started running
+struct Run {
+    sql: String,
+    started: Instant,
+    executed_at: DateTime<Utc>,
+}
+
+impl Run {
+    fn start(sql: &str) -> Self {
+        Self {
+            sql: sql.trim().to_string(),
+            started: Instant::now(),
+            executed_at: Utc::now(),
+        }
+    }
+}
+
+/// The bind form shown when a query has placeholders
+pub struct BindPrompt {
+    /// What to run once the values are in, as for `execute_query`
+    pub range: Option<Range<usize>>,
+    pub sql: String,
+    pub inputs: Vec<ParamInput>,
+    pub error: Option<String>,
+}
+
+pub struct ParamInput {
+    pub name: String,
+    pub kind: ParamKind,
+    pub text: String,
+}
+
+/// A `.sql` file open in a tab
+pub struct TabFile {
+    pub path: PathBuf,
+    /// When the file was last modified as of reading or writing it
+    modified: Option<SystemTime>,
+    /// The file was modified by something else since
+    pub changed_on_disk: bool,
+}
+
+impl TabFile {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            modified: modified(&path),
+            path,
+            changed_on_disk: false,
+        }
+    }
+
+    pub fn name(&self) -> String {
+        let name = self.path.file_name().unwrap_or(self.path.as_os_str());
+        name.to_string_lossy().to_string()
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Result of one statement of a script
+pub struct StatementTab {
+    pub sql: String,
+    pub status: StatementStatus,
+}
+
+pub enum StatementStatus {
+    /// Not run yet, or never run because the script stopped
+    Pending,
+    Running,
+    Done(QueryResult),
+    Failed(String),
+}
+
+/// What a tab needs from the app to run a query
+pub struct RunContext<'a> {
+    pub runtime: &'a Handle,
+    pub conn: Arc<dyn DatabaseConnection>,
+    pub dialect: Option<Dialect>,
+    /// Refuse statements that would modify the database
+    pub read_only: bool,
+    /// The database the connection was opened on, if one was given
+    pub database: Option<String>,
+    pub stop_on_error: bool,
+    pub fetch_size: usize,
+    /// Last values bound to each query's placeholders, by query text
+    pub bound_params: &'a mut HashMap<String, HashMap<String, ParamValue>>,
+}
+
+impl QueryTab {
+    pub fn new(id: u64, name: String, connection: Option<Uuid>) -> Self {
+        Self {
+            id,
+            name,
+            connection,
+            database: None,
+            saved_query: None,
+            file: None,
+            query_content: String::new(),
+            saved_content: String::new(),
+            query_executing: false,
+            editor_cursor: None,
+            executed_ranges: Vec::new(),
+            bind_prompt: None,
+            running_query: None,
+            query_cancelled: false,
+            query_result: None,
+            result_error: None,
+            viewing_document: None,
+            query_stream: None,
+            script: None,
+            statement_tabs: Vec::new(),
+            selected_statement: 0,
+            pending_query: None,
+            run: None,
+            finished: Vec::new(),
+            editable: None,
+            changes: ChangeSet::default(),
+            editing_cell: None,
+            reviewing_changes: false,
+            apply_error: None,
+            edit_notice: None,
+            pending_table: None,
+            source: None,
+            pending_apply: None,
+            table: None,
+            schema: None,
+            view: TableView::Results,
+            structure: None,
+            pending_structure: None,
+            design: None,
+            reviewing_design: false,
+            design_error: None,
+            pending_design: None,
+            schema_changed: false,
+        }
+    }
+
+    /// Whether a query is still running and needs polling
+    pub fn is_busy(&self) -> bool {
+        self.pending_query.is_some()
+            || self.script.is_some()
+            || self.query_stream.as_ref().is_some_and(|s| !s.paused)
+            || self.pending_table.is_some()
+            || self.pending_apply.is_some()
+            || self.pending_structure.is_some()
+            || self.pending_design.is_some()
+    }
+
+    /// Whether the editor holds edits that were not saved
+    pub fn is_dirty(&self) -> bool {
+        self.query_content != self.saved_content
+    }
+
+    /// Take the editor contents as saved
+    pub fn mark_saved(&mut self) {
+        self.saved_content = self.query_content.clone();
+    }
+
+    /// Replace the editor contents with a file's
+    pub fn open_file(&mut self, path: PathBuf) -> io::Result<()> {
+        self.query_content = fs::read_to_string(&path)?;
+        self.executed_ranges.clear();
+        self.mark_saved();
+        let file = TabFile::new(path);
+        self.name = file.name();
+        self.file = Some(file);
+        Ok(())
+    }
+
+    /// Attach a file without replacing the editor contents, which may hold
+    /// edits made to it before the app last closed
+    pub fn restore_file(&mut self, path: PathBuf) {
+        match fs::read_to_string(&path) {
+            Ok(content) => self.saved_content = content,
+            Err(e) => tracing::warn!("Failed to read {}: {}", path.display(), e),
+        }
+        self.file = Some(TabFile::new(path));
+    }
+
+    /// Write the editor contents to `path`, which becomes the tab's file
+    pub fn save_file(&mut self, path: PathBuf) -> io::Result<()> {
+        fs::write(&path, &self.query_content)?;
+        self.mark_saved();
+        let file = TabFile::new(path);
+        self.name = file.name();
+        self.file = Some(file);
+        Ok(())
+    }
+
+    /// Note whether the tab's file was modified on disk
+    pub fn check_file(&mut self) {
+        if let Some(file) = &mut self.file {
+            let modified = modified(&file.path);
+            if modified.is_some() && modified != file.modified {
+                file.changed_on_disk = true;
+            }
+        }
+    }
+
+    /// Read the tab's file again after it changed on disk
+    pub fn reload_file(&mut self) -> io::Result<()> {
+        match self.file.as_ref().map(|file| file.path.clone()) {
+            Some(path) => self.open_file(path),
+            None => Ok(()),
+        }
+    }
+
+    /// Keep the editor contents over a file that changed on disk. Saving
+    /// will overwrite it.
+    pub fn ignore_file_change(&mut self) {
+        if let Some(file) = &mut self.file {
+            file.modified = modified(&file.path);
+            file.changed_on_disk = false;
+        }
+    }
+
+    /// Statements that finished since the last call, to be recorded in the
+    /// history. The caller fills in the connection.
+    pub fn take_finished(&mut self) -> Vec<HistoryEntry> {
+        std::mem::take(&mut self.finished)
+    }
+
+    /// Close the record of the running statement with its outcome
+    fn finish_run(&mut self, rows: Option<u64>, affected_rows: Option<u64>, error: Option<String>) {
+        if let Some(run) = self.run.take() {
+            self.finished.push(HistoryEntry {
+                sql: run.sql,
+                connection: None,
+                database: self.database.clone(),
+                executed_at: run.executed_at,
+                duration_ms: run.started.elapsed().as_millis() as u64,
+                rows,
+                affected_rows,
+                error,
+            });
+        }
+    }
+
+    /// Collect whatever the running query delivered since the last frame
+    pub fn poll(&mut self) {
+        self.poll_query_stream();
+        self.poll_script();
+        self.poll_edits();
+        self.poll_structure();
+        self.poll_design();
+
+        // Poll query result
+        if let Some(rx) = &mut self.pending_query {
+            if let Ok(result) = rx.try_recv() {
+                self.query_executing = false;
+                self.running_query = None;
+                match result {
+                    Ok(qr) => {
+                        let rows = (!qr.columns.is_empty()).then_some(qr.rows.len() as u64);
+                        self.finish_run(rows, qr.affected_rows, None);
+                        self.query_result = Some(qr);
+                        self.result_error = None;
+                        self.viewing_document = None;
+                    }
+                    Err(e) => {
+                        self.finish_run(None, None, Some(e.clone()));
+                        self.query_result = None;
+                        self.result_error = Some(e);
+                    }
+                }
+                self.pending_query = None;
+            }
+        }
+    }
+
+    fn poll_query_stream(&mut self) {
+        let Some(stream) = &mut self.query_stream else {
+            return;
+        };
+        let events = stream.poll();
+        let elapsed_ms = stream.elapsed_ms();
+        let finished = stream.finished;
+
+        for event in events {
+            match event {
+                StreamEvent::Batch(batch) => {
+                    let result = self.query_result.get_or_insert_with(QueryResult::default);
+                    if let Some(columns) = batch.columns {
+                        result.columns = columns;
+                    }
+                    result.rows.extend(batch.rows);
+                    result.documents.extend(batch.documents);
+                    if batch.limit.is_some() {
+                        result.limit = batch.limit;
+                    }
+                    result.execution_time_ms = elapsed_ms;
+                }
+                StreamEvent::Paused | StreamEvent::Finished => {
+                    self.query_executing = false;
+                    let result = self.query_result.get_or_insert_with(QueryResult::default);
+                    result.execution_time_ms = elapsed_ms;
+                    let rows = result.rows.len() as u64;
+                    self.finish_run(Some(rows), None, None);
+                }
+                StreamEvent::Failed(e) => {
+                    self.finish_run(None, None, Some(e.clone()));
+                    self.query_executing = false;
+                    self.query_result = None;
+                    self.result_error = Some(e);
+                }
+            }
+        }
+
+        if finished {
+            self.query_stream = None;
+        }
+    }
+
+    fn poll_script(&mut self) {
+        let Some(script) = &mut self.script else {
+            return;
+        };
+        let (events, finished) = (script.poll(), script.finished);
+        for event in events {
+            match event {
+                ScriptEvent::Started(index) => {
+                    self.run = Some(Run::start(&self.statement_tabs[index].sql));
+                    self.statement_tabs[index].status = StatementStatus::Running;
+                    self.selected_statement = index;
+                }
+                ScriptEvent::Finished(index, result) => {
+                    // A script that could not start reports on its first
+                    // statement without starting it
+                    if self.run.is_none() {
+                        self.run = Some(Run::start(&self.statement_tabs[index].sql));
+                    }
+                    match &result {
+                        Ok(result) => {
+                            let rows =
+                                (!result.columns.is_empty()).then_some(result.rows.len() as u64);
+                            self.finish_run(rows, result.affected_rows, None);
+                        }
+                        Err(e) => self.finish_run(None, None, Some(e.clone())),
+                    }
+                    self.statement_tabs[index].status = match result {
+                        Ok(result) => StatementStatus::Done(result),
+                        Err(e) => StatementStatus::Failed(e),
+                    };
+                }
+            }
+        }
+
+        if finished {
+            self.script = None;
+            self.query_executing = false;
+        }
+    }
+
+    fn poll_edits(&mut self) {
+        if let Some((_, _, rx)) = &mut self.pending_table {
+            if let Ok(result) = rx.try_recv() {
+                if let Some((table, dialect, _)) = self.pending_table.take() {
+                    match result {
+                        Ok(info) => self.source = Some((table, dialect, info)),
+                        Err(e) => self.editable = Some(Err(e)),
+                    }
+                }
+            }
+        }
+
+        // The result's columns may arrive before or after the table's
+        if self.editable.is_none() {
+            let columns = self.query_result.as_ref().map(|r| &r.columns);
+            if let (Some((table, dialect, info)), Some(columns)) = (&self.source, columns) {
+                if !columns.is_empty() {
+                    let target = EditTarget::new(table.clone(), *dialect, info, columns);
+                    self.editable = Some(target);
+                }
+            }
+        }
+
+        if let Some(rx) = &mut self.pending_apply {
+            if let Ok(result) = rx.try_recv() {
+                self.pending_apply = None;
+                match result {
+                    Ok(_) => {
+                        if let Some(result) = &mut self.query_result {
+                            self.changes.apply_to(&mut result.rows);
+                        }
+                        let added = self.changes.inserts().len();
+                        self.edit_notice = (added > 0).then(|| {
+                            let rows = if added == 1 { "row" } else { "rows" };
+                            format!("Added {} {}; run the query again to see them", added, rows)
+                        });
+                        self.changes.clear();
+                        self.reviewing_changes = false;
+                        self.apply_error = None;
+                    }
+                    Err(e) => self.apply_error = Some(e),
+                }
+            }
+        }
+    }
+
+    /// Forget whether and how the result can be edited, along with any
+    /// staged edits, for a new result
+    fn reset_edits(&mut self) {
+        self.editable = None;
+        self.changes.clear();
+        self.editing_cell = None;
+        self.reviewing_changes = false;
+        self.apply_error = None;
+        self.edit_notice = None;
+        self.pending_table = None;
+        self.source = None;
+    }
+
+    /// Find out whether the rows about to be read from `table` can be
+    /// written back to it
+    fn describe_source(&mut self, cx: &RunContext<'_>, table: TableName, dialect: Dialect) {
+        if cx.read_only {
+            self.editable = Some(Err("The connection is read-only".to_string()));
+            return;
+        }
+        let (database, schema, name) = match dialect {
+            Dialect::MySql => {
+                let database = table
+                    .qualifier
+                    .clone()
+                    .or_else(|| self.database.clone())
+                    .or_else(|| cx.database.clone());
+                let Some(database) = database else {
+                    self.editable = Some(Err("No database is selected".to_string()));
+                    return;
+                };
+                (database, None, table.name.clone())
+            }
+            // The tab's database, or else the connection's own
+            Dialect::Postgres => (
+                self.database.clone().unwrap_or_default(),
+                table.qualifier.clone(),
+                table.name.clone(),
+            ),
+            // Attached databases are SQLite's schemas
+            Dialect::Sqlite => {
+                let database = table.qualifier.as_deref().unwrap_or("main");
+                (database.to_string(), None, table.name.clone())
+            }
+        };
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let conn = cx.conn.clone();
+        cx.runtime.spawn(async move {
+            let result = conn
+                .describe_table(&database, schema.as_deref(), &name)
+                .await
+                .map_err(|e| e.to_string());
+            let _ = tx.send(result);
+        });
+        self.pending_table = Some((table, dialect, rx));
+    }
+
+    fn poll_structure(&mut self) {
+        if let Some(rx) = &mut self.pending_structure {
+            if let Ok(result) = rx.try_recv() {
+                self.pending_structure = None;
+                self.structure = Some(result);
+            }
+        }
+    }
+
+    pub fn is_loading_structure(&self) -> bool {
+        self.pending_structure.is_some()
+    }
+
+    /// Read the columns, keys and triggers of the tab's table
+    pub fn load_structure(&mut self, cx: &RunContext<'_>) {
+        let (Some(table), Some(database)) = (self.table.clone(), self.database.clone()) else {
+            return;
+        };
+        let schema = self.schema.clone();
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let conn = cx.conn.clone();
+        cx.runtime.spawn(async move {
+            let result = conn
+                .table_structure(&database, schema.as_deref(), &table)
+                .await
+                .map_err(|e| e.to_string());
+            let _ = tx.send(result);
+        });
+        self.pending_structure = Some(rx);
+    }
+
+    fn poll_design(&mut self) {
+        if let Some(rx) = &mut self.pending_design {
+            if let Ok(result) = rx.try_recv() {
+                self.pending_design = None;
+                match result {
+                    Ok(()) => {
+                        // Show the table as it is now, under its new name
+                        if let Some(design) = self.design.take() {
+                            self.name = design.qualified_name();
+                            self.schema = design.schema().map(String::from);
+                            self.table = Some(design.table.name);
+                        }
+                        self.reviewing_design = false;
+                        self.design_error = None;
+                        self.structure = None;
+                        self.view = TableView::Structure;
+                        self.schema_changed = true;
+                    }
+                    Err(e) => self.design_error = Some(e),
+                }
+            }
+        }
+    }
+
+    /// Lay out a new table in `qualifier`, the database or schema
+    pub fn new_table(&mut self, dialect: Dialect, qualifier: Option<String>) {
+        self.design = Some(TableDesign::new(dialect, qualifier));
+        self.view = TableView::Design;
+    }
+
+    /// Start changing the tab's table from its structure, once that is read
+    pub fn start_design(&mut self, dialect: Dialect) {
+        if let (None, Some(Ok(structure))) = (&self.design, &self.structure) {
+            self.design = Some(TableDesign::from_structure(structure, dialect));
+        }
+    }
+
+    /// Throw the design away: changes to a table start over from its
+    /// structure, a new table from scratch
+    pub fn discard_design(&mut self) {
+        self.design = match self.design.take() {
+            Some(design) if design.is_new() => {
+                Some(TableDesign::new(design.dialect, design.qualifier))
+            }
+            _ => None,
+        };
+        self.design_error = None;
+    }
+
+    pub fn is_applying_design(&self) -> bool {
+        self.pending_design.is_some()
+    }
+
+    /// Run the design's script. The design stays, with the error, if it
+    /// fails.
+    pub fn apply_design(&mut self, cx: &RunContext<'_>) {
+        let Some(design) = &self.design else {
+            return;
+        };
+        if cx.read_only {
+            self.design_error = Some("The connection is read-only".to_string());
+            return;
+        }
+        match design.script() {
+            Ok(statements) => {
+                let (tx, rx) = tokio::sync::oneshot::channel();
+                let conn = cx.conn.clone();
+                let database = self.database.clone();
+                let dialect = design.dialect;
+                cx.runtime.spawn(async move {
+                    let id = QueryId::new();
+                    let result = design::apply(conn, database, statements, dialect, id).await;
+                    let _ = tx.send(result);
+                });
+                self.design_error = None;
+                self.pending_design = Some(rx);
+            }
+            Err(e) => self.design_error = Some(e),
+        }
+    }
+
+    /// Whether a table was created or changed since this was last asked
+    pub fn take_schema_changed(&mut self) -> bool {
+        std::mem::take(&mut self.schema_changed)
+    }
+
+    /// The statements that would apply the staged edits
+    pub fn change_statements(&self) -> Result<Vec<ChangeStatement>, String> {
+        match (&self.editable, &self.query_result) {
+            (Some(Ok(target)), Some(result)) => self.changes.statements(target, &result.rows),
+            _ => Err("The result can't be edited".to_string()),
+        }
+    }
+
+    pub fn is_applying(&self) -> bool {
+        self.pending_apply.is_some()
+    }
+
+    /// Run the staged edits as one transaction. They stay staged, with the
+    /// error, if it fails.
+    pub fn apply_changes(&mut self, cx: &RunContext<'_>) {
+        if cx.read_only {
+            self.apply_error = Some("The connection is read-only".to_string());
+            return;
+        }
+        match self.change_statements() {
+            Ok(statements) => {
+                let (tx, rx) = tokio::sync::oneshot::channel();
+                let conn = cx.conn.clone();
+                let database = self.database.clone();
+                cx.runtime.spawn(async move {
+                    let result = edit::apply(conn, database, statements, QueryId::new()).await;
+                    let _ = tx.send(result);
+                });
+                self.apply_error = None;
+                self.pending_apply = Some(rx);
+            }
+            Err(e) => self.apply_error = Some(e),
+        }
+    }
+
+    /// Stop the running query on the server and stop waiting for it.
+    /// Rows that were already streamed are kept.
+    pub fn cancel(&mut self, runtime: &Handle) {
+        if let Some(stream) = &mut self.query_stream {
+            stream.cancel();
+            self.query_stream = None;
+        }
+        if let Some(script) = &mut self.script {
+            script.cancel();
+            self.script = None;
+            for statement in &mut self.statement_tabs {
+                if let StatementStatus::Running = statement.status {
+                    statement.status = StatementStatus::Failed("Query cancelled".to_string());
+                }
+            }
+        }
+        if let Some((conn, id, task)) = self.running_query.take() {
+            cancel_task(runtime, conn, id, task);
+            self.pending_query = None;
+            // Whatever is on screen belongs to the previous query
+            self.query_result = None;
+            self.result_error = None;
+        }
+        self.finish_run(None, None, Some("Query cancelled".to_string()));
+        self.pending_table = None;
+        self.query_executing = false;
+        self.query_cancelled = true;
+    }
+
+    pub fn fetch_more(&mut self, rows: usize) {
+        if let Some(stream) = &mut self.query_stream {
+            stream.fetch_more(rows);
+            self.query_executing = !stream.paused;
+        }
+    }
+
+    /// Show the first rows of a table
+    pub fn load_table_data(
+        &mut self,
+        cx: &RunContext<'_>,
+        database: &str,
+        schema: Option<&str>,
+        table: &str,
+    ) {
+        let conn = cx.conn.clone();
+        self.query_executing = true;
+        self.query_cancelled = false;
+        self.query_stream = None;
+        self.run = None;
+        self.reset_edits();
+
+        match cx.dialect {
+            Some(Dialect::Postgres) => {
+                let table = TableName {
+                    qualifier: schema.map(String::from),
+                    name: table.to_string(),
+                };
+                self.describe_source(cx, table, Dialect::Postgres);
+            }
+            Some(dialect) => {
+                let table = TableName {
+                    qualifier: Some(database.to_string()),
+                    name: table.to_string(),
+                };
+                self.describe_source(cx, table, dialect);
+            }
+            None => {}
+        }
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let conn_clone = conn.clone();
+        let db = database.to_string();
+        let schema = schema.map(String::from);
+        let table = table.to_string();
+        let task = cx.runtime.spawn(async move {
+            let result = conn_clone
+                .get_table_data(&db, schema.as_deref(), &table, 100, 0)
+                .await
+                .map_err(|e| e.to_string());
+            let _ = tx.send(result);
+        });
+        self.running_query = Some((conn, QueryId::new(), task.abort_handle()));
+        self.pending_query = Some(rx);
+    }
+
+    /// Run the selected text, or else the statement under the cursor
+    pub fn execute_current(&mut self, cx: &mut RunContext<'_>) {
+        let Some((a, b)) = self.editor_cursor else {
+            self.execute_query(None, cx);
+            return;
+        };
+        let (start, end) = (
+            char_to_byte(&self.query_content, a.min(b)),
+            char_to_byte(&self.query_content, a.max(b)),
+        );
+
+        if start < end {
+            self.execute_query(Some(start..end), cx);
+        } else if let Some(dialect) = cx.dialect {
+            let statement = statement_at(&self.query_content, dialect, start)
+                .map(|statement| statement.start..statement.end);
+            if let Some(range) = statement {
+                self.execute_query(Some(range), cx);
+            }
+        } else {
+            self.execute_query(None, cx);
+        }
+    }
+
+    /// Run the editor text, or just the given byte range of it. If it has
+    /// placeholders, ask for their values first.
+    pub fn execute_query(&mut self, range: Option<Range<usize>>, cx: &mut RunContext<'_>) {
+        let text = match &range {
+            Some(range) => &self.query_content[range.clone()],
+            None => self.query_content.as_str(),
+        };
+        let names = match cx.dialect {
+            Some(dialect) => param_names(text, dialect),
+            None => Vec::new(),
+        };
+        if names.is_empty() {
+            self.run_query(range, &HashMap::new(), cx);
+            return;
+        }
+
+        let sql = text.trim().to_string();
+        let remembered = cx.bound_params.get(&sql);
+        let inputs = names
+            .into_iter()
+            .map(|name| {
+                let value = remembered.and_then(|values| values.get(&name));
+                ParamInput {
+                    kind: value.map_or(ParamKind::String, ParamValue::kind),
+                    text: value.map(ParamValue::input_text).unwrap_or_default(),
+                    name,
+                }
+            })
+            .collect();
+        self.bind_prompt = Some(BindPrompt {
+            range,
+            sql,
+            inputs,
+            error: None,
+        });
+    }
+
+    /// Run the query in the bind form with the values entered, or point
+    /// out the first one that doesn't parse
+    pub fn submit_bind_prompt(&mut self, cx: &mut RunContext<'_>) {
+        let Some(prompt) = &mut self.bind_prompt else {
+            return;
+        };
+        let values: Result<HashMap<String, ParamValue>, String> = prompt
+            .inputs
+            .iter()
+            .map(|input| {
+                let value = input
+                    .kind
+                    .parse(&input.text)
+                    .map_err(|e| format!("{}: {}", input.name, e))?;
+                Ok((input.name.clone(), value))
+            })
+            .collect();
+
+        match values {
+            Ok(values) => {
+                let Some(prompt) = self.bind_prompt.take() else {
+                    return;
+                };
+                cx.bound_params.insert(prompt.sql, values.clone());
+                self.run_query(prompt.range, &values, cx);
+            }
+            Err(e) => prompt.error = Some(e),
+        }
+    }
+
+    /// Run the editor text or a range of it, binding `values` to its
+    /// placeholders
+    fn run_query(
+        &mut self,
+        range: Option<Range<usize>>,
+        values: &HashMap<String, ParamValue>,
+        cx: &mut RunContext<'_>,
+    ) {
+        let conn = cx.conn.clone();
+        let dialect = cx.dialect;
+        let offset = range.as_ref().map_or(0, |range| range.start);
+        let text = match &range {
+            Some(range) => &self.query_content[range.clone()],
+            None => self.query_content.as_str(),
+        };
+        let parts = match dialect {
+            Some(dialect) => split(text, dialect),
+            None => Vec::new(),
+        };
+        let statements: Vec<String> = parts.iter().map(|s| s.text.to_string()).collect();
+        let text = text.to_string();
+        self.executed_ranges = match range {
+            Some(_) => parts
+                .iter()
+                .map(|s| offset + s.start..offset + s.end)
+                .collect(),
+            None => Vec::new(),
+        };
+        let classes: Vec<_> = dialect
+            .map(|dialect| {
+                statements
+                    .iter()
+                    .map(|sql| classify(sql, dialect))
+                    .collect()
+            })
+            .unwrap_or_default();
+        // Each statement in the engine's own placeholder syntax, with
+        // the values to bind to it
+        let bound: Vec<(String, Vec<ParamValue>)> = match dialect {
+            Some(dialect) => bind_statements(&statements, dialect, values),
+            None => Vec::new(),
+        };
+
+        if cx.read_only && classes.iter().any(|class| class.is_write()) {
+            self.reset_edits();
+            self.query_result = None;
+            self.statement_tabs.clear();
+            self.result_error =
+                Some("This connection is read-only and the statement would modify it".to_string());
+            return;
+        }
+
+        let id = QueryId::new();
+        self.query_executing = true;
+        self.query_cancelled = false;
+        self.query_stream = None;
+        self.script = None;
+        self.statement_tabs.clear();
+        self.reset_edits();
+
+        if statements.len() > 1 {
+            self.query_result = None;
+            self.result_error = None;
+            self.pending_query = None;
+            self.statement_tabs = statements
+                .iter()
+                .map(|sql| StatementTab {
+                    sql: sql.clone(),
+                    status: StatementStatus::Pending,
+                })
+                .collect();
+            self.selected_statement = 0;
+            let statements = bound
+                .into_iter()
+                .zip(&classes)
+                .map(|((sql, params), class)| ScriptStatement {
+                    sql,
+                    params,
+                    returns_rows: class.returns_rows,
+                })
+                .collect();
+            self.script = Some(ScriptRun::spawn(
+                cx.runtime,
+                conn.clone(),
+                self.database.clone(),
+                statements,
+                id,
+                cx.stop_on_error,
+            ));
+            return;
+        }
+
+        let class = classes.first().copied();
+        self.run = Some(Run::start(statements.first().unwrap_or(&text)));
+        let (sql, params) = bound.into_iter().next().unwrap_or((text, Vec::new()));
+
+        // Anything that returns rows is streamed into the grid. Document
+        // stores have no statements, only commands that return documents.
+        if class.is_none_or(|class| class.returns_rows) {
+            self.query_result = None;
+            self.result_error = None;
+            self.viewing_document = None;
+            self.pending_query = None;
+            let table = dialect.and_then(|dialect| Some((source_table(&sql, dialect)?, dialect)));
+            if let Some((table, dialect)) = table {
+                self.describe_source(cx, table, dialect);
+            }
+            self.query_stream = Some(QueryStream::spawn(
+                cx.runtime,
+                conn.clone(),
+                self.database.clone(),
+                sql,
+                params,
+                id,
+                cx.fetch_size,
+            ));
+            return;
+        }
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let conn_clone = conn.clone();
+        let database = self.database.clone();
+        let task = cx.runtime.spawn(async move {
+            let start = Instant::now();
+            let result = match conn_clone.as_sql() {
+                Some(sql_conn) => match sql_conn
+                    .execute_statement(database.as_deref(), &sql, &params, id)
+                    .await
+                {
+                    Ok(affected) => Ok(QueryResult {
+                        columns: vec![],
+                        rows: vec![],
+                        affected_rows: Some(affected),
+                        execution_time_ms: start.elapsed().as_millis() as u64,
+                        documents: Vec::new(),
+                        limit: None,
+                    }),
+                    Err(e) => Err(e.to_string()),
+                },
+                _ => conn_clone
+                    .execute_query(&sql)
+                    .await
+                    .map_err(|e| e.to_string()),
+            };
+            let _ = tx.send(result);
+        });
+        self.running_query = Some((conn, id, task.abort_handle()));
+        self.pending_query = Some(rx);
+    }
+}
+
+/// Byte offset of the `index`th character, clamped to the end of `text`
+fn char_to_byte(text: &str, index: usize) -> usize {
+    text.char_indices()
+        .nth(index)
+        .map_or(text.len(), |(i, _)| i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::create_connection;
+    use crate::edit::NewValue;
+    use crate::models::{CellValue, ConnectionConfig, DatabaseType};
+    use std::time::{Duration, Instant};
+
+    fn connect(runtime: &tokio::runtime::Runtime) -> Arc<dyn DatabaseConnection> {
+        let config = ConnectionConfig {
+            db_type: DatabaseType::SQLite,
+            database: ":memory:".to_string(),
+            ..ConnectionConfig::default()
+        };
+        Arc::from(runtime.block_on(create_connection(&config)).unwrap())
+    }
+
+    /// Start something on a tab and wait for it to finish
+    fn drive(
+        tab: &mut QueryTab,
+        runtime: &tokio::runtime::Runtime,
+        conn: Arc<dyn DatabaseConnection>,
+        read_only: bool,
+        start: impl FnOnce(&mut QueryTab, &mut RunContext<'_>),
+    ) {
+        let mut bound_params = HashMap::new();
+        let mut cx = RunContext {
+            runtime: runtime.handle(),
+            conn,
+            dialect: Some(Dialect::Sqlite),
+            read_only,
+            database: None,
+            stop_on_error: true,
+            fetch_size: 100,
+            bound_params: &mut bound_params,
+        };
+
+        start(tab, &mut cx);
+        let started = Instant::now();
+        while tab.is_busy() {
+            assert!(
+                started.elapsed() < Duration::from_secs(10),
+                "query never finished"
+            );
+            std::thread::sleep(Duration::from_millis(5));
+            tab.poll();
+        }
+    }
+
+    fn run(tab: &mut QueryTab, sql: &str, read_only: bool) {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let conn = connect(&runtime);
+        tab.query_content = sql.to_string();
+        drive(tab, &runtime, conn, read_only, |tab, cx| {
+            tab.execute_query(None, cx)
+        });
+    }
+
+    #[test]
+    fn test_tabs_keep_their_own_results() {
+        let mut first = QueryTab::new(1, "Query 1".to_string(), None);
+        let mut second = QueryTab::new(2, "Query 2".to_string(), None);
+
+        run(&mut first, "SELECT 1 AS one", false);
+        run(
+            &mut second,
+            "SELECT 'a' AS letter UNION ALL SELECT 'b'",
+            false,
+        );
+
+        let first = first.query_result.unwrap();
+        assert_eq!(first.columns[0].name, "one");
+        assert!(matches!(first.rows.as_slice(), [row] if matches!(row[0], CellValue::Int(1))));
+        let second = second.query_result.unwrap();
+        assert_eq!(second.columns[0].name, "letter");
+        assert_eq!(second.rows.len(), 2);
+    }
+
+    #[test]
+    fn test_finished_statements_are_recorded() {
+        let mut tab = QueryTab::new(1, "Query 1".to_string(), None);
+        tab.database = Some("main".to_string());
+        run(
+            &mut tab,
+            "CREATE TABLE t (id INTEGER); INSERT INTO t VALUES (1), (2); SELECT * FROM missing",
+            false,
+        );
+        let entries = tab.take_finished();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[1].sql, "INSERT INTO t VALUES (1), (2)");
+        assert_eq!(entries[1].affected_rows, Some(2));
+        assert_eq!(entries[1].database.as_deref(), Some("main"));
+        assert!(entries[2].error.as_ref().unwrap().contains("missing"));
+
+        run(&mut tab, "SELECT 1 UNION ALL SELECT 2", false);
+        let entries = tab.take_finished();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].rows, Some(2));
+        assert!(entries[0].succeeded());
+        assert!(tab.take_finished().is_empty());
+    }
+
+    #[test]
+    fn test_bind_across_statements() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let conn = connect(&runtime);
+        let mut tab = QueryTab::new(1, "Query 1".to_string(), None);
+        tab.query_content =
+            "CREATE TABLE t (a TEXT); INSERT INTO t VALUES (?); INSERT INTO t VALUES (?)"
+                .to_string();
+        drive(&mut tab, &runtime, conn.clone(), false, |tab, cx| {
+            tab.execute_query(None, cx)
+        });
+        let prompt = tab.bind_prompt.as_mut().unwrap();
+        assert_eq!(prompt.inputs.len(), 2);
+        prompt.inputs[0].text = "first".to_string();
+        prompt.inputs[1].text = "second".to_string();
+        drive(&mut tab, &runtime, conn.clone(), false, |tab, cx| {
+            tab.submit_bind_prompt(cx)
+        });
+
+        tab.query_content = "SELECT group_concat(a, ',') FROM t".to_string();
+        drive(&mut tab, &runtime, conn, false, |tab, cx| {
+            tab.execute_query(None, cx)
+        });
+        assert_eq!(
+            tab.query_result.as_ref().unwrap().rows[0][0].to_string(),
+            "first,second"
+        );
+    }
+
+    #[test]
+    fn test_file_edits_and_changes_on_disk() {
+        let path = std::env::temp_dir().join(format!("nebula-tab-{}.sql", Uuid::new_v4()));
+        let mut tab = QueryTab::new(1, "Query 1".to_string(), None);
+        tab.query_content = "SELECT 1".to_string();
+        assert!(tab.is_dirty());
+
+        tab.save_file(path.clone()).unwrap();
+        assert!(!tab.is_dirty());
+        assert_eq!(tab.name, path.file_name().unwrap().to_string_lossy());
+        tab.query_content.push_str(" + 1");
+        assert!(tab.is_dirty());
+
+        // Written by something else, with a later modification time
+        fs::write(&path, "SELECT 2").unwrap();
+        let later = SystemTime::now() + Duration::from_secs(5);
+        let file = fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(later).unwrap();
+        tab.check_file();
+        assert!(tab.file.as_ref().unwrap().changed_on_disk);
+
+        tab.reload_file().unwrap();
+        assert_eq!(tab.query_content, "SELECT 2");
+        assert!(!tab.is_dirty());
+        tab.check_file();
+        assert!(!tab.file.as_ref().unwrap().changed_on_disk);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_read_only_refuses_writes() {
+        let mut tab = QueryTab::new(1, "Query 1".to_string(), None);
+        run(&mut tab, "CREATE TABLE t (id INTEGER)", true);
+
+        assert!(!tab.query_executing);
+        assert!(tab.query_result.is_none());
+        assert!(tab.result_error.unwrap().contains("read-only"));
+    }
+
+    #[test]
+    fn test_edit_cells_and_apply() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let conn = connect(&runtime);
+        let query = |tab: &mut QueryTab, sql: &str| {
+            tab.query_content = sql.to_string();
+            drive(tab, &runtime, conn.clone(), false, |tab, cx| {
+                tab.execute_query(None, cx)
+            });
+        };
+        let mut tab = QueryTab::new(1, "Query 1".to_string(), None);
+        query(
+            &mut tab,
+            "CREATE TABLE t (id INTEGER PRIMARY KEY, name TEXT)",
+        );
+        query(&mut tab, "INSERT INTO t VALUES (1, 'a'), (2, 'b')");
+
+        query(&mut tab, "SELECT name, id FROM t ORDER BY id");
+        assert!(matches!(tab.editable, Some(Ok(_))));
+        let original = tab.query_result.as_ref().unwrap().rows[1][0].clone();
+        tab.changes.set(1, 0, Some("c".to_string()), &original);
+        assert_eq!(tab.changes.len(), 1);
+        drive(&mut tab, &runtime, conn.clone(), false, |tab, cx| {
+            tab.apply_changes(cx)
+        });
+        assert!(tab.apply_error.is_none());
+        assert!(tab.changes.is_empty());
+        assert_eq!(
+            tab.query_result.as_ref().unwrap().rows[1][0].to_string(),
+            "c"
+        );
+
+        query(&mut tab, "SELECT name FROM t WHERE id = 2");
+        assert_eq!(
+            tab.query_result.as_ref().unwrap().rows[0][0].to_string(),
+            "c"
+        );
+        // Rows without their key can only be added to
+        assert!(matches!(&tab.editable, Some(Ok(target)) if target.key.is_err()));
+
+        query(&mut tab, "SELECT * FROM t ORDER BY id");
+        let Some(Ok(target)) = &tab.editable else {
+            panic!("result is not editable");
+        };
+        let mut new_row = target.new_row();
+        new_row[1] = NewValue::Text("d".to_string());
+        tab.changes.insert(new_row);
+        tab.changes.delete(0);
+        drive(&mut tab, &runtime, conn.clone(), false, |tab, cx| {
+            tab.apply_changes(cx)
+        });
+        assert!(tab.apply_error.is_none());
+        assert_eq!(tab.query_result.as_ref().unwrap().rows.len(), 1);
+        assert!(tab.edit_notice.as_ref().unwrap().contains("Added 1 row"));
+
+        query(&mut tab, "SELECT id, name FROM t ORDER BY id");
+        let names: Vec<String> = tab
+            .query_result
+            .as_ref()
+            .unwrap()
+            .rows
+            .iter()
+            .map(|row| row[1].to_string())
+            .collect();
+        assert_eq!(names, ["c", "d"]);
+    }
+
+    #[test]
+    fn test_design_new_table() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let conn = connect(&runtime);
+        let mut tab = QueryTab::new(1, "New table".to_string(), None);
+        tab.database = Some("main".to_string());
+        tab.new_table(Dialect::Sqlite, Some("main".to_string()));
+        assert_eq!(tab.view, TableView::Design);
+
+        tab.design.as_mut().unwrap().table.name = "things".to_string();
+        drive(&mut tab, &runtime, conn.clone(), true, |tab, cx| {
+            tab.apply_design(cx)
+        });
+        assert!(tab.design_error.unwrap().contains("read-only"));
+
+        tab.design_error = None;
+        drive(&mut tab, &runtime, conn.clone(), false, |tab, cx| {
+            tab.apply_design(cx)
+        });
+        assert!(tab.design_error.is_none());
+        assert!(tab.design.is_none());
+        assert_eq!(tab.table.as_deref(), Some("things"));
+        assert_eq!(tab.view, TableView::Structure);
+        assert!(tab.take_schema_changed());
+        assert!(!tab.take_schema_changed());
+
+        drive(&mut tab, &runtime, conn, false, |tab, cx| {
+            tab.load_structure(cx)
+        });
+        let Some(Ok(structure)) = &tab.structure else {
+            panic!("structure not loaded");
+        };
+        assert_eq!(structure.table.columns[0].name, "id");
+    }
+}
@@ -13,12 +13,6 @@ pub const PRIMARY: Color32 = Color32::from_rgb(153, 82, 230);            // #995
 pub const PRIMARY_LIGHT: Color32 = Color32::from_rgb(186, 125, 242);     // #ba7df2 - light purple
 pub const PRIMARY_DARK: Color32 = Color32::from_rgb(115, 56, 179);       // #7338b3 - deep purple
 
-// Secondary - cosmic pink/magenta
-pub const SECONDARY: Color32 = Color32::from_rgb(232, 92, 163);          // #e85ca3 - cosmic pink
-
-// Tertiary - cyan/teal for contrast (like star colors)
-pub const TERTIARY: Color32 = Color32::from_rgb(77, 199, 230);           // #4dc7e6 - stellar cyan
-
 // Text colors - starlight whites
 pub const TEXT_PRIMARY: Color32 = Color32::from_rgb(240, 235, 247);      // #f0ebf7 - starlight
 pub const TEXT_SECONDARY: Color32 = Color32::from_rgb(179, 166, 199);    // #b3a6c7 - dim starlight
@@ -28,7 +22,6 @@ pub const TEXT_MUTED: Color32 = Color32::from_rgb(128, 115, 148);        // #807
 pub const SUCCESS: Color32 = Color32::from_rgb(102, 217, 153);           // #66d999 - aurora green
 pub const WARNING: Color32 = Color32::from_rgb(242, 191, 89);            // #f2bf59 - solar flare
 pub const DANGER: Color32 = Color32::from_rgb(242, 89, 115);             // #f25973 - red giant
-pub const INFO: Color32 = Color32::from_rgb(102, 179, 242);              // #66b3f2 - blue star
 
 // Borders - subtle purple-tinted
 pub const BORDER: Color32 = Color32::from_rgb(64, 51, 89);               // #403359 - nebula edge
@@ -73,3 +66,18 @@ pub fn dark_visuals() -> Visuals {
     visuals
 }
 
+
+/// Parse a connection color, stored as `#rrggbb`
+pub fn parse_hex(color: &str) -> Option<Color32> {
+    let hex = color.trim().strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some(Color32::from_rgb(channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// A color in the `#rrggbb` form `parse_hex` reads
+pub fn to_hex(color: Color32) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r(), color.g(), color.b())
+}